@@ -0,0 +1,22 @@
+use std::env;
+use std::process::Command;
+
+/// sets `GIT_COMMIT` for `/meta`. `GIT_COMMIT` passed to the build is preferred,
+/// since the source may be built without `.git` (e.g. in docker)
+fn main() {
+    println!("cargo:rerun-if-env-changed=GIT_COMMIT");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+
+    let commit = env::var("GIT_COMMIT").ok().or_else(|| {
+        Command::new("git")
+            .args(&["rev-parse", "--short", "HEAD"])
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .and_then(|output| String::from_utf8(output.stdout).ok())
+            .map(|commit| commit.trim().to_string())
+    });
+    if let Some(commit) = commit.filter(|commit| !commit.is_empty()) {
+        println!("cargo:rustc-env=GIT_COMMIT={}", commit);
+    }
+}
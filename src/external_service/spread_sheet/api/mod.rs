@@ -1,7 +1,9 @@
 mod operation;
+mod retry;
 
 pub use operation::*;
 use reqwest::Error as ReqError;
+use reqwest::StatusCode;
 use serde::Deserialize;
 use std::sync::Arc;
 use thiserror::Error;
@@ -15,11 +17,17 @@ pub enum SheetApiError {
     #[error("HTTP error {0}")]
     ReqwestError(#[from] ReqError),
 
-    #[error("sheet api error:bad request [{0}]")]
-    BadReqestError(String),
+    #[error("sheet api error:bad request [{}] {}", .0.status, .0.message)]
+    BadReqestError(GoogleApiError),
 
     #[error("Spread sheet not found")]
     SpreadSheetNotFoundError(String),
+
+    #[error("sheet api error:permission denied [{}] {}", .0.status, .0.message)]
+    PermissionDeniedError(GoogleApiError),
+
+    #[error("sheet api error:rate limit exceeded [{}] {}", .0.status, .0.message)]
+    RateLimitError(GoogleApiError),
 }
 
 impl SheetApiError {
@@ -30,9 +38,92 @@ impl SheetApiError {
             false
         }
     }
+
+    /// 401, 403 and 429 of google, which are passed through to the clients
+    pub fn access_error(&self) -> Option<&GoogleApiError> {
+        match self {
+            SheetApiError::PermissionDeniedError(google_error)
+            | SheetApiError::RateLimitError(google_error) => Some(google_error),
+            _ => None,
+        }
+    }
+
+    /// stable error code for api clients
+    pub fn code(&self) -> &'static str {
+        match self {
+            SheetApiError::ReqwestError(_) => "SHEET_API_ERROR",
+            SheetApiError::BadReqestError(_) => "SHEET_API_BAD_REQUEST",
+            SheetApiError::SpreadSheetNotFoundError(_) => "SPREAD_SHEET_NOT_FOUND",
+            SheetApiError::PermissionDeniedError(google_error)
+            | SheetApiError::RateLimitError(google_error) => google_error.error_code(),
+        }
+    }
+
+    /// 400 if google rejected the request, 500 if the request to google itself failed.
+    /// 401, 403 and 429 of google are passed through
+    pub fn http_status(&self) -> StatusCode {
+        match self {
+            SheetApiError::ReqwestError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            SheetApiError::BadReqestError(_) => StatusCode::BAD_REQUEST,
+            SheetApiError::SpreadSheetNotFoundError(_) => StatusCode::NOT_FOUND,
+            SheetApiError::PermissionDeniedError(google_error)
+            | SheetApiError::RateLimitError(google_error) => google_error.http_status(),
+        }
+    }
 }
 
+/// `error` of the error response of google apis.
+/// `{"error":{"code":400,"message":"Unable to parse range: Foo!A1","status":"INVALID_ARGUMENT"}}`
 #[derive(Deserialize, PartialEq, Debug, Clone)]
+pub struct GoogleApiError {
+    pub code: u16,
+    pub message: String,
+    /// e.g. `INVALID_ARGUMENT`
+    #[serde(default)]
+    pub status: String,
+}
+
+#[derive(Deserialize)]
+struct GoogleApiErrorEnvelope {
+    error: GoogleApiError,
+}
+
+impl GoogleApiError {
+    /// the whole body is the message if it is not the error envelope
+    pub fn from_body(http_status: u16, body: &str) -> Self {
+        match serde_json::from_str::<GoogleApiErrorEnvelope>(body) {
+            Ok(envelope) => envelope.error,
+            Err(_) => Self {
+                code: http_status,
+                message: body.to_string(),
+                status: String::new(),
+            },
+        }
+    }
+
+    /// 401, 403 and 429 are passed through as they are, the others are 400
+    pub fn http_status(&self) -> StatusCode {
+        match StatusCode::from_u16(self.code) {
+            Ok(
+                status @ (StatusCode::UNAUTHORIZED
+                | StatusCode::FORBIDDEN
+                | StatusCode::TOO_MANY_REQUESTS),
+            ) => status,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    /// stable error code for api clients
+    pub fn error_code(&self) -> &'static str {
+        match self.http_status() {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => "SHEET_API_PERMISSION_DENIED",
+            StatusCode::TOO_MANY_REQUESTS => "SHEET_API_RATE_LIMITED",
+            _ => "SHEET_API_BAD_REQUEST",
+        }
+    }
+}
+
+#[derive(Deserialize, PartialEq, Eq, Hash, Debug, Clone)]
 pub struct SpreadSheetId(String);
 impl SpreadSheetId {
     pub fn new(spread_sheet_id: String) -> Self {
@@ -78,7 +169,8 @@ impl Display for SheetName {
     }
 }
 
-const BASE_ENDPOINT_V4: &str = "https://sheets.googleapis.com/v4/spreadsheets";
+pub const DEFAULT_BASE_ENDPOINT_V4: &str = "https://sheets.googleapis.com/v4/spreadsheets";
+
 ///https://developers.google.com/sheets/api/reference/rest/v4/Dimension
 #[allow(dead_code)]
 pub enum MajorDimension {
@@ -101,33 +193,146 @@ impl std::fmt::Display for MajorDimension {
 
 /// https://developers.google.com/sheets/api/reference/rest/v4/ValueRenderOption
 #[allow(dead_code)]
+#[derive(Debug, PartialEq)]
 pub enum ValueRenderOption {
     FormattedValue,
     UnfromattedValue,
     Formula,
 }
 
+impl std::fmt::Display for ValueRenderOption {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let v = match self {
+            Self::FormattedValue => "FORMATTED_VALUE",
+            Self::UnfromattedValue => "UNFORMATTED_VALUE",
+            Self::Formula => "FORMULA",
+        };
+
+        write!(f, "{}", v)
+    }
+}
+
 /// https://developers.google.com/sheets/api/reference/rest/v4/DateTimeRenderOption
+/// ignored if `ValueRenderOption` is `FormattedValue`
 #[allow(dead_code)]
 pub enum DateTimeRenderOption {
     SerialNumber,
     FormattedString,
 }
 
+impl std::fmt::Display for DateTimeRenderOption {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let v = match self {
+            Self::SerialNumber => "SERIAL_NUMBER",
+            Self::FormattedString => "FORMATTED_STRING",
+        };
+
+        write!(f, "{}", v)
+    }
+}
+
 pub enum SheetOperation {
     Get,
     BatchGet,
+    /// range in a1 notation to find the table to append
+    Append(String),
 }
 
 impl SheetOperation {
-    pub fn endpoint(&self, spread_sheet_id: &SpreadSheetId) -> String {
+    pub fn endpoint(&self, base_endpoint: &str, spread_sheet_id: &SpreadSheetId) -> String {
         match self {
             Self::Get => {
-                format!("{}/{}", BASE_ENDPOINT_V4, spread_sheet_id)
+                format!("{}/{}", base_endpoint, spread_sheet_id)
             }
             Self::BatchGet => {
-                format!("{}/{}/values:batchGet", BASE_ENDPOINT_V4, spread_sheet_id)
+                format!("{}/{}/values:batchGet", base_endpoint, spread_sheet_id)
+            }
+            Self::Append(range) => {
+                format!(
+                    "{}/{}/values/{}:append",
+                    base_endpoint,
+                    spread_sheet_id,
+                    urlencoding::encode(range)
+                )
             }
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::Value as JsonValue;
+
+    #[test]
+    fn endpoint_test() {
+        let spread_sheet_id = SpreadSheetId::new("sheet_id".to_string());
+        assert_eq!(
+            SheetOperation::Get.endpoint("http://localhost:8080/v4/spreadsheets", &spread_sheet_id),
+            "http://localhost:8080/v4/spreadsheets/sheet_id"
+        );
+        assert_eq!(
+            SheetOperation::BatchGet.endpoint(DEFAULT_BASE_ENDPOINT_V4, &spread_sheet_id),
+            "https://sheets.googleapis.com/v4/spreadsheets/sheet_id/values:batchGet"
+        );
+    }
+
+    #[test]
+    fn first_non_empty_row_test() {
+        let value_range = |values: Option<Vec<Vec<JsonValue>>>| ValueRange {
+            range: "Sheet1!A1:C1".to_string(),
+            major_dimension: "ROWS".to_string(),
+            values,
+        };
+
+        assert_eq!(value_range(None).first_non_empty_row(), None);
+        assert_eq!(value_range(Some(vec![])).first_non_empty_row(), None);
+        assert_eq!(value_range(Some(vec![vec![]])).first_non_empty_row(), None);
+
+        let row = vec![JsonValue::from("a"), JsonValue::from("b")];
+        assert_eq!(
+            value_range(Some(vec![row.clone()])).first_non_empty_row(),
+            Some(&row)
+        );
+        assert_eq!(
+            value_range(Some(vec![vec![], row.clone()])).first_non_empty_row(),
+            Some(&row)
+        );
+    }
+
+    #[tokio::test]
+    async fn bad_request_error_test() {
+        let body = r#"{"error":{"code":400,"message":"Unable to parse range: Foo!A1","status":"INVALID_ARGUMENT"}}"#;
+        let response =
+            reqwest::Response::from(hyper::Response::builder().status(400).body(body).unwrap());
+
+        let e = bad_request_error(response).await;
+        assert_eq!(
+            e.to_string(),
+            "sheet api error:bad request [INVALID_ARGUMENT] Unable to parse range: Foo!A1"
+        );
+        match &e {
+            SheetApiError::BadReqestError(google_error) => assert_eq!(
+                google_error,
+                &GoogleApiError {
+                    code: 400,
+                    message: "Unable to parse range: Foo!A1".to_string(),
+                    status: "INVALID_ARGUMENT".to_string(),
+                }
+            ),
+            _ => panic!("unexpected error {:?}", e),
+        }
+    }
+
+    #[test]
+    fn not_envelope_test() {
+        assert_eq!(
+            GoogleApiError::from_body(400, "bad request"),
+            GoogleApiError {
+                code: 400,
+                message: "bad request".to_string(),
+                status: "".to_string(),
+            }
+        );
+    }
+}
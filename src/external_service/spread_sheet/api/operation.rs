@@ -1,38 +1,68 @@
 use super::super::TokenManager;
+use super::retry::send_with_retry;
 use super::*;
-use reqwest::{header, Client as ReqClient, StatusCode};
+use reqwest::{header, Client as ReqClient, Response, StatusCode};
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 
-#[derive(Deserialize, Serialize, Debug, PartialEq)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct Sheet {
     pub spreadsheet_id: String,
+    pub properties: Option<SpreadSheetProperties>,
     pub sheets: Vec<SheetProperty>,
 }
 
 impl Sheet {
+    pub fn time_zone(&self) -> Option<&str> {
+        self.properties
+            .as_ref()
+            .and_then(|properties| properties.time_zone.as_ref())
+            .map(|tz| tz.as_str())
+    }
+
     pub fn find_property_by_id(&self, sheet_id: u32) -> Option<&SheetProperty> {
         self.sheets
             .iter()
             .find(|e| e.properties.sheet_id == sheet_id)
     }
 
+    /// titles of the tab sheets in order
+    pub fn sheet_names(&self) -> Vec<String> {
+        self.sheets
+            .iter()
+            .map(|e| e.properties.title.clone())
+            .collect()
+    }
+
+    /// the tab at `index` in order. 0 is the first tab
+    pub fn find_property_by_index(&self, index: usize) -> Option<&SheetProperty> {
+        self.sheets.get(index)
+    }
+
     pub fn find_property_by_name(&self, name: Option<&str>) -> Option<&SheetProperty> {
         match name {
-            None => self.sheets.get(0),
+            None => self.find_property_by_index(0),
             Some(name) => self.sheets.iter().find(|e| e.properties.title == name),
         }
     }
 }
 
-#[derive(Deserialize, Serialize, Debug, PartialEq)]
+///https://developers.google.com/sheets/api/reference/rest/v4/spreadsheets#SpreadsheetProperties
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SpreadSheetProperties {
+    pub title: String,
+    pub time_zone: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct SheetProperty {
     pub properties: SheetPropertyData,
 }
 
-#[derive(Deserialize, Serialize, Debug, PartialEq)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct SheetPropertyData {
     pub sheet_id: u32,
@@ -42,7 +72,7 @@ pub struct SheetPropertyData {
     pub grid_properties: GridProperties,
 }
 
-#[derive(Deserialize, Serialize, Debug, PartialEq)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct GridProperties {
     pub row_count: usize,
@@ -57,30 +87,22 @@ pub struct GridProperties {
 ///https://developers.google.com/sheets/api/reference/rest/v4/spreadsheets#Spreadsheet
 pub async fn get_sheet<HttpConnector>(
     client: &ReqClient,
+    base_url: &str,
     token_manager: Arc<TokenManager<HttpConnector>>,
     spread_sheet_id: &SpreadSheetId,
 ) -> Result<Sheet> {
-    let url = SheetOperation::Get.endpoint(spread_sheet_id);
+    let url = SheetOperation::Get.endpoint(base_url, spread_sheet_id);
 
     let req_header = {
         let auth_token = token_manager.current_token().load();
         request_header(auth_token.as_str()).await
     };
 
-    let response = client.get(&url).headers(req_header).send().await?;
-    let result = if response.status() == StatusCode::NOT_FOUND {
-        return Err(SheetApiError::SpreadSheetNotFoundError(format!(
-            "{}",
-            spread_sheet_id
-        )));
-    } else if response.status() == StatusCode::BAD_REQUEST {
-        let json_value: JsonValue = response.json().await?;
-        log::error!("sheet apid error :{}", json_value);
-
-        return Err(SheetApiError::BadReqestError(format!("{}", json_value)));
-    } else {
-        response.json().await?
-    };
+    let response = send_with_retry(client.get(&url).headers(req_header)).await?;
+    let result = error_for_status(response, spread_sheet_id)
+        .await?
+        .json()
+        .await?;
 
     Ok(result)
 }
@@ -113,46 +135,145 @@ pub struct ValueRange {
     pub values: Option<Vec<Vec<JsonValue>>>,
 }
 
+impl ValueRange {
+    /// the first row with any cells. the api returns an empty range as either
+    /// no `values`, `[]` or `[[]]`, and all of them are `None`
+    pub fn first_non_empty_row(&self) -> Option<&Vec<JsonValue>> {
+        self.values
+            .as_ref()
+            .and_then(|rows| rows.iter().find(|row| !row.is_empty()))
+    }
+}
+
 /// https://developers.google.com/sheets/api/reference/rest/v4/spreadsheets.values/batchGet
+/// `value_ranges` of the result are in the same order as `ranges`
 pub async fn get_sheet_value<HttpConnector>(
     client: &ReqClient,
+    base_url: &str,
     token_manager: Arc<TokenManager<HttpConnector>>,
     spread_sheet_id: &SpreadSheetId,
-    ranges: &str,
-    _major_dimension: Option<MajorDimension>,
-    _value_render_option: Option<ValueRenderOption>,
-    _date_time_render_option: Option<DateTimeRenderOption>,
+    ranges: &[String],
+    major_dimension: Option<MajorDimension>,
+    value_render_option: Option<ValueRenderOption>,
+    date_time_render_option: Option<DateTimeRenderOption>,
 ) -> Result<SheetValues> {
-    let url = SheetOperation::BatchGet.endpoint(spread_sheet_id);
+    let url = SheetOperation::BatchGet.endpoint(base_url, spread_sheet_id);
 
     let req_header = {
         let auth_token = token_manager.current_token().load();
         request_header(auth_token.as_str()).await
     };
-    let query_param = vec![("ranges", ranges)];
+    let mut query_param: Vec<(&str, String)> = ranges
+        .iter()
+        .map(|range| ("ranges", range.to_string()))
+        .collect();
+    if let Some(major_dimension) = major_dimension {
+        query_param.push(("majorDimension", major_dimension.to_string()));
+    }
+    if let Some(value_render_option) = value_render_option {
+        query_param.push(("valueRenderOption", value_render_option.to_string()));
+    }
+    if let Some(date_time_render_option) = date_time_render_option {
+        query_param.push(("dateTimeRenderOption", date_time_render_option.to_string()));
+    }
+
+    let response =
+        send_with_retry(client.get(&url).headers(req_header).query(&query_param)).await?;
+
+    let result = error_for_status(response, spread_sheet_id)
+        .await?
+        .json()
+        .await?;
+
+    Ok(result)
+}
+
+#[derive(Deserialize, Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AppendValuesResponse {
+    pub spreadsheet_id: String,
+    pub table_range: Option<String>,
+    pub updates: Option<UpdateValuesResponse>,
+}
+
+#[derive(Deserialize, Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateValuesResponse {
+    pub updated_range: Option<String>,
+    pub updated_rows: Option<usize>,
+    pub updated_columns: Option<usize>,
+    pub updated_cells: Option<usize>,
+}
+
+/// https://developers.google.com/sheets/api/reference/rest/v4/spreadsheets.values/append
+/// the values are stored as they are (not parsed as formulas)
+pub async fn append_values<HttpConnector>(
+    client: &ReqClient,
+    base_url: &str,
+    token_manager: Arc<TokenManager<HttpConnector>>,
+    spread_sheet_id: &SpreadSheetId,
+    range: &str,
+    values: Vec<Vec<JsonValue>>,
+) -> Result<AppendValuesResponse> {
+    let url = SheetOperation::Append(range.to_string()).endpoint(base_url, spread_sheet_id);
+
+    let req_header = {
+        let auth_token = token_manager.current_token().load();
+        request_header(auth_token.as_str()).await
+    };
+    let query_param = vec![
+        ("valueInputOption", "RAW"),
+        ("insertDataOption", "INSERT_ROWS"),
+    ];
+    let body = ValueRange {
+        range: range.to_string(),
+        major_dimension: MajorDimension::Rows.to_string(),
+        values: Some(values),
+    };
 
     let response = client
-        .get(&url)
+        .post(&url)
         .headers(req_header)
         .query(&query_param)
+        .json(&body)
         .send()
         .await?;
 
-    let result = if response.status() == StatusCode::NOT_FOUND {
-        return Err(SheetApiError::SpreadSheetNotFoundError(format!(
+    let result = error_for_status(response, spread_sheet_id)
+        .await?
+        .json()
+        .await?;
+
+    Ok(result)
+}
+
+/// the errors of google are mapped before decoding the body.
+/// 401 and 403 are passed through as they are, and so is 429 that is left after the retries
+async fn error_for_status(response: Response, spread_sheet_id: &SpreadSheetId) -> Result<Response> {
+    match response.status() {
+        StatusCode::NOT_FOUND => Err(SheetApiError::SpreadSheetNotFoundError(format!(
             "{}",
             spread_sheet_id
-        )));
-    } else if response.status() == StatusCode::BAD_REQUEST {
-        let json_value: JsonValue = response.json().await?;
-        log::error!("sheet apid error :{}", json_value);
-
-        return Err(SheetApiError::BadReqestError(format!("{}", json_value)));
-    } else {
-        response.json().await?
-    };
+        ))),
+        StatusCode::BAD_REQUEST => Err(SheetApiError::BadReqestError(
+            google_api_error(response).await?,
+        )),
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Err(
+            SheetApiError::PermissionDeniedError(google_api_error(response).await?),
+        ),
+        StatusCode::TOO_MANY_REQUESTS => Err(SheetApiError::RateLimitError(
+            google_api_error(response).await?,
+        )),
+        _ => Ok(response),
+    }
+}
 
-    Ok(result)
+/// parses google's error envelope of an error response
+async fn google_api_error(response: Response) -> Result<GoogleApiError> {
+    let http_status = response.status().as_u16();
+    let body = response.text().await?;
+    log::error!("sheet api error :{}", body);
+    Ok(GoogleApiError::from_body(http_status, &body))
 }
 
 async fn request_header(token: &str) -> header::HeaderMap {
@@ -193,6 +314,7 @@ mod test {
 
         let result = get_sheet(
             &client,
+            DEFAULT_BASE_ENDPOINT_V4,
             token_manager,
             &SpreadSheetId::new(sheet_id.to_string()),
         )
@@ -271,10 +393,19 @@ mod test {
 
         let expected = Sheet {
             spreadsheet_id: "1HA4munsvl5UUlb9DKmJvhrwfGlSQ97hSQZf13M3ZO4Y".to_string(),
+            properties: None,
             sheets,
         };
 
-        assert_eq!(expected, result.unwrap());
+        let result = result.unwrap();
+        assert!(result.time_zone().is_some());
+        assert_eq!(
+            expected,
+            Sheet {
+                properties: None,
+                ..result
+            }
+        );
     }
 
     fn jstr(s: &str) -> JsonValue {
@@ -296,13 +427,14 @@ mod test {
 
         let client = Client::new();
         let sheet_id = TEST_SHEET1_ID;
-        let ranges = "A1:J4";
+        let ranges = vec!["A1:J4".to_string()];
 
         let result = get_sheet_value(
             &client,
+            DEFAULT_BASE_ENDPOINT_V4,
             token_manager,
             &SpreadSheetId::new(sheet_id.to_string()),
-            ranges,
+            &ranges,
             None,
             None,
             None,
@@ -385,6 +517,7 @@ mod test {
 
         let result = get_sheet(
             &client,
+            DEFAULT_BASE_ENDPOINT_V4,
             token_manager,
             &SpreadSheetId::new(sheet_id.to_string()),
         )
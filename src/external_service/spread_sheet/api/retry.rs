@@ -0,0 +1,134 @@
+use reqwest::{Error as ReqError, RequestBuilder, Response, StatusCode};
+use std::time::Duration;
+
+/// attempts including the first one
+const MAX_ATTEMPTS: usize = 3;
+/// doubled on each retry
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(100);
+
+/// timeouts, connection failures (refused, reset, dns) and the connections closed before the response.
+/// errors of building the request or decoding the response are not retried
+pub fn is_retryable_error(e: &ReqError) -> bool {
+    e.is_timeout() || e.is_connect() || e.is_request()
+}
+
+/// quota exceeded or transient failures of google
+pub fn is_retryable_status(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS
+        || status == StatusCode::INTERNAL_SERVER_ERROR
+        || status == StatusCode::BAD_GATEWAY
+        || status == StatusCode::SERVICE_UNAVAILABLE
+        || status == StatusCode::GATEWAY_TIMEOUT
+}
+
+/// sends `request` and retries on the retryable errors and statuses.
+/// only for idempotent requests. the response of the last attempt is returned as it is
+pub async fn send_with_retry(request: RequestBuilder) -> Result<Response, ReqError> {
+    let mut delay = RETRY_BASE_DELAY;
+    for attempt in 1..MAX_ATTEMPTS {
+        // a request with a streaming body can't be cloned, so it is sent only once
+        let each_request = match request.try_clone() {
+            None => break,
+            Some(each_request) => each_request,
+        };
+
+        match each_request.send().await {
+            Ok(response) if !is_retryable_status(response.status()) => return Ok(response),
+            Ok(response) => log::warn!(
+                "sheet api returned {}. retrying ({}/{})",
+                response.status(),
+                attempt,
+                MAX_ATTEMPTS
+            ),
+            Err(e) if is_retryable_error(&e) => log::warn!(
+                "sheet api request failed:{}. retrying ({}/{})",
+                e,
+                attempt,
+                MAX_ATTEMPTS
+            ),
+            Err(e) => return Err(e),
+        }
+        tokio::time::sleep(delay).await;
+        delay *= 2;
+    }
+    request.send().await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use reqwest::Client;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// drops the first `drop_count` connections, then responds `{}` with `status`
+    fn mock_server(drop_count: usize, status: &'static str) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let accepted = Arc::new(AtomicUsize::new(0));
+        let accepted_in_server = accepted.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut buf = [0u8; 1024];
+                let _ = stream.read(&mut buf);
+                let count = accepted_in_server.fetch_add(1, Ordering::SeqCst);
+                if count < drop_count {
+                    drop(stream);
+                    continue;
+                }
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{{}}",
+                    status
+                );
+            }
+        });
+        (format!("http://{}/", addr), accepted)
+    }
+
+    #[tokio::test]
+    async fn retry_dropped_connection_test() {
+        let (url, accepted) = mock_server(1, "200 OK");
+        let response = send_with_retry(Client::new().get(&url)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(accepted.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn give_up_retrying_test() {
+        let (url, accepted) = mock_server(MAX_ATTEMPTS, "200 OK");
+        let result = send_with_retry(Client::new().get(&url)).await;
+        assert!(is_retryable_error(&result.unwrap_err()));
+        assert_eq!(accepted.load(Ordering::SeqCst), MAX_ATTEMPTS);
+    }
+
+    #[tokio::test]
+    async fn retry_status_test() {
+        // the last response is returned as it is
+        let (url, accepted) = mock_server(0, "503 Service Unavailable");
+        let response = send_with_retry(Client::new().get(&url)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(accepted.load(Ordering::SeqCst), MAX_ATTEMPTS);
+
+        // not retried
+        let (url, accepted) = mock_server(0, "404 Not Found");
+        let response = send_with_retry(Client::new().get(&url)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(accepted.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn connection_refused_test() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        drop(listener);
+
+        let result = send_with_retry(Client::new().get(&url)).await;
+        let e = result.unwrap_err();
+        assert!(e.is_connect());
+        assert!(is_retryable_error(&e));
+    }
+}
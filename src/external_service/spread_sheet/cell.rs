@@ -22,7 +22,10 @@ pub(crate) fn valid_quote_cell_value_regex() -> &'static Regex {
 
 pub struct Cell;
 impl Cell {
+    /// a single leading `'` is the text format marker of the spread sheet (e.g. `'123`) and stripped.
+    /// the apostrophes elsewhere are kept
     pub fn sanitize_str_value(s: &str) -> Result<String> {
+        let s = s.strip_prefix('\'').unwrap_or(s);
         let re = valid_quote_cell_value_regex();
 
         re.captures(s).map_or_else(
@@ -70,4 +73,17 @@ mod test {
             );
         }
     }
+
+    #[test]
+    fn cell_sanitize_leading_apostrophe_test() {
+        assert_eq!(Ok("name".to_string()), Cell::sanitize_str_value("'name"));
+        assert_eq!(Ok("123".to_string()), Cell::sanitize_str_value("'123"));
+        assert_eq!(Ok("don't".to_string()), Cell::sanitize_str_value("don't"));
+        assert_eq!(Ok("name'".to_string()), Cell::sanitize_str_value("name'"));
+
+        // only the first one is the marker
+        assert_eq!(Ok("'".to_string()), Cell::sanitize_str_value("''"));
+        assert_eq!(Ok("'name".to_string()), Cell::sanitize_str_value("''name"));
+        assert_eq!(Ok("".to_string()), Cell::sanitize_str_value("'"));
+    }
 }
@@ -0,0 +1,1811 @@
+use super::*;
+
+/// reads and writes the spread sheets with its own http client and token manager.
+/// the free functions like `fetch_sheet_value` are the same as the methods of `SheetsClient::new`
+pub struct SheetsClient<HttpConnector> {
+    client: ReqClient,
+    token_manager: Arc<TokenManager<HttpConnector>>,
+    base_url: String,
+    sheet_cache: Option<Arc<SheetCache>>,
+    fresh_sheet: bool,
+}
+
+impl<HttpConnector> Clone for SheetsClient<HttpConnector> {
+    fn clone(&self) -> Self {
+        Self {
+            client: self.client.clone(),
+            token_manager: self.token_manager.clone(),
+            base_url: self.base_url.clone(),
+            sheet_cache: self.sheet_cache.clone(),
+            fresh_sheet: self.fresh_sheet,
+        }
+    }
+}
+
+impl<HttpConnector> SheetsClient<HttpConnector> {
+    /// with the shared http client configured by `init_reqwest_client`
+    /// and the shared cache of the sheets by `init_sheet_cache`
+    pub fn new(token_manager: Arc<TokenManager<HttpConnector>>) -> Self {
+        Self::with_client(reqwest_client().clone(), token_manager).with_sheet_cache(sheet_cache())
+    }
+
+    /// without the cache of the sheets
+    pub fn with_client(client: ReqClient, token_manager: Arc<TokenManager<HttpConnector>>) -> Self {
+        Self {
+            client,
+            token_manager,
+            base_url: DEFAULT_BASE_ENDPOINT_V4.to_string(),
+            sheet_cache: None,
+            fresh_sheet: false,
+        }
+    }
+
+    /// `DEFAULT_BASE_ENDPOINT_V4` unless set. e.g. a mock server or a proxy
+    pub fn with_base_url(mut self, base_url: String) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn with_sheet_cache(mut self, sheet_cache: Option<Arc<SheetCache>>) -> Self {
+        self.sheet_cache = sheet_cache;
+        self
+    }
+
+    /// the sheet is always fetched bypassing the cache. the fetched one is still cached
+    pub fn with_fresh_sheet(mut self, fresh_sheet: bool) -> Self {
+        self.fresh_sheet = fresh_sheet;
+        self
+    }
+
+    pub fn token_manager(&self) -> Arc<TokenManager<HttpConnector>> {
+        self.token_manager.clone()
+    }
+
+    pub async fn get_sheet(
+        &self,
+        spread_sheet_id: &SpreadSheetId,
+    ) -> std::result::Result<Sheet, SheetApiError> {
+        self.get_sheet_or_cached(spread_sheet_id, !self.fresh_sheet)
+            .await
+            .map(|(sheet, _)| sheet)
+    }
+
+    /// returns the sheet and whether it's from the cache
+    async fn get_sheet_or_cached(
+        &self,
+        spread_sheet_id: &SpreadSheetId,
+        use_cached: bool,
+    ) -> std::result::Result<(Sheet, bool), SheetApiError> {
+        let sheet_cache = match self.sheet_cache.as_ref() {
+            None => {
+                return get_sheet(
+                    &self.client,
+                    &self.base_url,
+                    self.token_manager.clone(),
+                    spread_sheet_id,
+                )
+                .await
+                .map(|sheet| (sheet, false))
+            }
+            Some(sheet_cache) => sheet_cache,
+        };
+
+        if use_cached {
+            if let Some(sheet) = sheet_cache.get(spread_sheet_id) {
+                return Ok((sheet, true));
+            }
+        }
+
+        match get_sheet(
+            &self.client,
+            &self.base_url,
+            self.token_manager.clone(),
+            spread_sheet_id,
+        )
+        .await
+        {
+            Ok(sheet) => {
+                sheet_cache.insert(spread_sheet_id.clone(), &sheet);
+                Ok((sheet, false))
+            }
+            Err(e) => {
+                if e.is_not_found() {
+                    sheet_cache.invalidate(spread_sheet_id);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// the sheet is fetched again if the tab is not found in the cached one,
+    /// since the tab may be added or renamed after it's cached
+    #[tracing::instrument(
+        name = "create_header_condition",
+        skip_all,
+        fields(spread_sheet_id = %sheet_meta.spread_sheet_id, cached = tracing::field::Empty)
+    )]
+    pub async fn create_header_condition_from_sheet_meta(
+        &self,
+        sheet_meta: SheetMeta,
+        specified_cell_range: Option<(CellRef, CellRef)>,
+        header_option: HeaderOption,
+    ) -> Result<HeaderSearchCondition> {
+        let spread_sheet_id = SpreadSheetId::new(sheet_meta.spread_sheet_id.clone());
+        let (sheet_info, cached) = self
+            .get_sheet_or_cached(&spread_sheet_id, !self.fresh_sheet)
+            .await
+            .map_err(|e| sheet_info_error(e, &spread_sheet_id))?;
+        tracing::Span::current().record("cached", &cached);
+
+        let header_condition = match HeaderSearchCondition::from_sheet_info(
+            sheet_info,
+            sheet_meta.clone(),
+            specified_cell_range.clone(),
+            header_option.clone(),
+        ) {
+            Err(HeaderError::SheetNameNotFound(_, _))
+            | Err(HeaderError::FetchSheetNameError(_))
+            | Err(HeaderError::SheetIndexOutOfRange(_, _))
+                if cached =>
+            {
+                let (sheet_info, _) = self
+                    .get_sheet_or_cached(&spread_sheet_id, false)
+                    .await
+                    .map_err(|e| sheet_info_error(e, &spread_sheet_id))?;
+                HeaderSearchCondition::from_sheet_info(
+                    sheet_info,
+                    sheet_meta,
+                    specified_cell_range,
+                    header_option,
+                )?
+            }
+            result => result?,
+        };
+        Ok(header_condition)
+    }
+
+    /// returns the tabs of the spread sheet ordered by its index
+    pub async fn list_sheet_tabs(
+        &self,
+        spread_sheet_id: &SpreadSheetId,
+    ) -> std::result::Result<Vec<SheetTab>, SheetApiError> {
+        let sheet = self.get_sheet(spread_sheet_id).await?;
+
+        let mut tabs: Vec<SheetTab> = sheet
+            .sheets
+            .iter()
+            .map(|sheet_property| SheetTab::from(&sheet_property.properties))
+            .collect();
+        tabs.sort_by_key(|tab| tab.index);
+        Ok(tabs)
+    }
+
+    pub async fn fetch_sheet_value(
+        &self,
+        header_search_condition: &HeaderSearchCondition,
+        row_search_condition: &FetchRowCondition,
+    ) -> Result<SheetValueResponse> {
+        let headers = self.read_sheet_headers(header_search_condition).await?;
+        self.fetch_sheet_value_with_headers(header_search_condition, headers, row_search_condition)
+            .await
+    }
+
+    /// appends `row` (a json object keyed by the headers) below the last row of the table.
+    /// the token manager must have `scopes::SHEET_READ_WRITE`
+    pub async fn append_sheet_row(
+        &self,
+        header_search_condition: &HeaderSearchCondition,
+        row: &JsonValue,
+    ) -> Result<AppendValuesResponse> {
+        let headers = self.read_sheet_headers(header_search_condition).await?;
+        let row_values = headers
+            .row_from_json(row)
+            .map_err(ValueError::InvalidRowJson)?;
+
+        let result = append_values(
+            &self.client,
+            &self.base_url,
+            self.token_manager.clone(),
+            &header_search_condition.spread_sheet_id,
+            &headers.range.as_string(),
+            vec![row_values],
+        )
+        .await
+        .map_err(|e| {
+            if e.is_not_found() {
+                ValueError::SpreadSheetNotFound(format!("{}", e))
+            } else if let Some(google_error) = e.access_error() {
+                ValueError::SheetApiAccessError(google_error.clone())
+            } else {
+                ValueError::AppendValueApiError(format!("{}", e))
+            }
+        })?;
+
+        // the grid grows by the inserted rows
+        if let Some(sheet_cache) = self.sheet_cache.as_ref() {
+            sheet_cache.invalidate(&header_search_condition.spread_sheet_id);
+        }
+        Ok(result)
+    }
+
+    pub async fn read_sheet_headers(
+        &self,
+        header_search_condition: &HeaderSearchCondition,
+    ) -> Result<RawHeaders> {
+        let headers = RawHeaders::read_raw_headers(
+            &self.client,
+            &self.base_url,
+            self.token_manager.clone(),
+            header_search_condition,
+        )
+        .await?;
+        Ok(headers)
+    }
+
+    /// number of the records of the grid without reading the values.
+    /// the rows below the headers, or the columns right of the labels in the key-value orientation.
+    /// it's counted from the grid size, so the empty rows at the bottom of the grid are counted too
+    /// (it's the same as `total_rows` of `fetch_sheet_value`).
+    /// `accurate_rows` of `row_search_condition` counts up to the last non-empty cell of the first header column instead,
+    /// with one more request scanning the column.
+    /// the records beyond `last_row_idx` (`last_col_idx` in the key-value orientation) are not counted
+    pub async fn count_sheet_records(
+        &self,
+        header_search_condition: &HeaderSearchCondition,
+        row_search_condition: &FetchRowCondition,
+    ) -> Result<usize> {
+        let headers = self.read_sheet_headers(header_search_condition).await?;
+
+        let sheet_name = header_search_condition
+            .sheet_name
+            .as_ref()
+            .map(|s| s.as_str());
+        match header_search_condition
+            .sheet_info
+            .find_property_by_name(sheet_name)
+        {
+            None => Err(HeaderError::UnknwonError(format!(
+                "sheet info not found:{:?}",
+                sheet_name
+            )))?,
+            Some(property) => {
+                let grid_properties = &property.properties.grid_properties;
+                let grid_to_read = GridProperties {
+                    row_count: row_search_condition.row_count_to_read(grid_properties.row_count),
+                    column_count: row_search_condition
+                        .col_count_to_read(grid_properties.column_count),
+                    ..grid_properties.clone()
+                };
+                let records_in_grid = count_records_in_grid(
+                    header_search_condition.header_option.orientation,
+                    &grid_to_read,
+                    &headers.range,
+                );
+                self.records_in_use(
+                    header_search_condition,
+                    &headers,
+                    records_in_grid,
+                    row_search_condition.accurate_rows(),
+                )
+                .await
+            }
+        }
+    }
+
+    /// `records_in_grid` as it is, or bounded by the last non-empty cell of the first header column
+    /// (the first label row in the key-value orientation) if `accurate_rows`
+    async fn records_in_use(
+        &self,
+        header_search_condition: &HeaderSearchCondition,
+        headers: &RawHeaders,
+        records_in_grid: usize,
+        accurate_rows: bool,
+    ) -> Result<usize> {
+        if !accurate_rows {
+            return Ok(records_in_grid);
+        }
+        let orientation = header_search_condition.header_option.orientation;
+        let start = if orientation == HeaderOrientation::KeyValue {
+            CellRef::new(
+                headers.range.next_col_index(),
+                headers.range.start.row_index,
+            )
+        } else {
+            CellRef::new(
+                headers.range.start.col_index,
+                headers.range.next_row_index(),
+            )
+        };
+        let records = RowValues::count_records_to_last_value(
+            &self.client,
+            &self.base_url,
+            self.token_manager.clone(),
+            &header_search_condition.spread_sheet_id,
+            header_search_condition.sheet_name.clone(),
+            orientation,
+            start,
+            records_in_grid,
+        )
+        .await?;
+        Ok(records)
+    }
+
+    /// reads the rows below the `headers` which are read by `read_sheet_headers`
+    pub async fn fetch_sheet_value_with_headers(
+        &self,
+        header_search_condition: &HeaderSearchCondition,
+        headers: RawHeaders,
+        row_search_condition: &FetchRowCondition,
+    ) -> Result<SheetValueResponse> {
+        let mut response = self
+            .fetch_records_with_headers(header_search_condition, headers, row_search_condition)
+            .await?;
+        if !row_search_condition.include_empty_rows() {
+            response.row_values.drop_empty_rows();
+        }
+        Ok(response)
+    }
+
+    async fn fetch_records_with_headers(
+        &self,
+        header_search_condition: &HeaderSearchCondition,
+        headers: RawHeaders,
+        row_search_condition: &FetchRowCondition,
+    ) -> Result<SheetValueResponse> {
+        if header_search_condition.header_option.orientation == HeaderOrientation::KeyValue {
+            return self
+                .fetch_key_value_records(header_search_condition, headers, row_search_condition)
+                .await;
+        }
+
+        let value_col_range = headers.range.col_range_indices();
+
+        let (start_row_idx, finish_row_idx, pagination_in_response) =
+            row_search_condition.row_index_window(headers.range.next_row_index());
+
+        let max_row_count_of_grid = {
+            let sheet_name = header_search_condition
+                .sheet_name
+                .as_ref()
+                .map(|s| s.as_str());
+            match header_search_condition
+                .sheet_info
+                .find_property_by_name(sheet_name)
+            {
+                None => {
+                    return Err(HeaderError::UnknwonError(format!(
+                        "sheet info not found:{:?}",
+                        sheet_name
+                    )))?
+                }
+                Some(property) => row_search_condition
+                    .row_count_to_read(property.properties.grid_properties.row_count),
+            }
+        };
+
+        if let Some(data_ranges) = row_search_condition.data_ranges(headers.range.next_row_index())
+        {
+            let ranges_option = ReadRangesOption::new(
+                header_search_condition.spread_sheet_id.clone(),
+                header_search_condition.sheet_name.clone(),
+                data_ranges,
+            )
+            .with_max_row_num(row_search_condition.row_limits().max_limit)
+            .with_unformatted_values(row_search_condition.unformatted_values)
+            .with_formulas(row_search_condition.formulas)
+            .with_restrictions(header_search_condition.header_option.restrictions);
+
+            let row_values = RowValues::read_ranges(
+                &self.client,
+                &self.base_url,
+                self.token_manager.clone(),
+                &ranges_option,
+            )
+            .await?;
+
+            let total_rows = row_values.values.len();
+            return Ok(SheetValueResponse {
+                headers,
+                row_values,
+                pagination: None,
+                total_rows: Some(total_rows),
+                rows_in_window: None,
+            });
+        }
+
+        let records_in_grid = max_row_count_of_grid.saturating_sub(headers.range.next_row_index());
+        let total_rows = self
+            .records_in_use(
+                header_search_condition,
+                &headers,
+                records_in_grid,
+                row_search_condition.accurate_rows(),
+            )
+            .await?;
+        let max_row_count_of_grid = headers.range.next_row_index() + total_rows;
+
+        if let Some(row_indices) = row_search_condition
+            .row_indices_in_grid(headers.range.next_row_index(), max_row_count_of_grid)
+        {
+            let rows_option = ReadRowsOption::new(
+                header_search_condition.spread_sheet_id.clone(),
+                header_search_condition.sheet_name.clone(),
+                value_col_range,
+                row_indices,
+            )
+            .with_max_row_num(row_search_condition.row_limits().max_limit)
+            .with_unformatted_values(row_search_condition.unformatted_values)
+            .with_formulas(row_search_condition.formulas)
+            .with_pad(row_search_condition.pad)
+            .with_restrictions(header_search_condition.header_option.restrictions);
+
+            let row_values = RowValues::read_rows(
+                &self.client,
+                &self.base_url,
+                self.token_manager.clone(),
+                &rows_option,
+            )
+            .await?;
+
+            return Ok(SheetValueResponse {
+                headers,
+                row_values,
+                pagination: None,
+                total_rows: Some(total_rows),
+                rows_in_window: None,
+            });
+        }
+
+        // the rows beyond the grid are never returned
+        let (row_values, rows_in_window) =
+            match last_index_in_grid(start_row_idx, finish_row_idx, max_row_count_of_grid) {
+                None => (RowValues::empty(), 0),
+                Some(last_row_idx) => {
+                    let value_option = ReadValueOption::new(
+                        header_search_condition.spread_sheet_id.clone(),
+                        header_search_condition.sheet_name.clone(),
+                        value_col_range,
+                        start_row_idx,
+                        last_row_idx,
+                    )
+                    .with_max_row_num(row_search_condition.row_limits().max_limit)
+                    .with_unformatted_values(row_search_condition.unformatted_values)
+                    .with_formulas(row_search_condition.formulas)
+                    .with_pad(row_search_condition.pad)
+                    .with_restrictions(header_search_condition.header_option.restrictions);
+
+                    let row_values = RowValues::read_values(
+                        &self.client,
+                        &self.base_url,
+                        self.token_manager.clone(),
+                        &value_option,
+                    )
+                    .await?;
+
+                    // trailing empty rows are omitted by the api. the next page starts after the window
+                    (row_values, last_row_idx + 1 - start_row_idx)
+                }
+            };
+
+        let total_rows = self
+            .records_of_page(
+                header_search_condition,
+                &headers,
+                &row_values,
+                rows_in_window,
+                pagination_in_response.is_some(),
+                total_rows,
+                row_search_condition.accurate_rows(),
+            )
+            .await?;
+
+        Ok(SheetValueResponse {
+            headers,
+            pagination: pagination_in_response
+                .map(|pagination| pagination.with_total_rows(total_rows, rows_in_window)),
+            row_values,
+            total_rows: Some(total_rows),
+            rows_in_window: Some(rows_in_window),
+        })
+    }
+
+    /// reads the columns right of the labels as the records.
+    /// the offset and the limit of `row_search_condition` are applied to the columns
+    async fn fetch_key_value_records(
+        &self,
+        header_search_condition: &HeaderSearchCondition,
+        headers: RawHeaders,
+        row_search_condition: &FetchRowCondition,
+    ) -> Result<SheetValueResponse> {
+        let (start_col_idx, finish_col_idx, pagination_in_response) =
+            row_search_condition.row_index_window(headers.range.next_col_index());
+
+        let max_col_count_of_grid = {
+            let sheet_name = header_search_condition
+                .sheet_name
+                .as_ref()
+                .map(|s| s.as_str());
+            match header_search_condition
+                .sheet_info
+                .find_property_by_name(sheet_name)
+            {
+                None => {
+                    return Err(HeaderError::UnknwonError(format!(
+                        "sheet info not found:{:?}",
+                        sheet_name
+                    )))?
+                }
+                Some(property) => row_search_condition
+                    .col_count_to_read(property.properties.grid_properties.column_count),
+            }
+        };
+
+        let records_in_grid = max_col_count_of_grid.saturating_sub(headers.range.next_col_index());
+        let total_records = self
+            .records_in_use(
+                header_search_condition,
+                &headers,
+                records_in_grid,
+                row_search_condition.accurate_rows(),
+            )
+            .await?;
+        let max_col_count_of_grid = headers.range.next_col_index() + total_records;
+
+        let (row_values, cols_in_window) =
+            match last_index_in_grid(start_col_idx, finish_col_idx, max_col_count_of_grid) {
+                None => (RowValues::empty(), 0),
+                Some(last_col_idx) => {
+                    let columns_option = ReadColumnsOption::new(
+                        header_search_condition.spread_sheet_id.clone(),
+                        header_search_condition.sheet_name.clone(),
+                        headers.range.row_range_indices(),
+                        start_col_idx,
+                        last_col_idx,
+                    )
+                    .with_max_col_num(row_search_condition.row_limits().max_limit)
+                    .with_unformatted_values(row_search_condition.unformatted_values)
+                    .with_formulas(row_search_condition.formulas)
+                    .with_restrictions(header_search_condition.header_option.restrictions);
+
+                    let row_values = RowValues::read_columns(
+                        &self.client,
+                        &self.base_url,
+                        self.token_manager.clone(),
+                        &columns_option,
+                    )
+                    .await?;
+                    (row_values, last_col_idx + 1 - start_col_idx)
+                }
+            };
+
+        let total_records = self
+            .records_of_page(
+                header_search_condition,
+                &headers,
+                &row_values,
+                cols_in_window,
+                pagination_in_response.is_some(),
+                total_records,
+                row_search_condition.accurate_rows(),
+            )
+            .await?;
+
+        Ok(SheetValueResponse {
+            headers,
+            pagination: pagination_in_response
+                .map(|pagination| pagination.with_total_rows(total_records, cols_in_window)),
+            row_values,
+            total_rows: Some(total_records),
+            rows_in_window: Some(cols_in_window),
+        })
+    }
+
+    /// `total_records` as it is, if the api returned the records up to the end of the window.
+    /// otherwise the page reached the last record, or it's of the empty rows or of a sheet without records,
+    /// then the records are counted up to the last value to tell them apart
+    async fn records_of_page(
+        &self,
+        header_search_condition: &HeaderSearchCondition,
+        headers: &RawHeaders,
+        row_values: &RowValues,
+        records_in_window: usize,
+        paginated: bool,
+        total_records: usize,
+        accurate_rows: bool,
+    ) -> Result<usize> {
+        let read_records = row_values.values.len();
+        if (read_records > 0 && read_records >= records_in_window) || !paginated || accurate_rows {
+            return Ok(total_records);
+        }
+        self.records_in_use(header_search_condition, headers, total_records, true)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::{
+        extract::{Extension, Path, Query},
+        http::{header, HeaderMap, StatusCode},
+        routing::{get, post},
+        AddExtensionLayer, Json, Router,
+    };
+    use once_cell::sync::Lazy;
+    use serde_json::json;
+    use std::str::FromStr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+    use std::time::Duration;
+    use yup_oauth2 as oauth;
+
+    /// the key of the mock service account, generated once per test run.
+    /// the tokens are issued by the mock server, so any key is accepted
+    static MOCK_SA_PRIVATE_KEY: Lazy<String> = Lazy::new(|| {
+        use rsa::pkcs8::{EncodePrivateKey, LineEnding};
+        rsa::RsaPrivateKey::new(&mut rand::thread_rng(), 2048)
+            .unwrap()
+            .to_pkcs8_pem(LineEnding::LF)
+            .unwrap()
+            .to_string()
+    });
+    const MOCK_TOKEN: &str = "mock-token";
+    const MOCK_SPREAD_SHEET_ID: &str = "mock-sheet";
+
+    /// the lines written by the fmt subscriber of the tests
+    #[derive(Clone, Default)]
+    struct CapturedLogs(Arc<Mutex<Vec<u8>>>);
+
+    impl CapturedLogs {
+        fn contents(&self) -> String {
+            String::from_utf8_lossy(&self.0.lock().unwrap()).to_string()
+        }
+    }
+
+    impl std::io::Write for CapturedLogs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// a tab of the mock spread sheet. `rows` are the formatted values from A1.
+    /// `formulas` are at the same positions as `rows`, and empty for the cells without formulas
+    struct MockTab {
+        sheet_id: u32,
+        title: &'static str,
+        index: usize,
+        row_count: usize,
+        column_count: usize,
+        rows: Vec<Vec<String>>,
+        formulas: Vec<Vec<String>>,
+    }
+
+    impl MockTab {
+        fn properties(&self) -> JsonValue {
+            json!({"properties": {
+                "sheetId": self.sheet_id, "title": self.title, "index": self.index, "sheetType": "GRID",
+                "gridProperties": {"rowCount": self.row_count, "columnCount": self.column_count},
+            }})
+        }
+
+        /// the formula of the cell instead of its value with `valueRenderOption=FORMULA`
+        fn cell(&self, col_idx: usize, row_idx: usize, formula: bool) -> String {
+            let cell_of = |lines: &Vec<Vec<String>>| {
+                lines
+                    .get(row_idx)
+                    .and_then(|row| row.get(col_idx))
+                    .filter(|cell| !cell.is_empty())
+                    .cloned()
+            };
+            formula
+                .then(|| cell_of(&self.formulas))
+                .flatten()
+                .or_else(|| cell_of(&self.rows))
+                .unwrap_or_default()
+        }
+
+        /// the value range of `range` clamped to the grid as the api returns.
+        /// the trailing empty cells and lines are omitted, and `values` too if all of them are empty
+        fn value_range(&self, range: &RangeRef, by_columns: bool, formula: bool) -> JsonValue {
+            let end = CellRef::new(
+                range.end.col_index.min(self.column_count - 1),
+                range.end.row_index.min(self.row_count - 1),
+            );
+            let (cols, rows) = (
+                range.start.col_index..=end.col_index,
+                range.start.row_index..=end.row_index,
+            );
+            let lines: Vec<Vec<String>> = if by_columns {
+                cols.map(|col_idx| {
+                    rows.clone()
+                        .map(|row_idx| self.cell(col_idx, row_idx, formula))
+                        .collect()
+                })
+                .collect()
+            } else {
+                rows.map(|row_idx| {
+                    cols.clone()
+                        .map(|col_idx| self.cell(col_idx, row_idx, formula))
+                        .collect()
+                })
+                .collect()
+            };
+
+            let mut lines: Vec<Vec<String>> = lines
+                .into_iter()
+                .map(|mut line| {
+                    while line.last().map(|cell| cell.is_empty()).unwrap_or(false) {
+                        line.pop();
+                    }
+                    line
+                })
+                .collect();
+            while lines.last().map(|line| line.is_empty()).unwrap_or(false) {
+                lines.pop();
+            }
+
+            let returned_range =
+                RangeRef::new(Some(self.title.to_string()), range.start.clone(), end);
+            let mut value_range = json!({
+                "range": returned_range.as_string(),
+                "majorDimension": if by_columns { "COLUMNS" } else { "ROWS" },
+            });
+            if !lines.is_empty() {
+                value_range["values"] = json!(lines);
+            }
+            value_range
+        }
+    }
+
+    fn mock_row(cells: &[&str]) -> Vec<String> {
+        cells.iter().map(|cell| cell.to_string()).collect()
+    }
+
+    /// the state of the mock server
+    #[derive(Default)]
+    struct MockSheets {
+        tabs: Mutex<Vec<MockTab>>,
+        get_sheet_calls: AtomicUsize,
+        /// `ranges` of each `values:batchGet` request
+        batch_get_ranges: Mutex<Vec<Vec<String>>>,
+    }
+
+    impl MockSheets {
+        /// "first" has the records with blank rows between them in the 1000 rows grid.
+        /// "second" is filled up to the end of its 10 rows grid.
+        /// "long" has 60 records in the 1000 rows grid, and "header_only" has no records.
+        /// "formula" has the totals computed by the formulas
+        fn new() -> Self {
+            let first_rows = vec![
+                mock_row(&["name", "age", "city"]),
+                mock_row(&["Alice", "21", "kyoto"]),
+                vec![],
+                mock_row(&["Bob", "34", "tokyo"]),
+                vec![],
+                mock_row(&["Carol", "45", "osaka"]),
+                mock_row(&["David", "52", "nara"]),
+            ];
+            let mut second_rows = vec![mock_row(&["name", "age", "city"])];
+            second_rows.extend((1..10).map(|n| {
+                vec![
+                    format!("user{}", n),
+                    format!("{}", 20 + n),
+                    format!("city{}", n),
+                ]
+            }));
+            let mut long_rows = vec![mock_row(&["name"])];
+            long_rows.extend((0..60).map(|n| vec![format!("row{}", n)]));
+
+            Self {
+                // not ordered by the index as the api may return
+                tabs: Mutex::new(vec![
+                    MockTab {
+                        sheet_id: 1,
+                        title: "second",
+                        index: 1,
+                        row_count: 10,
+                        column_count: 3,
+                        rows: second_rows,
+                        formulas: vec![],
+                    },
+                    MockTab {
+                        sheet_id: 0,
+                        title: "first",
+                        index: 0,
+                        row_count: 1000,
+                        column_count: 26,
+                        rows: first_rows,
+                        formulas: vec![],
+                    },
+                    MockTab {
+                        sheet_id: 2,
+                        title: "long",
+                        index: 2,
+                        row_count: 1000,
+                        column_count: 1,
+                        rows: long_rows,
+                        formulas: vec![],
+                    },
+                    MockTab {
+                        sheet_id: 3,
+                        title: "header_only",
+                        index: 3,
+                        row_count: 4,
+                        column_count: 3,
+                        rows: vec![mock_row(&["name", "age", "city"])],
+                        formulas: vec![],
+                    },
+                    MockTab {
+                        sheet_id: 4,
+                        title: "formula",
+                        index: 4,
+                        row_count: 3,
+                        column_count: 3,
+                        rows: vec![
+                            mock_row(&["item", "price", "total"]),
+                            mock_row(&["apple", "100", "110"]),
+                            mock_row(&["grape", "300", "330"]),
+                        ],
+                        formulas: vec![
+                            vec![],
+                            mock_row(&["", "", "=B2*1.1"]),
+                            mock_row(&["", "", "=B3*1.1"]),
+                        ],
+                    },
+                ]),
+                ..Self::default()
+            }
+        }
+
+        fn batch_get_count(&self) -> usize {
+            self.batch_get_ranges.lock().unwrap().len()
+        }
+    }
+
+    type MockError = (StatusCode, Json<JsonValue>);
+
+    fn mock_error(status: StatusCode, message: String) -> MockError {
+        (
+            status,
+            Json(json!({"error": {
+                "code": status.as_u16(),
+                "message": message,
+                "status": status.canonical_reason().unwrap_or_default(),
+            }})),
+        )
+    }
+
+    /// the spread sheets named like `status-403` respond the status
+    fn check_request(
+        spread_sheet_id: &str,
+        headers: &HeaderMap,
+    ) -> std::result::Result<(), MockError> {
+        let authorized = headers
+            .get(header::AUTHORIZATION)
+            .map(|auth| auth == &format!("Bearer {}", MOCK_TOKEN))
+            .unwrap_or(false);
+        if !authorized {
+            return Err(mock_error(StatusCode::UNAUTHORIZED, "no token".to_string()));
+        }
+
+        let forced_status = spread_sheet_id
+            .strip_prefix("status-")
+            .and_then(|status| status.parse::<u16>().ok())
+            .and_then(|status| StatusCode::from_u16(status).ok());
+        match forced_status {
+            Some(status) => Err(mock_error(status, format!("mock {}", status))),
+            None => Ok(()),
+        }
+    }
+
+    async fn mock_get_sheet(
+        Path(spread_sheet_id): Path<String>,
+        headers: HeaderMap,
+        Extension(sheets): Extension<Arc<MockSheets>>,
+    ) -> std::result::Result<Json<JsonValue>, MockError> {
+        sheets.get_sheet_calls.fetch_add(1, Ordering::SeqCst);
+        check_request(&spread_sheet_id, &headers)?;
+
+        let tabs = sheets.tabs.lock().unwrap();
+        Ok(Json(json!({
+            "spreadsheetId": spread_sheet_id,
+            "properties": {"title": "mock", "timeZone": "Asia/Tokyo"},
+            "sheets": tabs.iter().map(|tab| tab.properties()).collect::<Vec<JsonValue>>(),
+        })))
+    }
+
+    /// `values:batchGet`
+    async fn mock_batch_get(
+        Path((spread_sheet_id, operation)): Path<(String, String)>,
+        Query(query): Query<Vec<(String, String)>>,
+        headers: HeaderMap,
+        Extension(sheets): Extension<Arc<MockSheets>>,
+    ) -> std::result::Result<Json<JsonValue>, MockError> {
+        check_request(&spread_sheet_id, &headers)?;
+        if operation.trim_start_matches('/') != "values:batchGet" {
+            return Err(mock_error(StatusCode::NOT_FOUND, operation));
+        }
+
+        let param = |name: &str| -> Vec<String> {
+            query
+                .iter()
+                .filter(|(key, _)| key == name)
+                .map(|(_, value)| value.clone())
+                .collect()
+        };
+        let by_columns = param("majorDimension")
+            .iter()
+            .any(|value| value == "COLUMNS");
+        let formula = param("valueRenderOption")
+            .iter()
+            .any(|value| value == "FORMULA");
+        let ranges = param("ranges");
+        sheets.batch_get_ranges.lock().unwrap().push(ranges.clone());
+
+        let tabs = sheets.tabs.lock().unwrap();
+        let mut value_ranges = Vec::new();
+        for range in ranges.iter() {
+            let range = RangeRef::from_str(range)
+                .map_err(|e| mock_error(StatusCode::BAD_REQUEST, format!("{}", e)))?;
+            let tab = tabs.iter().find(|tab| match range.sheet_name.as_deref() {
+                Some(sheet_name) => tab.title == sheet_name,
+                None => tab.index == 0,
+            });
+            match tab {
+                Some(tab) => value_ranges.push(tab.value_range(&range, by_columns, formula)),
+                None => {
+                    return Err(mock_error(
+                        StatusCode::BAD_REQUEST,
+                        format!("Unable to parse range: {}", range),
+                    ))
+                }
+            }
+        }
+        Ok(Json(json!({
+            "spreadsheetId": spread_sheet_id,
+            "valueRanges": value_ranges,
+        })))
+    }
+
+    /// `values/{range}:append` with `INSERT_ROWS`. the rows are inserted below the last
+    /// non-empty row of the tab, and the grid grows by them
+    async fn mock_append(
+        Path((spread_sheet_id, operation)): Path<(String, String)>,
+        headers: HeaderMap,
+        Extension(sheets): Extension<Arc<MockSheets>>,
+        Json(body): Json<JsonValue>,
+    ) -> std::result::Result<Json<JsonValue>, MockError> {
+        // `append-status-403` can be read but refuses the appends
+        check_request(
+            spread_sheet_id
+                .strip_prefix("append-")
+                .unwrap_or(&spread_sheet_id),
+            &headers,
+        )?;
+        let operation = urlencoding::decode(operation.trim_start_matches('/'))
+            .map_err(|e| mock_error(StatusCode::BAD_REQUEST, format!("{}", e)))?
+            .into_owned();
+        let range = operation
+            .strip_prefix("values/")
+            .and_then(|range| range.strip_suffix(":append"))
+            .and_then(|range| RangeRef::from_str(range).ok())
+            .ok_or_else(|| mock_error(StatusCode::NOT_FOUND, operation.clone()))?;
+
+        let values: Vec<Vec<String>> = body["values"]
+            .as_array()
+            .map(|rows| {
+                rows.iter()
+                    .map(|row| {
+                        row.as_array()
+                            .map(|cells| {
+                                cells
+                                    .iter()
+                                    .map(|cell| match cell {
+                                        JsonValue::String(s) => s.clone(),
+                                        other => other.to_string(),
+                                    })
+                                    .collect()
+                            })
+                            .unwrap_or_default()
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let mut tabs = sheets.tabs.lock().unwrap();
+        let tab = tabs
+            .iter_mut()
+            .find(|tab| Some(tab.title) == range.sheet_name.as_deref())
+            .ok_or_else(|| mock_error(StatusCode::BAD_REQUEST, format!("{}", range)))?;
+        while tab
+            .rows
+            .last()
+            .map(|row| row.iter().all(|cell| cell.is_empty()))
+            .unwrap_or(false)
+        {
+            tab.rows.pop();
+        }
+        let start_row_idx = tab.rows.len();
+        let width = values.iter().map(|row| row.len()).max().unwrap_or(0).max(1);
+        let updated_range = RangeRef::new(
+            Some(tab.title.to_string()),
+            CellRef::new(range.start.col_index, start_row_idx),
+            CellRef::new(
+                range.start.col_index + width - 1,
+                start_row_idx + values.len().max(1) - 1,
+            ),
+        );
+        tab.row_count += values.len();
+        let updated_rows = values.len();
+        tab.rows.extend(values);
+
+        Ok(Json(json!({
+            "spreadsheetId": spread_sheet_id,
+            "tableRange": range.as_string(),
+            "updates": {
+                "updatedRange": updated_range.as_string(),
+                "updatedRows": updated_rows,
+            },
+        })))
+    }
+
+    fn mock_server(sheets: Arc<MockSheets>) -> Router {
+        Router::new()
+            .route(
+                "/token",
+                post(|| async {
+                    Json(json!({
+                        "access_token": MOCK_TOKEN,
+                        "token_type": "Bearer",
+                        "expires_in": 3600,
+                    }))
+                }),
+            )
+            .route("/v4/spreadsheets/:spread_sheet_id", get(mock_get_sheet))
+            .route(
+                "/v4/spreadsheets/:spread_sheet_id/*operation",
+                get(mock_batch_get).post(mock_append),
+            )
+            .layer(AddExtensionLayer::new(sheets))
+    }
+
+    /// the mock server and the client reading it
+    struct MockContext {
+        sheets: Arc<MockSheets>,
+        sheets_client: SheetsClient<DefaultConnector>,
+        stopper: RefreshingStopper,
+    }
+
+    impl MockContext {
+        async fn start() -> Self {
+            let sheets = Arc::new(MockSheets::new());
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let addr = listener.local_addr().unwrap();
+            tokio::spawn(
+                axum::Server::from_tcp(listener)
+                    .unwrap()
+                    .serve(mock_server(sheets.clone()).into_make_service()),
+            );
+
+            let sa_key = oauth::parse_service_account_key(
+                json!({
+                    "type": "service_account",
+                    "client_email": "mock@example.com",
+                    "private_key": MOCK_SA_PRIVATE_KEY.as_str(),
+                    "token_uri": format!("http://{}/token", addr),
+                })
+                .to_string(),
+            )
+            .unwrap();
+            let authenticator = oauth::ServiceAccountAuthenticator::builder(sa_key)
+                .build()
+                .await
+                .unwrap();
+
+            let stopper = RefreshingStopper::new();
+            let token_manager = TokenManager::start(
+                authenticator,
+                scopes::SHEET_READ_ONLY,
+                stopper.subscribe(),
+                None,
+            )
+            .await
+            .unwrap();
+            assert_eq!(MOCK_TOKEN, token_manager.current_token().load().as_str());
+
+            let sheets_client =
+                SheetsClient::with_client(ReqClient::new(), Arc::new(token_manager))
+                    .with_base_url(format!("http://{}/v4/spreadsheets", addr));
+            Self {
+                sheets,
+                sheets_client,
+                stopper,
+            }
+        }
+
+        async fn header_condition(
+            &self,
+            sheet_name: &str,
+            header_option: HeaderOption,
+        ) -> HeaderSearchCondition {
+            self.sheets_client
+                .create_header_condition_from_sheet_meta(
+                    SheetMeta::new(
+                        MOCK_SPREAD_SHEET_ID.to_string(),
+                        None,
+                        Some(sheet_name.to_string()),
+                    ),
+                    None,
+                    header_option,
+                )
+                .await
+                .unwrap()
+        }
+
+        /// the page of `?offset=&limit=`
+        async fn fetch_page(
+            &self,
+            condition: &HeaderSearchCondition,
+            offset: usize,
+            limit: usize,
+        ) -> SheetValueResponse {
+            self.sheets_client
+                .fetch_sheet_value(
+                    condition,
+                    &FetchRowCondition::with_pagination(Some(offset), Some(limit)),
+                )
+                .await
+                .unwrap()
+        }
+    }
+
+    #[tokio::test]
+    async fn sheets_client_mock_server_test() {
+        let mock = MockContext::start().await;
+        let get_sheet_calls = || mock.sheets.get_sheet_calls.load(Ordering::SeqCst);
+
+        let tabs = mock
+            .sheets_client
+            .list_sheet_tabs(&SpreadSheetId::new(MOCK_SPREAD_SHEET_ID.to_string()))
+            .await
+            .unwrap();
+        let titles: Vec<&str> = tabs.iter().map(|tab| tab.title.as_str()).collect();
+        assert_eq!(
+            titles,
+            vec!["first", "second", "long", "header_only", "formula"]
+        );
+        assert_eq!(get_sheet_calls(), 1);
+
+        // the second request within the ttl is served from the cache
+        let spread_sheet_id = SpreadSheetId::new("cached-sheet".to_string());
+        let sheets_client = mock
+            .sheets_client
+            .clone()
+            .with_sheet_cache(Some(Arc::new(SheetCache::new(Duration::from_secs(60)))));
+        let header_condition = sheets_client
+            .create_header_condition_from_sheet_meta(
+                SheetMeta::new("cached-sheet".to_string(), None, Some("second".to_string())),
+                None,
+                HeaderOption::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            header_condition.sheet_info.sheet_names(),
+            vec!["second", "first", "long", "header_only", "formula"]
+        );
+        sheets_client.get_sheet(&spread_sheet_id).await.unwrap();
+        assert_eq!(get_sheet_calls(), 2);
+
+        // a tab not in the cached sheet fetches the sheet again
+        assert!(sheets_client
+            .create_header_condition_from_sheet_meta(
+                SheetMeta::new("cached-sheet".to_string(), None, Some("third".to_string())),
+                None,
+                HeaderOption::default(),
+            )
+            .await
+            .is_err());
+        assert_eq!(get_sheet_calls(), 3);
+
+        // bypassing the cache
+        let fresh_client = sheets_client.clone().with_fresh_sheet(true);
+        fresh_client.get_sheet(&spread_sheet_id).await.unwrap();
+        assert_eq!(get_sheet_calls(), 4);
+        sheets_client.get_sheet(&spread_sheet_id).await.unwrap();
+        assert_eq!(get_sheet_calls(), 4);
+
+        mock.stopper.stop();
+    }
+
+    #[tokio::test]
+    async fn read_headers_span_test() {
+        let mock = MockContext::start().await;
+        let condition = mock
+            .header_condition("second", HeaderOption::default())
+            .await;
+
+        let logs = CapturedLogs::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer({
+                let logs = logs.clone();
+                move || logs.clone()
+            })
+            .with_ansi(false)
+            .with_max_level(tracing::Level::TRACE)
+            .with_span_events(tracing_subscriber::fmt::format::FmtSpan::NEW)
+            .finish();
+
+        // a span is emitted for the header read even if the range is out of the grid
+        let out_of_grid = HeaderSearchCondition {
+            specified_cell_range: Some((CellRef::new(5, 0), CellRef::new(6, 0))),
+            ..condition
+        };
+        {
+            let _guard = tracing::subscriber::set_default(subscriber);
+            assert!(mock
+                .sheets_client
+                .read_sheet_headers(&out_of_grid)
+                .await
+                .is_err());
+        }
+        let logs = logs.contents();
+        assert!(logs.contains("read_raw_headers{"), "{}", logs);
+        assert!(
+            logs.contains(&format!("spread_sheet_id={}", MOCK_SPREAD_SHEET_ID)),
+            "{}",
+            logs
+        );
+
+        mock.stopper.stop();
+    }
+
+    #[tokio::test]
+    async fn sheet_api_status_test() {
+        let mock = MockContext::start().await;
+        let sheet_info = mock
+            .header_condition("second", HeaderOption::default())
+            .await
+            .sheet_info;
+
+        for (status, code) in [
+            (StatusCode::UNAUTHORIZED, "SHEET_API_PERMISSION_DENIED"),
+            (StatusCode::FORBIDDEN, "SHEET_API_PERMISSION_DENIED"),
+            (StatusCode::TOO_MANY_REQUESTS, "SHEET_API_RATE_LIMITED"),
+        ] {
+            let spread_sheet_id = format!("status-{}", status.as_u16());
+
+            // get_sheet
+            let e = mock
+                .sheets_client
+                .create_header_condition_from_sheet_meta(
+                    SheetMeta::new(spread_sheet_id.clone(), None, Some("second".to_string())),
+                    None,
+                    HeaderOption::default(),
+                )
+                .await
+                .unwrap_err();
+            assert_eq!(e.http_status(), status);
+            assert_eq!(e.code(), code);
+
+            // get_sheet_value
+            let condition = HeaderSearchCondition {
+                spread_sheet_id: SpreadSheetId::new(spread_sheet_id),
+                sheet_name: Some(SheetName::new("second".to_string())),
+                specified_cell_range: None,
+                header_option: HeaderOption::default(),
+                sheet_info: sheet_info.clone(),
+            };
+            let e = mock
+                .sheets_client
+                .fetch_sheet_value(&condition, &FetchRowCondition::with_pagination(None, None))
+                .await
+                .unwrap_err();
+            assert_eq!(e.http_status(), status);
+            assert_eq!(e.code(), code);
+
+            // append_values
+            let append_condition = HeaderSearchCondition {
+                spread_sheet_id: SpreadSheetId::new(format!("append-status-{}", status.as_u16())),
+                ..condition
+            };
+            let e = mock
+                .sheets_client
+                .append_sheet_row(&append_condition, &json!({"name": "user10"}))
+                .await
+                .unwrap_err();
+            assert_eq!(e.http_status(), status);
+            assert_eq!(e.code(), code);
+        }
+
+        mock.stopper.stop();
+    }
+
+    #[tokio::test]
+    async fn restrictions_test() {
+        let mock = MockContext::start().await;
+        let header_option = HeaderOption {
+            restrictions: Some(Restrictions {
+                max_col: DEFAULT_MAX_COL,
+                max_row: 5,
+            }),
+            ..HeaderOption::default()
+        };
+        let condition = mock.header_condition("first", header_option).await;
+        let is_out_of_restriction = |result: Result<SheetValueResponse>| {
+            matches!(
+                result,
+                Err(SpreadSheetError::ValueError(
+                    ValueError::RowIndexOutOfRescription(5)
+                ))
+            )
+        };
+
+        // the row index 4 is the 5th row of the sheet
+        let response = mock
+            .sheets_client
+            .fetch_sheet_value(&condition, &FetchRowCondition::with_row_indices(vec![2]))
+            .await
+            .unwrap();
+        assert_eq!(
+            response.row_values.values[0][0],
+            CellValue::new(json!("Bob"))
+        );
+        let response = mock
+            .sheets_client
+            .fetch_sheet_value(
+                &condition,
+                &FetchRowCondition::with_pagination(Some(0), Some(3)),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.row_values.values.len(), 3);
+
+        // rejected before reading the values
+        let batch_gets = mock.sheets.batch_get_count();
+        assert!(is_out_of_restriction(
+            mock.sheets_client
+                .fetch_sheet_value(&condition, &FetchRowCondition::with_row_indices(vec![2, 4]))
+                .await
+        ));
+        assert!(is_out_of_restriction(
+            mock.sheets_client
+                .fetch_sheet_value(
+                    &condition,
+                    &FetchRowCondition::with_pagination(Some(0), Some(5)),
+                )
+                .await
+        ));
+        // only the headers are read
+        assert_eq!(mock.sheets.batch_get_count(), batch_gets + 2);
+
+        mock.stopper.stop();
+    }
+
+    /// the first cells of the rows of the page
+    fn first_cells(response: &SheetValueResponse) -> Vec<String> {
+        response
+            .row_values
+            .values
+            .iter()
+            .map(|row| row[0].as_inner().as_str().unwrap_or_default().to_string())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn last_row_idx_test() {
+        let mock = MockContext::start().await;
+        let condition = mock.header_condition("long", HeaderOption::default()).await;
+
+        // `?range=A1:A50`. the data rows are 1..=49
+        let response = mock
+            .sheets_client
+            .fetch_sheet_value(
+                &condition,
+                &FetchRowCondition::with_pagination(Some(45), Some(10)).with_last_row_idx(49),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.total_rows, Some(49));
+        assert_eq!(
+            first_cells(&response),
+            vec!["row45", "row46", "row47", "row48"]
+        );
+        assert_eq!(response.next_cursor(), None);
+
+        mock.stopper.stop();
+    }
+
+    #[tokio::test]
+    async fn walk_rows_with_cursor_test() {
+        let mock = MockContext::start().await;
+        let condition = mock.header_condition("long", HeaderOption::default()).await;
+
+        let mut walked = vec![];
+        let mut row_condition = FetchRowCondition::with_pagination(None, Some(7));
+        loop {
+            let response = mock
+                .sheets_client
+                .fetch_sheet_value(&condition, &row_condition)
+                .await
+                .unwrap();
+            walked.extend(first_cells(&response));
+
+            match response.next_cursor() {
+                None => break,
+                Some(cursor) => {
+                    let cursor = Cursor::decode(&cursor.encode()).unwrap();
+                    row_condition = FetchRowCondition::with_cursor(&cursor);
+                }
+            }
+        }
+
+        let rows: Vec<String> = (0..60).map(|n| format!("row{}", n)).collect();
+        assert_eq!(walked, rows);
+
+        mock.stopper.stop();
+    }
+
+    #[tokio::test]
+    async fn has_more_test() {
+        let mock = MockContext::start().await;
+        // the grid has 9 data rows
+        let condition = mock
+            .header_condition("second", HeaderOption::default())
+            .await;
+        let response = mock.fetch_page(&condition, 0, 4).await;
+        let pagination = response.pagination.unwrap();
+        assert_eq!(pagination.total_rows(), Some(9));
+        assert_eq!(pagination.has_more(), Some(true));
+
+        let response = mock.fetch_page(&condition, 6, 4).await;
+        assert_eq!(response.row_values.values.len(), 3);
+        assert_eq!(response.pagination.unwrap().has_more(), Some(false));
+
+        mock.stopper.stop();
+    }
+
+    #[tokio::test]
+    async fn window_at_last_row_boundary_test() {
+        let mock = MockContext::start().await;
+        // the grid has 9 data rows
+        let condition = mock
+            .header_condition("second", HeaderOption::default())
+            .await;
+        // exactly `limit` rows are returned, not `limit + 1`
+        let response = mock.fetch_page(&condition, 0, 3).await;
+        assert_eq!(response.row_values.values.len(), 3);
+        assert_eq!(response.pagination.unwrap().has_more(), Some(true));
+
+        // the page ends exactly at the last row
+        let response = mock.fetch_page(&condition, 6, 3).await;
+        assert_eq!(response.row_values.values.len(), 3);
+        assert_eq!(response.next_cursor(), None);
+        assert_eq!(response.pagination.unwrap().has_more(), Some(false));
+
+        // the offset is at the last row
+        let response = mock.fetch_page(&condition, 8, 3).await;
+        assert_eq!(first_cells(&response), vec!["user9"]);
+        assert_eq!(response.pagination.unwrap().has_more(), Some(false));
+
+        // the offset is one past the last row
+        let response = mock.fetch_page(&condition, 9, 3).await;
+        assert!(response.is_empty());
+        assert!(response.is_empty_page());
+        assert_eq!(response.pagination.unwrap().has_more(), Some(false));
+
+        mock.stopper.stop();
+    }
+
+    #[tokio::test]
+    async fn is_empty_page_test() {
+        let mock = MockContext::start().await;
+        let condition = mock
+            .header_condition("second", HeaderOption::default())
+            .await;
+        let response = mock.fetch_page(&condition, 10, 10).await;
+        assert!(response.is_empty());
+        assert!(response.is_empty_page());
+        assert_eq!(response.pagination.unwrap().has_more(), Some(false));
+
+        let response = mock.fetch_page(&condition, 1, 10).await;
+        assert!(!response.is_empty_page());
+
+        // the sheet has no rows
+        let header_only = mock
+            .header_condition("header_only", HeaderOption::default())
+            .await;
+        let response = mock.fetch_page(&header_only, 0, 10).await;
+        assert!(response.is_empty());
+        assert!(!response.is_empty_page());
+
+        mock.stopper.stop();
+    }
+
+    #[tokio::test]
+    async fn cursor_over_empty_rows_test() {
+        let mock = MockContext::start().await;
+        // Alice, (blank), Bob, (blank), Carol, David
+        let condition = mock
+            .header_condition("first", HeaderOption::default())
+            .await;
+
+        // the dropped rows are not read again by the next page
+        let mut walked = vec![];
+        let mut row_condition =
+            FetchRowCondition::with_pagination(None, Some(2)).with_include_empty_rows(false);
+        loop {
+            let response = mock
+                .sheets_client
+                .fetch_sheet_value(&condition, &row_condition)
+                .await
+                .unwrap();
+            walked.extend(first_cells(&response));
+
+            match response.next_cursor() {
+                None => break,
+                Some(cursor) => {
+                    row_condition =
+                        FetchRowCondition::with_cursor(&cursor).with_include_empty_rows(false);
+                }
+            }
+        }
+        assert_eq!(walked, vec!["Alice", "Bob", "Carol", "David"]);
+
+        // a page of a blank row is not the end
+        let response = mock
+            .sheets_client
+            .fetch_sheet_value(
+                &condition,
+                &FetchRowCondition::with_pagination(Some(1), Some(1))
+                    .with_include_empty_rows(false),
+            )
+            .await
+            .unwrap();
+        assert!(response.is_empty());
+        assert!(response.is_empty_page());
+        assert_eq!(response.next_cursor(), Some(Cursor::new(2, 1)));
+
+        mock.stopper.stop();
+    }
+
+    #[tokio::test]
+    async fn has_more_in_large_grid_test() {
+        let mock = MockContext::start().await;
+        // 6 records in the 1000 rows grid
+        let condition = mock
+            .header_condition("first", HeaderOption::default())
+            .await;
+
+        // the api returned the rows up to the end of the window
+        let response = mock.fetch_page(&condition, 0, 3).await;
+        assert_eq!(response.total_rows, Some(999));
+        assert_eq!(response.pagination.unwrap().has_more(), Some(true));
+
+        // the page reaches the last record
+        let response = mock.fetch_page(&condition, 0, 10).await;
+        assert_eq!(
+            first_cells(&response),
+            vec!["Alice", "", "Bob", "", "Carol", "David"]
+        );
+        assert_eq!(response.next_cursor(), None);
+        let pagination = response.pagination.unwrap();
+        assert_eq!(pagination.total_rows(), Some(6));
+        assert_eq!(pagination.has_more(), Some(false));
+
+        // past the last record
+        let response = mock.fetch_page(&condition, 50, 10).await;
+        assert!(response.is_empty_page());
+        assert_eq!(response.next_cursor(), None);
+        assert_eq!(response.pagination.unwrap().has_more(), Some(false));
+
+        // a sheet without records is not an empty page wherever the offset is
+        let header_only = mock
+            .header_condition("header_only", HeaderOption::default())
+            .await;
+        for offset in [0, 2, 5] {
+            let response = mock.fetch_page(&header_only, offset, 10).await;
+            assert!(response.is_empty());
+            assert!(!response.is_empty_page());
+        }
+
+        mock.stopper.stop();
+    }
+
+    #[tokio::test]
+    async fn read_after_append_test() {
+        let mock = MockContext::start().await;
+        let sheets_client = mock
+            .sheets_client
+            .clone()
+            .with_sheet_cache(Some(Arc::new(SheetCache::new(Duration::from_secs(60)))));
+        let header_condition = || {
+            sheets_client.create_header_condition_from_sheet_meta(
+                SheetMeta::new(
+                    MOCK_SPREAD_SHEET_ID.to_string(),
+                    None,
+                    Some("second".to_string()),
+                ),
+                None,
+                HeaderOption::default(),
+            )
+        };
+
+        // the 10 rows grid is filled up
+        let condition = header_condition().await.unwrap();
+        sheets_client
+            .append_sheet_row(
+                &condition,
+                &json!({"name": "user10", "age": "30", "city": "city10"}),
+            )
+            .await
+            .unwrap();
+
+        let condition = header_condition().await.unwrap();
+        let response = sheets_client
+            .fetch_sheet_value(
+                &condition,
+                &FetchRowCondition::with_pagination(None, Some(20)),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.total_rows, Some(10));
+        assert_eq!(first_cells(&response).last().unwrap(), "user10");
+        assert_eq!(mock.sheets.get_sheet_calls.load(Ordering::SeqCst), 2);
+
+        mock.stopper.stop();
+    }
+
+    #[tokio::test]
+    async fn no_cursor_at_the_end_of_grid_test() {
+        let mock = MockContext::start().await;
+        let condition = mock
+            .header_condition("second", HeaderOption::default())
+            .await;
+
+        let response = mock.fetch_page(&condition, 0, 20).await;
+        assert_eq!(response.row_values.values.len(), 9);
+        assert_eq!(response.next_cursor(), None);
+
+        mock.stopper.stop();
+    }
+
+    /// `?render=formula`
+    #[tokio::test]
+    async fn formulas_test() {
+        let mock = MockContext::start().await;
+        let condition = mock
+            .header_condition("formula", HeaderOption::default())
+            .await;
+        let totals = |response: SheetValueResponse| -> Vec<String> {
+            response
+                .row_values
+                .values
+                .iter()
+                .map(|row| row[2].as_inner().as_str().unwrap_or_default().to_string())
+                .collect()
+        };
+
+        let response = mock
+            .sheets_client
+            .fetch_sheet_value(
+                &condition,
+                &FetchRowCondition::with_pagination(None, None).with_formulas(true),
+            )
+            .await
+            .unwrap();
+        // the headers are read formatted anyway
+        assert_eq!(response.headers.column_index_of("total"), Some(2));
+        assert_eq!(totals(response), vec!["=B2*1.1", "=B3*1.1"]);
+
+        let response = mock.fetch_page(&condition, 0, 10).await;
+        assert_eq!(totals(response), vec!["110", "330"]);
+
+        mock.stopper.stop();
+    }
+
+    /// `?range=A1:B5` of the handler. the headers are the first row of the range,
+    /// and the rows and the columns beyond the range are not read
+    #[tokio::test]
+    async fn range_test() {
+        let mock = MockContext::start().await;
+        let condition = mock
+            .sheets_client
+            .create_header_condition_from_sheet_meta(
+                SheetMeta::new(
+                    MOCK_SPREAD_SHEET_ID.to_string(),
+                    None,
+                    Some("second".to_string()),
+                ),
+                Some((CellRef::new(0, 0), CellRef::new(1, 0))),
+                HeaderOption::default(),
+            )
+            .await
+            .unwrap();
+
+        let batch_get_count = mock.sheets.batch_get_count();
+        let response = mock
+            .sheets_client
+            .fetch_sheet_value(
+                &condition,
+                &FetchRowCondition::with_pagination(None, None).with_last_row_idx(4),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            first_cells(&response),
+            vec!["user1", "user2", "user3", "user4"]
+        );
+        assert!(response.row_values.values.iter().all(|row| row.len() == 2));
+        assert_eq!(response.total_rows, Some(4));
+
+        let batch_get_ranges = mock.sheets.batch_get_ranges.lock().unwrap();
+        assert!(batch_get_ranges.len() > batch_get_count);
+        for range in batch_get_ranges[batch_get_count..].iter().flatten() {
+            let range = RangeRef::from_str(range).unwrap();
+            assert!(range.end.col_index <= 1, "{}", range);
+            assert!(range.end.row_index <= 4, "{}", range);
+        }
+        drop(batch_get_ranges);
+
+        mock.stopper.stop();
+    }
+
+    /// `?rows=` all beyond the grid
+    #[tokio::test]
+    async fn rows_beyond_grid_test() {
+        let mock = MockContext::start().await;
+        let condition = mock
+            .header_condition("second", HeaderOption::default())
+            .await;
+
+        let batch_get_count = mock.sheets.batch_get_count();
+        let response = mock
+            .sheets_client
+            .fetch_sheet_value(
+                &condition,
+                &FetchRowCondition::with_row_indices(vec![20, usize::MAX]),
+            )
+            .await
+            .unwrap();
+        assert!(response.is_empty());
+        assert_eq!(response.total_rows, Some(9));
+        // only the headers are read
+        assert_eq!(mock.sheets.batch_get_count(), batch_get_count + 1);
+
+        mock.stopper.stop();
+    }
+
+    #[tokio::test]
+    async fn count_sheet_records_test() {
+        let mock = MockContext::start().await;
+        let condition = mock
+            .header_condition("second", HeaderOption::default())
+            .await;
+
+        let count = mock
+            .sheets_client
+            .count_sheet_records(&condition, &FetchRowCondition::with_pagination(None, None))
+            .await
+            .unwrap();
+        assert_eq!(count, 9);
+
+        // `?range=A1:C5`
+        let count = mock
+            .sheets_client
+            .count_sheet_records(
+                &condition,
+                &FetchRowCondition::with_pagination(None, None).with_last_row_idx(4),
+            )
+            .await
+            .unwrap();
+        assert_eq!(count, 4);
+
+        mock.stopper.stop();
+    }
+}
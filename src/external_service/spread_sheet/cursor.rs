@@ -0,0 +1,49 @@
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+type Result<T> = std::result::Result<T, CursorError>;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum CursorError {
+    #[error("invalid cursor:{0}")]
+    InvalidCursor(String),
+}
+
+/// opaque token to resume reading rows. `offset` is relative to the row just below the header
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct Cursor {
+    pub offset: usize,
+    pub limit: usize,
+}
+
+impl Cursor {
+    pub fn new(offset: usize, limit: usize) -> Self {
+        Self { offset, limit }
+    }
+
+    pub fn encode(&self) -> String {
+        // serializing the struct never fails
+        let json = serde_json::to_vec(self).unwrap();
+        base64::encode_config(json, base64::URL_SAFE_NO_PAD)
+    }
+
+    pub fn decode(cursor: &str) -> Result<Self> {
+        let json = base64::decode_config(cursor, base64::URL_SAFE_NO_PAD)
+            .map_err(|_| CursorError::InvalidCursor(cursor.to_string()))?;
+        serde_json::from_slice(&json).map_err(|_| CursorError::InvalidCursor(cursor.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn encode_decode_test() {
+        let cursor = Cursor::new(200, 100);
+        assert_eq!(Cursor::decode(&cursor.encode()), Ok(cursor));
+
+        assert!(Cursor::decode("not a cursor").is_err());
+        assert!(Cursor::decode(&base64::encode("{}")).is_err());
+    }
+}
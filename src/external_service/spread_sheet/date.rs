@@ -0,0 +1,170 @@
+use super::Sheet;
+use chrono::{Duration, NaiveDate, NaiveDateTime, SecondsFormat, TimeZone};
+use chrono_tz::Tz;
+use serde_json::Value as JsonValue;
+use thiserror::Error;
+
+type Result<T> = std::result::Result<T, DateError>;
+
+const SECONDS_PER_DAY: f64 = 86400.0;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum DateError {
+    #[error("invalid time zone:{0}")]
+    InvalidTimeZone(String),
+}
+
+/// serial numbers of google spread sheet count days from 1899-12-30 00:00:00
+fn serial_date_epoch() -> NaiveDateTime {
+    NaiveDate::from_ymd(1899, 12, 30).and_hms(0, 0, 0)
+}
+
+pub fn parse_time_zone(tz: &str) -> Result<Tz> {
+    tz.parse::<Tz>()
+        .map_err(|_| DateError::InvalidTimeZone(tz.to_string()))
+}
+
+/// the time zone used to format date time values.
+/// `tz` (from the request) > time zone of the spread sheet > UTC
+pub fn resolve_time_zone(tz: Option<&str>, sheet: &Sheet) -> Result<Tz> {
+    match tz {
+        Some(tz) => parse_time_zone(tz),
+        None => match sheet.time_zone() {
+            Some(sheet_tz) => parse_time_zone(sheet_tz).or_else(|e| {
+                log::warn!("unknown time zone of spread sheet, fallback to UTC: {}", e);
+                Ok(Tz::UTC)
+            }),
+            None => Ok(Tz::UTC),
+        },
+    }
+}
+
+/// 44197.0 -> 2021-01-01T00:00:00
+/// 44197.5 -> 2021-01-01T12:00:00
+pub fn serial_to_naive_datetime(serial: f64) -> Option<NaiveDateTime> {
+    if !serial.is_finite() {
+        return None;
+    }
+    let seconds = (serial * SECONDS_PER_DAY).round() as i64;
+    serial_date_epoch().checked_add_signed(Duration::seconds(seconds))
+}
+
+/// The serial number is a wall clock time of the spread sheet, so the fractional part
+/// is interpreted as the local time in `tz`.
+///
+/// 44197.0 -> "2021-01-01"
+/// 44197.5 (Asia/Tokyo) -> "2021-01-01T12:00:00+09:00"
+pub fn serial_to_iso_string(serial: f64, tz: &Tz) -> Option<String> {
+    let naive = serial_to_naive_datetime(serial)?;
+    if serial.fract() == 0.0 {
+        return Some(naive.date().format("%Y-%m-%d").to_string());
+    }
+
+    tz.from_local_datetime(&naive)
+        .earliest()
+        .map(|datetime| datetime.to_rfc3339_opts(SecondsFormat::Secs, false))
+}
+
+/// converts the serial numbers of the date columns into iso 8601 strings
+#[derive(Debug, Clone, PartialEq)]
+pub struct DateOption {
+    pub col_indices: Vec<usize>,
+    pub tz: Tz,
+}
+
+impl DateOption {
+    pub fn is_date_col(&self, col_idx: usize) -> bool {
+        self.col_indices.contains(&col_idx)
+    }
+
+    /// numbers and numeric strings are converted.
+    /// `None` for the other values like `""` or dates already formatted
+    pub fn convert(&self, value: &JsonValue) -> Option<JsonValue> {
+        let serial = match value {
+            JsonValue::Number(n) => n.as_f64()?,
+            JsonValue::String(s) => s.trim().parse::<f64>().ok()?,
+            _ => return None,
+        };
+        serial_to_iso_string(serial, &self.tz).map(JsonValue::String)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::SpreadSheetProperties;
+    use super::*;
+
+    fn sheet_with_time_zone(time_zone: Option<&str>) -> Sheet {
+        Sheet {
+            spreadsheet_id: "test".to_string(),
+            properties: Some(SpreadSheetProperties {
+                title: "test".to_string(),
+                time_zone: time_zone.map(|tz| tz.to_string()),
+            }),
+            sheets: vec![],
+        }
+    }
+
+    #[test]
+    fn serial_to_iso_string_test() {
+        assert_eq!(
+            serial_to_iso_string(44197.0, &Tz::UTC),
+            Some("2021-01-01".to_string())
+        );
+
+        assert_eq!(
+            serial_to_iso_string(44197.75, &Tz::UTC),
+            Some("2021-01-01T18:00:00+00:00".to_string())
+        );
+
+        assert_eq!(
+            serial_to_iso_string(44197.75, &Tz::Asia__Tokyo),
+            Some("2021-01-01T18:00:00+09:00".to_string())
+        );
+
+        assert_eq!(serial_to_iso_string(f64::NAN, &Tz::UTC), None);
+    }
+
+    #[test]
+    fn date_option_convert_test() {
+        use serde_json::json;
+
+        let date_option = DateOption {
+            col_indices: vec![1],
+            tz: Tz::UTC,
+        };
+        assert!(date_option.is_date_col(1));
+        assert!(!date_option.is_date_col(0));
+
+        assert_eq!(
+            date_option.convert(&json!(44197)),
+            Some(json!("2021-01-01"))
+        );
+        // 0.25 day is 06:00
+        assert_eq!(
+            date_option.convert(&json!(44197.25)),
+            Some(json!("2021-01-01T06:00:00+00:00"))
+        );
+        assert_eq!(
+            date_option.convert(&json!("44197")),
+            Some(json!("2021-01-01"))
+        );
+        assert_eq!(date_option.convert(&json!("")), None);
+        assert_eq!(date_option.convert(&json!("2021/01/01")), None);
+    }
+
+    #[test]
+    fn resolve_time_zone_test() {
+        let sheet = sheet_with_time_zone(Some("Asia/Tokyo"));
+        assert_eq!(
+            resolve_time_zone(Some("America/New_York"), &sheet),
+            Ok(Tz::America__New_York)
+        );
+        assert_eq!(resolve_time_zone(None, &sheet), Ok(Tz::Asia__Tokyo));
+
+        let sheet = sheet_with_time_zone(None);
+        assert_eq!(resolve_time_zone(None, &sheet), Ok(Tz::UTC));
+
+        assert!(resolve_time_zone(Some("Mars/Olympus"), &sheet).is_err());
+    }
+}
@@ -1,5 +1,8 @@
 use super::*;
+use crate::json_structure;
+use regex::Regex;
 use reqwest::Client as ReqClient;
+use std::collections::HashMap;
 use std::str::FromStr;
 use std::sync::Arc;
 use thiserror::Error;
@@ -17,8 +20,22 @@ pub enum HeaderError {
     #[error("spread sheet not found:{0}")]
     SpreadSheetNotFound(String),
 
-    #[error("multiple header not supported:{0}")]
-    UnsupportedMultipleHeader(String),
+    #[error("sheet name:{0} is not found. available sheets:[{}]", .1.join(", "))]
+    SheetNameNotFound(String, Vec<String>),
+
+    #[error("sheet index:{0} is out of range. the spread sheet has {1} sheets")]
+    SheetIndexOutOfRange(usize, usize),
+
+    #[error("spread sheet:{0} has no tab sheets. check the sharing of the spread sheet and its shared drive")]
+    NoSheetsInSpreadsheet(String),
+
+    /// the range and the number of its leading rows which look like the headers.
+    /// `None` if the rows couldn't be read
+    #[error(
+        "multiple header not supported:{0}{}",
+        .1.map(|rows| format!(". {} rows look like the headers", rows)).unwrap_or_default()
+    )]
+    UnsupportedMultipleHeader(String, Option<usize>),
 
     #[error("failed to fetch header values from api:{0}")]
     FetchHeaderApiError(String),
@@ -32,32 +49,153 @@ pub enum HeaderError {
     #[error("empty header values. sheet [{0}]")]
     EmptyHeaderValues(String),
 
+    #[error("range is out of the grid:{0}")]
+    RangeOutOfGrid(String),
+
     #[error("cell error :{0}")]
     CellError(#[from] CellError),
 
     #[error("unknown error occured:{0}")]
     UnknwonError(String),
 
-    #[cfg(feature = "restricted")]
     #[error("col index out of restriction:{0}")]
     ColIndexOutOfRescription(usize),
+
+    #[error("sheet api refused the request [{}] {}", .0.status, .0.message)]
+    SheetApiAccessError(GoogleApiError),
 }
 
 impl HeaderError {
     pub fn is_not_found(&self) -> bool {
-        if let HeaderError::SpreadSheetNotFound(_) = self {
-            true
+        match self {
+            HeaderError::SpreadSheetNotFound(_)
+            | HeaderError::SheetNameNotFound(_, _)
+            | HeaderError::NoSheetsInSpreadsheet(_)
+            | HeaderError::FetchSheetNameError(_) => true,
+            _ => false,
+        }
+    }
+
+    /// stable error code for api clients
+    pub fn code(&self) -> &'static str {
+        match self {
+            HeaderError::FetchSheetInfoError(_) => "FETCH_SHEET_INFO_FAILED",
+            HeaderError::FetchSheetNameError(_) => "SHEET_NOT_FOUND",
+            HeaderError::SpreadSheetNotFound(_) => "SPREAD_SHEET_NOT_FOUND",
+            HeaderError::SheetNameNotFound(_, _) => "SHEET_NOT_FOUND",
+            HeaderError::SheetIndexOutOfRange(_, _) => "SHEET_INDEX_OUT_OF_RANGE",
+            HeaderError::NoSheetsInSpreadsheet(_) => "NO_SHEETS_IN_SPREAD_SHEET",
+            HeaderError::UnsupportedMultipleHeader(_, _) => "UNSUPPORTED_MULTIPLE_HEADER",
+            HeaderError::FetchHeaderApiError(_) => "SHEET_API_ERROR",
+            HeaderError::EmptyHeaderValueRanges(_) => "EMPTY_HEADER",
+            HeaderError::InvalidRangeRefInReturnedValue(_) => "UNEXPECTED_SHEET_API_RESPONSE",
+            HeaderError::EmptyHeaderValues(_) => "EMPTY_HEADER",
+            HeaderError::RangeOutOfGrid(_) => "RANGE_OUT_OF_GRID",
+            HeaderError::CellError(_) => "INVALID_CELL_VALUE",
+            HeaderError::UnknwonError(_) => "UNKNOWN_ERROR",
+            HeaderError::ColIndexOutOfRescription(_) => "COL_INDEX_OUT_OF_RESTRICTION",
+            HeaderError::SheetApiAccessError(google_error) => google_error.error_code(),
+        }
+    }
+
+    /// 404 for missing spread sheets or tab sheets, 500 for the failures of the sheet api
+    /// and the unexpected responses, 401, 403 and 429 of the sheet api as they are, otherwise 400
+    pub fn http_status(&self) -> StatusCode {
+        if self.is_not_found() {
+            return StatusCode::NOT_FOUND;
+        }
+        match self {
+            HeaderError::SheetApiAccessError(google_error) => google_error.http_status(),
+            HeaderError::FetchSheetInfoError(_)
+            | HeaderError::FetchHeaderApiError(_)
+            | HeaderError::InvalidRangeRefInReturnedValue(_)
+            | HeaderError::UnknwonError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    /// the number of the rows which look like the headers if the header range is multi-line
+    pub fn detected_header_rows(&self) -> Option<usize> {
+        if let HeaderError::UnsupportedMultipleHeader(_, detected_header_rows) = self {
+            *detected_header_rows
+        } else {
+            None
+        }
+    }
+
+    /// how to fix the request, for the errors that clients can recover from
+    pub fn suggestion(&self) -> Option<String> {
+        match self.detected_header_rows()? {
+            0 | 1 => Some("narrow the range to the header row".to_string()),
+            header_rows => Some(format!("try ?header_rows={}", header_rows)),
+        }
+    }
+
+    /// the tab sheet names of the spread sheet if the requested sheet name doesn't exist
+    pub fn available_sheets(&self) -> Option<&[String]> {
+        if let HeaderError::SheetNameNotFound(_, sheet_names) = self {
+            Some(sheet_names)
         } else {
-            false
+            None
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct HeaderOption {
+    pub header_row_index: usize, //zero_base
+    /// number of the header rows from `header_row_index`. 0 is treated as 1.
+    /// the values of the rows are joined into dotted keys like `address.city`
+    pub header_rows: usize,
+    /// trim surrounding whitespaces of each header
+    pub trim: bool,
+    /// lowercase each header
+    pub lowercase: bool,
+    pub orientation: HeaderOrientation,
+    /// limits of the columns of the headers and the rows below them. not enforced if `None`
+    pub restrictions: Option<Restrictions>,
+}
+
+/// how the headers and the records are laid out in the sheet
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum HeaderOrientation {
+    /// headers in a row. each row below the headers is a record
+    Rows,
+    /// labels down a column. each column right of the labels is a record.
+    /// `header_row_index` is the row of the first label
+    KeyValue,
+}
+
+impl Default for HeaderOrientation {
+    fn default() -> Self {
+        Self::Rows
+    }
+}
+
+impl FromStr for HeaderOrientation {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "rows" => Ok(Self::Rows),
+            "key_value" => Ok(Self::KeyValue),
+            _ => Err(format!("invalid orientation:{}", s)),
         }
     }
 }
 
+impl HeaderOption {
+    pub fn header_row_count(&self) -> usize {
+        self.header_rows.max(1)
+    }
+}
+
 #[derive(PartialEq, Debug)]
 pub struct HeaderSearchCondition {
     pub spread_sheet_id: SpreadSheetId,
     pub sheet_name: Option<SheetName>,
     pub specified_cell_range: Option<(CellRef, CellRef)>,
+    pub header_option: HeaderOption,
     pub sheet_info: Sheet,
 }
 
@@ -66,12 +204,14 @@ impl HeaderSearchCondition {
         spread_sheet_id: SpreadSheetId,
         sheet_name: Option<SheetName>,
         specified_cell_range: Option<(CellRef, CellRef)>,
+        header_option: HeaderOption,
         sheet_info: Sheet,
     ) -> Self {
         Self {
             spread_sheet_id,
             sheet_name,
             specified_cell_range,
+            header_option,
             sheet_info,
         }
     }
@@ -93,68 +233,196 @@ impl HeaderSearchCondition {
 
     pub async fn create<HttpConnector>(
         client: &ReqClient,
+        base_url: &str,
         token_manager: Arc<TokenManager<HttpConnector>>,
         meta: SheetMeta,
         specified_cell_range: Option<(CellRef, CellRef)>,
+        header_option: HeaderOption,
     ) -> Result<HeaderSearchCondition> {
-        let spread_sheet_id = SpreadSheetId::new(meta.spread_sheet_id);
+        let spread_sheet_id = SpreadSheetId::new(meta.spread_sheet_id.clone());
 
-        let sheet_info = api::get_sheet(client, token_manager, &spread_sheet_id)
+        let sheet_info = api::get_sheet(client, base_url, token_manager, &spread_sheet_id)
             .await
-            .map_err(|e| {
-                if e.is_not_found() {
-                    HeaderError::SpreadSheetNotFound(format!(
-                        "spread sheet:{} not found",
-                        spread_sheet_id
-                    ))
-                } else {
-                    log::error!(
-                        "error on fetching spread sheet: {},  error:{}",
-                        spread_sheet_id,
-                        e
-                    );
-                    HeaderError::FetchSheetInfoError(format!("spread sheet {}:", spread_sheet_id,))
-                }
-            })?;
+            .map_err(|e| sheet_info_error(e, &spread_sheet_id))?;
 
-        let sheet_name = match meta.sheet_id_or_name.is_need_get_sheet_name_by_id() {
-            Some(sheet_id) => {
-                match sheet_info
-                    .find_property_by_id(sheet_id)
-                    .map(|prop| SheetName::new(prop.properties.title.to_string()))
-                {
-                    v @ Some(_) => v,
-                    None => {
-                        return Err(HeaderError::FetchSheetNameError(format!(
-                            "spread sheet :{} {}",
-                            spread_sheet_id, sheet_id
-                        )))
-                    }
-                }
-            }
-            None => meta
-                .sheet_id_or_name
-                .sheet_name()
-                .map(|name| SheetName::new(name)),
-        };
+        Self::from_sheet_info(sheet_info, meta, specified_cell_range, header_option)
+    }
+
+    /// with the `Sheet` already fetched, e.g. from `SheetCache`
+    pub fn from_sheet_info(
+        sheet_info: Sheet,
+        meta: SheetMeta,
+        specified_cell_range: Option<(CellRef, CellRef)>,
+        header_option: HeaderOption,
+    ) -> Result<HeaderSearchCondition> {
+        let spread_sheet_id = SpreadSheetId::new(meta.spread_sheet_id);
+        let sheet_name = resolve_sheet_name(&sheet_info, &spread_sheet_id, meta.sheet_id_or_name)?;
 
         Ok(Self::new(
             spread_sheet_id,
             sheet_name,
             specified_cell_range,
+            header_option,
             sheet_info,
         ))
     }
 }
 
-fn default_header_range(sheet_name: Option<&SheetName>) -> RangeRef {
+pub(crate) fn sheet_info_error(e: SheetApiError, spread_sheet_id: &SpreadSheetId) -> HeaderError {
+    if e.is_not_found() {
+        HeaderError::SpreadSheetNotFound(format!("spread sheet:{} not found", spread_sheet_id))
+    } else if let Some(google_error) = e.access_error() {
+        HeaderError::SheetApiAccessError(google_error.clone())
+    } else {
+        log::error!(
+            "error on fetching spread sheet: {},  error:{}",
+            spread_sheet_id,
+            e
+        );
+        HeaderError::FetchSheetInfoError(format!("spread sheet {}:", spread_sheet_id,))
+    }
+}
+
+/// the tab sheet name by the gid, or the passed name if it exists in the spread sheet,
+/// or the name of the tab at the index
+fn resolve_sheet_name(
+    sheet_info: &Sheet,
+    spread_sheet_id: &SpreadSheetId,
+    sheet_id_or_name: SheetIdOrName,
+) -> Result<Option<SheetName>> {
+    // e.g. some spread sheets on shared drives. nothing to read without any tab
+    if sheet_info.sheets.is_empty() {
+        return Err(HeaderError::NoSheetsInSpreadsheet(
+            spread_sheet_id.to_string(),
+        ));
+    }
+
+    match sheet_id_or_name.is_need_get_sheet_name_by_id() {
+        Some(sheet_id) => match sheet_info.find_property_by_id(sheet_id) {
+            Some(prop) => Ok(Some(SheetName::new(prop.properties.title.to_string()))),
+            None => Err(HeaderError::FetchSheetNameError(format!(
+                "spread sheet :{} {}",
+                spread_sheet_id, sheet_id
+            ))),
+        },
+        None => match sheet_id_or_name.tab_sheet_name {
+            None if sheet_id_or_name.tab_sheet_id.is_none() => {
+                match sheet_id_or_name.tab_sheet_index {
+                    None => Ok(None),
+                    Some(index) => match sheet_info.find_property_by_index(index) {
+                        Some(prop) => Ok(Some(SheetName::new(prop.properties.title.to_string()))),
+                        None => Err(HeaderError::SheetIndexOutOfRange(
+                            index,
+                            sheet_info.sheets.len(),
+                        )),
+                    },
+                }
+            }
+            None => Ok(None),
+            Some(name) => {
+                if sheet_info.find_property_by_name(Some(&name)).is_none() {
+                    return Err(HeaderError::SheetNameNotFound(
+                        name,
+                        sheet_info.sheet_names(),
+                    ));
+                }
+                Ok(Some(SheetName::new(name)))
+            }
+        },
+    }
+}
+
+/// the rows of a rejected multi-line header range read to detect the header rows
+const PEEK_HEADER_ROWS: usize = 10;
+
+/// the number of the leading rows which look like the headers.
+/// a header row has some cells and none of them is a number like the data rows
+fn header_like_rows(rows: &[Vec<JsonValue>]) -> usize {
+    rows.iter()
+        .take_while(|row| {
+            let mut cells = row
+                .iter()
+                .filter(|cell| match cell {
+                    JsonValue::Null => false,
+                    JsonValue::String(s) => !s.trim().is_empty(),
+                    _ => true,
+                })
+                .peekable();
+            cells.peek().is_some()
+                && cells.all(|cell| {
+                    cell.as_str()
+                        .map(|s| s.trim().parse::<f64>().is_err())
+                        .unwrap_or(false)
+                })
+        })
+        .count()
+}
+
+/// header_row_index 0 -> A1:Z1
+/// header_row_index 1 -> A2:Z2
+/// header_row_index 0, header_rows 2 -> A1:Z2
+fn default_header_range(
+    sheet_name: Option<&SheetName>,
+    header_row_index: usize,
+    header_rows: usize,
+) -> RangeRef {
     RangeRef::new(
         sheet_name.map(|e| e.clone().into_inner()),
-        CellRef::new(0, 0),  //A1
-        CellRef::new(25, 0), //Z1
+        CellRef::new(0, header_row_index), //A{n}
+        CellRef::new(25, header_row_index + header_rows.max(1) - 1), //Z{n+rows-1}
     )
 }
 
+/// joins the values of the header rows top-to-bottom into dotted keys.
+/// empty cells of the parent rows are filled with the value of the left cell
+/// as long as they are under the same parent, since merged cells have the value only at the first cell.
+///
+/// ```ignore
+/// address | ""  | name
+/// city    | zip | ""
+/// => ["address.city", "address.zip", "name"]
+/// ```
+fn compose_header_rows(rows: Vec<Vec<JsonValue>>) -> Vec<JsonValue> {
+    let width = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    let last_row_idx = rows.len().saturating_sub(1);
+
+    let mut filled_rows: Vec<Vec<String>> = Vec::with_capacity(rows.len());
+    for (row_idx, row) in rows.iter().enumerate() {
+        let mut filled_row: Vec<String> = (0..width)
+            .map(|col_idx| match row.get(col_idx) {
+                None | Some(JsonValue::Null) => "".to_string(),
+                Some(JsonValue::String(s)) => s.to_string(),
+                Some(v) => v.to_string(),
+            })
+            .collect();
+
+        // the bottom row is never merged
+        if row_idx != last_row_idx {
+            for col_idx in 1..width {
+                let same_parent = match filled_rows.last() {
+                    None => true,
+                    Some(parent_row) => parent_row[col_idx] == parent_row[col_idx - 1],
+                };
+                if filled_row[col_idx].is_empty() && same_parent {
+                    filled_row[col_idx] = filled_row[col_idx - 1].clone();
+                }
+            }
+        }
+        filled_rows.push(filled_row);
+    }
+
+    (0..width)
+        .map(|col_idx| {
+            let keys: Vec<&str> = filled_rows
+                .iter()
+                .map(|row| row[col_idx].as_str())
+                .filter(|key| !key.is_empty())
+                .collect();
+            JsonValue::String(keys.join("."))
+        })
+        .collect()
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct RecordHeader(String);
 
@@ -168,6 +436,17 @@ impl RecordHeader {
         &self.0
     }
 
+    pub fn normalized(self, option: &HeaderOption) -> Self {
+        let mut s = self.0;
+        if option.trim {
+            s = s.trim().to_string();
+        }
+        if option.lowercase {
+            s = s.to_lowercase();
+        }
+        Self(s)
+    }
+
     pub fn empty_values(len: usize) -> Vec<RecordHeader> {
         (0..len)
             .into_iter()
@@ -183,9 +462,94 @@ pub struct RawHeaders {
 }
 
 impl RawHeaders {
+    /// index of the first header whose value equals to `name`.
+    /// the index is the position in `values`, not the column index of the sheet.
+    /// surrounding whitespaces are ignored the same way as the json keys are.
+    pub fn column_index_of(&self, name: &str) -> Option<usize> {
+        let name = name.trim();
+        self.values
+            .iter()
+            .position(|header| header.as_str().trim() == name)
+    }
+
+    /// indices of all the headers that match `re`, in column order
+    pub fn columns_matching(&self, re: &Regex) -> Vec<usize> {
+        self.values
+            .iter()
+            .enumerate()
+            .filter(|(_, header)| re.is_match(header.as_str().trim()))
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
+    /// maps a json object keyed by the headers into the values of a row in column order.
+    /// nested keys like `{"address":{"city":"kyoto"}}` are mapped to `address.city`,
+    /// and the values of duplicated headers are taken from an array in order.
+    /// missing values are `""`
+    pub fn row_from_json(&self, row: &JsonValue) -> std::result::Result<Vec<JsonValue>, String> {
+        let row_obj = match row {
+            JsonValue::Object(obj) => obj,
+            _ => return Err("row must be a json object".to_string()),
+        };
+
+        for key in row_obj.keys() {
+            let is_header = self.values.iter().any(|header| {
+                json_structure::split_keys(header.as_str().trim())
+                    .first()
+                    .map(|first_key| json_structure::unescape_key(first_key))
+                    .as_deref()
+                    == Some(key.as_str())
+            });
+            if !is_header {
+                return Err(format!("unknown header:{}", key));
+            }
+        }
+
+        let mut occurrences: HashMap<&str, usize> = HashMap::new();
+        let mut result = Vec::with_capacity(self.values.len());
+        for header in self.values.iter() {
+            let header = header.as_str().trim();
+            if header.is_empty() {
+                result.push(JsonValue::from(""));
+                continue;
+            }
+
+            let nth = occurrences.entry(header).or_insert(0);
+            let mut value = Some(row);
+            for key in json_structure::split_keys(header) {
+                value = value.and_then(|v| v.get(json_structure::unescape_key(key).as_ref()));
+            }
+            let value = match value {
+                Some(JsonValue::Array(values)) => values.get(*nth),
+                v => {
+                    if *nth == 0 {
+                        v
+                    } else {
+                        None
+                    }
+                }
+            };
+            *nth += 1;
+
+            match value {
+                None | Some(JsonValue::Null) => result.push(JsonValue::from("")),
+                Some(JsonValue::Object(_)) | Some(JsonValue::Array(_)) => {
+                    return Err(format!("value of {} must be a scalar", header))
+                }
+                Some(v) => result.push(v.clone()),
+            }
+        }
+        Ok(result)
+    }
+
+    /// `requested_ranges` are the ranges requested for each of `value_ranges`.
+    /// google sometimes returns a range that can't be parsed (e.g. a sheet name with special chars unquoted),
+    /// then the requested range is used instead
     pub(crate) fn convert_from(
         value_ranges: Vec<ValueRange>,
+        requested_ranges: &[RangeRef],
         specified_range: bool,
+        header_option: &HeaderOption,
     ) -> Result<RawHeaders> {
         if value_ranges.is_empty() {
             return Err(HeaderError::InvalidRangeRefInReturnedValue("".to_string()));
@@ -194,24 +558,38 @@ impl RawHeaders {
         let mut merged_range: Option<RangeRef> = None;
         let mut all_headers: Vec<RecordHeader> = Vec::new();
 
-        for each_value_range in value_ranges {
-            let mut each_response_header_range = RangeRef::from_str(&each_value_range.range)
-                .map_err(|e| {
-                    log::error!("invalid range ref in returned value of header {}", e);
-                    HeaderError::InvalidRangeRefInReturnedValue(each_value_range.range.clone())
-                })?;
+        for (idx, each_value_range) in value_ranges.into_iter().enumerate() {
+            let mut each_response_header_range = match RangeRef::from_str(&each_value_range.range) {
+                Ok(range) => range,
+                Err(e) => match requested_ranges.get(idx) {
+                    Some(requested_range) => {
+                        log::warn!(
+                                "invalid range ref in returned value of header {}. use the requested range {}",
+                                e,
+                                requested_range
+                            );
+                        requested_range.clone()
+                    }
+                    None => {
+                        log::error!("invalid range ref in returned value of header {}", e);
+                        return Err(HeaderError::InvalidRangeRefInReturnedValue(
+                            each_value_range.range.clone(),
+                        ));
+                    }
+                },
+            };
 
-            if each_value_range.values.is_none() {
+            if each_value_range.first_non_empty_row().is_none() {
                 break;
             }
 
             let mut each_header_values = each_value_range.values.unwrap();
-            if each_header_values.is_empty() {
-                break;
-            }
 
-            // not multiple header lines
-            let each_header_values = each_header_values.swap_remove(0);
+            let each_header_values = if each_header_values.len() == 1 {
+                each_header_values.swap_remove(0)
+            } else {
+                compose_header_rows(each_header_values)
+            };
 
             let (headers, range) = if specified_range {
                 let headers_values = if each_header_values.is_empty() {
@@ -220,7 +598,9 @@ impl RawHeaders {
                     let header_value_len = each_header_values.len();
                     let mut headers = Vec::<RecordHeader>::new();
                     for each in each_header_values.into_iter() {
-                        headers.push(RecordHeader::new(each.to_string().as_ref())?);
+                        headers.push(
+                            RecordHeader::new(each.to_string().as_ref())?.normalized(header_option),
+                        );
                     }
 
                     let padding_size: i64 = each_response_header_range.col_range_size() as i64
@@ -245,7 +625,9 @@ impl RawHeaders {
 
                 let mut headers = Vec::<RecordHeader>::new();
                 for each in empty_splited_headers.into_iter() {
-                    headers.push(RecordHeader::new(each.to_string().as_ref())?);
+                    headers.push(
+                        RecordHeader::new(each.to_string().as_ref())?.normalized(header_option),
+                    );
                 }
 
                 if headers.is_empty() {
@@ -288,47 +670,261 @@ impl RawHeaders {
         }
     }
 
+    /// drop the headers beyond the grid. google may return more header columns than
+    /// the `column_count` of the sheet properties, then reading the values of them fails
+    pub(crate) fn clamp_to_grid_width(mut self, max_col_count_of_grid: usize) -> RawHeaders {
+        if self.range.next_col_index() <= max_col_count_of_grid
+            || max_col_count_of_grid <= self.range.start.col_index
+        {
+            return self;
+        }
+        let width = max_col_count_of_grid - self.range.start.col_index;
+        log::warn!(
+            "header range {} exceeds the grid column count {}. clamp to {} headers",
+            self.range,
+            max_col_count_of_grid,
+            width
+        );
+        self.range.end.col_index = max_col_count_of_grid - 1;
+        self.values.truncate(width);
+        self
+    }
+
+    /// the labels down the column of `HeaderOrientation::KeyValue`.
+    /// the labels end at the first empty cell
+    pub(crate) fn convert_from_column(
+        value_range: ValueRange,
+        header_option: &HeaderOption,
+    ) -> Result<RawHeaders> {
+        let mut label_range = RangeRef::from_str(&value_range.range).map_err(|e| {
+            log::error!("invalid range ref in returned value of header {}", e);
+            HeaderError::InvalidRangeRefInReturnedValue(value_range.range.clone())
+        })?;
+
+        let mut labels = Vec::<RecordHeader>::new();
+        for each_row in value_range.values.unwrap_or_default() {
+            let label = match each_row.into_iter().next() {
+                None | Some(JsonValue::Null) => break,
+                Some(JsonValue::String(s)) if s.is_empty() => break,
+                Some(v) => v,
+            };
+            labels.push(RecordHeader::new(label.to_string().as_ref())?.normalized(header_option));
+        }
+
+        if labels.is_empty() {
+            return Err(HeaderError::EmptyHeaderValues(format!(
+                "range:{}",
+                value_range.range
+            )));
+        }
+
+        label_range.end = CellRef::new(
+            label_range.start.col_index,
+            label_range.start.row_index + labels.len() - 1,
+        );
+        Ok(RawHeaders {
+            range: label_range,
+            values: labels,
+        })
+    }
+
+    /// (column count, row count) of the tab sheet
+    fn grid_size(condition: &HeaderSearchCondition) -> Result<(usize, usize)> {
+        let sheet_name = condition.sheet_name.as_ref().map(|s| s.as_str());
+        match condition.sheet_info.find_property_by_name(sheet_name) {
+            None => Err(HeaderError::UnknwonError(format!(
+                "sheet info not found:{:?}",
+                sheet_name
+            ))),
+            Some(property) => Ok((
+                property.properties.grid_properties.column_count,
+                property.properties.grid_properties.row_count,
+            )),
+        }
+    }
+
+    /// reads the labels from the first column of the specified range, or the column A
+    async fn read_raw_headers_in_column<HttpConnector>(
+        client: &ReqClient,
+        base_url: &str,
+        token_manager: Arc<TokenManager<HttpConnector>>,
+        condition: &HeaderSearchCondition,
+    ) -> Result<RawHeaders> {
+        let (max_col_count_of_grid, max_row_count_of_grid) = Self::grid_size(condition)?;
+
+        let label_range = match condition.as_range() {
+            Some(mut range) => {
+                if !range.clamp_to_grid(max_col_count_of_grid, max_row_count_of_grid) {
+                    return Err(HeaderError::RangeOutOfGrid(format!("{}", range)));
+                }
+                let label_end = CellRef::new(range.start.col_index, range.end.row_index);
+                RangeRef::new(range.sheet_name, range.start, label_end)
+            }
+            None => {
+                let label_start = CellRef::new(0, condition.header_option.header_row_index);
+                if max_row_count_of_grid <= label_start.row_index {
+                    return Err(HeaderError::RangeOutOfGrid(format!("{}", label_start)));
+                }
+                RangeRef::new(
+                    condition
+                        .sheet_name
+                        .as_ref()
+                        .map(|s| s.as_str().to_string()),
+                    label_start,
+                    CellRef::new(0, max_row_count_of_grid - 1),
+                )
+            }
+        };
+
+        log::debug!("fetching label range :{}", label_range);
+
+        let sheet_values = get_sheet_value(
+            client,
+            base_url,
+            token_manager,
+            &condition.spread_sheet_id,
+            &[label_range.as_string()],
+            None,
+            None,
+            None,
+        )
+        .await
+        .map_err(|e| {
+            if e.is_not_found() {
+                HeaderError::SpreadSheetNotFound(format!(
+                    "spread sheet {} is not found",
+                    &condition.spread_sheet_id,
+                ))
+            } else if let Some(google_error) = e.access_error() {
+                HeaderError::SheetApiAccessError(google_error.clone())
+            } else {
+                HeaderError::FetchHeaderApiError(format!("{}", e))
+            }
+        })?;
+
+        match sheet_values
+            .value_ranges
+            .and_then(|value_ranges| value_ranges.into_iter().next())
+        {
+            None => Err(HeaderError::EmptyHeaderValueRanges(format!(
+                "latest range {}",
+                label_range
+            ))),
+            Some(value_range) => Self::convert_from_column(value_range, &condition.header_option),
+        }
+    }
+
+    /// reads the first `PEEK_HEADER_ROWS` rows of the rejected multi-line `header_range` to suggest `header_rows`.
+    /// `None` if it fails to read
+    async fn peek_header_like_rows<HttpConnector>(
+        client: &ReqClient,
+        base_url: &str,
+        token_manager: Arc<TokenManager<HttpConnector>>,
+        condition: &HeaderSearchCondition,
+        header_range: &RangeRef,
+    ) -> Option<usize> {
+        let mut peek_range = header_range.clone();
+        peek_range.end.row_index = peek_range.end.row_index.min(
+            peek_range
+                .start
+                .row_index
+                .saturating_add(PEEK_HEADER_ROWS - 1),
+        );
+        let sheet_values = get_sheet_value(
+            client,
+            base_url,
+            token_manager,
+            &condition.spread_sheet_id,
+            &[peek_range.as_string()],
+            None,
+            None,
+            None,
+        )
+        .await;
+        match sheet_values {
+            Err(e) => {
+                log::warn!("failed to peek the header range {} :{}", peek_range, e);
+                None
+            }
+            Ok(sheet_values) => Some(
+                sheet_values
+                    .value_ranges
+                    .and_then(|value_ranges| value_ranges.into_iter().next())
+                    .and_then(|value_range| value_range.values)
+                    .map(|rows| header_like_rows(&rows))
+                    .unwrap_or(0),
+            ),
+        }
+    }
+
+    #[tracing::instrument(
+        name = "read_raw_headers",
+        skip_all,
+        fields(
+            spread_sheet_id = %condition.spread_sheet_id,
+            sheet_name = ?condition.sheet_name.as_ref().map(|name| name.as_str()),
+            range = tracing::field::Empty,
+            headers = tracing::field::Empty,
+        )
+    )]
     pub async fn read_raw_headers<HttpConnector>(
         client: &ReqClient,
+        base_url: &str,
         token_manager: Arc<TokenManager<HttpConnector>>,
         condition: &HeaderSearchCondition,
     ) -> Result<RawHeaders> {
+        if condition.header_option.orientation == HeaderOrientation::KeyValue {
+            return Self::read_raw_headers_in_column(client, base_url, token_manager, condition)
+                .await;
+        }
+
         let specified_range = condition.specified_cell_range.is_some();
+        let (max_col_count_of_grid, max_row_count_of_grid) = Self::grid_size(condition)?;
+
         let mut header_range = match condition.as_range() {
-            Some(range) => range,
-            None => default_header_range(condition.sheet_name.as_ref()),
+            Some(mut range) => {
+                if !range.clamp_to_grid(max_col_count_of_grid, max_row_count_of_grid) {
+                    return Err(HeaderError::RangeOutOfGrid(format!("{}", range)));
+                }
+                range
+            }
+            None => default_header_range(
+                condition.sheet_name.as_ref(),
+                condition.header_option.header_row_index,
+                condition.header_option.header_rows,
+            ),
         };
 
-        if !header_range.is_one_line_row() {
+        if header_range.row_range_size() != condition.header_option.header_row_count() {
             log::warn!("header range is multiple line :{}", header_range);
-            return Err(HeaderError::UnsupportedMultipleHeader(format!(
-                "{}",
-                header_range
-            )));
+            let detected_header_rows = Self::peek_header_like_rows(
+                client,
+                base_url,
+                token_manager.clone(),
+                condition,
+                &header_range,
+            )
+            .await;
+            return Err(HeaderError::UnsupportedMultipleHeader(
+                format!("{}", header_range),
+                detected_header_rows,
+            ));
         }
 
         log::debug!("fetching header range :{}", header_range);
+        tracing::Span::current().record("range", &header_range.as_string().as_str());
 
         let mut all_sheet_values: Option<SheetValues> = None;
-
-        let max_col_count_of_grid = {
-            let sheet_name = condition.sheet_name.as_ref().map(|s| s.as_str());
-            match condition.sheet_info.find_property_by_name(sheet_name) {
-                None => {
-                    return Err(HeaderError::UnknwonError(format!(
-                        "sheet info not found:{:?}",
-                        sheet_name
-                    )))
-                }
-                Some(property) => property.properties.grid_properties.column_count,
-            }
-        };
+        let mut requested_ranges: Vec<RangeRef> = Vec::new();
 
         let mut range_str: String = "".to_string();
         loop {
-            #[cfg(feature = "restricted")]
-            if let Err(e) = is_col_range_overflow(header_range.end_col_index()) {
-                return Err(HeaderError::ColIndexOutOfRescription(e));
+            if let Some(restrictions) = condition.header_option.restrictions.as_ref() {
+                if let Err(e) =
+                    is_col_range_overflow(header_range.end_col_index() + 1, restrictions.max_col)
+                {
+                    return Err(HeaderError::ColIndexOutOfRescription(e));
+                }
             }
 
             if max_col_count_of_grid <= header_range.start_col_index() {
@@ -338,9 +934,10 @@ impl RawHeaders {
 
             let sheet_values = get_sheet_value(
                 &client,
+                base_url,
                 token_manager.clone(),
                 &condition.spread_sheet_id,
-                &range_str,
+                &[range_str.clone()],
                 None,
                 None,
                 None,
@@ -358,6 +955,8 @@ impl RawHeaders {
                             &condition.spread_sheet_id,
                         )),
                     }
+                } else if let Some(google_error) = e.access_error() {
+                    HeaderError::SheetApiAccessError(google_error.clone())
                 } else {
                     HeaderError::FetchHeaderApiError(format!("{}", e))
                 }
@@ -369,29 +968,38 @@ impl RawHeaders {
                 }
                 Ok(sheet_values) => sheet_values,
             };
+            requested_ranges.push(header_range.clone());
 
             if specified_range {
+                // the api returns all the columns of the requested range at once however wide it is.
+                // the 26 columns window below is only for searching the end of the headers
                 all_sheet_values = Some(sheet_values);
                 break;
             } else {
-                // if returned value contains empty data or blank data, finish the loop
-                let is_break = match sheet_values.value_ranges.as_ref() {
-                    Some(value_ranges) => match value_ranges.first() {
-                        None => true,
-                        Some(first_value_line) => match first_value_line.values.as_ref() {
-                            Some(values) => match values.first() {
-                                None => true,
-                                Some(line_values) => line_values
-                                    .iter()
-                                    .find(|each| each.to_string().as_str() == "")
-                                    .is_some(),
-                            },
-
-                            None => true,
-                        },
-                    },
-
-                    None => true,
+                // if returned value contains empty data or blank data, finish the loop.
+                // the bottom row is checked since the parent rows of multiple header rows contain merged(empty) cells
+                let is_break = match sheet_values
+                    .value_ranges
+                    .as_ref()
+                    .and_then(|value_ranges| value_ranges.first())
+                {
+                    Some(first_value_range)
+                        if first_value_range.first_non_empty_row().is_some() =>
+                    {
+                        first_value_range
+                            .values
+                            .iter()
+                            .flatten()
+                            .last()
+                            .map(|line_values| {
+                                line_values.is_empty()
+                                    || line_values
+                                        .iter()
+                                        .any(|each| each.to_string().as_str() == "")
+                            })
+                            .unwrap_or(true)
+                    }
+                    _ => true,
                 };
 
                 match all_sheet_values.as_mut() {
@@ -434,13 +1042,685 @@ impl RawHeaders {
             }
         };
 
-        let result = Self::convert_from(value_ranges, specified_range)?;
+        let result = Self::convert_from(
+            value_ranges,
+            &requested_ranges,
+            specified_range,
+            &condition.header_option,
+        )?
+        .clamp_to_grid_width(max_col_count_of_grid);
+        tracing::Span::current().record("headers", &result.values.len());
         Ok(result)
     }
 }
 
 #[cfg(test)]
-mod test {}
+mod test {
+    use super::*;
+
+    fn jstr(s: &str) -> JsonValue {
+        JsonValue::String(s.to_string())
+    }
+
+    fn sheet_info(titles: &[&str]) -> Sheet {
+        let sheets: Vec<JsonValue> = titles
+            .iter()
+            .enumerate()
+            .map(|(idx, title)| {
+                serde_json::json!({
+                    "properties": {
+                        "sheetId": idx,
+                        "title": title,
+                        "index": idx,
+                        "sheetType": "GRID",
+                        "gridProperties": {"rowCount": 1000, "columnCount": 26},
+                    }
+                })
+            })
+            .collect();
+        serde_json::from_value(serde_json::json!({
+            "spreadsheetId": "sheet_id",
+            "sheets": sheets,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn header_like_rows_test() {
+        let rows: Vec<Vec<JsonValue>> = serde_json::from_value(serde_json::json!([
+            ["user", "", "address"],
+            ["name", "age", "city"],
+            ["Alice", "20", "kyoto"],
+            ["Bob", "30", "osaka"],
+        ]))
+        .unwrap();
+        assert_eq!(header_like_rows(&rows), 2);
+
+        let err = HeaderError::UnsupportedMultipleHeader(
+            "A1:C4".to_string(),
+            Some(header_like_rows(&rows)),
+        );
+        assert_eq!(err.suggestion(), Some("try ?header_rows=2".to_string()));
+
+        // the header row and the data rows
+        assert_eq!(header_like_rows(&rows[1..]), 1);
+        let err = HeaderError::UnsupportedMultipleHeader("A2:C4".to_string(), Some(1));
+        assert_eq!(
+            err.suggestion(),
+            Some("narrow the range to the header row".to_string())
+        );
+
+        // the rows couldn't be read
+        let err = HeaderError::UnsupportedMultipleHeader("A1:C4".to_string(), None);
+        assert_eq!(err.detected_header_rows(), None);
+        assert_eq!(err.suggestion(), None);
+        assert_eq!(err.to_string(), "multiple header not supported:A1:C4");
+
+        let rows: Vec<Vec<JsonValue>> =
+            serde_json::from_value(serde_json::json!([[], ["", " "], ["name"]])).unwrap();
+        assert_eq!(header_like_rows(&rows), 0);
+        assert_eq!(header_like_rows(&[]), 0);
+    }
+
+    #[test]
+    fn resolve_sheet_name_test() {
+        let sheet_info = sheet_info(&["Sheet1", "orders"]);
+        let spread_sheet_id = SpreadSheetId::new("sheet_id".to_string());
+
+        let sheet_name = resolve_sheet_name(
+            &sheet_info,
+            &spread_sheet_id,
+            SheetIdOrName {
+                tab_sheet_id: None,
+                tab_sheet_name: Some("orders".to_string()),
+                tab_sheet_index: None,
+            },
+        );
+        assert_eq!(sheet_name, Ok(Some(SheetName::new("orders".to_string()))));
+
+        let sheet_name = resolve_sheet_name(
+            &sheet_info,
+            &spread_sheet_id,
+            SheetIdOrName {
+                tab_sheet_id: Some(1),
+                tab_sheet_name: None,
+                tab_sheet_index: None,
+            },
+        );
+        assert_eq!(sheet_name, Ok(Some(SheetName::new("orders".to_string()))));
+
+        let sheet_name = resolve_sheet_name(
+            &sheet_info,
+            &spread_sheet_id,
+            SheetIdOrName {
+                tab_sheet_id: None,
+                tab_sheet_name: None,
+                tab_sheet_index: None,
+            },
+        );
+        assert_eq!(sheet_name, Ok(None));
+    }
+
+    #[test]
+    fn resolve_sheet_name_by_index_test() {
+        let sheet_info = sheet_info(&["Sheet1", "orders"]);
+        let spread_sheet_id = SpreadSheetId::new("sheet_id".to_string());
+        let by_index = |index: usize| {
+            resolve_sheet_name(
+                &sheet_info,
+                &spread_sheet_id,
+                SheetIdOrName {
+                    tab_sheet_id: None,
+                    tab_sheet_name: None,
+                    tab_sheet_index: Some(index),
+                },
+            )
+        };
+
+        assert_eq!(by_index(1), Ok(Some(SheetName::new("orders".to_string()))));
+
+        // the same tab as the default
+        assert_eq!(by_index(0), Ok(Some(SheetName::new("Sheet1".to_string()))));
+        assert_eq!(
+            sheet_info
+                .find_property_by_name(None)
+                .map(|prop| prop.properties.title.as_str()),
+            Some("Sheet1")
+        );
+
+        let err = by_index(2).unwrap_err();
+        assert_eq!(err, HeaderError::SheetIndexOutOfRange(2, 2));
+        assert!(!err.is_not_found());
+        assert_eq!(err.code(), "SHEET_INDEX_OUT_OF_RANGE");
+        assert_eq!(err.http_status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            err.to_string(),
+            "sheet index:2 is out of range. the spread sheet has 2 sheets"
+        );
+
+        // the name is preferred to the index
+        let sheet_name = resolve_sheet_name(
+            &sheet_info,
+            &spread_sheet_id,
+            SheetIdOrName {
+                tab_sheet_id: None,
+                tab_sheet_name: Some("orders".to_string()),
+                tab_sheet_index: Some(0),
+            },
+        );
+        assert_eq!(sheet_name, Ok(Some(SheetName::new("orders".to_string()))));
+    }
+
+    #[test]
+    fn resolve_sheet_name_not_found_test() {
+        let sheet_info = sheet_info(&["Sheet1", "orders"]);
+        let spread_sheet_id = SpreadSheetId::new("sheet_id".to_string());
+
+        let err = resolve_sheet_name(
+            &sheet_info,
+            &spread_sheet_id,
+            SheetIdOrName {
+                tab_sheet_id: None,
+                tab_sheet_name: Some("Sheeet1".to_string()),
+                tab_sheet_index: None,
+            },
+        )
+        .unwrap_err();
+
+        assert!(err.is_not_found());
+        assert_eq!(
+            err.available_sheets(),
+            Some(["Sheet1".to_string(), "orders".to_string()].as_slice())
+        );
+        assert_eq!(
+            err.to_string(),
+            "sheet name:Sheeet1 is not found. available sheets:[Sheet1, orders]"
+        );
+    }
+
+    #[test]
+    fn resolve_sheet_name_no_sheets_test() {
+        let sheet_info = sheet_info(&[]);
+        let spread_sheet_id = SpreadSheetId::new("sheet_id".to_string());
+
+        for sheet_id_or_name in [
+            SheetIdOrName {
+                tab_sheet_id: None,
+                tab_sheet_name: None,
+                tab_sheet_index: None,
+            },
+            SheetIdOrName {
+                tab_sheet_id: Some(0),
+                tab_sheet_name: None,
+                tab_sheet_index: None,
+            },
+        ] {
+            let err =
+                resolve_sheet_name(&sheet_info, &spread_sheet_id, sheet_id_or_name).unwrap_err();
+            assert_eq!(
+                err,
+                HeaderError::NoSheetsInSpreadsheet("sheet_id".to_string())
+            );
+            assert!(err.is_not_found());
+            assert_eq!(err.code(), "NO_SHEETS_IN_SPREAD_SHEET");
+        }
+    }
+
+    fn raw_headers(values: &[&str]) -> RawHeaders {
+        RawHeaders {
+            range: RangeRef::from_str("A1:E1").unwrap(),
+            values: values.iter().map(|v| RecordHeader(v.to_string())).collect(),
+        }
+    }
+
+    #[test]
+    fn record_header_leading_apostrophe_test() {
+        assert_eq!(RecordHeader::new("'name").unwrap().as_str(), "name");
+        assert_eq!(RecordHeader::new("don't").unwrap().as_str(), "don't");
+        assert_eq!(RecordHeader::new("''").unwrap().as_str(), "'");
+    }
+
+    #[test]
+    fn column_index_of_test() {
+        let headers = raw_headers(&["name", " age ", "favorite", "favorite", "address.city"]);
+
+        assert_eq!(headers.column_index_of("name"), Some(0));
+        assert_eq!(headers.column_index_of("age"), Some(1));
+        assert_eq!(headers.column_index_of(" name"), Some(0));
+        // duplicated header returns the first one
+        assert_eq!(headers.column_index_of("favorite"), Some(2));
+        assert_eq!(headers.column_index_of("address.city"), Some(4));
+        assert_eq!(headers.column_index_of("address"), None);
+        assert_eq!(headers.column_index_of("not_exists"), None);
+    }
+
+    #[test]
+    fn columns_matching_test() {
+        let headers = raw_headers(&["name", " age ", "favorite", "favorite", "address.city"]);
+
+        assert_eq!(
+            headers.columns_matching(&Regex::new("^favorite$").unwrap()),
+            vec![2, 3]
+        );
+        assert_eq!(
+            headers.columns_matching(&Regex::new(r"^a").unwrap()),
+            vec![1, 4]
+        );
+        assert!(headers
+            .columns_matching(&Regex::new("^zzz").unwrap())
+            .is_empty());
+    }
+
+    #[test]
+    fn row_from_json_test() {
+        let headers = raw_headers(&[
+            "name",
+            "favorite",
+            "favorite",
+            "address.city.name",
+            "address.zipcode",
+        ]);
+
+        let row = serde_json::json!({
+            "name": "Alice",
+            "favorite": ["diving", "programming"],
+            "address": {"city": {"name": "kyoto"}, "zipcode": 6000000},
+        });
+        assert_eq!(
+            headers.row_from_json(&row).unwrap(),
+            vec![
+                jstr("Alice"),
+                jstr("diving"),
+                jstr("programming"),
+                jstr("kyoto"),
+                JsonValue::from(6000000),
+            ]
+        );
+
+        // missing values are empty
+        let row = serde_json::json!({"name": "Bob", "favorite": "shopping"});
+        assert_eq!(
+            headers.row_from_json(&row).unwrap(),
+            vec![jstr("Bob"), jstr("shopping"), jstr(""), jstr(""), jstr("")]
+        );
+    }
+
+    #[test]
+    fn row_from_json_invalid_test() {
+        let headers = raw_headers(&["name", "address.city"]);
+
+        assert!(headers
+            .row_from_json(&serde_json::json!(["Alice"]))
+            .is_err());
+        assert!(headers
+            .row_from_json(&serde_json::json!({"unknown": "x"}))
+            .is_err());
+        assert!(headers
+            .row_from_json(&serde_json::json!({"address": {"city": {"name": "kyoto"}}}))
+            .is_err());
+    }
+
+    #[test]
+    fn convert_from_normalize_header() {
+        let value_ranges = || {
+            vec![ValueRange {
+                range: "grouping!A1:Z1".to_string(),
+                major_dimension: "ROWS".to_string(),
+                values: Some(vec![vec![jstr("  name "), jstr("Age")]]),
+            }]
+        };
+
+        let result =
+            RawHeaders::convert_from(value_ranges(), &[], false, &HeaderOption::default()).unwrap();
+        assert_eq!(
+            result.values,
+            vec![
+                RecordHeader("  name ".to_string()),
+                RecordHeader("Age".to_string())
+            ]
+        );
+
+        let option = HeaderOption {
+            trim: true,
+            ..HeaderOption::default()
+        };
+        let result = RawHeaders::convert_from(value_ranges(), &[], false, &option).unwrap();
+        assert_eq!(
+            result.values,
+            vec![
+                RecordHeader("name".to_string()),
+                RecordHeader("Age".to_string())
+            ]
+        );
+
+        let option = HeaderOption {
+            trim: true,
+            lowercase: true,
+            ..HeaderOption::default()
+        };
+        let result = RawHeaders::convert_from(value_ranges(), &[], false, &option).unwrap();
+        assert_eq!(
+            result.values,
+            vec![
+                RecordHeader("name".to_string()),
+                RecordHeader("age".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn default_header_range_test() {
+        assert_eq!(default_header_range(None, 0, 1).as_string(), "A1:Z1");
+        assert_eq!(
+            default_header_range(Some(&SheetName::new("s".to_string())), 1, 1).as_string(),
+            "'s'!A2:Z2"
+        );
+        assert_eq!(default_header_range(None, 0, 0).as_string(), "A1:Z1");
+        assert_eq!(default_header_range(None, 1, 2).as_string(), "A2:Z3");
+    }
+
+    #[test]
+    fn convert_from_wide_specified_range_test() {
+        let header_values = |len: usize| -> Vec<JsonValue> {
+            (0..len)
+                .map(|idx| jstr(&format!("col_{}", num_to_alphabet_base_number(idx))))
+                .collect()
+        };
+        let value_ranges = |values: Vec<JsonValue>| {
+            vec![ValueRange {
+                range: "Sheet1!A1:BZ1".to_string(),
+                major_dimension: "ROWS".to_string(),
+                values: Some(vec![values]),
+            }]
+        };
+
+        let result = RawHeaders::convert_from(
+            value_ranges(header_values(78)),
+            &[],
+            true,
+            &HeaderOption::default(),
+        )
+        .unwrap();
+        assert_eq!(result.range.as_string(), "'Sheet1'!A1:BZ1");
+        assert_eq!(result.values.len(), 78);
+        assert_eq!(result.values[26].as_str(), "col_AA");
+        assert_eq!(result.values[77].as_str(), "col_BZ");
+
+        // the trailing empty cells omitted by the api are padded up to the range
+        let result = RawHeaders::convert_from(
+            value_ranges(header_values(30)),
+            &[],
+            true,
+            &HeaderOption::default(),
+        )
+        .unwrap();
+        assert_eq!(result.values.len(), 78);
+        assert_eq!(result.values[29].as_str(), "col_AD");
+        assert_eq!(result.values[77].as_str(), "");
+    }
+
+    #[test]
+    fn convert_from_beyond_grid_test() {
+        // the returned header is wider than the 3 columns grid
+        let value_ranges = vec![ValueRange {
+            range: "Sheet1!A1:E1".to_string(),
+            major_dimension: "ROWS".to_string(),
+            values: Some(vec![vec![
+                jstr("a"),
+                jstr("b"),
+                jstr("c"),
+                jstr("d"),
+                jstr("e"),
+            ]]),
+        }];
+
+        let result = RawHeaders::convert_from(value_ranges, &[], false, &HeaderOption::default())
+            .unwrap()
+            .clamp_to_grid_width(3);
+        assert_eq!(result.range.as_string(), "'Sheet1'!A1:C1");
+        assert_eq!(
+            result
+                .values
+                .iter()
+                .map(|each| each.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["a", "b", "c"]
+        );
+
+        // headers in the grid are untouched
+        let result = result.clamp_to_grid_width(10);
+        assert_eq!(result.range.as_string(), "'Sheet1'!A1:C1");
+        assert_eq!(result.values.len(), 3);
+    }
+
+    #[test]
+    fn convert_from_unparseable_range_test() {
+        let value_ranges = || {
+            vec![ValueRange {
+                range: "'odd sheet'!1:1".to_string(),
+                major_dimension: "ROWS".to_string(),
+                values: Some(vec![vec![jstr("name"), jstr("age"), jstr("")]]),
+            }]
+        };
+        let requested_range = RangeRef::new(
+            Some("odd sheet".to_string()),
+            CellRef::new(0, 0),
+            CellRef::new(25, 0),
+        );
+
+        let result = RawHeaders::convert_from(
+            value_ranges(),
+            &[requested_range],
+            false,
+            &HeaderOption::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            result.values,
+            vec![
+                RecordHeader("name".to_string()),
+                RecordHeader("age".to_string())
+            ]
+        );
+        assert_eq!(result.range.col_range_indices(), (0, 1));
+
+        // no range to fall back on
+        assert_eq!(
+            RawHeaders::convert_from(value_ranges(), &[], false, &HeaderOption::default()),
+            Err(HeaderError::InvalidRangeRefInReturnedValue(
+                "'odd sheet'!1:1".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn convert_from_header_at_second_row() {
+        let value_ranges = vec![ValueRange {
+            range: "grouping!A2:Z2".to_string(),
+            major_dimension: "ROWS".to_string(),
+            values: Some(vec![vec![jstr("name"), jstr("age"), jstr("")]]),
+        }];
+
+        let result =
+            RawHeaders::convert_from(value_ranges, &[], false, &HeaderOption::default()).unwrap();
+        assert_eq!(result.range.as_string(), "'grouping'!A2:B2");
+        assert_eq!(
+            result.values,
+            vec![
+                RecordHeader("name".to_string()),
+                RecordHeader("age".to_string())
+            ]
+        );
+
+        // data starts from the row just below the header
+        assert_eq!(result.range.next_row_index(), 2);
+        let (start_row_idx, finish_row_idx, _) = FetchRowCondition::with_pagination(None, Some(10))
+            .row_index_window(result.range.next_row_index());
+        assert_eq!((start_row_idx, finish_row_idx), (2, 12));
+        assert_eq!(
+            last_index_in_grid(start_row_idx, finish_row_idx, 1000),
+            Some(11)
+        );
+
+        // the configured default limit is used if no limit is requested
+        let (start_row_idx, finish_row_idx, _) = FetchRowCondition::with_pagination(None, None)
+            .with_row_limits(RowLimits::new(5, 50))
+            .row_index_window(result.range.next_row_index());
+        assert_eq!((start_row_idx, finish_row_idx), (2, 7));
+
+        let (start_row_idx, _, _) = FetchRowCondition::with_specific_row_idx(0)
+            .row_index_window(result.range.next_row_index());
+        assert_eq!(start_row_idx, 2);
+    }
+
+    #[test]
+    fn convert_from_column_test() {
+        // key value sheet
+        //   | A        | B        | C       |
+        // 1 | name     | Alice    | Bob     |
+        // 2 | age      | 21       | 34      |
+        // 3 | city     | kyoto    |         |
+        // 4 |          |          |         |
+        // 5 | memo     | ignored  |         |
+        let value_range = ValueRange {
+            range: "Sheet1!A1:A1000".to_string(),
+            major_dimension: "ROWS".to_string(),
+            values: Some(vec![
+                vec![jstr("name")],
+                vec![jstr(" age ")],
+                vec![jstr("city")],
+                vec![],
+                vec![jstr("memo")],
+            ]),
+        };
+        let option = HeaderOption {
+            trim: true,
+            orientation: HeaderOrientation::KeyValue,
+            ..HeaderOption::default()
+        };
+        let headers = RawHeaders::convert_from_column(value_range, &option).unwrap();
+        assert_eq!(
+            headers.values,
+            vec![
+                RecordHeader("name".to_string()),
+                RecordHeader("age".to_string()),
+                RecordHeader("city".to_string()),
+            ]
+        );
+        assert_eq!(headers.range, RangeRef::from_str("Sheet1!A1:A3").unwrap());
+        assert_eq!(headers.range.next_col_index(), 1);
+        assert_eq!(headers.range.row_range_indices(), (0, 2));
+
+        // the columns B and C read by columns
+        let sheet_response = SheetValueResponse {
+            headers,
+            row_values: RowValues::new(vec![
+                vec![
+                    CellValue::new(jstr("Alice")),
+                    CellValue::new(jstr("21")),
+                    CellValue::new(jstr("kyoto")),
+                ],
+                vec![
+                    CellValue::new(jstr("Bob")),
+                    CellValue::new(jstr("34")),
+                    CellValue::new(jstr("")),
+                ],
+            ]),
+            pagination: None,
+            total_rows: Some(2),
+            rows_in_window: None,
+        };
+        assert_eq!(
+            sheet_response.to_json(false).unwrap(),
+            serde_json::json!([
+                {"name": "Alice", "age": "21", "city": "kyoto"},
+                {"name": "Bob", "age": "34", "city": ""},
+            ])
+        );
+
+        let value_range = ValueRange {
+            range: "Sheet1!A1:A1000".to_string(),
+            major_dimension: "ROWS".to_string(),
+            values: None,
+        };
+        assert!(matches!(
+            RawHeaders::convert_from_column(value_range, &option),
+            Err(HeaderError::EmptyHeaderValues(_))
+        ));
+    }
+
+    #[test]
+    fn header_orientation_test() {
+        assert_eq!(
+            HeaderOrientation::from_str("key_value"),
+            Ok(HeaderOrientation::KeyValue)
+        );
+        assert_eq!(
+            HeaderOrientation::from_str("rows"),
+            Ok(HeaderOrientation::Rows)
+        );
+        assert!(HeaderOrientation::from_str("columns").is_err());
+        assert_eq!(HeaderOption::default().orientation, HeaderOrientation::Rows);
+    }
+
+    #[test]
+    fn compose_header_rows_test() {
+        // address spans 2 columns as a merged cell
+        let rows = vec![
+            vec![jstr("name"), jstr("address"), jstr(""), jstr("age")],
+            vec![jstr(""), jstr("city"), jstr("zip")],
+        ];
+        assert_eq!(
+            compose_header_rows(rows),
+            vec![
+                jstr("name"),
+                jstr("address.city"),
+                jstr("address.zip"),
+                jstr("age")
+            ]
+        );
+
+        // the forward filling stops at the boundary of the grand parent
+        let rows = vec![
+            vec![jstr("a"), jstr(""), jstr("b"), jstr("")],
+            vec![jstr("x"), jstr(""), jstr(""), jstr("")],
+            vec![jstr("1"), jstr("2"), jstr("3"), jstr("4")],
+        ];
+        assert_eq!(
+            compose_header_rows(rows),
+            vec![jstr("a.x.1"), jstr("a.x.2"), jstr("b.3"), jstr("b.4")]
+        );
+    }
+
+    #[test]
+    fn convert_from_multiple_header_rows() {
+        let value_ranges = vec![ValueRange {
+            range: "grouping!A1:Z2".to_string(),
+            major_dimension: "ROWS".to_string(),
+            values: Some(vec![
+                vec![jstr("name"), jstr("address"), jstr("")],
+                vec![jstr(""), jstr("city"), jstr("zip")],
+            ]),
+        }];
+
+        let option = HeaderOption {
+            header_rows: 2,
+            ..HeaderOption::default()
+        };
+        let result = RawHeaders::convert_from(value_ranges, &[], false, &option).unwrap();
+        assert_eq!(
+            result.values,
+            vec![
+                RecordHeader("name".to_string()),
+                RecordHeader("address.city".to_string()),
+                RecordHeader("address.zip".to_string()),
+            ]
+        );
+        assert_eq!(result.range.as_string(), "'grouping'!A1:C2");
+        // data starts from the row just below the header rows
+        assert_eq!(result.range.next_row_index(), 2);
+    }
+}
 
 #[cfg(all(test, feature = "test-using-sa"))]
 mod cloud_test {
@@ -456,6 +1736,7 @@ mod cloud_test {
     fn get_expected_sheet_info() -> Sheet {
         Sheet {
             spreadsheet_id: TEST_SHEET1_ID.to_string(),
+            properties: None,
             sheets: vec![
                 SheetProperty {
                     properties: SheetPropertyData {
@@ -547,17 +1828,29 @@ mod cloud_test {
 
         let sheet_mata = SheetMeta::new(sheet_id.to_string(), Some(TEST_SHEET1_EMPTY_TAG_ID), None);
 
-        let result = HeaderSearchCondition::create(&client, token_manager, sheet_mata, None).await;
+        let result = HeaderSearchCondition::create(
+            &client,
+            DEFAULT_BASE_ENDPOINT_V4,
+            token_manager,
+            sheet_mata,
+            None,
+            HeaderOption::default(),
+        )
+        .await;
         assert!(result.is_ok());
+        let mut result = result.unwrap();
+        // spread sheet properties (e.g. time zone) are not a concern of these tests
+        result.sheet_info.properties = None;
 
         let expected = HeaderSearchCondition::new(
             SpreadSheetId::new(TEST_SHEET1_ID.to_string()),
             Some(SheetName::new("empty_sheet".to_string())),
             None,
+            HeaderOption::default(),
             get_expected_sheet_info(),
         );
 
-        assert_eq!(expected, result.unwrap());
+        assert_eq!(expected, result);
     }
 
     #[tokio::test]
@@ -577,17 +1870,29 @@ mod cloud_test {
         let sheet_id = TEST_SHEET1_ID;
 
         let sheet_mata = SheetMeta::new(sheet_id.to_string(), None, None);
-        let result = HeaderSearchCondition::create(&client, token_manager, sheet_mata, None).await;
+        let result = HeaderSearchCondition::create(
+            &client,
+            DEFAULT_BASE_ENDPOINT_V4,
+            token_manager,
+            sheet_mata,
+            None,
+            HeaderOption::default(),
+        )
+        .await;
         assert!(result.is_ok());
+        let mut result = result.unwrap();
+        // spread sheet properties (e.g. time zone) are not a concern of these tests
+        result.sheet_info.properties = None;
 
         let expected = HeaderSearchCondition::new(
             SpreadSheetId::new(TEST_SHEET1_ID.to_string()),
             None,
             None,
+            HeaderOption::default(),
             get_expected_sheet_info(),
         );
 
-        assert_eq!(expected, result.unwrap());
+        assert_eq!(expected, result);
     }
 
     #[tokio::test]
@@ -611,17 +1916,29 @@ mod cloud_test {
             None,
             Some("name_specified_sheet".to_string()),
         );
-        let result = HeaderSearchCondition::create(&client, token_manager, sheet_mata, None).await;
+        let result = HeaderSearchCondition::create(
+            &client,
+            DEFAULT_BASE_ENDPOINT_V4,
+            token_manager,
+            sheet_mata,
+            None,
+            HeaderOption::default(),
+        )
+        .await;
         assert!(result.is_ok());
+        let mut result = result.unwrap();
+        // spread sheet properties (e.g. time zone) are not a concern of these tests
+        result.sheet_info.properties = None;
 
         let expected = HeaderSearchCondition::new(
             SpreadSheetId::new(TEST_SHEET1_ID.to_string()),
             Some(SheetName::new("name_specified_sheet".to_string())),
             None,
+            HeaderOption::default(),
             get_expected_sheet_info(),
         );
 
-        assert_eq!(expected, result.unwrap());
+        assert_eq!(expected, result);
     }
 
     #[tokio::test]
@@ -641,17 +1958,29 @@ mod cloud_test {
         let sheet_id = TEST_SHEET1_ID;
 
         let sheet_mata = SheetMeta::new(sheet_id.to_string(), Some(0), None);
-        let result = HeaderSearchCondition::create(&client, token_manager, sheet_mata, None).await;
+        let result = HeaderSearchCondition::create(
+            &client,
+            DEFAULT_BASE_ENDPOINT_V4,
+            token_manager,
+            sheet_mata,
+            None,
+            HeaderOption::default(),
+        )
+        .await;
         assert!(result.is_ok());
+        let mut result = result.unwrap();
+        // spread sheet properties (e.g. time zone) are not a concern of these tests
+        result.sheet_info.properties = None;
 
         let expected = HeaderSearchCondition::new(
             SpreadSheetId::new(TEST_SHEET1_ID.to_string()),
             None,
             None,
+            HeaderOption::default(),
             get_expected_sheet_info(),
         );
 
-        assert_eq!(expected, result.unwrap());
+        assert_eq!(expected, result);
     }
 
     #[tokio::test]
@@ -672,7 +2001,15 @@ mod cloud_test {
         let sheet_not_exist_tag_id = 12345;
 
         let sheet_mata = SheetMeta::new(sheet_id.to_string(), Some(sheet_not_exist_tag_id), None);
-        let result = HeaderSearchCondition::create(&client, token_manager, sheet_mata, None).await;
+        let result = HeaderSearchCondition::create(
+            &client,
+            DEFAULT_BASE_ENDPOINT_V4,
+            token_manager,
+            sheet_mata,
+            None,
+            HeaderOption::default(),
+        )
+        .await;
         assert!(result.is_err());
     }
 
@@ -693,12 +2030,24 @@ mod cloud_test {
         let sheet_id = TEST_SHEET1_ID;
 
         let sheet_mata = SheetMeta::new(sheet_id.to_string(), None, None);
-        let condition =
-            HeaderSearchCondition::create(&client, token_manager.clone(), sheet_mata, None)
-                .await
-                .unwrap();
+        let condition = HeaderSearchCondition::create(
+            &client,
+            DEFAULT_BASE_ENDPOINT_V4,
+            token_manager.clone(),
+            sheet_mata,
+            None,
+            HeaderOption::default(),
+        )
+        .await
+        .unwrap();
 
-        let result = RawHeaders::read_raw_headers(&client, token_manager, &condition).await;
+        let result = RawHeaders::read_raw_headers(
+            &client,
+            DEFAULT_BASE_ENDPOINT_V4,
+            token_manager,
+            &condition,
+        )
+        .await;
 
         assert!(result.is_ok());
         let result = result.unwrap();
@@ -746,17 +2095,25 @@ mod cloud_test {
         );
         let condition = HeaderSearchCondition::create(
             &client,
+            DEFAULT_BASE_ENDPOINT_V4,
             token_manager.clone(),
             sheet_mata,
             Some((
                 CellRef::from_str("B2").unwrap(),
                 CellRef::from_str("D2").unwrap(),
             )),
+            HeaderOption::default(),
         )
         .await
         .unwrap();
 
-        let result = RawHeaders::read_raw_headers(&client, token_manager, &condition).await;
+        let result = RawHeaders::read_raw_headers(
+            &client,
+            DEFAULT_BASE_ENDPOINT_V4,
+            token_manager,
+            &condition,
+        )
+        .await;
 
         assert!(result.is_ok());
         let result = result.unwrap();
@@ -793,12 +2150,24 @@ mod cloud_test {
         let sheet_mata =
             SheetMeta::new(sheet_id.to_string(), None, Some("many headers".to_string()));
 
-        let condition =
-            HeaderSearchCondition::create(&client, token_manager.clone(), sheet_mata, None)
-                .await
-                .unwrap();
+        let condition = HeaderSearchCondition::create(
+            &client,
+            DEFAULT_BASE_ENDPOINT_V4,
+            token_manager.clone(),
+            sheet_mata,
+            None,
+            HeaderOption::default(),
+        )
+        .await
+        .unwrap();
 
-        let result = RawHeaders::read_raw_headers(&client, token_manager, &condition).await;
+        let result = RawHeaders::read_raw_headers(
+            &client,
+            DEFAULT_BASE_ENDPOINT_V4,
+            token_manager,
+            &condition,
+        )
+        .await;
 
         assert!(result.is_ok());
         let result = result.unwrap();
@@ -838,12 +2207,24 @@ mod cloud_test {
             Some("not existing shet".to_string()),
         );
 
-        let condition =
-            HeaderSearchCondition::create(&client, token_manager.clone(), sheet_mata, None)
-                .await
-                .unwrap();
+        let condition = HeaderSearchCondition::create(
+            &client,
+            DEFAULT_BASE_ENDPOINT_V4,
+            token_manager.clone(),
+            sheet_mata,
+            None,
+            HeaderOption::default(),
+        )
+        .await
+        .unwrap();
 
-        let result = RawHeaders::read_raw_headers(&client, token_manager, &condition).await;
+        let result = RawHeaders::read_raw_headers(
+            &client,
+            DEFAULT_BASE_ENDPOINT_V4,
+            token_manager,
+            &condition,
+        )
+        .await;
 
         assert!(result.is_err());
     }
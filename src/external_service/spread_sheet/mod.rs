@@ -1,32 +1,82 @@
 mod api;
 mod cell;
+mod client;
+mod cursor;
+mod date;
 mod header;
 mod range;
 mod restricted;
 mod sheet;
+mod sheet_cache;
+mod to_json;
 mod token_manager;
 mod value;
 
 pub use api::*;
 pub use cell::*;
+pub use client::*;
+pub use cursor::*;
+pub use date::*;
 pub use header::*;
 use once_cell::sync::OnceCell;
 pub use range::*;
 use reqwest::Client as ReqClient;
-#[cfg(feature = "restricted")]
-use restricted::*;
+use reqwest::StatusCode;
+pub use restricted::*;
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 pub use sheet::*;
+pub use sheet_cache::*;
 use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
+pub use to_json::*;
 pub use token_manager::*;
 pub use value::*;
 
 static REQWEST_CLIENT: OnceCell<ReqClient> = OnceCell::new();
 
+/// the client with the default settings is used unless `init_reqwest_client` is called before
 fn reqwest_client() -> &'static ReqClient {
-    REQWEST_CLIENT.get_or_init(|| ReqClient::new())
+    REQWEST_CLIENT.get_or_init(|| {
+        build_reqwest_client(&HttpClientOption::default()).expect("failed to build http client")
+    })
+}
+
+/// settings of the http client to the google apis
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HttpClientOption {
+    pub connect_timeout: Option<Duration>,
+    /// of each request to the google apis
+    pub timeout: Option<Duration>,
+    /// e.g. `http://proxy.example.com:8080`
+    pub https_proxy: Option<String>,
+    pub pool_max_idle_per_host: Option<usize>,
+}
+
+/// the responses are requested compressed with `Accept-Encoding: gzip, br` and decoded transparently
+fn build_reqwest_client(option: &HttpClientOption) -> reqwest::Result<ReqClient> {
+    let mut builder = ReqClient::builder().gzip(true).brotli(true);
+    if let Some(connect_timeout) = option.connect_timeout {
+        builder = builder.connect_timeout(connect_timeout);
+    }
+    if let Some(timeout) = option.timeout {
+        builder = builder.timeout(timeout);
+    }
+    if let Some(https_proxy) = option.https_proxy.as_ref() {
+        builder = builder.proxy(reqwest::Proxy::https(https_proxy)?);
+    }
+    if let Some(pool_max_idle_per_host) = option.pool_max_idle_per_host {
+        builder = builder.pool_max_idle_per_host(pool_max_idle_per_host);
+    }
+    builder.build()
+}
+
+/// builds the shared http client. must be called before any request to the google apis,
+/// otherwise the client with the default settings has been already built and `Ok(false)` is returned
+pub fn init_reqwest_client(option: &HttpClientOption) -> reqwest::Result<bool> {
+    let client = build_reqwest_client(option)?;
+    Ok(REQWEST_CLIENT.set(client).is_ok())
 }
 
 type Result<T> = std::result::Result<T, SpreadSheetError>;
@@ -50,43 +100,447 @@ impl SpreadSheetError {
             false
         }
     }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            SpreadSheetError::HeaderError(e) => e.code(),
+            SpreadSheetError::ValueError(e) => e.code(),
+        }
+    }
+
+    pub fn http_status(&self) -> StatusCode {
+        match self {
+            SpreadSheetError::HeaderError(e) => e.http_status(),
+            SpreadSheetError::ValueError(e) => e.http_status(),
+        }
+    }
+
+    pub fn available_sheets(&self) -> Option<&[String]> {
+        if let SpreadSheetError::HeaderError(e) = self {
+            e.available_sheets()
+        } else {
+            None
+        }
+    }
+
+    pub fn detected_header_rows(&self) -> Option<usize> {
+        if let SpreadSheetError::HeaderError(e) = self {
+            e.detected_header_rows()
+        } else {
+            None
+        }
+    }
+
+    pub fn suggestion(&self) -> Option<String> {
+        if let SpreadSheetError::HeaderError(e) = self {
+            e.suggestion()
+        } else {
+            None
+        }
+    }
 }
 
 pub mod scopes {
+    use std::str::FromStr;
+
     pub const SHEET_READ_ONLY: &[&'static str] =
         &["https://www.googleapis.com/auth/spreadsheets.readonly"];
+
+    pub const SHEET_READ_WRITE: &[&'static str] = &["https://www.googleapis.com/auth/spreadsheets"];
+
+    pub const DRIVE_READ_ONLY: &[&'static str] =
+        &["https://www.googleapis.com/auth/drive.readonly"];
+
+    /// the scope that the server requests to google
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum SheetScope {
+        ReadOnly,
+        ReadWrite,
+    }
+
+    impl SheetScope {
+        pub fn scopes(&self) -> &'static [&'static str] {
+            match self {
+                Self::ReadOnly => SHEET_READ_ONLY,
+                Self::ReadWrite => SHEET_READ_WRITE,
+            }
+        }
+
+        pub fn is_writable(&self) -> bool {
+            *self == Self::ReadWrite
+        }
+    }
+
+    impl Default for SheetScope {
+        fn default() -> Self {
+            Self::ReadOnly
+        }
+    }
+
+    /// a scope name of `--scopes`
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum ScopeKey {
+        SheetsReadOnly,
+        Sheets,
+        DriveReadOnly,
+    }
+
+    impl ScopeKey {
+        pub fn scopes(&self) -> &'static [&'static str] {
+            match self {
+                Self::SheetsReadOnly => SHEET_READ_ONLY,
+                Self::Sheets => SHEET_READ_WRITE,
+                Self::DriveReadOnly => DRIVE_READ_ONLY,
+            }
+        }
+    }
+
+    impl FromStr for ScopeKey {
+        type Err = String;
+
+        /// `sheets.readonly`, `sheets` or `drive.readonly`
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s {
+                "sheets.readonly" => Ok(Self::SheetsReadOnly),
+                "sheets" => Ok(Self::Sheets),
+                "drive.readonly" => Ok(Self::DriveReadOnly),
+                _ => Err(format!("unknown scope:{}", s)),
+            }
+        }
+    }
+
+    /// "sheets.readonly, drive.readonly" -> [SheetsReadOnly, DriveReadOnly]
+    pub fn parse_scope_keys(keys: &str) -> Result<Vec<ScopeKey>, String> {
+        keys.split(',')
+            .map(|each| each.trim())
+            .filter(|each| !each.is_empty())
+            .map(ScopeKey::from_str)
+            .collect()
+    }
+
+    /// the scope urls of `keys` without duplicates
+    pub fn scope_urls(keys: &[ScopeKey]) -> Vec<&'static str> {
+        let mut urls: Vec<&'static str> = Vec::new();
+        for url in keys.iter().flat_map(|key| key.scopes().iter()) {
+            if !urls.contains(url) {
+                urls.push(url);
+            }
+        }
+        urls
+    }
+
+    impl FromStr for SheetScope {
+        type Err = String;
+
+        /// `readonly` or `readwrite`
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s.to_lowercase().as_str() {
+                "readonly" => Ok(Self::ReadOnly),
+                "readwrite" => Ok(Self::ReadWrite),
+                _ => Err(format!("unknown sheet scope:{}", s)),
+            }
+        }
+    }
 }
 
 pub struct FetchRowCondition {
     specific_row_idx: Option<usize>,
+    row_indices: Option<Vec<usize>>,
     pagination: Option<Pagination>,
+    row_limits: RowLimits,
+    last_row_idx: Option<usize>,
+    last_col_idx: Option<usize>,
+    unformatted_values: bool,
+    formulas: bool,
+    pad: bool,
+    ranges: Option<Vec<RangeRef>>,
+    accurate_rows: bool,
+    include_empty_rows: bool,
 }
 
 impl FetchRowCondition {
     pub fn with_specific_row_idx(row_idx: usize) -> Self {
         Self {
             specific_row_idx: Some(row_idx),
+            row_indices: None,
             pagination: None,
+            row_limits: RowLimits::default(),
+            last_row_idx: None,
+            last_col_idx: None,
+            unformatted_values: false,
+            formulas: false,
+            pad: true,
+            ranges: None,
+            accurate_rows: false,
+            include_empty_rows: true,
+        }
+    }
+
+    /// the rows are returned in the order of `row_indices`
+    pub fn with_row_indices(row_indices: Vec<usize>) -> Self {
+        Self {
+            specific_row_idx: None,
+            row_indices: Some(row_indices),
+            pagination: None,
+            row_limits: RowLimits::default(),
+            last_row_idx: None,
+            last_col_idx: None,
+            unformatted_values: false,
+            formulas: false,
+            pad: true,
+            ranges: None,
+            accurate_rows: false,
+            include_empty_rows: true,
         }
     }
 
     pub fn with_pagination(offset: Option<usize>, limit: Option<usize>) -> Self {
         Self {
             specific_row_idx: None,
+            row_indices: None,
             pagination: Some(Pagination::new(offset, limit)),
+            row_limits: RowLimits::default(),
+            last_row_idx: None,
+            last_col_idx: None,
+            unformatted_values: false,
+            formulas: false,
+            pad: true,
+            ranges: None,
+            accurate_rows: false,
+            include_empty_rows: true,
+        }
+    }
+
+    /// the rows below `last_row_idx` are not read as if the grid ends at the row
+    pub fn with_last_row_idx(mut self, last_row_idx: usize) -> Self {
+        self.last_row_idx = Some(last_row_idx);
+        self
+    }
+
+    /// the row count of the grid limited by `last_row_idx`
+    pub fn row_count_to_read(&self, max_row_count_of_grid: usize) -> usize {
+        match self.last_row_idx {
+            Some(last_row_idx) => max_row_count_of_grid.min(last_row_idx.saturating_add(1)),
+            None => max_row_count_of_grid,
+        }
+    }
+
+    /// the columns right of `last_col_idx` are not read as if the grid ends at the column.
+    /// for the key-value orientation, whose records are the columns
+    pub fn with_last_col_idx(mut self, last_col_idx: usize) -> Self {
+        self.last_col_idx = Some(last_col_idx);
+        self
+    }
+
+    /// the column count of the grid limited by `last_col_idx`
+    pub fn col_count_to_read(&self, max_col_count_of_grid: usize) -> usize {
+        match self.last_col_idx {
+            Some(last_col_idx) => max_col_count_of_grid.min(last_col_idx.saturating_add(1)),
+            None => max_col_count_of_grid,
+        }
+    }
+
+    /// dates are read as serial numbers and numbers as numbers
+    pub fn with_unformatted_values(mut self, unformatted_values: bool) -> Self {
+        self.unformatted_values = unformatted_values;
+        self
+    }
+
+    /// the formulas are read instead of the computed values. the headers are read formatted anyway
+    pub fn with_formulas(mut self, formulas: bool) -> Self {
+        self.formulas = formulas;
+        self
+    }
+
+    /// `false` returns the short rows at their natural length instead of filling them with `""`
+    pub fn with_pad(mut self, pad: bool) -> Self {
+        self.pad = pad;
+        self
+    }
+
+    pub fn pad(&self) -> bool {
+        self.pad
+    }
+
+    /// the records are bounded by the last non-empty cell of the first header column
+    /// instead of the grid size. it costs one more request to scan the column
+    pub fn with_accurate_rows(mut self, accurate_rows: bool) -> Self {
+        self.accurate_rows = accurate_rows;
+        self
+    }
+
+    pub fn accurate_rows(&self) -> bool {
+        self.accurate_rows
+    }
+
+    /// `false` drops the rows whose cells are all empty after reading the window.
+    /// the dropped rows still count against the window, so a page may have fewer rows than the limit
+    /// and the cursor and the offset of the next page are not shifted
+    pub fn with_include_empty_rows(mut self, include_empty_rows: bool) -> Self {
+        self.include_empty_rows = include_empty_rows;
+        self
+    }
+
+    pub fn include_empty_rows(&self) -> bool {
+        self.include_empty_rows
+    }
+
+    /// the rows of the disjoint ranges of a same width are concatenated.
+    /// the headers are of the first range and every range starts with the header rows,
+    /// which are skipped in the other ranges. preferred to the other conditions
+    pub fn with_ranges(mut self, ranges: Vec<RangeRef>) -> Self {
+        self.ranges = Some(ranges);
+        self
+    }
+
+    /// the ranges of `with_ranges` without the header rows at the top of each range.
+    /// `data_start_row_idx` is the row index just below the header rows of the first range
+    pub fn data_ranges(&self, data_start_row_idx: usize) -> Option<Vec<RangeRef>> {
+        let ranges = self.ranges.as_ref()?;
+        let header_row_count = match ranges.first() {
+            None => 0,
+            Some(first) => data_start_row_idx.saturating_sub(first.start.row_index),
+        };
+        let data_ranges = ranges
+            .iter()
+            .filter_map(|range| {
+                let data_start_row_idx = range.start.row_index.saturating_add(header_row_count);
+                if range.end.row_index < data_start_row_idx {
+                    return None;
+                }
+                let mut data_range = range.clone();
+                data_range.start.row_index = data_start_row_idx;
+                Some(data_range)
+            })
+            .collect();
+        Some(data_ranges)
+    }
+
+    pub fn with_row_limits(mut self, row_limits: RowLimits) -> Self {
+        self.row_limits = row_limits;
+        self
+    }
+
+    pub fn row_limits(&self) -> &RowLimits {
+        &self.row_limits
+    }
+
+    pub fn with_cursor(cursor: &Cursor) -> Self {
+        Self::with_pagination(Some(cursor.offset), Some(cursor.limit))
+    }
+
+    /// the rows are selected by `with_row_indices`
+    pub fn is_row_selection(&self) -> bool {
+        self.row_indices.is_some()
+    }
+
+    /// the row indices of the grid of `with_row_indices`.
+    /// the rows beyond the grid, including the ones whose index overflows, are skipped
+    pub fn row_indices_in_grid(
+        &self,
+        data_start_row_idx: usize,
+        max_row_count_of_grid: usize,
+    ) -> Option<Vec<usize>> {
+        self.row_indices.as_ref().map(|row_indices| {
+            row_indices
+                .iter()
+                .filter_map(|row_idx| data_start_row_idx.checked_add(*row_idx))
+                .filter(|row_idx| *row_idx < max_row_count_of_grid)
+                .collect()
+        })
+    }
+
+    /// returns (start_row_idx, finish_row_idx, pagination) of the rows to read.
+    /// `finish_row_idx` is exclusive. `data_start_row_idx` is the row index just below the header row
+    pub fn row_index_window(
+        &self,
+        data_start_row_idx: usize,
+    ) -> (usize, usize, Option<Pagination>) {
+        if let Some(specific_row_idx) = self.specific_row_idx {
+            let row_idx = data_start_row_idx.saturating_add(specific_row_idx);
+            (row_idx, row_idx.saturating_add(1), None)
+        } else {
+            let default_limit = self.row_limits.default_limit;
+            let (offset, limit) = match &self.pagination {
+                None => (0, default_limit),
+                Some(pagination) => {
+                    let offset = pagination.offset.unwrap_or(0);
+                    let limit = pagination.limit.unwrap_or(default_limit);
+                    (offset, limit)
+                }
+            };
+
+            let start_row_idx = data_start_row_idx.saturating_add(offset);
+            let finish_row_idx = start_row_idx.saturating_add(limit);
+            (
+                start_row_idx,
+                finish_row_idx,
+                Some(Pagination::new(Some(offset), Some(limit))),
+            )
         }
     }
 }
 
+/// the last (inclusive) index of the window `start_idx..finish_idx` clamped to the grid.
+/// `None` if no index of the window is in the grid
+pub(crate) fn last_index_in_grid(
+    start_idx: usize,
+    finish_idx: usize,
+    max_count_of_grid: usize,
+) -> Option<usize> {
+    let finish_idx = finish_idx.min(max_count_of_grid);
+    if finish_idx <= start_idx {
+        None
+    } else {
+        Some(finish_idx - 1)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Pagination {
     offset: Option<usize>,
     limit: Option<usize>,
+    /// number of the data rows of the grid (below the header row)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    total_rows: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    has_more: Option<bool>,
 }
 
 impl Pagination {
     pub fn new(offset: Option<usize>, limit: Option<usize>) -> Self {
-        Self { offset, limit }
+        Self {
+            offset,
+            limit,
+            total_rows: None,
+            has_more: None,
+        }
+    }
+
+    /// `returned_row_num`: number of the rows in the grid read from `offset`
+    pub fn with_total_rows(self, total_rows: usize, returned_row_num: usize) -> Self {
+        let has_more = self.offset.unwrap_or(0) + returned_row_num < total_rows;
+        Self {
+            total_rows: Some(total_rows),
+            has_more: Some(has_more),
+            ..self
+        }
+    }
+
+    pub fn total_rows(&self) -> Option<usize> {
+        self.total_rows
+    }
+
+    pub fn has_more(&self) -> Option<bool> {
+        self.has_more
+    }
+
+    pub fn offset(&self) -> Option<usize> {
+        self.offset
+    }
+
+    pub fn limit(&self) -> Option<usize> {
+        self.limit
     }
 }
 
@@ -94,13 +548,52 @@ pub async fn create_header_condition_from_sheet_meta<HttpConnector>(
     token_manager: Arc<TokenManager<HttpConnector>>,
     sheet_meta: SheetMeta,
     specified_cell_range: Option<(CellRef, CellRef)>,
+    header_option: HeaderOption,
 ) -> Result<HeaderSearchCondition> {
-    //TODO(tacogips)  restriction
-    let client = reqwest_client();
-    let header_condition =
-        HeaderSearchCondition::create(client, token_manager, sheet_meta, specified_cell_range)
-            .await?;
-    Ok(header_condition)
+    SheetsClient::new(token_manager)
+        .create_header_condition_from_sheet_meta(sheet_meta, specified_cell_range, header_option)
+        .await
+}
+
+/// a tab of a spread sheet
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct SheetTab {
+    pub sheet_id: u32,
+    pub title: String,
+    pub index: usize,
+    pub row_count: usize,
+    pub column_count: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frozen_row_count: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frozen_column_count: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hide_gridlines: Option<bool>,
+}
+
+impl From<&SheetPropertyData> for SheetTab {
+    fn from(property: &SheetPropertyData) -> Self {
+        Self {
+            sheet_id: property.sheet_id,
+            title: property.title.clone(),
+            index: property.index,
+            row_count: property.grid_properties.row_count,
+            column_count: property.grid_properties.column_count,
+            frozen_row_count: property.grid_properties.frozen_row_count,
+            frozen_column_count: property.grid_properties.frozen_column_count,
+            hide_gridlines: property.grid_properties.hide_gridlines,
+        }
+    }
+}
+
+/// returns the tabs of the spread sheet ordered by its index
+pub async fn list_sheet_tabs<HttpConnector>(
+    token_manager: Arc<TokenManager<HttpConnector>>,
+    spread_sheet_id: &SpreadSheetId,
+) -> std::result::Result<Vec<SheetTab>, SheetApiError> {
+    SheetsClient::new(token_manager)
+        .list_sheet_tabs(spread_sheet_id)
+        .await
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -108,12 +601,52 @@ pub struct SheetValueResponse {
     pub headers: RawHeaders,
     pub row_values: RowValues,
     pub pagination: Option<Pagination>,
+    /// number of the data rows of the grid (below the header row)
+    pub total_rows: Option<usize>,
+    /// number of the rows of the paginated window in the grid. it counts the empty rows
+    /// dropped by `FetchRowCondition::with_include_empty_rows` or omitted by the api too
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rows_in_window: Option<usize>,
 }
 
 impl SheetValueResponse {
     pub fn is_empty(&self) -> bool {
         self.row_values.values.is_empty()
     }
+
+    /// no rows in the page although the sheet has the records. the page is past the last record,
+    /// or all the rows of the page are empty. a sheet without records is not
+    pub fn is_empty_page(&self) -> bool {
+        self.is_empty()
+            && self.pagination.is_some()
+            && self
+                .total_rows
+                .map(|total_rows| total_rows > 0)
+                .unwrap_or(false)
+    }
+
+    /// the cursor to the rows just after the window of the page.
+    /// `None` if no rows are likely to remain in the grid
+    pub fn next_cursor(&self) -> Option<Cursor> {
+        let pagination = self.pagination.as_ref()?;
+        let total_rows = self.total_rows?;
+        let rows_in_window = self
+            .rows_in_window
+            .unwrap_or_else(|| self.row_values.values.len());
+        if rows_in_window == 0 {
+            return None;
+        }
+
+        let next_offset = pagination.offset.unwrap_or(0) + rows_in_window;
+        if next_offset < total_rows {
+            let limit = pagination
+                .limit
+                .unwrap_or(DEFAULT_ROW_NUMBER_TO_READ_AT_ONCE);
+            Some(Cursor::new(next_offset, limit))
+        } else {
+            None
+        }
+    }
 }
 
 pub async fn fetch_sheet_value<HttpConnector>(
@@ -121,89 +654,81 @@ pub async fn fetch_sheet_value<HttpConnector>(
     header_search_condition: &HeaderSearchCondition,
     row_serach_condition: &FetchRowCondition,
 ) -> Result<SheetValueResponse> {
-    //TODO(tacogips)  restriction
-    let client = reqwest_client();
-
-    let headers =
-        RawHeaders::read_raw_headers(&client, token_manager.clone(), header_search_condition)
-            .await?;
-
-    let value_col_range = headers.range.col_range_indices();
+    SheetsClient::new(token_manager)
+        .fetch_sheet_value(header_search_condition, row_serach_condition)
+        .await
+}
 
-    let (start_row_idx, finish_row_idx, pagination_in_response) =
-        if let Some(specific_row_idx) = row_serach_condition.specific_row_idx {
-            let row_idx = headers.range.next_row_index() + specific_row_idx;
-            (row_idx, row_idx, None)
-        } else {
-            let (offset, limit) = match &row_serach_condition.pagination {
-                None => (0, DEFAULT_ROW_NUMBER_TO_READ_AT_ONCE),
-                Some(pagination) => {
-                    let offset = pagination.offset.unwrap_or(0);
-                    let limit = pagination
-                        .limit
-                        .unwrap_or(DEFAULT_ROW_NUMBER_TO_READ_AT_ONCE);
-                    (offset, limit)
-                }
-            };
+/// appends `row` (a json object keyed by the headers) below the last row of the table.
+/// the token manager must have `scopes::SHEET_READ_WRITE`
+pub async fn append_sheet_row<HttpConnector>(
+    token_manager: Arc<TokenManager<HttpConnector>>,
+    header_search_condition: &HeaderSearchCondition,
+    row: &JsonValue,
+) -> Result<AppendValuesResponse> {
+    SheetsClient::new(token_manager)
+        .append_sheet_row(header_search_condition, row)
+        .await
+}
 
-            let start_row_idx = headers.range.next_row_index() + offset;
-            let finish_row_idx = start_row_idx + limit;
-            (
-                start_row_idx,
-                finish_row_idx,
-                Some(Pagination::new(Some(offset), Some(limit))),
-            )
-        };
+pub async fn read_sheet_headers<HttpConnector>(
+    token_manager: Arc<TokenManager<HttpConnector>>,
+    header_search_condition: &HeaderSearchCondition,
+) -> Result<RawHeaders> {
+    SheetsClient::new(token_manager)
+        .read_sheet_headers(header_search_condition)
+        .await
+}
 
-    let max_row_count_of_grid = {
-        let sheet_name = header_search_condition
-            .sheet_name
-            .as_ref()
-            .map(|s| s.as_str());
-        match header_search_condition
-            .sheet_info
-            .find_property_by_name(sheet_name)
-        {
-            None => {
-                return Err(HeaderError::UnknwonError(format!(
-                    "sheet info not found:{:?}",
-                    sheet_name
-                )))?
-            }
-            Some(property) => property.properties.grid_properties.row_count,
-        }
-    };
+/// see `SheetsClient::count_sheet_records`
+pub async fn count_sheet_records<HttpConnector>(
+    token_manager: Arc<TokenManager<HttpConnector>>,
+    header_search_condition: &HeaderSearchCondition,
+    accurate_rows: bool,
+) -> Result<usize> {
+    SheetsClient::new(token_manager)
+        .count_sheet_records(
+            header_search_condition,
+            &FetchRowCondition::with_pagination(None, None).with_accurate_rows(accurate_rows),
+        )
+        .await
+}
 
-    if max_row_count_of_grid <= start_row_idx {
-        return Ok(SheetValueResponse {
-            headers,
-            row_values: RowValues::empty(),
-            pagination: pagination_in_response,
-        });
+fn count_records_in_grid(
+    orientation: HeaderOrientation,
+    grid_properties: &GridProperties,
+    header_range: &RangeRef,
+) -> usize {
+    if orientation == HeaderOrientation::KeyValue {
+        grid_properties
+            .column_count
+            .saturating_sub(header_range.next_col_index())
     } else {
-        let value_option = ReadValueOption::new(
-            header_search_condition.spread_sheet_id.clone(),
-            header_search_condition.sheet_name.clone(),
-            value_col_range,
-            start_row_idx,
-            finish_row_idx,
-        );
-
-        let row_values =
-            RowValues::read_values(&client, token_manager.clone(), &value_option).await?;
-
-        return Ok(SheetValueResponse {
-            headers,
-            row_values,
-            pagination: pagination_in_response,
-        });
+        grid_properties
+            .row_count
+            .saturating_sub(header_range.next_row_index())
     }
 }
 
+/// reads the rows below the `headers` which are read by `read_sheet_headers`
+pub async fn fetch_sheet_value_with_headers<HttpConnector>(
+    token_manager: Arc<TokenManager<HttpConnector>>,
+    header_search_condition: &HeaderSearchCondition,
+    headers: RawHeaders,
+    row_serach_condition: &FetchRowCondition,
+) -> Result<SheetValueResponse> {
+    SheetsClient::new(token_manager)
+        .fetch_sheet_value_with_headers(header_search_condition, headers, row_serach_condition)
+        .await
+}
+
 #[cfg(test)]
 mod test {
+    use super::*;
     #[cfg(feature = "test-using-sa")]
     use std::path::PathBuf;
+    #[cfg(feature = "test-using-sa")]
+    use tokio::sync::broadcast;
 
     pub const TEST_SHEET1 :&str= "https://docs.google.com/spreadsheets/d/1HA4munsvl5UUlb9DKmJvhrwfGlSQ97hSQZf13M3ZO4Y/edit#gid=0";
     pub const TEST_SHEET1_ID: &str = "1HA4munsvl5UUlb9DKmJvhrwfGlSQ97hSQZf13M3ZO4Y";
@@ -217,4 +742,421 @@ mod test {
         p.push("dev-secret/test-sa-key.json");
         p
     }
+
+    #[test]
+    fn sheet_scope_test() {
+        use scopes::SheetScope;
+        use std::str::FromStr;
+
+        assert_eq!(SheetScope::from_str("readonly"), Ok(SheetScope::ReadOnly));
+        assert_eq!(SheetScope::from_str("ReadWrite"), Ok(SheetScope::ReadWrite));
+        assert!(SheetScope::from_str("write").is_err());
+
+        assert!(!SheetScope::default().is_writable());
+        assert_eq!(SheetScope::default().scopes(), scopes::SHEET_READ_ONLY);
+        assert!(SheetScope::ReadWrite.is_writable());
+        assert_eq!(SheetScope::ReadWrite.scopes(), scopes::SHEET_READ_WRITE);
+    }
+
+    #[test]
+    fn scope_keys_test() {
+        use scopes::ScopeKey;
+
+        assert_eq!(
+            scopes::parse_scope_keys("sheets.readonly, drive.readonly"),
+            Ok(vec![ScopeKey::SheetsReadOnly, ScopeKey::DriveReadOnly])
+        );
+        assert_eq!(
+            scopes::parse_scope_keys("sheets"),
+            Ok(vec![ScopeKey::Sheets])
+        );
+        assert!(scopes::parse_scope_keys("sheets,drive").is_err());
+        assert!(scopes::parse_scope_keys("Sheets").is_err());
+
+        assert_eq!(
+            scopes::scope_urls(&[ScopeKey::Sheets, ScopeKey::DriveReadOnly, ScopeKey::Sheets]),
+            vec![
+                "https://www.googleapis.com/auth/spreadsheets",
+                "https://www.googleapis.com/auth/drive.readonly",
+            ]
+        );
+        assert_eq!(
+            scopes::scope_urls(&[ScopeKey::SheetsReadOnly]),
+            scopes::SHEET_READ_ONLY.to_vec()
+        );
+    }
+
+    #[test]
+    fn row_indices_in_grid_test() {
+        // the data starts from the row index 1 and the grid has 10 rows
+        let condition = FetchRowCondition::with_row_indices(vec![3, 7, 12, 0, 8, 9]);
+        assert_eq!(condition.row_indices_in_grid(1, 10), Some(vec![4, 8, 1, 9]));
+
+        // all beyond the grid
+        let condition = FetchRowCondition::with_row_indices(vec![usize::MAX, 12]);
+        assert_eq!(condition.row_indices_in_grid(1, 10), Some(vec![]));
+        assert!(condition.is_row_selection());
+
+        let condition = FetchRowCondition::with_pagination(None, Some(10));
+        assert_eq!(condition.row_indices_in_grid(1, 10), None);
+    }
+
+    #[test]
+    fn last_row_idx_test() {
+        let condition = FetchRowCondition::with_pagination(None, Some(10));
+        assert_eq!(condition.row_count_to_read(1000), 1000);
+
+        // `?range=B2:F50`
+        let condition = condition.with_last_row_idx(49);
+        assert_eq!(condition.row_count_to_read(1000), 50);
+        assert_eq!(condition.row_count_to_read(30), 30);
+        assert_eq!(condition.col_count_to_read(26), 26);
+
+        // `?range=A1:E3&orientation=key_value`
+        let condition = FetchRowCondition::with_pagination(None, Some(10)).with_last_col_idx(4);
+        assert_eq!(condition.col_count_to_read(26), 5);
+        assert_eq!(condition.row_count_to_read(1000), 1000);
+    }
+
+    #[test]
+    fn data_ranges_test() {
+        use std::str::FromStr;
+        let ranges = vec![
+            RangeRef::from_str("A1:C10").unwrap(),
+            RangeRef::from_str("E1:G10").unwrap(),
+        ];
+        let condition = FetchRowCondition::with_pagination(None, None).with_ranges(ranges);
+        assert_eq!(
+            condition.data_ranges(1),
+            Some(vec![
+                RangeRef::from_str("A2:C10").unwrap(),
+                RangeRef::from_str("E2:G10").unwrap(),
+            ])
+        );
+
+        // the first range has only the header row
+        let condition = FetchRowCondition::with_pagination(None, None).with_ranges(vec![
+            RangeRef::from_str("A1:C1").unwrap(),
+            RangeRef::from_str("E5:G10").unwrap(),
+        ]);
+        assert_eq!(
+            condition.data_ranges(1),
+            Some(vec![RangeRef::from_str("E6:G10").unwrap()])
+        );
+
+        // `header_rows=2` on the ranges starting at the different rows
+        let condition = FetchRowCondition::with_pagination(None, None).with_ranges(vec![
+            RangeRef::from_str("A3:C10").unwrap(),
+            RangeRef::from_str("E1:G2").unwrap(),
+            RangeRef::from_str("I5:K7").unwrap(),
+        ]);
+        assert_eq!(
+            condition.data_ranges(5),
+            Some(vec![
+                RangeRef::from_str("A5:C10").unwrap(),
+                RangeRef::from_str("I7:K7").unwrap(),
+            ])
+        );
+
+        assert_eq!(
+            FetchRowCondition::with_pagination(None, None).data_ranges(1),
+            None
+        );
+    }
+
+    #[test]
+    fn last_index_in_grid_test() {
+        assert_eq!(last_index_in_grid(1, 11, 100), Some(10));
+        assert_eq!(last_index_in_grid(1, 11, 5), Some(4));
+        assert_eq!(last_index_in_grid(4, 14, 5), Some(4));
+        assert_eq!(last_index_in_grid(5, 15, 5), None);
+        // `limit=0`
+        assert_eq!(last_index_in_grid(1, 1, 100), None);
+    }
+
+    #[tokio::test]
+    async fn build_reqwest_client_test() {
+        // a proxy that accepts the CONNECT but never responds
+        let proxy = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy_addr = proxy.local_addr().unwrap();
+        let (connected_tx, connected_rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            use std::io::Read;
+            let (mut stream, _) = proxy.accept().unwrap();
+            let mut buf = [0u8; 64];
+            let n = stream.read(&mut buf).unwrap();
+            connected_tx
+                .send(String::from_utf8_lossy(&buf[..n]).to_string())
+                .unwrap();
+            std::thread::sleep(Duration::from_secs(3));
+        });
+
+        let client = build_reqwest_client(&HttpClientOption {
+            connect_timeout: Some(Duration::from_secs(1)),
+            timeout: Some(Duration::from_millis(300)),
+            https_proxy: Some(format!("http://{}", proxy_addr)),
+            pool_max_idle_per_host: Some(1),
+        })
+        .unwrap();
+
+        let result = client.get(DEFAULT_BASE_ENDPOINT_V4).send().await;
+        assert!(result.unwrap_err().is_timeout());
+        assert!(connected_rx
+            .recv_timeout(Duration::from_secs(1))
+            .unwrap()
+            .starts_with("CONNECT sheets.googleapis.com:443"));
+
+        assert!(build_reqwest_client(&HttpClientOption {
+            https_proxy: Some("not a url".to_string()),
+            ..HttpClientOption::default()
+        })
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn compressed_response_test() {
+        use std::io::{Read, Write};
+
+        // {"spreadsheetId":"sheet_id","valueRanges":[{"range":"'Sheet1'!A1:B2","majorDimension":"ROWS","values":[["name","age"],["Alice","21"]]}]}
+        const GZIPPED_BODY: &[u8] = &[
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0xab, 0x56, 0x2a, 0x2e,
+            0x28, 0x4a, 0x4d, 0x4c, 0x29, 0xce, 0x48, 0x4d, 0x2d, 0xf1, 0x4c, 0x51, 0xb2, 0x52,
+            0x02, 0xb3, 0xe2, 0x33, 0x53, 0x94, 0x74, 0x94, 0xca, 0x12, 0x73, 0x4a, 0x53, 0x83,
+            0x12, 0xf3, 0xd2, 0x53, 0x8b, 0x95, 0xac, 0xa2, 0xab, 0x95, 0x8a, 0x40, 0x4c, 0xa0,
+            0x12, 0xf5, 0x60, 0x90, 0x1a, 0x43, 0x75, 0x45, 0x47, 0x43, 0x2b, 0x27, 0x23, 0xa0,
+            0xc2, 0xdc, 0xc4, 0xac, 0xfc, 0x22, 0x97, 0xcc, 0xdc, 0xd4, 0xbc, 0xe2, 0xcc, 0xfc,
+            0x3c, 0xa0, 0x8a, 0x20, 0xff, 0xf0, 0x60, 0x98, 0x01, 0x20, 0xbd, 0xd1, 0x4a, 0x79,
+            0x89, 0xb9, 0xa9, 0x40, 0x91, 0x44, 0xa0, 0x01, 0xb1, 0x3a, 0xd1, 0x4a, 0x8e, 0x39,
+            0x99, 0xc9, 0x20, 0xbe, 0x91, 0xa1, 0x52, 0x6c, 0x6c, 0x6d, 0x6c, 0x2d, 0x00, 0x22,
+            0x49, 0xdd, 0xa8, 0x88, 0x00, 0x00, 0x00,
+        ];
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (request_tx, request_rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0u8; 1024];
+            let n = stream.read(&mut buf).unwrap();
+            request_tx
+                .send(String::from_utf8_lossy(&buf[..n]).to_lowercase())
+                .unwrap();
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Encoding: gzip\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                GZIPPED_BODY.len()
+            )
+            .unwrap();
+            stream.write_all(GZIPPED_BODY).unwrap();
+        });
+
+        let client = build_reqwest_client(&HttpClientOption::default()).unwrap();
+        let sheet_values: SheetValues = client
+            .get(format!("http://{}/", addr))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+
+        let request = request_rx.recv().unwrap();
+        let accept_encoding = request
+            .lines()
+            .find(|line| line.starts_with("accept-encoding:"))
+            .unwrap();
+        assert!(accept_encoding.contains("gzip"));
+        assert!(accept_encoding.contains("br"));
+
+        let expected: SheetValues = serde_json::from_value(serde_json::json!({
+            "spreadsheetId": "sheet_id",
+            "valueRanges": [{
+                "range": "'Sheet1'!A1:B2",
+                "majorDimension": "ROWS",
+                "values": [["name", "age"], ["Alice", "21"]],
+            }],
+        }))
+        .unwrap();
+        assert_eq!(sheet_values, expected);
+    }
+
+    #[test]
+    fn sheet_tab_from_property_test() {
+        let property: SheetPropertyData = serde_json::from_value(serde_json::json!({
+            "sheetId": 0,
+            "title": "frozen",
+            "index": 1,
+            "sheetType": "GRID",
+            "gridProperties": {"rowCount": 1000, "columnCount": 26, "frozenRowCount": 2},
+        }))
+        .unwrap();
+
+        let tab = serde_json::to_value(SheetTab::from(&property)).unwrap();
+        assert_eq!(
+            tab,
+            serde_json::json!({
+                "sheet_id": 0,
+                "title": "frozen",
+                "index": 1,
+                "row_count": 1000,
+                "column_count": 26,
+                "frozen_row_count": 2,
+            })
+        );
+    }
+
+    #[test]
+    fn count_records_in_grid_test() {
+        let grid_properties: GridProperties = serde_json::from_value(serde_json::json!({
+            "rowCount": 1000,
+            "columnCount": 26,
+        }))
+        .unwrap();
+
+        // header at the first row
+        let header_range = RangeRef::new(None, CellRef::new(0, 0), CellRef::new(2, 0));
+        assert_eq!(
+            count_records_in_grid(HeaderOrientation::Rows, &grid_properties, &header_range),
+            999
+        );
+
+        // 2 header rows from the 3rd row
+        let header_range = RangeRef::new(None, CellRef::new(0, 2), CellRef::new(2, 3));
+        assert_eq!(
+            count_records_in_grid(HeaderOrientation::Rows, &grid_properties, &header_range),
+            996
+        );
+
+        // labels at the column A
+        let header_range = RangeRef::new(None, CellRef::new(0, 0), CellRef::new(0, 4));
+        assert_eq!(
+            count_records_in_grid(HeaderOrientation::KeyValue, &grid_properties, &header_range),
+            25
+        );
+
+        // header at the last row
+        let header_range = RangeRef::new(None, CellRef::new(0, 999), CellRef::new(2, 999));
+        assert_eq!(
+            count_records_in_grid(HeaderOrientation::Rows, &grid_properties, &header_range),
+            0
+        );
+    }
+
+    #[cfg(feature = "test-using-sa")]
+    #[tokio::test]
+    async fn count_sheet_records_test() {
+        let (_, rx) = broadcast::channel(1);
+        let token_manager = token_manager_from_service_account_file(
+            scopes::SHEET_READ_ONLY,
+            load_test_sa_file_path(),
+            rx,
+            None,
+        )
+        .await
+        .unwrap();
+        let token_manager = Arc::new(token_manager);
+
+        let condition = create_header_condition_from_sheet_meta(
+            token_manager.clone(),
+            SheetMeta::from_url(TEST_SHEET1).unwrap(),
+            None,
+            HeaderOption::default(),
+        )
+        .await
+        .unwrap();
+
+        let count = count_sheet_records(token_manager.clone(), &condition, false)
+            .await
+            .unwrap();
+        let sheet_value = fetch_sheet_value(
+            token_manager,
+            &condition,
+            &FetchRowCondition::with_pagination(None, Some(1)),
+        )
+        .await
+        .unwrap();
+        assert!(count > 0);
+        assert_eq!(Some(count), sheet_value.total_rows);
+    }
+
+    #[cfg(feature = "test-using-sa")]
+    #[tokio::test]
+    async fn count_sheet_records_accurately_test() {
+        let (_, rx) = broadcast::channel(1);
+        let token_manager = token_manager_from_service_account_file(
+            scopes::SHEET_READ_ONLY,
+            load_test_sa_file_path(),
+            rx,
+            None,
+        )
+        .await
+        .unwrap();
+        let token_manager = Arc::new(token_manager);
+
+        let condition = create_header_condition_from_sheet_meta(
+            token_manager.clone(),
+            SheetMeta::from_url(TEST_SHEET1).unwrap(),
+            None,
+            HeaderOption::default(),
+        )
+        .await
+        .unwrap();
+
+        // the test sheet has the default 1000 rows allocated and only a few of them are filled
+        let allocated = count_sheet_records(token_manager.clone(), &condition, false)
+            .await
+            .unwrap();
+        let actual = count_sheet_records(token_manager.clone(), &condition, true)
+            .await
+            .unwrap();
+        assert!(actual > 0);
+        assert!(actual < allocated);
+
+        let sheet_value = fetch_sheet_value(
+            token_manager,
+            &condition,
+            &FetchRowCondition::with_pagination(None, Some(actual + 10)).with_accurate_rows(true),
+        )
+        .await
+        .unwrap();
+        assert_eq!(Some(actual), sheet_value.total_rows);
+        assert_eq!(sheet_value.row_values.values.len(), actual);
+        assert_eq!(sheet_value.next_cursor(), None);
+    }
+
+    #[cfg(feature = "test-using-sa")]
+    #[tokio::test]
+    async fn list_sheet_tabs_test() {
+        let (_, rx) = broadcast::channel(1);
+        let token_manager = token_manager_from_service_account_file(
+            scopes::SHEET_READ_ONLY,
+            load_test_sa_file_path(),
+            rx,
+            None,
+        )
+        .await
+        .unwrap();
+
+        let result = list_sheet_tabs(
+            Arc::new(token_manager),
+            &SpreadSheetId::new(TEST_SHEET1_ID.to_string()),
+        )
+        .await
+        .unwrap();
+
+        let titles: Vec<(usize, &str)> = result
+            .iter()
+            .map(|tab| (tab.index, tab.title.as_str()))
+            .collect();
+        assert_eq!(
+            titles,
+            vec![
+                (0, "grouping"),
+                (1, "empty_sheet"),
+                (2, "many headers"),
+                (3, "header escape"),
+            ]
+        );
+        assert_eq!(result[1].sheet_id, TEST_SHEET1_EMPTY_TAG_ID);
+    }
 }
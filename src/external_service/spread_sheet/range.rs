@@ -41,6 +41,9 @@ pub enum RangeError {
 
     #[error("invalid range ref row index:{0}")]
     InvalidRangeRefRow(String),
+
+    #[error("cell ref out of bounds:{0}")]
+    CellRefOutOfBounds(String),
 }
 
 /// 0 -> "A"
@@ -149,6 +152,25 @@ impl CellRef {
         }
     }
 
+    /// the cell moved by the deltas. error if the col or the row goes negative
+    pub fn offset(&self, col_delta: i64, row_delta: i64) -> Result<CellRef> {
+        let shifted = |index: usize, delta: i64| -> Option<usize> {
+            let shifted = (index as i64).checked_add(delta)?;
+            usize::try_from(shifted).ok()
+        };
+
+        match (
+            shifted(self.col_index, col_delta),
+            shifted(self.row_index, row_delta),
+        ) {
+            (Some(col_index), Some(row_index)) => Ok(CellRef::new(col_index, row_index)),
+            _ => Err(RangeError::CellRefOutOfBounds(format!(
+                "{} offset by col:{} row:{}",
+                self, col_delta, row_delta
+            ))),
+        }
+    }
+
     fn from_row_and_col<'a>(col_alpha: &ColAlphabet<'a>, row_num_str: &str) -> Result<Self> {
         let col_index = col_alphabet_to_num(&col_alpha.0)?;
         let row_num = row_num_str.parse::<usize>().map_err(|_e| {
@@ -223,7 +245,7 @@ fn sanitize_sheet_name(sheet_name: &str) -> Result<Option<String>> {
     }
 }
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RangeRef {
     pub sheet_name: Option<String>,
     pub start: CellRef,
@@ -243,10 +265,18 @@ impl RangeRef {
         self.end.row_index + 1
     }
 
+    pub fn next_col_index(&self) -> usize {
+        self.end.col_index + 1
+    }
+
     pub fn col_range_indices(&self) -> (usize, usize) {
         (self.start.col_index, self.end.col_index)
     }
 
+    pub fn row_range_indices(&self) -> (usize, usize) {
+        (self.start.row_index, self.end.row_index)
+    }
+
     pub fn contains(&mut self, other: &RangeRef) -> bool {
         self.start.col_index <= other.start.col_index
             && self.start.row_index <= other.start.row_index
@@ -290,8 +320,34 @@ impl RangeRef {
         }
     }
 
+    /// 0 if the end is left of the start
     pub fn col_range_size(&self) -> usize {
-        self.end.col_index - self.start.col_index + 1
+        range_size(self.start.col_index, self.end.col_index)
+    }
+
+    /// 0 if the end is above the start
+    pub fn row_range_size(&self) -> usize {
+        range_size(self.start.row_index, self.end.row_index)
+    }
+
+    /// number of the cells in the range. "A1:B3" -> Some(6).
+    /// None if it overflows, 0 if the start is not at upper-left of the end
+    pub fn cell_count(&self) -> Option<usize> {
+        self.col_range_size().checked_mul(self.row_range_size())
+    }
+
+    /// `cell_count` saturated at `usize::MAX`
+    pub fn num_cells(&self) -> usize {
+        self.cell_count().unwrap_or(usize::MAX)
+    }
+
+    /// every cell in the range, row by row. "A1:B2" -> A1, B1, A2, B2
+    /// yields nothing if the start cell is not at upper-left of the end cell
+    pub fn cells(&self) -> impl Iterator<Item = CellRef> {
+        let (start_col, end_col) = (self.start.col_index, self.end.col_index);
+        (self.start.row_index..=self.end.row_index).flat_map(move |row_index| {
+            (start_col..=end_col).map(move |col_index| CellRef::new(col_index, row_index))
+        })
     }
 
     pub fn as_string(&self) -> String {
@@ -299,27 +355,53 @@ impl RangeRef {
     }
 
     pub fn shift_in_col(&mut self, shift: i32) -> Result<()> {
-        let start_col_index = (self.start.col_index as i32) + shift;
-        if start_col_index < 0 {
-            return Err(RangeError::InvalidRangeRefRow(format!(
-                "invalid start cell row :{}",
-                start_col_index
-            )));
-        }
-        let end_col_index = (self.end.col_index as i32) + shift;
+        let start = self.start.offset(shift as i64, 0)?;
+        let end = self.end.offset(shift as i64, 0)?;
 
-        if end_col_index < 0 {
-            return Err(RangeError::InvalidRangeRefRow(format!(
-                "invalid end cell row :{}",
-                end_col_index
-            )));
-        }
-
-        self.start.col_index = start_col_index as usize;
-        self.end.col_index = end_col_index as usize;
+        self.start = start;
+        self.end = end;
         Ok(())
     }
 
+    /// the overlapping rectangle of the two ranges.
+    /// None if they are disjoint or on different sheets
+    pub fn intersection(&self, other: &RangeRef) -> Option<RangeRef> {
+        if self.is_disjoint(other) {
+            return None;
+        }
+        Some(RangeRef::new(
+            self.sheet_name.clone(),
+            CellRef::new(
+                self.start.col_index.max(other.start.col_index),
+                self.start.row_index.max(other.start.row_index),
+            ),
+            CellRef::new(
+                self.end.col_index.min(other.end.col_index),
+                self.end.row_index.min(other.end.row_index),
+            ),
+        ))
+    }
+
+    /// true if the ranges share no cells. ranges on different sheets are always disjoint
+    pub fn is_disjoint(&self, other: &RangeRef) -> bool {
+        self.sheet_name != other.sheet_name
+            || self.end.col_index < other.start.col_index
+            || other.end.col_index < self.start.col_index
+            || self.end.row_index < other.start.row_index
+            || other.end.row_index < self.start.row_index
+    }
+
+    /// caps the end cell to the grid of `col_count` x `row_count`.
+    /// returns false and leaves the range as it is if the start cell is out of the grid
+    pub fn clamp_to_grid(&mut self, col_count: usize, row_count: usize) -> bool {
+        if col_count <= self.start.col_index || row_count <= self.start.row_index {
+            return false;
+        }
+        self.end.col_index = self.end.col_index.min(col_count - 1);
+        self.end.row_index = self.end.row_index.min(row_count - 1);
+        true
+    }
+
     pub fn is_one_line_row(&self) -> bool {
         self.start.row_index == self.end.row_index
     }
@@ -334,10 +416,18 @@ impl RangeRef {
         Ok(())
     }
 }
+/// the sheet name is always quoted (google accepts quoted names even if it's not necessary)
+/// and single quotes in it are escaped as `''`, the reverse of `sanitize_sheet_name`.
 impl Display for RangeRef {
     fn fmt(&self, f: &mut Formatter) -> FmtResult {
         match self.sheet_name.as_ref() {
-            Some(sheet_name) => write!(f, "'{}'!{}:{}", sheet_name, self.start, self.end),
+            Some(sheet_name) => write!(
+                f,
+                "'{}'!{}:{}",
+                sheet_name.replace("'", "''"),
+                self.start,
+                self.end
+            ),
             None => write!(f, "{}:{}", self.start, self.end),
         }
     }
@@ -383,6 +473,13 @@ impl FromStr for RangeRef {
     }
 }
 
+/// number of the indices from `start` to `end` inclusive. 0 if `end` is before `start`
+fn range_size(start: usize, end: usize) -> usize {
+    end.checked_sub(start)
+        .map(|size| size.saturating_add(1))
+        .unwrap_or(0)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -466,6 +563,45 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_cell_ref_offset() {
+        let cell = CellRef::from_str("C3").unwrap();
+
+        assert_eq!(cell.offset(0, 0), Ok(CellRef::from_str("C3").unwrap()));
+        assert_eq!(cell.offset(2, 5), Ok(CellRef::from_str("E8").unwrap()));
+        assert_eq!(cell.offset(24, 0), Ok(CellRef::from_str("AA3").unwrap()));
+
+        // negative but stays in bounds
+        assert_eq!(cell.offset(-2, -2), Ok(CellRef::from_str("A1").unwrap()));
+        assert_eq!(cell.offset(-1, 1), Ok(CellRef::from_str("B4").unwrap()));
+
+        // underflow
+        assert!(matches!(
+            cell.offset(-3, 0),
+            Err(RangeError::CellRefOutOfBounds(_))
+        ));
+        assert!(matches!(
+            cell.offset(0, -3),
+            Err(RangeError::CellRefOutOfBounds(_))
+        ));
+        assert!(matches!(
+            cell.offset(i64::MIN, 0),
+            Err(RangeError::CellRefOutOfBounds(_))
+        ));
+    }
+
+    #[test]
+    fn test_shift_in_col() {
+        let mut range = RangeRef::from_str("B1:C2").unwrap();
+        range.shift_in_col(26).unwrap();
+        assert_eq!(range, RangeRef::from_str("AB1:AC2").unwrap());
+
+        let mut range = RangeRef::from_str("B1:C2").unwrap();
+        assert!(range.shift_in_col(-2).is_err());
+        // unchanged on error
+        assert_eq!(range, RangeRef::from_str("B1:C2").unwrap());
+    }
+
     #[test]
     fn test_range_ref_from_str() {
         {
@@ -583,6 +719,88 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_intersection() {
+        let range = |s: &str| RangeRef::from_str(s).unwrap();
+
+        // overlapping
+        assert_eq!(
+            range("A1:C3").intersection(&range("B2:D4")),
+            Some(range("B2:C3"))
+        );
+        assert_eq!(
+            range("B2:D4").intersection(&range("A1:C3")),
+            Some(range("B2:C3"))
+        );
+        // contained
+        assert_eq!(
+            range("A1:Z100").intersection(&range("C3:D4")),
+            Some(range("C3:D4"))
+        );
+        // sharing only a corner cell
+        assert_eq!(
+            range("A1:B2").intersection(&range("B2:C3")),
+            Some(range("B2:B2"))
+        );
+        // crossing
+        assert_eq!(
+            range("B1:B10").intersection(&range("A5:Z5")),
+            Some(range("B5:B5"))
+        );
+        // same sheet
+        assert_eq!(
+            range("'s'!A1:C3").intersection(&range("'s'!B2:D4")),
+            Some(range("'s'!B2:C3"))
+        );
+    }
+
+    #[test]
+    fn test_is_disjoint() {
+        let range = |s: &str| RangeRef::from_str(s).unwrap();
+
+        // touching edges but not overlapping
+        assert!(range("A1:B2").is_disjoint(&range("C1:D2")));
+        assert!(range("C1:D2").is_disjoint(&range("A1:B2")));
+        assert!(range("A1:B2").is_disjoint(&range("A3:B4")));
+        assert!(range("A3:B4").is_disjoint(&range("A1:B2")));
+        // touching only at the diagonal corner
+        assert!(range("A1:B2").is_disjoint(&range("C3:D4")));
+        assert_eq!(range("A1:B2").intersection(&range("C3:D4")), None);
+
+        // different sheets
+        assert!(range("'s1'!A1:C3").is_disjoint(&range("'s2'!A1:C3")));
+        assert!(range("'s1'!A1:C3").is_disjoint(&range("A1:C3")));
+        assert_eq!(range("'s1'!A1:C3").intersection(&range("'s2'!A1:C3")), None);
+
+        assert!(!range("A1:B2").is_disjoint(&range("B2:C3")));
+    }
+
+    #[test]
+    fn test_clamp_to_grid() {
+        // inside
+        let mut range = RangeRef::from_str("B2:D10").unwrap();
+        assert!(range.clamp_to_grid(26, 1000));
+        assert_eq!(range, RangeRef::from_str("B2:D10").unwrap());
+
+        // just at the edge of the grid
+        let mut range = RangeRef::from_str("A1:Z1000").unwrap();
+        assert!(range.clamp_to_grid(26, 1000));
+        assert_eq!(range, RangeRef::from_str("A1:Z1000").unwrap());
+
+        // partially outside
+        let mut range = RangeRef::from_str("'sheet1'!X2:AD2000").unwrap();
+        assert!(range.clamp_to_grid(26, 1000));
+        assert_eq!(range, RangeRef::from_str("'sheet1'!X2:Z1000").unwrap());
+
+        // entirely outside
+        let mut range = RangeRef::from_str("AA1:AB2").unwrap();
+        assert!(!range.clamp_to_grid(26, 1000));
+        assert_eq!(range, RangeRef::from_str("AA1:AB2").unwrap());
+
+        let mut range = RangeRef::from_str("A1001:B1002").unwrap();
+        assert!(!range.clamp_to_grid(26, 1000));
+    }
+
     #[test]
     fn test_fmt_range() {
         {
@@ -600,6 +818,79 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_fmt_range_sheet_name_round_trip() {
+        for (sheet_name, expected) in [
+            ("sheet1", "'sheet1'!A1:B2"),
+            ("sheet name 1", "'sheet name 1'!A1:B2"),
+            ("Bob's data", "'Bob''s data'!A1:B2"),
+        ] {
+            let range = RangeRef::new(
+                Some(sheet_name.to_string()),
+                CellRef::new(0, 0),
+                CellRef::new(1, 1),
+            );
+            assert_eq!(range.to_string(), expected);
+
+            let parsed = RangeRef::from_str(&range.to_string()).unwrap();
+            assert_eq!(parsed.sheet_name.as_deref(), Some(sheet_name));
+            assert_eq!(parsed, range);
+        }
+
+        // unquoted plain name
+        let parsed = RangeRef::from_str("sheet1!A1:B2").unwrap();
+        assert_eq!(parsed.to_string(), "'sheet1'!A1:B2");
+    }
+
+    #[test]
+    fn test_num_cells() {
+        assert_eq!(RangeRef::from_str("A1:A1").unwrap().num_cells(), 1);
+        assert_eq!(RangeRef::from_str("A1:B3").unwrap().num_cells(), 6);
+        assert_eq!(RangeRef::from_str("B2:D2").unwrap().num_cells(), 3);
+        assert_eq!(
+            RangeRef::from_str("A1:ZZ100000").unwrap().num_cells(),
+            702 * 100000
+        );
+
+        // inverted
+        let inverted = RangeRef::new(None, CellRef::new(3, 5), CellRef::new(1, 2));
+        assert_eq!(inverted.col_range_size(), 0);
+        assert_eq!(inverted.row_range_size(), 0);
+        assert_eq!(inverted.cell_count(), Some(0));
+        assert_eq!(inverted.num_cells(), inverted.cells().count());
+
+        // overflow
+        let huge = RangeRef::new(
+            None,
+            CellRef::new(0, 0),
+            CellRef::new(usize::MAX, usize::MAX),
+        );
+        assert_eq!(huge.col_range_size(), usize::MAX);
+        assert_eq!(huge.cell_count(), None);
+        assert_eq!(huge.num_cells(), usize::MAX);
+    }
+
+    #[test]
+    fn test_cells() {
+        let cells = |s: &str| -> Vec<String> {
+            RangeRef::from_str(s)
+                .unwrap()
+                .cells()
+                .map(|c| c.to_string())
+                .collect()
+        };
+
+        assert_eq!(cells("A1:B2"), vec!["A1", "B1", "A2", "B2"]);
+        assert_eq!(cells("B2:B2"), vec!["B2"]);
+        assert_eq!(cells("B2:D2"), vec!["B2", "C2", "D2"]);
+        assert_eq!(cells("Z1:AA2"), vec!["Z1", "AA1", "Z2", "AA2"]);
+
+        for range in ["A1:A1", "A1:B3", "C5:F9", "'sheet'!B2:D2"] {
+            let range = RangeRef::from_str(range).unwrap();
+            assert_eq!(range.cells().count(), range.num_cells());
+        }
+    }
+
     #[test]
     fn test_expand() {
         {
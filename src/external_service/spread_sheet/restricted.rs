@@ -1,19 +1,48 @@
-const MAX_COL: usize = 130;
-const MAX_ROW: usize = 1000;
+pub const DEFAULT_MAX_COL: usize = 130;
+pub const DEFAULT_MAX_ROW: usize = 1000;
 
-pub fn is_col_range_overflow(col_num: usize) -> Result<(), usize> {
-    // 130:A to DZ
-    if col_num > MAX_COL {
-        Err(MAX_COL)
+/// limits of the cells to read. not enforced if `None` is passed in the options
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Restrictions {
+    pub max_col: usize,
+    pub max_row: usize,
+}
+
+impl Default for Restrictions {
+    fn default() -> Self {
+        Self {
+            // 130:A to DZ
+            max_col: DEFAULT_MAX_COL,
+            max_row: DEFAULT_MAX_ROW,
+        }
+    }
+}
+
+pub fn is_col_range_overflow(col_num: usize, max_col: usize) -> Result<(), usize> {
+    if col_num > max_col {
+        Err(max_col)
     } else {
         Ok(())
     }
 }
 
-pub fn is_row_range_overflow(row_num: usize) -> Result<(), usize> {
-    if row_num > MAX_ROW {
-        Err(MAX_ROW)
+pub fn is_row_range_overflow(row_num: usize, max_row: usize) -> Result<(), usize> {
+    if row_num > max_row {
+        Err(max_row)
     } else {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn range_overflow_test() {
+        assert_eq!(is_col_range_overflow(130, 130), Ok(()));
+        assert_eq!(is_col_range_overflow(131, 130), Err(130));
+        assert_eq!(is_row_range_overflow(1000, 1000), Ok(()));
+        assert_eq!(is_row_range_overflow(1001, 1000), Err(1000));
+    }
+}
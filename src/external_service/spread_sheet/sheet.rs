@@ -1,11 +1,12 @@
 use once_cell::sync::OnceCell;
 use regex::Regex;
+use reqwest::StatusCode;
 use serde::Serialize;
 use thiserror::Error;
 
 static VALID_SHEET_ID_RE: OnceCell<Regex> = OnceCell::new();
 static VALID_SHEET_URL_RE: OnceCell<Regex> = OnceCell::new();
-static VALID_SHEET_URL_WITH_TAB_ID_RE: OnceCell<Regex> = OnceCell::new();
+static TAB_ID_PARAM_RE: OnceCell<Regex> = OnceCell::new();
 
 type Result<T> = std::result::Result<T, SheetMetaError>;
 
@@ -21,6 +22,22 @@ pub enum SheetMetaError {
     InvalidSheetUrl(String),
 }
 
+impl SheetMetaError {
+    /// stable error code for api clients
+    pub fn code(&self) -> &'static str {
+        match self {
+            SheetMetaError::InvalidSheetId(_) => "INVALID_SHEET_ID",
+            SheetMetaError::InvalidTabId(_) => "INVALID_TAB_ID",
+            SheetMetaError::InvalidSheetUrl(_) => "INVALID_SHEET_URL",
+        }
+    }
+
+    /// always 400. the ids and the urls are passed by the clients
+    pub fn http_status(&self) -> StatusCode {
+        StatusCode::BAD_REQUEST
+    }
+}
+
 fn valid_sheet_id_regex() -> &'static Regex {
     VALID_SHEET_ID_RE.get_or_init(|| {
         let r = Regex::new(r"^[A-Za-z0-9]+$").unwrap();
@@ -37,20 +54,46 @@ fn valid_sheet_url_regex() -> &'static Regex {
     })
 }
 
-fn valid_sheet_url_with_tab_id_regex() -> &'static Regex {
-    VALID_SHEET_URL_WITH_TAB_ID_RE.get_or_init(|| {
-        let r = Regex::new(
-            r"^https://docs.google.com/spreadsheets/d/(?P<SHEET_ID>[A-Za-z0-9]+)/?.*gid=(?P<TAB_ID>[0-9]+)$",
-        )
-        .unwrap();
+/// `gid=` in the query or the fragment of the url
+fn tab_id_param_regex() -> &'static Regex {
+    TAB_ID_PARAM_RE.get_or_init(|| {
+        let r = Regex::new(r"(?:^|[?&#])gid=(?P<TAB_ID>[0-9]+)(?:$|[&#])").unwrap();
         r
     })
 }
 
-#[derive(Debug, PartialEq, Serialize)]
+/// the gid in the fragment is preferred to the one in the query,
+/// since the fragment follows the tab sheet opened in the browser
+fn find_tab_id(url: &str) -> Result<Option<u32>> {
+    let (query, fragment) = match url.split_once('#') {
+        Some((query, fragment)) => (query, Some(fragment)),
+        None => (url, None),
+    };
+
+    let re = tab_id_param_regex();
+    let tab_id = fragment
+        .and_then(|fragment| {
+            re.captures(&format!("#{}", fragment))
+                .map(|c| c["TAB_ID"].to_string())
+        })
+        .or_else(|| re.captures(query).map(|c| c["TAB_ID"].to_string()));
+
+    match tab_id {
+        None => Ok(None),
+        Some(tab_id) => tab_id
+            .parse::<u32>()
+            .map(Some)
+            .map_err(|_e| SheetMetaError::InvalidTabId(tab_id)),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct SheetIdOrName {
     pub tab_sheet_id: Option<u32>,
     pub tab_sheet_name: Option<String>,
+    /// 0-based position of the tab. used if neither the id nor the name is passed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tab_sheet_index: Option<usize>,
 }
 
 impl SheetIdOrName {
@@ -70,7 +113,7 @@ impl SheetIdOrName {
     }
 }
 
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct SheetMeta {
     pub spread_sheet_id: String,
     pub sheet_id_or_name: SheetIdOrName,
@@ -85,6 +128,7 @@ impl SheetMeta {
         let sheet_id_or_name = SheetIdOrName {
             tab_sheet_id,
             tab_sheet_name,
+            tab_sheet_index: None,
         };
 
         Self {
@@ -93,34 +137,35 @@ impl SheetMeta {
         }
     }
 
+    pub fn with_tab_sheet_index(mut self, tab_sheet_index: Option<usize>) -> Self {
+        self.sheet_id_or_name.tab_sheet_index = tab_sheet_index;
+        self
+    }
+
+    /// `.../d/<spread sheet id>/edit#gid=0`, `.../edit?gid=0#range=A1` or `.../edit?usp=sharing`
     pub fn from_url(url: &str) -> Result<SheetMeta> {
-        let re = valid_sheet_url_with_tab_id_regex();
-        let sheet_meta = re.captures(url).map_or_else(
-            || Err(SheetMetaError::InvalidSheetUrl(url.to_string())),
-            |capture| {
-                let sheet_id = &capture["SHEET_ID"];
-                let tab_id = &capture["TAB_ID"];
-
-                let tab_id = tab_id
-                    .parse::<u32>()
-                    .map_err(|_e| SheetMetaError::InvalidTabId(tab_id.to_string()))?;
-                Ok(SheetMeta::new(sheet_id.to_string(), Some(tab_id), None))
-            },
-        );
-        let sheet_meta = match sheet_meta {
-            Ok(meta) => Ok(meta),
-            Err(_) => {
-                let re = valid_sheet_url_regex();
-                re.captures(url).map_or_else(
-                    || Err(SheetMetaError::InvalidSheetUrl(url.to_string())),
-                    |capture| {
-                        let sheet_id = &capture["SHEET_ID"];
-                        Ok(SheetMeta::new(sheet_id.to_string(), None, None))
-                    },
-                )
-            }
+        let re = valid_sheet_url_regex();
+        let sheet_id = match re.captures(url) {
+            None => return Err(SheetMetaError::InvalidSheetUrl(url.to_string())),
+            Some(capture) => capture["SHEET_ID"].to_string(),
         };
-        sheet_meta.and_then(|sheet_meta| sheet_meta.validate())
+        let tab_id = find_tab_id(url)?;
+
+        SheetMeta::new(sheet_id, tab_id, None).validate()
+    }
+
+    /// a bare spread sheet id with the optional tab sheet id (gid) and name, validated
+    pub fn from_id(
+        spread_sheet_id: &str,
+        tab_sheet_id: Option<u32>,
+        tab_sheet_name: Option<String>,
+    ) -> Result<SheetMeta> {
+        SheetMeta::new(spread_sheet_id.to_string(), tab_sheet_id, tab_sheet_name).validate()
+    }
+
+    /// accepts a spread sheet url (with or without gid) or a bare spread sheet id
+    pub fn from_any(input: &str) -> Result<SheetMeta> {
+        Self::from_url(input).or_else(|_| Self::from_id(input, None, None))
     }
 
     pub fn validate(self) -> Result<Self> {
@@ -197,6 +242,76 @@ mod test {
         );
     }
 
+    #[test]
+    fn sheet_meta_from_id_test() {
+        let sheet_id = "1HA4munsvl5UUlb9DKmJvhrwfGlSQ97hSQZf13M3ZO4Y";
+        assert_eq!(
+            SheetMeta::from_id(sheet_id, None, None),
+            Ok(SheetMeta::new(sheet_id.to_string(), None, None))
+        );
+
+        let sheet_meta = SheetMeta::from_id(sheet_id, Some(2089556915), None).unwrap();
+        assert_eq!(sheet_meta.spread_sheet_id, sheet_id);
+        assert_eq!(
+            sheet_meta.sheet_id_or_name.is_need_get_sheet_name_by_id(),
+            Some(2089556915)
+        );
+
+        assert_eq!(
+            SheetMeta::from_id(sheet_id, None, Some("grouping".to_string())),
+            Ok(SheetMeta::new(
+                sheet_id.to_string(),
+                None,
+                Some("grouping".to_string())
+            ))
+        );
+
+        for invalid_id in ["1HA4munsvl5UUlb9/edit", "1HA4 munsvl5", "../etc", ""] {
+            assert_eq!(
+                SheetMeta::from_id(invalid_id, Some(0), None),
+                Err(SheetMetaError::InvalidSheetId(invalid_id.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn sheet_meta_parse_url_gid_variants() {
+        let expected_id = "1HA4munsvl5UUlb9DKmJvhrwfGlSQ97hSQZf13M3ZO4Y";
+        let base = format!("https://docs.google.com/spreadsheets/d/{}", expected_id);
+
+        for (suffix, expected_tab_id) in [
+            ("/edit#gid=123", Some(123)),
+            ("/edit?gid=123", Some(123)),
+            ("/edit?gid=123#range=A1", Some(123)),
+            ("/edit?usp=sharing&gid=123", Some(123)),
+            ("/edit?gid=123&usp=sharing", Some(123)),
+            ("/edit#range=A1&gid=123", Some(123)),
+            // the fragment is preferred
+            ("/edit?gid=123#gid=456", Some(456)),
+            ("/edit?usp=sharing", None),
+            ("/edit", None),
+            ("/edit?usp=sharing#range=A1", None),
+            // not a gid parameter
+            ("/edit?xgid=123", None),
+        ] {
+            assert_eq!(
+                SheetMeta::from_url(&format!("{}{}", base, suffix)),
+                Ok(SheetMeta::new(
+                    expected_id.to_string(),
+                    expected_tab_id,
+                    None
+                )),
+                "{}",
+                suffix
+            );
+        }
+
+        assert_eq!(
+            SheetMeta::from_url(&format!("{}/edit#gid=99999999999", base)),
+            Err(SheetMetaError::InvalidTabId("99999999999".to_string()))
+        );
+    }
+
     #[test]
     fn sheet_meta_parse_url_invalid_1() {
         let sheet_meta = SheetMeta::from_url(
@@ -234,4 +349,32 @@ mod test {
         let sheet_meta = sheet_meta.validate();
         assert!(sheet_meta.is_err());
     }
+
+    #[test]
+    fn sheet_meta_from_any() {
+        let expected_id = "1HA4munsvl5UUlb9DKmJvhrwfGlSQ97hSQZf13M3ZO4Y";
+        assert_eq!(
+            SheetMeta::from_any(expected_id),
+            Ok(SheetMeta::new(expected_id.to_string(), None, None))
+        );
+
+        assert_eq!(
+            SheetMeta::from_any(
+                "https://docs.google.com/spreadsheets/d/1HA4munsvl5UUlb9DKmJvhrwfGlSQ97hSQZf13M3ZO4Y/edit"
+            ),
+            Ok(SheetMeta::new(expected_id.to_string(), None, None))
+        );
+
+        assert_eq!(
+            SheetMeta::from_any(TEST_SHEET1_WITH_TAG_ID),
+            Ok(SheetMeta::new(
+                expected_id.to_string(),
+                Some(2089556915),
+                None
+            ))
+        );
+
+        assert!(SheetMeta::from_any("https://example.com/spreadsheets/xxx").is_err());
+        assert!(SheetMeta::from_any("1HA4;?munsvl5UUlb9DKmJvhrwfGlSQ97hSQZf13M3ZO4Y").is_err());
+    }
 }
@@ -0,0 +1,105 @@
+use super::*;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+static SHEET_CACHE: OnceCell<Arc<SheetCache>> = OnceCell::new();
+
+/// keeps the `Sheet` of each spread sheet for `ttl` not to fetch the tabs on every read.
+/// the tabs are rarely changed. `ttl` of zero disables the cache.
+/// the values are read within the grid size of the cached sheet, so the rows appended by
+/// the others are not read until it expires. the rows appended by `SheetsClient` invalidate it
+pub struct SheetCache {
+    ttl: Duration,
+    sheets: Mutex<HashMap<SpreadSheetId, (Instant, Sheet)>>,
+}
+
+impl SheetCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            sheets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// `None` if not cached or expired
+    pub fn get(&self, spread_sheet_id: &SpreadSheetId) -> Option<Sheet> {
+        if self.ttl.is_zero() {
+            return None;
+        }
+        let sheets = self.sheets.lock().unwrap();
+        sheets
+            .get(spread_sheet_id)
+            .filter(|(fetched_at, _)| fetched_at.elapsed() < self.ttl)
+            .map(|(_, sheet)| sheet.clone())
+    }
+
+    /// the expired sheets are dropped at the same time
+    pub fn insert(&self, spread_sheet_id: SpreadSheetId, sheet: &Sheet) {
+        if self.ttl.is_zero() {
+            return;
+        }
+        let mut sheets = self.sheets.lock().unwrap();
+        sheets.retain(|_, (fetched_at, _)| fetched_at.elapsed() < self.ttl);
+        sheets.insert(spread_sheet_id, (Instant::now(), sheet.clone()));
+    }
+
+    pub fn invalidate(&self, spread_sheet_id: &SpreadSheetId) {
+        self.sheets.lock().unwrap().remove(spread_sheet_id);
+    }
+}
+
+/// the cache that `SheetsClient::new` uses. `None` unless `init_sheet_cache` is called
+pub fn sheet_cache() -> Option<Arc<SheetCache>> {
+    SHEET_CACHE.get().cloned()
+}
+
+/// builds the shared cache of the sheets. `false` if it's already initialized
+pub fn init_sheet_cache(ttl: Duration) -> bool {
+    SHEET_CACHE.set(Arc::new(SheetCache::new(ttl))).is_ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sheet(spread_sheet_id: &str) -> Sheet {
+        serde_json::from_value(serde_json::json!({
+            "spreadsheetId": spread_sheet_id,
+            "sheets": [],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn sheet_cache_test() {
+        let cache = SheetCache::new(Duration::from_secs(60));
+        let spread_sheet_id = SpreadSheetId::new("abc".to_string());
+        assert_eq!(cache.get(&spread_sheet_id), None);
+
+        cache.insert(spread_sheet_id.clone(), &sheet("abc"));
+        assert_eq!(cache.get(&spread_sheet_id), Some(sheet("abc")));
+        assert_eq!(cache.get(&SpreadSheetId::new("def".to_string())), None);
+
+        cache.invalidate(&spread_sheet_id);
+        assert_eq!(cache.get(&spread_sheet_id), None);
+    }
+
+    #[test]
+    fn sheet_cache_expired_test() {
+        let cache = SheetCache::new(Duration::from_millis(10));
+        let spread_sheet_id = SpreadSheetId::new("abc".to_string());
+        cache.insert(spread_sheet_id.clone(), &sheet("abc"));
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(cache.get(&spread_sheet_id), None);
+
+        // disabled
+        let cache = SheetCache::new(Duration::from_secs(0));
+        cache.insert(spread_sheet_id.clone(), &sheet("abc"));
+        assert_eq!(cache.get(&spread_sheet_id), None);
+    }
+}
@@ -0,0 +1,657 @@
+use super::*;
+use crate::json_structure::{self, ArrayOrder, DupHeaders, JsonStructureError, MissingValues};
+use serde_json::Map as JsonMap;
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+type Result<T> = std::result::Result<T, JsonStructureError>;
+
+/// how to build the json from the headers and the rows
+#[derive(Debug, Default, Clone)]
+pub struct JsonOption {
+    /// returns the first row as an object instead of an array of the rows
+    pub as_single_obj: bool,
+    /// groups the rows by the value of the column
+    pub group_by_col_idx: Option<usize>,
+    /// header -> renamed header
+    pub rename: HashMap<String, String>,
+    pub first_col_as_key: bool,
+    pub coerce: CoerceOption,
+    /// `user` and `user.name` are built into `{"user":{"_value":..,"name":..}}` instead of an error
+    pub lenient_structure: bool,
+    /// the headers which fail to build the structure are dropped with warnings instead of an error
+    pub skip_invalid_headers: bool,
+    /// converts serial numbers of the columns into iso 8601 dates
+    pub dates: Option<DateOption>,
+    pub dup_headers: DupHeaders,
+    /// the order of the values of the duplicated headers merged into an array
+    pub array_order: ArrayOrder,
+    /// how the columns beyond the rows shorter than the headers are built
+    pub missing_values: MissingValues,
+    /// the dot paths kept in the json of each row. see `json_structure::project`
+    pub projection: Option<Vec<String>>,
+}
+
+impl SheetValueResponse {
+    /// builds the json of the rows keyed by the headers.
+    /// e.g. headers `["name","address.city"]` -> `[{"name":"Alice","address":{"city":"kyoto"}}]`
+    pub fn to_json(&self, as_single_obj: bool) -> Result<JsonValue> {
+        self.to_json_with(&JsonOption {
+            as_single_obj,
+            ..JsonOption::default()
+        })
+    }
+
+    /// `option.group_by_col_idx`: rows are grouped into `{"<value of the column>":[rows...]}`.
+    /// the order of the rows in each group is preserved and
+    /// rows whose group value is empty are grouped into `""`
+    ///
+    /// `option.first_col_as_key`: rows are keyed by the first column like
+    /// `{"<value of the first column>":{...rest of the columns}}`.
+    /// if the values of the first column are duplicated, the last row wins.
+    /// the single row is also returned as `{"<key>":{...}}`
+    ///
+    /// headers are renamed by `option.rename` before building the structure,
+    /// so headers renamed to a same key are merged into an array like duplicated headers.
+    ///
+    /// `null` is returned for `option.as_single_obj` if there are no rows.
+    pub fn to_json_with(&self, option: &JsonOption) -> Result<JsonValue> {
+        self.to_json_with_warnings(option).map(|(json, _)| json)
+    }
+
+    /// `to_json_with` and the warnings of the headers dropped by `option.skip_invalid_headers`
+    pub fn to_json_with_warnings(&self, option: &JsonOption) -> Result<(JsonValue, Vec<String>)> {
+        let headers = structure_headers(&self.headers, option);
+        let (strcuture_obj, warnings) = build_structure(headers.as_slice(), option)?;
+        let structure_obj = json_structure::Structure::new_obj(strcuture_obj);
+        self.build_json_of_rows(&structure_obj, option)
+            .map(|json| (json, warnings))
+    }
+
+    fn build_json_of_rows(
+        &self,
+        structure_obj: &json_structure::Structure,
+        option: &JsonOption,
+    ) -> Result<JsonValue> {
+        if option.first_col_as_key {
+            let mut result = JsonMap::new();
+            let rows = if option.as_single_obj {
+                self.row_values.values.get(..1).unwrap_or_default()
+            } else {
+                self.row_values.values.as_slice()
+            };
+            for each_row in rows {
+                let key = json_value_as_key(each_row.get(0).map(|v| v.as_inner()));
+                let response_json = build_row_json(&structure_obj, each_row, option)?;
+                result.insert(key, response_json);
+            }
+
+            Ok(JsonValue::Object(result))
+        } else if option.as_single_obj {
+            match self.row_values.values.get(0) {
+                None => Ok(JsonValue::Null),
+                Some(first_row) => build_row_json(&structure_obj, first_row, option),
+            }
+        } else if let Some(group_by_col_idx) = option.group_by_col_idx {
+            let mut result = JsonMap::new();
+            for each_row in &self.row_values.values {
+                let group_key =
+                    json_value_as_key(each_row.get(group_by_col_idx).map(|v| v.as_inner()));
+
+                let response_json = build_row_json(&structure_obj, each_row, option)?;
+                match result
+                    .entry(group_key)
+                    .or_insert_with(|| JsonValue::Array(vec![]))
+                {
+                    JsonValue::Array(group) => group.push(response_json),
+                    _ => unreachable!(),
+                }
+            }
+
+            Ok(JsonValue::Object(result))
+        } else {
+            let result = build_row_jsons(&structure_obj, &self.row_values.values, option)
+                .collect::<Result<Vec<JsonValue>>>()?;
+
+            Ok(JsonValue::Array(result))
+        }
+    }
+}
+
+/// headers renamed by `option.rename`, then suffixed if duplicated with `DupHeaders::Suffix`.
+/// the first column is excluded with `option.first_col_as_key`
+pub(crate) fn structure_headers<'a>(
+    headers: &'a RawHeaders,
+    option: &'a JsonOption,
+) -> Vec<Cow<'a, str>> {
+    let skip = if option.first_col_as_key { 1 } else { 0 };
+    let renamed: Vec<&'a str> = headers
+        .values
+        .iter()
+        .skip(skip)
+        .map(|header_value| {
+            option
+                .rename
+                .get(header_value.as_str())
+                .map(|renamed| renamed.as_str())
+                .unwrap_or_else(|| header_value.as_str())
+        })
+        .collect();
+
+    match option.dup_headers {
+        DupHeaders::Array => renamed.into_iter().map(Cow::Borrowed).collect(),
+        DupHeaders::Suffix => json_structure::suffix_duplicated_keys(&renamed),
+    }
+}
+
+/// the structure of `headers` built by `structure_headers`.
+/// with `option.skip_invalid_headers`, the invalid headers are dropped and returned as the warnings
+pub(crate) fn build_structure<'a>(
+    headers: &'a [Cow<'a, str>],
+    option: &JsonOption,
+) -> Result<(json_structure::Object<'a>, Vec<String>)> {
+    let headers: Vec<&'a str> = headers.iter().map(|header| header.as_ref()).collect();
+    let headers = headers.as_slice();
+    if !option.skip_invalid_headers {
+        let obj = json_structure::Object::from_strs(headers, option.lenient_structure)?
+            .with_array_order(option.array_order);
+        return Ok((obj, vec![]));
+    }
+
+    let (obj, skipped) =
+        json_structure::Object::from_strs_skipping_errors(headers, option.lenient_structure);
+    let warnings = skipped
+        .iter()
+        .map(|skipped_key| {
+            format!(
+                "header `{}` is dropped: {}",
+                skipped_key.key, skipped_key.error
+            )
+        })
+        .collect();
+    Ok((obj.with_array_order(option.array_order), warnings))
+}
+
+/// the first column is excluded with `option.first_col_as_key`
+pub(crate) fn build_row_json<'a>(
+    structure_obj: &'a json_structure::Structure<'a>,
+    row: &[CellValue],
+    option: &JsonOption,
+) -> Result<JsonValue> {
+    let skip = if option.first_col_as_key { 1 } else { 0 };
+    let row = converted_row(row, option);
+    let row: Vec<&JsonValue> = row.iter().skip(skip).map(|v| v.as_ref()).collect();
+    let response_json = structure_obj
+        .build_json_with(row.as_slice(), option.missing_values)?
+        .into_json_value();
+    match option.projection.as_ref() {
+        None => Ok(response_json),
+        Some(paths) => Ok(json_structure::project(&response_json, paths)),
+    }
+}
+
+/// builds the json of each row lazily
+pub(crate) fn build_row_jsons<'a>(
+    structure_obj: &'a json_structure::Structure<'a>,
+    rows: &'a [Vec<CellValue>],
+    option: &'a JsonOption,
+) -> impl Iterator<Item = Result<JsonValue>> + 'a {
+    rows.iter()
+        .map(move |each_row| build_row_json(structure_obj, each_row, option))
+}
+
+/// dates of `option.dates` are converted and the rest are coerced by `option.coerce`
+fn converted_row<'a>(row: &'a [CellValue], option: &JsonOption) -> Vec<Cow<'a, JsonValue>> {
+    row.iter()
+        .enumerate()
+        .map(|(col_idx, v)| {
+            match option
+                .dates
+                .as_ref()
+                .filter(|dates| dates.is_date_col(col_idx))
+                .and_then(|dates| dates.convert(v.as_inner()))
+            {
+                Some(date) => Cow::Owned(date),
+                None => v.coerced(&option.coerce),
+            }
+        })
+        .collect()
+}
+
+/// cell value as a key of json object. empty cells are `""`
+fn json_value_as_key(value: Option<&JsonValue>) -> String {
+    match value {
+        None | Some(JsonValue::Null) => "".to_string(),
+        Some(JsonValue::String(s)) => s.to_string(),
+        Some(v) => v.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn sheet_response_fixture() -> SheetValueResponse {
+        serde_json::from_value(json!({
+            "headers": {
+                "range": {
+                    "sheet_name": "grouping",
+                    "start": {"col_index": 0, "row_index": 0},
+                    "end": {"col_index": 3, "row_index": 0},
+                },
+                "values": ["name", "favorite", "favorite", "address.city.name"],
+            },
+            "row_values": {
+                "values": [
+                    ["Alice", "diving", "programming", "kyoto"],
+                    ["Bob", "shopping", "", "tokyo"],
+                ],
+            },
+            "pagination": {"offset": 0, "limit": 2},
+            "total_rows": 999,
+        }))
+        .unwrap()
+    }
+
+    fn rename(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(from, to)| (from.to_string(), to.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn to_json_test() {
+        let sheet_response = sheet_response_fixture();
+
+        let result = sheet_response.to_json(false).unwrap();
+        let expected = json!([
+            {"name": "Alice", "favorite": ["diving", "programming"], "address": {"city": {"name": "kyoto"}}},
+            {"name": "Bob", "favorite": ["shopping", ""], "address": {"city": {"name": "tokyo"}}},
+        ]);
+        assert_eq!(result, expected);
+
+        let result = sheet_response.to_json(true).unwrap();
+        let expected = json!(
+            {"name": "Alice", "favorite": ["diving", "programming"], "address": {"city": {"name": "kyoto"}}}
+        );
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn to_json_empty_rows_test() {
+        let mut sheet_response = sheet_response_fixture();
+        sheet_response.row_values = RowValues::empty();
+
+        assert_eq!(sheet_response.to_json(false).unwrap(), json!([]));
+        assert_eq!(sheet_response.to_json(true).unwrap(), JsonValue::Null);
+    }
+
+    #[test]
+    fn to_json_group_by_test() {
+        let sheet_response: SheetValueResponse = serde_json::from_value(json!({
+            "headers": {
+                "range": {
+                    "sheet_name": null,
+                    "start": {"col_index": 0, "row_index": 0},
+                    "end": {"col_index": 2, "row_index": 0},
+                },
+                "values": ["name", "region", "sales"],
+            },
+            "row_values": {
+                "values": [
+                    ["Alice", "West", "10"],
+                    ["Bob", "East", "20"],
+                    ["Charlie", "West", "30"],
+                    ["David", "", "40"],
+                ],
+            },
+            "pagination": null,
+        }))
+        .unwrap();
+
+        let group_by_col_idx = sheet_response.headers.column_index_of("region");
+        assert_eq!(group_by_col_idx, Some(1));
+
+        let option = JsonOption {
+            group_by_col_idx,
+            ..JsonOption::default()
+        };
+        let result = sheet_response.to_json_with(&option).unwrap();
+        let expected = json!({
+            "West": [
+                {"name": "Alice", "region": "West", "sales": "10"},
+                {"name": "Charlie", "region": "West", "sales": "30"},
+            ],
+            "East": [
+                {"name": "Bob", "region": "East", "sales": "20"},
+            ],
+            "": [
+                {"name": "David", "region": "", "sales": "40"},
+            ],
+        });
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn to_json_skip_invalid_headers_test() {
+        let sheet_response = sheet_response_fixture();
+        // `name` collides with `name.first`
+        let option = JsonOption {
+            rename: rename(&[("favorite", "name.first")]),
+            ..JsonOption::default()
+        };
+        assert!(sheet_response.to_json_with(&option).is_err());
+
+        let option = JsonOption {
+            skip_invalid_headers: true,
+            ..option
+        };
+        let (result, warnings) = sheet_response.to_json_with_warnings(&option).unwrap();
+        let expected = json!([
+            {"name": "Alice", "address": {"city": {"name": "kyoto"}}},
+            {"name": "Bob", "address": {"city": {"name": "tokyo"}}},
+        ]);
+        assert_eq!(result, expected);
+        assert_eq!(
+            warnings,
+            vec![
+                "header `name.first` is dropped: invalid json structure definition :key:`name` supposed to be a object but value";
+                2
+            ]
+        );
+
+        // no warnings without invalid headers
+        let (_, warnings) = sheet_response_fixture()
+            .to_json_with_warnings(&JsonOption {
+                skip_invalid_headers: true,
+                ..JsonOption::default()
+            })
+            .unwrap();
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn to_json_dup_headers_test() {
+        let sheet_response = sheet_response_fixture();
+
+        let result = sheet_response
+            .to_json_with(&JsonOption {
+                dup_headers: DupHeaders::Array,
+                ..JsonOption::default()
+            })
+            .unwrap();
+        let expected = json!([
+            {"name": "Alice", "favorite": ["diving", "programming"], "address": {"city": {"name": "kyoto"}}},
+            {"name": "Bob", "favorite": ["shopping", ""], "address": {"city": {"name": "tokyo"}}},
+        ]);
+        assert_eq!(result, expected);
+
+        let result = sheet_response
+            .to_json_with(&JsonOption {
+                dup_headers: DupHeaders::Suffix,
+                ..JsonOption::default()
+            })
+            .unwrap();
+        let expected = json!([
+            {"name": "Alice", "favorite": "diving", "favorite_2": "programming", "address": {"city": {"name": "kyoto"}}},
+            {"name": "Bob", "favorite": "shopping", "favorite_2": "", "address": {"city": {"name": "tokyo"}}},
+        ]);
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn to_json_unpadded_rows_test() {
+        let mut sheet_response = sheet_response_fixture();
+        // padded rows
+        let result = sheet_response.to_json(false).unwrap();
+        assert_eq!(result[1]["favorite"], json!(["shopping", ""]));
+
+        // `?pad=false` returns the rows at their natural length
+        sheet_response.row_values = RowValues::new(vec![
+            vec![
+                CellValue::new(json!("Alice")),
+                CellValue::new(json!("diving")),
+            ],
+            vec![CellValue::new(json!("Bob"))],
+        ]);
+        let result = sheet_response.to_json(false).unwrap();
+        let expected = json!([
+            {"name": "Alice", "favorite": ["diving"]},
+            {"name": "Bob"},
+        ]);
+        assert_eq!(result, expected);
+
+        let result = sheet_response
+            .to_json_with(&JsonOption {
+                dup_headers: DupHeaders::Suffix,
+                ..JsonOption::default()
+            })
+            .unwrap();
+        let expected = json!([
+            {"name": "Alice", "favorite": "diving"},
+            {"name": "Bob"},
+        ]);
+        assert_eq!(result, expected);
+
+        let result = sheet_response
+            .to_json_with(&JsonOption {
+                missing_values: MissingValues::Null,
+                ..JsonOption::default()
+            })
+            .unwrap();
+        let expected = json!([
+            {"name": "Alice", "favorite": ["diving", null], "address": {"city": {"name": null}}},
+            {"name": "Bob", "favorite": [null, null], "address": {"city": {"name": null}}},
+        ]);
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn to_json_rename_test() {
+        let sheet_response = sheet_response_fixture();
+        let option = JsonOption {
+            rename: rename(&[("name", "full_name"), ("address.city.name", "city")]),
+            ..JsonOption::default()
+        };
+
+        let result = sheet_response.to_json_with(&option).unwrap();
+        let expected = json!([
+            {"full_name": "Alice", "favorite": ["diving", "programming"], "city": "kyoto"},
+            {"full_name": "Bob", "favorite": ["shopping", ""], "city": "tokyo"},
+        ]);
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn to_json_rename_collision_test() {
+        let sheet_response = sheet_response_fixture();
+        let option = JsonOption {
+            rename: rename(&[("address.city.name", "name")]),
+            ..JsonOption::default()
+        };
+
+        let result = sheet_response.to_json_with(&option).unwrap();
+        let expected = json!([
+            {"name": ["Alice", "kyoto"], "favorite": ["diving", "programming"]},
+            {"name": ["Bob", "tokyo"], "favorite": ["shopping", ""]},
+        ]);
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn to_json_first_col_as_key_test() {
+        let sheet_response = sheet_response_fixture();
+        let option = JsonOption {
+            first_col_as_key: true,
+            ..JsonOption::default()
+        };
+
+        let result = sheet_response.to_json_with(&option).unwrap();
+        let expected = json!({
+            "Alice": {"favorite": ["diving", "programming"], "address": {"city": {"name": "kyoto"}}},
+            "Bob": {"favorite": ["shopping", ""], "address": {"city": {"name": "tokyo"}}},
+        });
+        assert_eq!(result, expected);
+
+        let option = JsonOption {
+            first_col_as_key: true,
+            as_single_obj: true,
+            ..JsonOption::default()
+        };
+        let result = sheet_response.to_json_with(&option).unwrap();
+        let expected = json!({
+            "Alice": {"favorite": ["diving", "programming"], "address": {"city": {"name": "kyoto"}}},
+        });
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn to_json_first_col_as_key_duplicated_test() {
+        let sheet_response: SheetValueResponse = serde_json::from_value(json!({
+            "headers": {
+                "range": {
+                    "sheet_name": null,
+                    "start": {"col_index": 0, "row_index": 0},
+                    "end": {"col_index": 1, "row_index": 0},
+                },
+                "values": ["key", "value"],
+            },
+            "row_values": {
+                "values": [
+                    ["timeout", "10"],
+                    ["retry", "3"],
+                    ["timeout", "30"],
+                ],
+            },
+            "pagination": null,
+        }))
+        .unwrap();
+        let option = JsonOption {
+            first_col_as_key: true,
+            ..JsonOption::default()
+        };
+
+        let result = sheet_response.to_json_with(&option).unwrap();
+        // the last row wins
+        let expected = json!({
+            "timeout": {"value": "30"},
+            "retry": {"value": "3"},
+        });
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn to_json_big_int_as_string_test() {
+        let sheet_response: SheetValueResponse = serde_json::from_value(json!({
+            "headers": {
+                "range": {
+                    "sheet_name": null,
+                    "start": {"col_index": 0, "row_index": 0},
+                    "end": {"col_index": 1, "row_index": 0},
+                },
+                "values": ["id", "count"],
+            },
+            "row_values": {
+                "values": [
+                    ["9007199254740993", "12"],
+                    ["9007199254740991", "3"],
+                ],
+            },
+            "pagination": null,
+        }))
+        .unwrap();
+        let option = JsonOption {
+            coerce: CoerceOption {
+                numbers: true,
+                big_int_as_string: true,
+            },
+            ..JsonOption::default()
+        };
+
+        let result = sheet_response.to_json_with(&option).unwrap();
+        let expected = json!([
+            {"id": "9007199254740993", "count": 12},
+            {"id": 9007199254740991i64, "count": 3},
+        ]);
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn to_json_dates_test() {
+        // read with the unformatted value rendering
+        let sheet_response: SheetValueResponse = serde_json::from_value(json!({
+            "headers": {
+                "range": {
+                    "sheet_name": null,
+                    "start": {"col_index": 0, "row_index": 0},
+                    "end": {"col_index": 2, "row_index": 0},
+                },
+                "values": ["name", "birthday", "checked_in_at"],
+            },
+            "row_values": {
+                "values": [
+                    ["Alice", 44197, 44197.5],
+                    ["Bob", "", 44561.75],
+                ],
+            },
+            "pagination": null,
+        }))
+        .unwrap();
+        let option = JsonOption {
+            dates: Some(DateOption {
+                col_indices: vec![1, 2],
+                tz: chrono_tz::Tz::UTC,
+            }),
+            ..JsonOption::default()
+        };
+
+        let result = sheet_response.to_json_with(&option).unwrap();
+        let expected = json!([
+            {"name": "Alice", "birthday": "2021-01-01", "checked_in_at": "2021-01-01T12:00:00+00:00"},
+            {"name": "Bob", "birthday": "", "checked_in_at": "2021-12-31T18:00:00+00:00"},
+        ]);
+        assert_eq!(result, expected);
+
+        // the column indices include the key column
+        let option = JsonOption {
+            first_col_as_key: true,
+            ..option
+        };
+        let result = sheet_response.to_json_with(&option).unwrap();
+        assert_eq!(result["Alice"]["birthday"], json!("2021-01-01"));
+    }
+
+    #[test]
+    fn to_json_projection_test() {
+        let sheet_response = sheet_response_fixture();
+        let option = JsonOption {
+            projection: Some(vec!["address.city".to_string(), "favorite".to_string()]),
+            ..JsonOption::default()
+        };
+        let result = sheet_response.to_json_with(&option).unwrap();
+        assert_eq!(
+            result,
+            json!([
+                {"favorite": ["diving", "programming"], "address": {"city": {"name": "kyoto"}}},
+                {"favorite": ["shopping", ""], "address": {"city": {"name": "tokyo"}}},
+            ])
+        );
+
+        // the keys of the grouped rows are kept
+        let option = JsonOption {
+            first_col_as_key: true,
+            projection: Some(vec!["address.city.name".to_string(), "age".to_string()]),
+            ..JsonOption::default()
+        };
+        let result = sheet_response.to_json_with(&option).unwrap();
+        assert_eq!(
+            result,
+            json!({
+                "Alice": {"address": {"city": {"name": "kyoto"}}},
+                "Bob": {"address": {"city": {"name": "tokyo"}}},
+            })
+        );
+    }
+}
@@ -6,7 +6,7 @@ use once_cell::sync::OnceCell;
 use std::path::PathBuf;
 use std::sync::Arc;
 use thiserror::Error;
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, Mutex};
 use tokio::task::{JoinError, JoinHandle};
 use tokio::time::timeout;
 use yup_oauth2::{
@@ -17,6 +17,8 @@ use yup_oauth2::{
 
 type Result<T> = std::result::Result<T, GoogleTokenManagerError>;
 
+pub type DefaultConnector = <DefaultHyperClient as HyperClientBuilder>::Connector;
+
 #[derive(Error, Debug)]
 pub enum GoogleTokenManagerError {
     #[error("oauth error:{0}")]
@@ -38,11 +40,45 @@ fn get_token_buffer_duraiton_to_expire() -> &'static Duration {
     TOKEN_BUFFER_DURATION_TO_EXPIRE.get_or_init(|| Duration::minutes(2))
 }
 
+/// stops the token refreshing loops of all the token managers started with its `subscribe()`.
+/// a token manager subscribing after `stop()` won't be stopped by it
+#[derive(Clone)]
+pub struct RefreshingStopper {
+    tx: broadcast::Sender<()>,
+}
+
+impl RefreshingStopper {
+    pub fn new() -> Self {
+        // a single message is enough as every receiver gets its own copy
+        let (tx, _) = broadcast::channel(1);
+        Self { tx }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<()> {
+        self.tx.subscribe()
+    }
+
+    /// returns the number of the refreshing loops notified.
+    /// the loops already finished are not counted
+    pub fn stop(&self) -> usize {
+        self.tx.send(()).unwrap_or(0)
+    }
+}
+
+impl Default for RefreshingStopper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[allow(dead_code)]
 pub struct TokenManager<HttpConnector> {
-    authenticator: Arc<Authenticator<HttpConnector>>,
+    /// swapped on `reload`
+    authenticator: Arc<ArcSwap<Authenticator<HttpConnector>>>,
     scopes: &'static [&'static str],
     inner_current_token: Arc<ArcSwap<AccessToken>>,
+    /// prevents the refreshing loop from overwriting the token of the reloaded authenticator
+    refreshing_lock: Arc<Mutex<()>>,
     token_refreshing_loop_jh: JoinHandle<()>,
 }
 
@@ -60,11 +96,13 @@ where
         let access_token = authenticator.token(scopes.as_ref()).await?;
         let current_token = Arc::new(ArcSwap::from(Arc::new(access_token)));
 
-        let authenticator = Arc::new(authenticator);
+        let authenticator = Arc::new(ArcSwap::from(Arc::new(authenticator)));
+        let refreshing_lock = Arc::new(Mutex::new(()));
 
         let token_refreshing_loop_jh = Self::periodically_refreshing_token(
             authenticator.clone(),
             current_token.clone(),
+            refreshing_lock.clone(),
             scopes,
             stop_refreshing_notifyer_rx,
             token_refresh_period,
@@ -75,14 +113,16 @@ where
             authenticator,
             scopes,
             inner_current_token: current_token,
+            refreshing_lock,
             token_refreshing_loop_jh,
         };
         Ok(result)
     }
 
     async fn periodically_refreshing_token(
-        authenticator: Arc<Authenticator<HttpConnector>>,
+        authenticator: Arc<ArcSwap<Authenticator<HttpConnector>>>,
         shared_token: Arc<ArcSwap<AccessToken>>,
+        refreshing_lock: Arc<Mutex<()>>,
         scopes: &'static [&'static str],
         mut stop_refreshing_notifyer_rx: broadcast::Receiver<()>,
         token_refresh_period: Option<Duration>,
@@ -113,7 +153,8 @@ where
                     .unwrap_or(false);
 
                 if need_refresh {
-                    let new_token = Self::get_new_token(&authenticator, &scopes).await;
+                    let _refreshing = refreshing_lock.lock().await;
+                    let new_token = Self::get_new_token(&authenticator.load_full(), &scopes).await;
                     match new_token {
                         Ok(access_token) => shared_token.store(Arc::new(access_token)),
                         Err(e) => {
@@ -130,12 +171,24 @@ where
 
     #[allow(dead_code)]
     pub fn authenticator(&self) -> Arc<Authenticator<HttpConnector>> {
-        Arc::clone(&self.authenticator)
+        self.authenticator.load_full()
+    }
+
+    /// replaces the authenticator (e.g. with a rotated service account key).
+    /// the current token is kept until the new authenticator issues a token,
+    /// so the requests in flight are not interrupted and a broken key doesn't replace the working one
+    pub async fn reload(&self, authenticator: Authenticator<HttpConnector>) -> Result<()> {
+        let _refreshing = self.refreshing_lock.lock().await;
+        let access_token = Self::get_new_token(&authenticator, &self.scopes).await?;
+        self.authenticator.store(Arc::new(authenticator));
+        self.inner_current_token.store(Arc::new(access_token));
+        Ok(())
     }
 
     #[allow(dead_code)]
     pub async fn force_refresh_token(&mut self) -> Result<()> {
-        let new_token = Self::get_new_token(&self.authenticator, &self.scopes).await;
+        let _refreshing = self.refreshing_lock.lock().await;
+        let new_token = Self::get_new_token(&self.authenticator.load_full(), &self.scopes).await;
         match new_token {
             Ok(access_token) => {
                 self.current_token().store(Arc::new(access_token));
@@ -162,18 +215,31 @@ where
     }
 }
 
+/// waits for the refreshing loops of all the token managers. call after `RefreshingStopper::stop()`
+pub async fn wait_until_all_refreshing_finished<HttpConnector>(
+    token_managers: Vec<TokenManager<HttpConnector>>,
+) -> Result<()>
+where
+    HttpConnector: hyper::client::connect::Connect + Clone + Send + Sync + 'static,
+{
+    futures::future::try_join_all(
+        token_managers
+            .into_iter()
+            .map(|token_manager| token_manager.wait_until_refreshing_finished()),
+    )
+    .await?;
+    Ok(())
+}
+
 impl<ANY> TokenManager<ANY> {
     pub fn current_token(&self) -> Arc<ArcSwap<AccessToken>> {
         Arc::clone(&self.inner_current_token)
     }
 }
 
-pub async fn token_manager_from_service_account_file(
-    scopes: &'static [&'static str],
-    service_account_cred_file: PathBuf, //TODO(tacogips) PathBuf to reference type
-    stop_refreshing_notifyer_rx: broadcast::Receiver<()>,
-    token_refresh_period: Option<Duration>,
-) -> Result<TokenManager<<DefaultHyperClient as HyperClientBuilder>::Connector>> {
+pub async fn authenticator_from_service_account_file(
+    service_account_cred_file: PathBuf,
+) -> Result<Authenticator<DefaultConnector>> {
     let sa_key = oauth::read_service_account_key(&service_account_cred_file)
         .await
         .map_err(|e| {
@@ -183,12 +249,21 @@ pub async fn token_manager_from_service_account_file(
             )
         })?;
 
-    let authenticator = oauth::ServiceAccountAuthenticator::builder(sa_key)
+    oauth::ServiceAccountAuthenticator::builder(sa_key)
         .build()
         .await
         .map_err(|e| {
             GoogleTokenManagerError::InvalidServiceAccountFileError(service_account_cred_file, e)
-        })?;
+        })
+}
+
+pub async fn token_manager_from_service_account_file(
+    scopes: &'static [&'static str],
+    service_account_cred_file: PathBuf, //TODO(tacogips) PathBuf to reference type
+    stop_refreshing_notifyer_rx: broadcast::Receiver<()>,
+    token_refresh_period: Option<Duration>,
+) -> Result<TokenManager<DefaultConnector>> {
+    let authenticator = authenticator_from_service_account_file(service_account_cred_file).await?;
 
     TokenManager::start(
         authenticator,
@@ -203,7 +278,11 @@ pub async fn token_manager_from_service_account_file(
 mod test {
     use super::super::scopes;
     use super::super::test::load_test_sa_file_path;
-    use super::token_manager_from_service_account_file;
+    use super::{
+        authenticator_from_service_account_file, token_manager_from_service_account_file,
+        wait_until_all_refreshing_finished, RefreshingStopper,
+    };
+    use std::time::Duration;
     use tokio::sync::broadcast;
 
     #[tokio::test]
@@ -221,4 +300,61 @@ mod test {
         let token = token_manager.current_token();
         assert_ne!("", token.load().as_str());
     }
+
+    #[tokio::test]
+    async fn reload_test() {
+        let (_, rx) = broadcast::channel(1);
+        let token_manager = token_manager_from_service_account_file(
+            scopes::SHEET_READ_ONLY,
+            load_test_sa_file_path(),
+            rx,
+            None,
+        )
+        .await
+        .unwrap();
+
+        // a token read before the reload
+        let current_token = token_manager.current_token();
+        let in_flight_token = current_token.load_full();
+
+        let authenticator = authenticator_from_service_account_file(load_test_sa_file_path())
+            .await
+            .unwrap();
+        token_manager.reload(authenticator).await.unwrap();
+
+        // the in-flight read still holds a valid token
+        assert_ne!("", in_flight_token.as_str());
+        // and the new reads see the token of the new authenticator
+        assert_ne!("", token_manager.current_token().load().as_str());
+    }
+
+    #[tokio::test]
+    async fn stop_multiple_token_managers_test() {
+        let stopper = RefreshingStopper::new();
+        let mut token_managers = vec![];
+        for _ in 0..2 {
+            let token_manager = token_manager_from_service_account_file(
+                scopes::SHEET_READ_ONLY,
+                load_test_sa_file_path(),
+                stopper.subscribe(),
+                None,
+            )
+            .await
+            .unwrap();
+            token_managers.push(token_manager);
+        }
+
+        assert_eq!(2, stopper.stop());
+
+        // the loops wait 30 seconds between the refreshes unless stopped
+        let finished = tokio::time::timeout(
+            Duration::from_secs(5),
+            wait_until_all_refreshing_finished(token_managers),
+        )
+        .await;
+        assert!(matches!(finished, Ok(Ok(()))));
+
+        // nothing left to stop
+        assert_eq!(0, stopper.stop());
+    }
 }
@@ -1,6 +1,8 @@
 use super::TokenManager;
 use super::*;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::str::FromStr;
 use std::sync::Arc;
 use thiserror::Error;
 
@@ -14,6 +16,37 @@ pub const DEFAULT_ROW_NUMBER_TO_READ_AT_ONCE: usize = 100;
 #[cfg(not(feature = "restricted"))]
 pub const MAX_ROW_NUMBER_TO_READ_AT_ONCE: usize = 10000;
 
+/// number of the rows to read at once
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RowLimits {
+    /// used if no limit is requested
+    pub default_limit: usize,
+    pub max_limit: usize,
+}
+
+impl RowLimits {
+    /// `max_limit` is capped by `MAX_ROW_NUMBER_TO_READ_AT_ONCE` with the `restricted` feature,
+    /// and `default_limit` is capped by `max_limit`
+    pub fn new(default_limit: usize, max_limit: usize) -> Self {
+        #[cfg(feature = "restricted")]
+        let max_limit = max_limit.min(MAX_ROW_NUMBER_TO_READ_AT_ONCE);
+
+        Self {
+            default_limit: default_limit.min(max_limit),
+            max_limit,
+        }
+    }
+}
+
+impl Default for RowLimits {
+    fn default() -> Self {
+        Self::new(
+            DEFAULT_ROW_NUMBER_TO_READ_AT_ONCE,
+            MAX_ROW_NUMBER_TO_READ_AT_ONCE,
+        )
+    }
+}
+
 #[derive(Error, Debug, PartialEq)]
 pub enum ValueError {
     #[error("failed to fetch values from api:{0}")]
@@ -22,6 +55,12 @@ pub enum ValueError {
     #[error("spread sheet not found:{0}")]
     SpreadSheetNotFound(String),
 
+    #[error("failed to append values:{0}")]
+    AppendValueApiError(String),
+
+    #[error("invalid row:{0}")]
+    InvalidRowJson(String),
+
     #[error("invalid row number start:{0} end:{1}")]
     InvalidRowNumber(usize, usize),
 
@@ -31,9 +70,20 @@ pub enum ValueError {
     #[error("too many row number to read. max is {0}, passed {1} ")]
     TooManyRowNumber(usize, usize),
 
-    #[cfg(feature = "restricted")]
+    #[error("too many columns to read. max is {0}, passed {1} ")]
+    TooManyColumns(usize, usize),
+
     #[error("row index out of restriction:{0}")]
     RowIndexOutOfRescription(usize),
+
+    #[error("col index out of restriction:{0}")]
+    ColIndexOutOfRescription(usize),
+
+    #[error("the ranges must have the same number of columns:{0} and {1}")]
+    IncompatibleRanges(String, String),
+
+    #[error("sheet api refused the request [{}] {}", .0.status, .0.message)]
+    SheetApiAccessError(GoogleApiError),
 }
 
 impl ValueError {
@@ -44,14 +94,121 @@ impl ValueError {
             false
         }
     }
+
+    /// stable error code for api clients
+    pub fn code(&self) -> &'static str {
+        match self {
+            ValueError::FetchValueApiError(_) => "SHEET_API_ERROR",
+            ValueError::SpreadSheetNotFound(_) => "SPREAD_SHEET_NOT_FOUND",
+            ValueError::AppendValueApiError(_) => "SHEET_API_ERROR",
+            ValueError::InvalidRowJson(_) => "INVALID_ROW",
+            ValueError::InvalidRowNumber(_, _) => "INVALID_ROW_NUMBER",
+            ValueError::InvalidColRange(_, _) => "INVALID_RANGE",
+            ValueError::TooManyRowNumber(_, _) => "TOO_MANY_ROWS",
+            ValueError::TooManyColumns(_, _) => "TOO_MANY_COLUMNS",
+            ValueError::RowIndexOutOfRescription(_) => "ROW_INDEX_OUT_OF_RESTRICTION",
+            ValueError::ColIndexOutOfRescription(_) => "COL_INDEX_OUT_OF_RESTRICTION",
+            ValueError::IncompatibleRanges(_, _) => "INCOMPATIBLE_RANGES",
+            ValueError::SheetApiAccessError(google_error) => google_error.error_code(),
+        }
+    }
+
+    /// 404 for missing spread sheets, 500 for the failures of the sheet api,
+    /// 401, 403 and 429 of the sheet api as they are, otherwise 400
+    pub fn http_status(&self) -> StatusCode {
+        match self {
+            ValueError::SheetApiAccessError(google_error) => google_error.http_status(),
+            ValueError::SpreadSheetNotFound(_) => StatusCode::NOT_FOUND,
+            ValueError::FetchValueApiError(_) | ValueError::AppendValueApiError(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+/// 2^53 - 1. integers larger than this lose their precision in javascript
+pub const MAX_SAFE_INTEGER: i64 = 9_007_199_254_740_991;
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CoerceOption {
+    /// "21" -> 21, "1.5" -> 1.5
+    pub numbers: bool,
+    /// keeps integers beyond `MAX_SAFE_INTEGER` as strings
+    pub big_int_as_string: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct CellValue(JsonValue);
 impl CellValue {
+    pub fn new(value: JsonValue) -> Self {
+        Self(value)
+    }
+
+    /// `""` or null
+    pub fn is_empty(&self) -> bool {
+        match &self.0 {
+            JsonValue::Null => true,
+            JsonValue::String(s) => s.is_empty(),
+            _ => false,
+        }
+    }
+
     pub fn as_inner(&self) -> &JsonValue {
         &self.0
     }
+
+    pub fn into_inner(self) -> JsonValue {
+        self.0
+    }
+
+    pub fn coerced(&self, option: &CoerceOption) -> Cow<'_, JsonValue> {
+        match &self.0 {
+            JsonValue::String(s) if option.numbers => match coerce_number(s, option) {
+                Some(number) => Cow::Owned(number),
+                None => Cow::Borrowed(&self.0),
+            },
+            JsonValue::Number(n) if option.big_int_as_string && is_big_int(n) => {
+                Cow::Owned(JsonValue::String(n.to_string()))
+            }
+            _ => Cow::Borrowed(&self.0),
+        }
+    }
+}
+
+fn is_big_int(n: &serde_json::Number) -> bool {
+    match (n.as_i64(), n.as_u64()) {
+        (Some(i), _) => !(-MAX_SAFE_INTEGER..=MAX_SAFE_INTEGER).contains(&i),
+        (None, Some(_)) => true,
+        _ => false,
+    }
+}
+
+fn coerce_number(s: &str, option: &CoerceOption) -> Option<JsonValue> {
+    let s = s.trim();
+    let digits = s.trim_start_matches('-');
+    if s.is_empty() || (digits.len() > 1 && digits.starts_with('0') && !digits.starts_with("0.")) {
+        // leading zeros like a zip code are not numbers
+        return None;
+    }
+
+    if let Ok(i) = s.parse::<i64>() {
+        let number = serde_json::Number::from(i);
+        if option.big_int_as_string && is_big_int(&number) {
+            return None;
+        }
+        return Some(JsonValue::Number(number));
+    }
+
+    if s.chars().all(|c| c.is_ascii_digit()) {
+        // beyond i64. never be a precise number
+        return None;
+    }
+
+    s.parse::<f64>()
+        .ok()
+        .and_then(serde_json::Number::from_f64)
+        .map(JsonValue::Number)
 }
 
 pub struct ReadValueOption {
@@ -60,6 +217,11 @@ pub struct ReadValueOption {
     col_range: (usize, usize),
     start_row_idx: usize,
     end_row_idx: usize,
+    max_row_num: usize,
+    unformatted_values: bool,
+    formulas: bool,
+    restrictions: Option<Restrictions>,
+    pad: bool,
 }
 
 impl ReadValueOption {
@@ -76,8 +238,45 @@ impl ReadValueOption {
             col_range,
             start_row_idx,
             end_row_idx,
+            max_row_num: MAX_ROW_NUMBER_TO_READ_AT_ONCE,
+            unformatted_values: false,
+            formulas: false,
+            restrictions: None,
+            pad: true,
         }
     }
+
+    /// the short rows are filled with `""` up to the requested columns.
+    /// with `false` the rows are returned at their natural length
+    pub fn with_pad(mut self, pad: bool) -> Self {
+        self.pad = pad;
+        self
+    }
+
+    /// `RowLimits::max_limit`
+    pub fn with_max_row_num(mut self, max_row_num: usize) -> Self {
+        self.max_row_num = max_row_num;
+        self
+    }
+
+    /// the rows beyond `Restrictions::max_row` are not read
+    pub fn with_restrictions(mut self, restrictions: Option<Restrictions>) -> Self {
+        self.restrictions = restrictions;
+        self
+    }
+
+    /// dates are read as serial numbers
+    pub fn with_unformatted_values(mut self, unformatted_values: bool) -> Self {
+        self.unformatted_values = unformatted_values;
+        self
+    }
+
+    /// the formulas like `=SUM(A1:A2)` are read instead of the computed values
+    pub fn with_formulas(mut self, formulas: bool) -> Self {
+        self.formulas = formulas;
+        self
+    }
+
     pub fn validate(&self) -> Result<()> {
         let row_num = self.end_row_idx as i64 - self.start_row_idx as i64;
         if row_num < 0 {
@@ -87,12 +286,275 @@ impl ReadValueOption {
             ));
         }
 
-        if row_num as usize > MAX_ROW_NUMBER_TO_READ_AT_ONCE {
+        if row_num as usize > self.max_row_num {
             return Err(ValueError::TooManyRowNumber(
-                MAX_ROW_NUMBER_TO_READ_AT_ONCE,
+                self.max_row_num,
                 row_num as usize,
             ));
         }
+
+        if let Some(restrictions) = self.restrictions.as_ref() {
+            if let Err(e) = is_row_range_overflow(self.end_row_idx + 1, restrictions.max_row) {
+                return Err(ValueError::RowIndexOutOfRescription(e));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// reads the rows at `row_indices` with one batch request
+pub struct ReadRowsOption {
+    spread_sheet_id: SpreadSheetId,
+    sheet_name: Option<SheetName>,
+    col_range: (usize, usize),
+    row_indices: Vec<usize>,
+    max_row_num: usize,
+    unformatted_values: bool,
+    formulas: bool,
+    restrictions: Option<Restrictions>,
+    pad: bool,
+}
+
+impl ReadRowsOption {
+    pub fn new(
+        spread_sheet_id: SpreadSheetId,
+        sheet_name: Option<SheetName>,
+        col_range: (usize, usize),
+        row_indices: Vec<usize>,
+    ) -> Self {
+        Self {
+            spread_sheet_id,
+            sheet_name,
+            col_range,
+            row_indices,
+            max_row_num: MAX_ROW_NUMBER_TO_READ_AT_ONCE,
+            unformatted_values: false,
+            formulas: false,
+            restrictions: None,
+            pad: true,
+        }
+    }
+
+    /// the short rows are filled with `""` up to the requested columns.
+    /// with `false` the rows are returned at their natural length
+    pub fn with_pad(mut self, pad: bool) -> Self {
+        self.pad = pad;
+        self
+    }
+
+    /// `RowLimits::max_limit`
+    pub fn with_max_row_num(mut self, max_row_num: usize) -> Self {
+        self.max_row_num = max_row_num;
+        self
+    }
+
+    /// the rows beyond `Restrictions::max_row` are not read
+    pub fn with_restrictions(mut self, restrictions: Option<Restrictions>) -> Self {
+        self.restrictions = restrictions;
+        self
+    }
+
+    /// dates are read as serial numbers
+    pub fn with_unformatted_values(mut self, unformatted_values: bool) -> Self {
+        self.unformatted_values = unformatted_values;
+        self
+    }
+
+    /// the formulas like `=SUM(A1:A2)` are read instead of the computed values
+    pub fn with_formulas(mut self, formulas: bool) -> Self {
+        self.formulas = formulas;
+        self
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.row_indices.len() > self.max_row_num {
+            return Err(ValueError::TooManyRowNumber(
+                self.max_row_num,
+                self.row_indices.len(),
+            ));
+        }
+
+        if let (Some(restrictions), Some(last_row_idx)) =
+            (self.restrictions.as_ref(), self.row_indices.iter().max())
+        {
+            if let Err(e) = is_row_range_overflow(last_row_idx + 1, restrictions.max_row) {
+                return Err(ValueError::RowIndexOutOfRescription(e));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// reads the disjoint ranges of a same width at once. the rows are concatenated in the order of `ranges`
+pub struct ReadRangesOption {
+    spread_sheet_id: SpreadSheetId,
+    sheet_name: Option<SheetName>,
+    ranges: Vec<RangeRef>,
+    max_row_num: usize,
+    unformatted_values: bool,
+    formulas: bool,
+    restrictions: Option<Restrictions>,
+}
+
+impl ReadRangesOption {
+    /// the sheet names of `ranges` are ignored. all the ranges are read from `sheet_name`
+    pub fn new(
+        spread_sheet_id: SpreadSheetId,
+        sheet_name: Option<SheetName>,
+        ranges: Vec<RangeRef>,
+    ) -> Self {
+        Self {
+            spread_sheet_id,
+            sheet_name,
+            ranges,
+            max_row_num: MAX_ROW_NUMBER_TO_READ_AT_ONCE,
+            unformatted_values: false,
+            formulas: false,
+            restrictions: None,
+        }
+    }
+
+    /// `RowLimits::max_limit` of the total rows of the ranges
+    pub fn with_max_row_num(mut self, max_row_num: usize) -> Self {
+        self.max_row_num = max_row_num;
+        self
+    }
+
+    /// none of the ranges may end beyond `Restrictions::max_row` or `Restrictions::max_col`
+    pub fn with_restrictions(mut self, restrictions: Option<Restrictions>) -> Self {
+        self.restrictions = restrictions;
+        self
+    }
+
+    /// dates are read as serial numbers
+    pub fn with_unformatted_values(mut self, unformatted_values: bool) -> Self {
+        self.unformatted_values = unformatted_values;
+        self
+    }
+
+    /// the formulas like `=SUM(A1:A2)` are read instead of the computed values
+    pub fn with_formulas(mut self, formulas: bool) -> Self {
+        self.formulas = formulas;
+        self
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if let Some(first) = self.ranges.first() {
+            if let Some(other) = self
+                .ranges
+                .iter()
+                .find(|range| range.col_range_size() != first.col_range_size())
+            {
+                return Err(ValueError::IncompatibleRanges(
+                    first.as_string(),
+                    other.as_string(),
+                ));
+            }
+        }
+
+        let row_num = self.ranges.iter().fold(0usize, |sum, range| {
+            sum.saturating_add(range.row_range_size())
+        });
+        if row_num > self.max_row_num {
+            return Err(ValueError::TooManyRowNumber(self.max_row_num, row_num));
+        }
+
+        if let Some(restrictions) = self.restrictions.as_ref() {
+            for range in self.ranges.iter() {
+                if let Err(e) = is_col_range_overflow(range.next_col_index(), restrictions.max_col)
+                {
+                    return Err(ValueError::ColIndexOutOfRescription(e));
+                }
+                if let Err(e) = is_row_range_overflow(range.next_row_index(), restrictions.max_row)
+                {
+                    return Err(ValueError::RowIndexOutOfRescription(e));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// reads the columns from `start_col_idx` to `end_col_idx` as the records
+/// of `HeaderOrientation::KeyValue`
+pub struct ReadColumnsOption {
+    spread_sheet_id: SpreadSheetId,
+    sheet_name: Option<SheetName>,
+    row_range: (usize, usize),
+    start_col_idx: usize,
+    end_col_idx: usize,
+    max_col_num: usize,
+    unformatted_values: bool,
+    formulas: bool,
+    restrictions: Option<Restrictions>,
+}
+
+impl ReadColumnsOption {
+    pub fn new(
+        spread_sheet_id: SpreadSheetId,
+        sheet_name: Option<SheetName>,
+        row_range: (usize, usize),
+        start_col_idx: usize,
+        end_col_idx: usize,
+    ) -> Self {
+        Self {
+            spread_sheet_id,
+            sheet_name,
+            row_range,
+            start_col_idx,
+            end_col_idx,
+            max_col_num: MAX_ROW_NUMBER_TO_READ_AT_ONCE,
+            unformatted_values: false,
+            formulas: false,
+            restrictions: None,
+        }
+    }
+
+    /// `RowLimits::max_limit`
+    pub fn with_max_col_num(mut self, max_col_num: usize) -> Self {
+        self.max_col_num = max_col_num;
+        self
+    }
+
+    /// the columns beyond `Restrictions::max_col` and the rows beyond `Restrictions::max_row` are not read
+    pub fn with_restrictions(mut self, restrictions: Option<Restrictions>) -> Self {
+        self.restrictions = restrictions;
+        self
+    }
+
+    /// dates are read as serial numbers
+    pub fn with_unformatted_values(mut self, unformatted_values: bool) -> Self {
+        self.unformatted_values = unformatted_values;
+        self
+    }
+
+    /// the formulas like `=SUM(A1:A2)` are read instead of the computed values
+    pub fn with_formulas(mut self, formulas: bool) -> Self {
+        self.formulas = formulas;
+        self
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.end_col_idx < self.start_col_idx {
+            return Err(ValueError::InvalidColRange(
+                self.start_col_idx,
+                self.end_col_idx,
+            ));
+        }
+
+        let col_num = self.end_col_idx - self.start_col_idx + 1;
+        if col_num > self.max_col_num {
+            return Err(ValueError::TooManyColumns(self.max_col_num, col_num));
+        }
+
+        if let Some(restrictions) = self.restrictions.as_ref() {
+            if let Err(e) = is_col_range_overflow(self.end_col_idx + 1, restrictions.max_col) {
+                return Err(ValueError::ColIndexOutOfRescription(e));
+            }
+            if let Err(e) = is_row_range_overflow(self.row_range.1 + 1, restrictions.max_row) {
+                return Err(ValueError::RowIndexOutOfRescription(e));
+            }
+        }
         Ok(())
     }
 }
@@ -115,8 +577,24 @@ impl RowValues {
         self.values.push(row)
     }
 
+    /// drops the rows whose cells are all empty. the rows without any cells are dropped too
+    pub fn drop_empty_rows(&mut self) {
+        self.values
+            .retain(|row| row.iter().any(|cell| !cell.is_empty()))
+    }
+
+    #[tracing::instrument(
+        name = "read_values",
+        skip_all,
+        fields(
+            spread_sheet_id = %option.spread_sheet_id,
+            range = tracing::field::Empty,
+            rows = tracing::field::Empty,
+        )
+    )]
     pub async fn read_values<HttpConnector>(
         client: &ReqClient,
+        base_url: &str,
         token_manager: Arc<TokenManager<HttpConnector>>,
         option: &ReadValueOption,
     ) -> Result<RowValues> {
@@ -126,11 +604,6 @@ impl RowValues {
         if start_col > end_col {
             return Err(ValueError::InvalidColRange(start_col, end_col));
         }
-        #[cfg(feature = "restricted")]
-        if let Err(e) = is_row_range_overflow(end_col) {
-            return Err(ValueError::RowIndexOutOfRescription(e));
-        }
-
         let col_size = end_col - start_col + 1;
 
         let sheet_name = option.sheet_name.clone().map(|v| v.into_inner());
@@ -140,49 +613,420 @@ impl RowValues {
         let end = CellRef::new(end_col, option.end_row_idx);
 
         let value_range = RangeRef::new(sheet_name.clone(), start, end);
+        tracing::Span::current().record("range", &value_range.as_string().as_str());
 
         let sheet_values = get_sheet_value(
             &client,
+            base_url,
             token_manager.clone(),
             &option.spread_sheet_id,
-            &value_range.as_string(),
+            &[value_range.as_string()],
             None,
+            value_render_option(option.unformatted_values, option.formulas),
+            date_time_render_option(option.unformatted_values),
+        )
+        .await
+        .map_err(|e| fetch_value_error(e, sheet_name, &option.spread_sheet_id))?;
+
+        let mut result = RowValues::default();
+        if let Some(first_values) = sheet_values
+            .value_ranges
+            .and_then(|values| values.into_iter().next())
+        {
+            for each_row in aligned_rows(first_values, start_col, col_size) {
+                result.push(padded_or_not(each_row, option.pad));
+            }
+        };
+        tracing::Span::current().record("rows", &result.values.len());
+        Ok(result)
+    }
+
+    /// reads the rows in the order of `ReadRowsOption::row_indices`.
+    /// the rows must be in the grid. an empty row is filled with `""`
+    pub async fn read_rows<HttpConnector>(
+        client: &ReqClient,
+        base_url: &str,
+        token_manager: Arc<TokenManager<HttpConnector>>,
+        option: &ReadRowsOption,
+    ) -> Result<RowValues> {
+        option.validate()?;
+
+        let (start_col, end_col) = option.col_range;
+        if start_col > end_col {
+            return Err(ValueError::InvalidColRange(start_col, end_col));
+        }
+        if option.row_indices.is_empty() {
+            return Ok(RowValues::empty());
+        }
+
+        let col_size = end_col - start_col + 1;
+
+        let sheet_name = option.sheet_name.clone().map(|v| v.into_inner());
+
+        let ranges: Vec<String> = option
+            .row_indices
+            .iter()
+            .map(|row_idx| {
+                RangeRef::new(
+                    sheet_name.clone(),
+                    CellRef::new(start_col, *row_idx),
+                    CellRef::new(end_col, *row_idx),
+                )
+                .as_string()
+            })
+            .collect();
+
+        let sheet_values = get_sheet_value(
+            &client,
+            base_url,
+            token_manager.clone(),
+            &option.spread_sheet_id,
+            &ranges,
             None,
+            value_render_option(option.unformatted_values, option.formulas),
+            date_time_render_option(option.unformatted_values),
+        )
+        .await
+        .map_err(|e| fetch_value_error(e, sheet_name, &option.spread_sheet_id))?;
+
+        Ok(RowValues::new(
+            rows_of_value_ranges(
+                sheet_values.value_ranges.unwrap_or_default(),
+                start_col,
+                col_size,
+            )
+            .into_iter()
+            .map(|each_row| padded_or_not(each_row, option.pad))
+            .collect(),
+        ))
+    }
+
+    /// all the ranges are read in a single batch call
+    pub async fn read_ranges<HttpConnector>(
+        client: &ReqClient,
+        base_url: &str,
+        token_manager: Arc<TokenManager<HttpConnector>>,
+        option: &ReadRangesOption,
+    ) -> Result<RowValues> {
+        option.validate()?;
+        if option.ranges.is_empty() {
+            return Ok(RowValues::empty());
+        }
+
+        let sheet_name = option.sheet_name.clone().map(|v| v.into_inner());
+        let ranges: Vec<RangeRef> = option
+            .ranges
+            .iter()
+            .map(|range| RangeRef::new(sheet_name.clone(), range.start.clone(), range.end.clone()))
+            .collect();
+
+        let sheet_values = get_sheet_value(
+            &client,
+            base_url,
+            token_manager.clone(),
+            &option.spread_sheet_id,
+            &ranges
+                .iter()
+                .map(|range| range.as_string())
+                .collect::<Vec<String>>(),
             None,
+            value_render_option(option.unformatted_values, option.formulas),
+            date_time_render_option(option.unformatted_values),
         )
         .await
-        .map_err(|e| {
-            if e.is_not_found() {
-                ValueError::SpreadSheetNotFound(format!(
-                    "sheet name: [{}] not found on spread sheet: {}",
-                    sheet_name.unwrap_or_default(),
-                    &option.spread_sheet_id,
-                ))
-            } else {
-                ValueError::FetchValueApiError(format!("{}", e))
-            }
-        })?;
+        .map_err(|e| fetch_value_error(e, sheet_name, &option.spread_sheet_id))?;
+
+        Ok(RowValues::new(rows_of_ranges(
+            sheet_values.value_ranges.unwrap_or_default(),
+            &ranges,
+        )))
+    }
+
+    /// each column is returned as a row aligned to `ReadColumnsOption::row_range`
+    pub async fn read_columns<HttpConnector>(
+        client: &ReqClient,
+        base_url: &str,
+        token_manager: Arc<TokenManager<HttpConnector>>,
+        option: &ReadColumnsOption,
+    ) -> Result<RowValues> {
+        option.validate()?;
+
+        let (start_row, end_row) = option.row_range;
+        if start_row > end_row {
+            return Err(ValueError::InvalidRowNumber(start_row, end_row));
+        }
+        let row_size = end_row - start_row + 1;
+
+        let sheet_name = option.sheet_name.clone().map(|v| v.into_inner());
+
+        let value_range = RangeRef::new(
+            sheet_name.clone(),
+            CellRef::new(option.start_col_idx, start_row),
+            CellRef::new(option.end_col_idx, end_row),
+        );
+
+        let sheet_values = get_sheet_value(
+            &client,
+            base_url,
+            token_manager.clone(),
+            &option.spread_sheet_id,
+            &[value_range.as_string()],
+            Some(MajorDimension::Columns),
+            value_render_option(option.unformatted_values, option.formulas),
+            date_time_render_option(option.unformatted_values),
+        )
+        .await
+        .map_err(|e| fetch_value_error(e, sheet_name, &option.spread_sheet_id))?;
 
         let mut result = RowValues::default();
-        if let Some(mut values) = sheet_values.value_ranges {
-            if !values.is_empty() {
-                let first_values = values.remove(0);
-                if let Some(rows) = first_values.values {
-                    for mut each_row in rows {
-                        // fill tailing
-                        if each_row.len() < col_size {
-                            let mut padding = std::iter::repeat("".into())
-                                .take(col_size - each_row.len())
-                                .collect();
-                            each_row.append(&mut padding);
-                        }
-                        result.push(each_row.into_iter().map(CellValue).collect());
-                    }
-                }
+        if let Some(first_values) = sheet_values
+            .value_ranges
+            .and_then(|values| values.into_iter().next())
+        {
+            for each_col in aligned_columns(first_values, option.start_col_idx, start_row, row_size)
+            {
+                result.push(each_col);
             }
         };
         Ok(result)
     }
+
+    /// the number of the records up to the last non-empty cell of the line from `start`.
+    /// the column is scanned downward in the rows orientation, the row rightward in the key-value one.
+    /// `records_in_grid` bounds the scan
+    #[tracing::instrument(
+        name = "count_records_to_last_value",
+        skip_all,
+        fields(spread_sheet_id = %spread_sheet_id, records = tracing::field::Empty)
+    )]
+    pub async fn count_records_to_last_value<HttpConnector>(
+        client: &ReqClient,
+        base_url: &str,
+        token_manager: Arc<TokenManager<HttpConnector>>,
+        spread_sheet_id: &SpreadSheetId,
+        sheet_name: Option<SheetName>,
+        orientation: HeaderOrientation,
+        start: CellRef,
+        records_in_grid: usize,
+    ) -> Result<usize> {
+        if records_in_grid == 0 {
+            return Ok(0);
+        }
+        let sheet_name = sheet_name.map(|v| v.into_inner());
+
+        let (end, major_dimension) = if orientation == HeaderOrientation::KeyValue {
+            (
+                CellRef::new(start.col_index + records_in_grid - 1, start.row_index),
+                MajorDimension::Rows,
+            )
+        } else {
+            (
+                CellRef::new(start.col_index, start.row_index + records_in_grid - 1),
+                MajorDimension::Columns,
+            )
+        };
+        let scan_range = RangeRef::new(sheet_name.clone(), start.clone(), end);
+        log::debug!("scanning the last value :{}", scan_range);
+
+        let sheet_values = get_sheet_value(
+            &client,
+            base_url,
+            token_manager.clone(),
+            spread_sheet_id,
+            &[scan_range.as_string()],
+            Some(major_dimension),
+            None,
+            None,
+        )
+        .await
+        .map_err(|e| fetch_value_error(e, sheet_name, spread_sheet_id))?;
+
+        let records = sheet_values
+            .value_ranges
+            .and_then(|values| values.into_iter().next())
+            .map(|value_range| records_to_last_value(&value_range, orientation, &start))
+            .unwrap_or(0)
+            .min(records_in_grid);
+        tracing::Span::current().record("records", &records);
+        Ok(records)
+    }
+}
+
+/// the values are formatted as they are displayed unless `unformatted_values` or `formulas`.
+/// `formulas` is preferred, the cells without formula are read unformatted then
+fn value_render_option(unformatted_values: bool, formulas: bool) -> Option<ValueRenderOption> {
+    if formulas {
+        Some(ValueRenderOption::Formula)
+    } else if unformatted_values {
+        Some(ValueRenderOption::UnfromattedValue)
+    } else {
+        None
+    }
+}
+
+fn date_time_render_option(unformatted_values: bool) -> Option<DateTimeRenderOption> {
+    if unformatted_values {
+        Some(DateTimeRenderOption::SerialNumber)
+    } else {
+        None
+    }
+}
+
+/// the columns of a value range read with `MajorDimension::Columns`, aligned to the requested rows.
+/// the leading empty columns that the api omits are returned as the columns of `""`
+fn aligned_columns(
+    value_range: ValueRange,
+    start_col: usize,
+    start_row: usize,
+    row_size: usize,
+) -> Vec<Vec<CellValue>> {
+    let (leading_empty_cols, leading_empty_rows) = RangeRef::from_str(&value_range.range)
+        .map(|returned_range| {
+            (
+                returned_range.start.col_index.saturating_sub(start_col),
+                returned_range.start.row_index.saturating_sub(start_row),
+            )
+        })
+        .unwrap_or((0, 0));
+
+    let leading = (0..leading_empty_cols).map(|_| align_row(vec![], 0, row_size));
+    let columns = non_empty_values(value_range)
+        .into_iter()
+        .map(|each_col| align_row(each_col, leading_empty_rows, row_size));
+    leading.chain(columns).collect()
+}
+
+/// the length of the single line read by `count_records_to_last_value`.
+/// the api omits the trailing empty cells, and the leading ones by shifting the returned range
+fn records_to_last_value(
+    value_range: &ValueRange,
+    orientation: HeaderOrientation,
+    start: &CellRef,
+) -> usize {
+    let line_len = match value_range.first_non_empty_row() {
+        None => return 0,
+        Some(line) => line.len(),
+    };
+    let leading_empty_cells = RangeRef::from_str(&value_range.range)
+        .map(|returned_range| {
+            if orientation == HeaderOrientation::KeyValue {
+                returned_range
+                    .start
+                    .col_index
+                    .saturating_sub(start.col_index)
+            } else {
+                returned_range
+                    .start
+                    .row_index
+                    .saturating_sub(start.row_index)
+            }
+        })
+        .unwrap_or(0);
+    leading_empty_cells + line_len
+}
+
+fn fetch_value_error(
+    e: SheetApiError,
+    sheet_name: Option<String>,
+    spread_sheet_id: &SpreadSheetId,
+) -> ValueError {
+    if e.is_not_found() {
+        ValueError::SpreadSheetNotFound(format!(
+            "sheet name: [{}] not found on spread sheet: {}",
+            sheet_name.unwrap_or_default(),
+            spread_sheet_id,
+        ))
+    } else if let Some(google_error) = e.access_error() {
+        ValueError::SheetApiAccessError(google_error.clone())
+    } else {
+        ValueError::FetchValueApiError(format!("{}", e))
+    }
+}
+
+/// the rows of a value range aligned to the requested columns
+fn aligned_rows(value_range: ValueRange, start_col: usize, col_size: usize) -> Vec<Vec<CellValue>> {
+    // the returned range can start right of the requested one
+    // when the leading columns are empty
+    let leading_empty_cols = RangeRef::from_str(&value_range.range)
+        .map(|returned_range| {
+            returned_range
+                .col_range_indices()
+                .0
+                .saturating_sub(start_col)
+        })
+        .unwrap_or(0);
+    non_empty_values(value_range)
+        .into_iter()
+        .map(|each_row| align_row(each_row, leading_empty_cols, col_size))
+        .collect()
+}
+
+/// no rows for all the shapes of an empty range. see `ValueRange::first_non_empty_row`
+fn non_empty_values(value_range: ValueRange) -> Vec<Vec<JsonValue>> {
+    if value_range.first_non_empty_row().is_none() {
+        return vec![];
+    }
+    value_range.values.unwrap_or_default()
+}
+
+/// one row per value range of single row ranges.
+/// the value range of an empty row has no values, which becomes a row of `""`
+fn rows_of_value_ranges(
+    value_ranges: Vec<ValueRange>,
+    start_col: usize,
+    col_size: usize,
+) -> Vec<Vec<CellValue>> {
+    value_ranges
+        .into_iter()
+        .map(|value_range| {
+            aligned_rows(value_range, start_col, col_size)
+                .into_iter()
+                .next()
+                .unwrap_or_else(|| align_row(vec![], 0, col_size))
+        })
+        .collect()
+}
+
+/// the rows of the value ranges concatenated in order. each value range is of the range at the same position
+fn rows_of_ranges(value_ranges: Vec<ValueRange>, ranges: &[RangeRef]) -> Vec<Vec<CellValue>> {
+    value_ranges
+        .into_iter()
+        .zip(ranges.iter())
+        .flat_map(|(value_range, range)| {
+            aligned_rows(value_range, range.start.col_index, range.col_range_size())
+        })
+        .collect()
+}
+
+/// aligns the cells of a row to the requested columns.
+/// the api omits the trailing empty cells of each row, and the empty cells
+/// in the middle may be returned as null, so every row is filled into exactly `col_size` cells
+/// with `""` at the position of the omitted cells.
+fn align_row(row: Vec<JsonValue>, leading_empty_cols: usize, col_size: usize) -> Vec<CellValue> {
+    let leading = std::iter::repeat(JsonValue::from("")).take(leading_empty_cols);
+    let cells = row.into_iter().map(|cell| match cell {
+        JsonValue::Null => JsonValue::from(""),
+        cell => cell,
+    });
+    let trailing = std::iter::repeat(JsonValue::from(""));
+
+    leading
+        .chain(cells)
+        .chain(trailing)
+        .take(col_size)
+        .map(CellValue)
+        .collect()
+}
+
+/// the trailing `""` filled by `align_row` are dropped without `pad`.
+/// the api omits the trailing empty cells, so the row is back at its natural length
+fn padded_or_not(mut row: Vec<CellValue>, pad: bool) -> Vec<CellValue> {
+    if !pad {
+        while row.last().map(|cell| cell.is_empty()).unwrap_or(false) {
+            row.pop();
+        }
+    }
+    row
 }
 
 impl Default for RowValues {
@@ -192,7 +1036,582 @@ impl Default for RowValues {
 }
 
 #[cfg(test)]
-mod test {}
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn value_render_option_test() {
+        assert_eq!(value_render_option(false, false), None);
+        assert_eq!(
+            value_render_option(true, false),
+            Some(ValueRenderOption::UnfromattedValue)
+        );
+        assert_eq!(
+            value_render_option(false, true),
+            Some(ValueRenderOption::Formula)
+        );
+        assert_eq!(
+            value_render_option(true, true),
+            Some(ValueRenderOption::Formula)
+        );
+    }
+
+    #[test]
+    fn row_limits_test() {
+        let limits = RowLimits::new(10, 50);
+        assert_eq!(
+            limits,
+            RowLimits {
+                default_limit: 10,
+                max_limit: 50
+            }
+        );
+
+        // the default can't exceed the max
+        assert_eq!(RowLimits::new(100, 50).default_limit, 50);
+
+        let limits = RowLimits::new(10, MAX_ROW_NUMBER_TO_READ_AT_ONCE + 1);
+        #[cfg(feature = "restricted")]
+        assert_eq!(limits.max_limit, MAX_ROW_NUMBER_TO_READ_AT_ONCE);
+        #[cfg(not(feature = "restricted"))]
+        assert_eq!(limits.max_limit, MAX_ROW_NUMBER_TO_READ_AT_ONCE + 1);
+    }
+
+    #[test]
+    fn read_value_option_restrictions_test() {
+        let option = |end_row_idx, restrictions| {
+            ReadValueOption::new(
+                SpreadSheetId::new("sheet_id".to_string()),
+                None,
+                (0, 9),
+                end_row_idx - 10,
+                end_row_idx,
+            )
+            .with_restrictions(restrictions)
+        };
+        let restrictions = Some(Restrictions {
+            max_col: 130,
+            max_row: 1000,
+        });
+
+        // the row index 999 is the 1000th row
+        assert!(option(999, restrictions).validate().is_ok());
+        assert_eq!(
+            option(1000, restrictions).validate(),
+            Err(ValueError::RowIndexOutOfRescription(1000))
+        );
+
+        // not enforced
+        assert!(option(1000, None).validate().is_ok());
+    }
+
+    #[test]
+    fn read_rows_option_restrictions_test() {
+        let option = |row_indices, restrictions| {
+            ReadRowsOption::new(
+                SpreadSheetId::new("sheet_id".to_string()),
+                None,
+                (0, 9),
+                row_indices,
+            )
+            .with_restrictions(restrictions)
+        };
+        let restrictions = Some(Restrictions {
+            max_col: 130,
+            max_row: 1000,
+        });
+
+        assert!(option(vec![999, 3], restrictions).validate().is_ok());
+        assert_eq!(
+            option(vec![3, 1000], restrictions).validate(),
+            Err(ValueError::RowIndexOutOfRescription(1000))
+        );
+        assert!(option(vec![], restrictions).validate().is_ok());
+
+        // not enforced
+        assert!(option(vec![1000], None).validate().is_ok());
+    }
+
+    #[test]
+    fn read_columns_option_restrictions_test() {
+        let option = |row_range, end_col_idx| {
+            ReadColumnsOption::new(
+                SpreadSheetId::new("sheet_id".to_string()),
+                None,
+                row_range,
+                1,
+                end_col_idx,
+            )
+            .with_restrictions(Some(Restrictions {
+                max_col: 130,
+                max_row: 1000,
+            }))
+        };
+
+        assert!(option((0, 999), 129).validate().is_ok());
+        assert_eq!(
+            option((0, 999), 130).validate(),
+            Err(ValueError::ColIndexOutOfRescription(130))
+        );
+        assert_eq!(
+            option((0, 1000), 129).validate(),
+            Err(ValueError::RowIndexOutOfRescription(1000))
+        );
+
+        // columns 1..=100
+        assert!(option((0, 9), 100).with_max_col_num(100).validate().is_ok());
+        assert_eq!(
+            option((0, 9), 101).with_max_col_num(100).validate(),
+            Err(ValueError::TooManyColumns(100, 101))
+        );
+    }
+
+    #[test]
+    fn read_value_option_validate_test() {
+        let option = |start_row_idx, end_row_idx| {
+            ReadValueOption::new(
+                SpreadSheetId::new("sheet_id".to_string()),
+                None,
+                (0, 9),
+                start_row_idx,
+                end_row_idx,
+            )
+        };
+
+        assert!(option(10, 60).with_max_row_num(50).validate().is_ok());
+        assert_eq!(
+            option(10, 61).with_max_row_num(50).validate(),
+            Err(ValueError::TooManyRowNumber(50, 51))
+        );
+        assert_eq!(
+            option(10, 9).validate(),
+            Err(ValueError::InvalidRowNumber(10, 9))
+        );
+    }
+
+    #[test]
+    fn read_rows_option_validate_test() {
+        let option = |row_indices| {
+            ReadRowsOption::new(
+                SpreadSheetId::new("sheet_id".to_string()),
+                None,
+                (0, 9),
+                row_indices,
+            )
+        };
+
+        assert!(option(vec![3, 7, 12])
+            .with_max_row_num(3)
+            .validate()
+            .is_ok());
+        assert_eq!(
+            option(vec![3, 7, 12]).with_max_row_num(2).validate(),
+            Err(ValueError::TooManyRowNumber(2, 3))
+        );
+    }
+
+    #[test]
+    fn rows_of_value_ranges_test() {
+        let value_range = |range: &str, values: Option<JsonValue>| ValueRange {
+            range: range.to_string(),
+            major_dimension: "ROWS".to_string(),
+            values: values.map(|v| serde_json::from_value(v).unwrap()),
+        };
+
+        // requested B13:D13, B4:D4 and B8:D8 in this order
+        let value_ranges = vec![
+            value_range("Sheet1!B13:D13", Some(json!([["Charlie", "40", "osaka"]]))),
+            // the leading empty column is omitted
+            value_range("Sheet1!C4:D4", Some(json!([["20", "kyoto"]]))),
+            // empty row
+            value_range("Sheet1!B8:D8", None),
+        ];
+
+        let rows = rows_of_value_ranges(value_ranges, 1, 3);
+        assert_eq!(
+            rows,
+            vec![
+                vec![
+                    CellValue(json!("Charlie")),
+                    CellValue(json!("40")),
+                    CellValue(json!("osaka")),
+                ],
+                vec![
+                    CellValue(json!("")),
+                    CellValue(json!("20")),
+                    CellValue(json!("kyoto")),
+                ],
+                vec![
+                    CellValue(json!("")),
+                    CellValue(json!("")),
+                    CellValue(json!("")),
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn drop_empty_rows_test() {
+        let row = |cells: JsonValue| -> Vec<CellValue> {
+            serde_json::from_value::<Vec<JsonValue>>(cells)
+                .unwrap()
+                .into_iter()
+                .map(CellValue::new)
+                .collect()
+        };
+        let mut row_values = RowValues::new(vec![
+            row(json!(["Alice", "20"])),
+            row(json!(["", ""])),
+            row(json!([])),
+            row(json!(["", "30"])),
+            row(json!([null, ""])),
+            row(json!(["Carol", ""])),
+            row(json!(["", ""])),
+        ]);
+        row_values.drop_empty_rows();
+        assert_eq!(
+            row_values.values,
+            vec![
+                row(json!(["Alice", "20"])),
+                row(json!(["", "30"])),
+                row(json!(["Carol", ""])),
+            ]
+        );
+
+        let mut row_values = RowValues::new(vec![row(json!(["", ""]))]);
+        row_values.drop_empty_rows();
+        assert!(row_values.values.is_empty());
+    }
+
+    #[test]
+    fn records_to_last_value_test() {
+        let value_range = |range: &str, major_dimension: &str, values: JsonValue| ValueRange {
+            range: range.to_string(),
+            major_dimension: major_dimension.to_string(),
+            values: Some(serde_json::from_value(values).unwrap()),
+        };
+        let start = CellRef::new(0, 1);
+
+        // an empty cell in the middle of the column is counted
+        let column = value_range("Sheet1!A2:A1000", "COLUMNS", json!([["a", "", "b"]]));
+        assert_eq!(
+            records_to_last_value(&column, HeaderOrientation::Rows, &start),
+            3
+        );
+
+        // the leading empty rows are omitted by the api
+        let column = value_range("Sheet1!A4:A1000", "COLUMNS", json!([["a", "b"]]));
+        assert_eq!(
+            records_to_last_value(&column, HeaderOrientation::Rows, &start),
+            4
+        );
+
+        let empty = value_range("Sheet1!A2:A1000", "COLUMNS", json!([]));
+        assert_eq!(
+            records_to_last_value(&empty, HeaderOrientation::Rows, &start),
+            0
+        );
+
+        // labels at the column A. the records start from the column B
+        let row = value_range("Sheet1!C2:Z2", "ROWS", json!([["x"]]));
+        assert_eq!(
+            records_to_last_value(&row, HeaderOrientation::KeyValue, &CellRef::new(1, 1)),
+            2
+        );
+    }
+
+    #[test]
+    fn rows_of_ranges_test() {
+        let value_range = |range: &str, values: JsonValue| ValueRange {
+            range: range.to_string(),
+            major_dimension: "ROWS".to_string(),
+            values: Some(serde_json::from_value(values).unwrap()),
+        };
+        let ranges = vec![
+            RangeRef::from_str("A2:C3").unwrap(),
+            RangeRef::from_str("E1:G2").unwrap(),
+        ];
+        // two responses merged like the chunks of a batch
+        let mut sheet_values = SheetValues {
+            spreadsheet_id: "abc".to_string(),
+            value_ranges: Some(vec![value_range(
+                "Sheet1!A2:C3",
+                json!([["Alice", "20", "kyoto"], ["Bob", "30"]]),
+            )]),
+        };
+        sheet_values.merge(SheetValues {
+            spreadsheet_id: "abc".to_string(),
+            value_ranges: Some(vec![value_range(
+                "Sheet1!F1:G2",
+                json!([["40", "osaka"], ["50", "nara"]]),
+            )]),
+        });
+
+        let rows = rows_of_ranges(sheet_values.value_ranges.unwrap(), &ranges);
+        let rows: Vec<Vec<&JsonValue>> = rows
+            .iter()
+            .map(|row| row.iter().map(|cell| cell.as_inner()).collect())
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                vec![&json!("Alice"), &json!("20"), &json!("kyoto")],
+                vec![&json!("Bob"), &json!("30"), &json!("")],
+                vec![&json!(""), &json!("40"), &json!("osaka")],
+                vec![&json!(""), &json!("50"), &json!("nara")],
+            ]
+        );
+    }
+
+    #[test]
+    fn read_ranges_option_validate_test() {
+        let option = |ranges: &[&str]| {
+            ReadRangesOption::new(
+                SpreadSheetId::new("abc".to_string()),
+                None,
+                ranges
+                    .iter()
+                    .map(|range| RangeRef::from_str(range).unwrap())
+                    .collect(),
+            )
+        };
+        assert!(option(&["A1:C10", "E1:G10"]).validate().is_ok());
+        assert_eq!(
+            option(&["A1:C10", "E1:H10"]).validate(),
+            Err(ValueError::IncompatibleRanges(
+                "A1:C10".to_string(),
+                "E1:H10".to_string()
+            ))
+        );
+        assert!(option(&["A1:C10", "E1:G10"])
+            .with_max_row_num(15)
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn read_ranges_option_restrictions_test() {
+        let option = |ranges: &[&str]| {
+            ReadRangesOption::new(
+                SpreadSheetId::new("abc".to_string()),
+                None,
+                ranges
+                    .iter()
+                    .map(|range| RangeRef::from_str(range).unwrap())
+                    .collect(),
+            )
+            .with_restrictions(Some(Restrictions {
+                max_col: 130,
+                max_row: 1000,
+            }))
+        };
+
+        assert!(option(&["A1:C10", "E991:G1000"]).validate().is_ok());
+        assert_eq!(
+            option(&["A1:C10", "E992:G1001"]).validate(),
+            Err(ValueError::RowIndexOutOfRescription(1000))
+        );
+        // the column index 130 is `EA`
+        assert_eq!(
+            option(&["A1:C10", "DY1:EA10"]).validate(),
+            Err(ValueError::ColIndexOutOfRescription(130))
+        );
+    }
+
+    #[test]
+    fn aligned_rows_empty_range_test() {
+        let value_range = |values: Option<JsonValue>| ValueRange {
+            range: "Sheet1!B2:D3".to_string(),
+            major_dimension: "ROWS".to_string(),
+            values: values.map(|v| serde_json::from_value(v).unwrap()),
+        };
+
+        // no `values`, `[]` and `[[]]` are all no rows
+        assert!(aligned_rows(value_range(None), 1, 3).is_empty());
+        assert!(aligned_rows(value_range(Some(json!([]))), 1, 3).is_empty());
+        assert!(aligned_rows(value_range(Some(json!([[]]))), 1, 3).is_empty());
+        assert_eq!(
+            aligned_rows(value_range(Some(json!([[], ["a"]]))), 1, 3).len(),
+            2
+        );
+    }
+
+    #[test]
+    fn aligned_columns_test() {
+        // requested B1:E3 by columns. the column B is empty
+        let value_range = ValueRange {
+            range: "Sheet1!C1:E3".to_string(),
+            major_dimension: "COLUMNS".to_string(),
+            values: Some(
+                serde_json::from_value(json!([["Alice", "21"], [], ["Bob", "", "tokyo"]])).unwrap(),
+            ),
+        };
+
+        let columns = aligned_columns(value_range, 1, 0, 3);
+        let empty = || {
+            vec![
+                CellValue(json!("")),
+                CellValue(json!("")),
+                CellValue(json!("")),
+            ]
+        };
+        assert_eq!(
+            columns,
+            vec![
+                empty(),
+                vec![
+                    CellValue(json!("Alice")),
+                    CellValue(json!("21")),
+                    CellValue(json!("")),
+                ],
+                empty(),
+                vec![
+                    CellValue(json!("Bob")),
+                    CellValue(json!("")),
+                    CellValue(json!("tokyo")),
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn align_row_test() {
+        // headers: name | age | memo | city
+        // row 2 has a blank memo (column 3)
+        let rows = vec![
+            vec![json!("Alice"), json!("20"), json!("hello"), json!("kyoto")],
+            vec![json!("Bob"), json!("30"), json!(""), json!("tokyo")],
+            vec![
+                json!("Charlie"),
+                json!("40"),
+                JsonValue::Null,
+                json!("osaka"),
+            ],
+            vec![json!("David")],
+        ];
+        let aligned: Vec<Vec<CellValue>> =
+            rows.into_iter().map(|row| align_row(row, 0, 4)).collect();
+
+        assert_eq!(aligned[1].len(), 4);
+        assert_eq!(aligned[1][2], CellValue(json!("")));
+        assert_eq!(aligned[1][3], CellValue(json!("tokyo")));
+        assert_eq!(aligned[2][2], CellValue(json!("")));
+        assert_eq!(aligned[2][3], CellValue(json!("osaka")));
+        assert_eq!(
+            aligned[3],
+            vec![
+                CellValue(json!("David")),
+                CellValue(json!("")),
+                CellValue(json!("")),
+                CellValue(json!("")),
+            ]
+        );
+    }
+
+    #[test]
+    fn padded_or_not_test() {
+        let aligned = align_row(vec![json!("20"), JsonValue::Null, json!("kyoto")], 1, 6);
+        assert_eq!(aligned.len(), 6);
+
+        let unpadded = padded_or_not(aligned, false);
+        assert_eq!(
+            unpadded,
+            vec![
+                CellValue(json!("")),
+                CellValue(json!("20")),
+                CellValue(json!("")),
+                CellValue(json!("kyoto")),
+            ]
+        );
+
+        let padded = padded_or_not(align_row(vec![json!("a")], 0, 3), true);
+        assert_eq!(padded.len(), 3);
+
+        assert!(padded_or_not(align_row(vec![], 0, 3), false).is_empty());
+    }
+
+    #[test]
+    fn align_row_leading_empty_cols_test() {
+        // requested A:D but returned B:D
+        let aligned = align_row(vec![json!("20"), json!(""), json!("kyoto")], 1, 4);
+        assert_eq!(
+            aligned,
+            vec![
+                CellValue(json!("")),
+                CellValue(json!("20")),
+                CellValue(json!("")),
+                CellValue(json!("kyoto")),
+            ]
+        );
+
+        // cells beyond the requested columns are dropped
+        let aligned = align_row(vec![json!("a"), json!("b"), json!("c")], 0, 2);
+        assert_eq!(aligned, vec![CellValue(json!("a")), CellValue(json!("b"))]);
+    }
+
+    #[test]
+    fn coerced_test() {
+        let option = CoerceOption {
+            numbers: true,
+            big_int_as_string: false,
+        };
+        let coerced = |v: JsonValue| CellValue(v).coerced(&option).into_owned();
+
+        assert_eq!(coerced(json!("21")), json!(21));
+        assert_eq!(coerced(json!("-1.5")), json!(-1.5));
+        assert_eq!(coerced(json!("0.5")), json!(0.5));
+        assert_eq!(coerced(json!("0123")), json!("0123"));
+        assert_eq!(coerced(json!("abc")), json!("abc"));
+        assert_eq!(coerced(json!("")), json!(""));
+
+        let not_coerced = CellValue(json!("21")).coerced(&CoerceOption::default());
+        assert_eq!(not_coerced.into_owned(), json!("21"));
+    }
+
+    #[test]
+    fn coerced_big_int_as_string_test() {
+        let option = CoerceOption {
+            numbers: true,
+            big_int_as_string: true,
+        };
+        let coerced = |v: JsonValue| CellValue(v).coerced(&option).into_owned();
+
+        // below the threshold
+        assert_eq!(
+            coerced(json!("9007199254740991")),
+            json!(9007199254740991i64)
+        );
+        assert_eq!(
+            coerced(json!("-9007199254740991")),
+            json!(-9007199254740991i64)
+        );
+        // above the threshold
+        assert_eq!(
+            coerced(json!("9007199254740992")),
+            json!("9007199254740992")
+        );
+        assert_eq!(
+            coerced(json!("-9007199254740992")),
+            json!("-9007199254740992")
+        );
+        assert_eq!(
+            coerced(json!(9007199254740993i64)),
+            json!("9007199254740993")
+        );
+
+        let option = CoerceOption {
+            numbers: true,
+            big_int_as_string: false,
+        };
+        assert_eq!(
+            CellValue(json!("9007199254740993"))
+                .coerced(&option)
+                .into_owned(),
+            json!(9007199254740993i64)
+        );
+    }
+}
 
 #[cfg(all(test, feature = "test-using-sa"))]
 mod cloud_test {
@@ -224,7 +1643,8 @@ mod cloud_test {
         let option =
             ReadValueOption::new(SpreadSheetId::new(sheet_id.to_string()), None, (0, 9), 1, 2);
 
-        let row_values = RowValues::read_values(&client, token_manager, &option).await;
+        let row_values =
+            RowValues::read_values(&client, DEFAULT_BASE_ENDPOINT_V4, token_manager, &option).await;
         assert!(row_values.is_ok());
 
         let row_values = row_values.unwrap();
@@ -278,7 +1698,8 @@ mod cloud_test {
         let option =
             ReadValueOption::new(SpreadSheetId::new(sheet_id.to_string()), None, (0, 9), 4, 4);
 
-        let row_values = RowValues::read_values(&client, token_manager, &option).await;
+        let row_values =
+            RowValues::read_values(&client, DEFAULT_BASE_ENDPOINT_V4, token_manager, &option).await;
         assert!(row_values.is_ok());
 
         let row_values = row_values.unwrap();
@@ -298,4 +1719,49 @@ mod cloud_test {
 
         assert_eq!(row_values, expected);
     }
+
+    #[tokio::test]
+    async fn read_rows_test() {
+        let (_, rx) = broadcast::channel(1);
+        let token_manager = token_manager_from_service_account_file(
+            scopes::SHEET_READ_ONLY,
+            load_test_sa_file_path(),
+            rx,
+            None,
+        )
+        .await
+        .unwrap();
+        let token_manager = Arc::new(token_manager);
+
+        let client = Client::new();
+        let sheet_id = TEST_SHEET1_ID;
+
+        // returned in the requested order
+        let option = ReadRowsOption::new(
+            SpreadSheetId::new(sheet_id.to_string()),
+            None,
+            (0, 2),
+            vec![4, 1],
+        );
+
+        let row_values =
+            RowValues::read_rows(&client, DEFAULT_BASE_ENDPOINT_V4, token_manager, &option)
+                .await
+                .unwrap();
+
+        let expected = RowValues::new(vec![
+            vec![
+                CellValue("David".into()),
+                CellValue("48".into()),
+                CellValue("male".into()),
+            ],
+            vec![
+                CellValue("Alice".into()),
+                CellValue("21".into()),
+                CellValue("female".into()),
+            ],
+        ]);
+
+        assert_eq!(row_values, expected);
+    }
 }
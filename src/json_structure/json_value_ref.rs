@@ -1,11 +1,13 @@
-use super::Key;
+use super::{unescape_key, Key};
 use serde::Serialize;
 use serde_json::{Map as JMap, Value as JsonValue};
+use std::borrow::Cow;
 use std::collections::HashMap;
 
 pub enum JsonValueRef<'v, 'k: 'v> {
     Object(Vec<(Key<'k>, JsonValueRef<'v, 'k>)>),
     Array(Vec<&'v JsonValue>),
+    Objects(Vec<JsonValueRef<'v, 'k>>),
     Value(&'v JsonValue),
 }
 
@@ -15,7 +17,7 @@ impl JsonValueRef<'_, '_> {
             JsonValueRef::Object(key_values) => {
                 let mut obj = JMap::with_capacity(key_values.len());
                 for (k, v) in key_values.into_iter() {
-                    obj.insert(k.to_string(), v.into_json_value());
+                    obj.insert(unescape_key(k).into_owned(), v.into_json_value());
                 }
                 JsonValue::Object(obj)
             }
@@ -23,6 +25,11 @@ impl JsonValueRef<'_, '_> {
                 let arr_values: Vec<JsonValue> = arr.into_iter().map(|each| each.clone()).collect();
                 JsonValue::Array(arr_values)
             }
+            JsonValueRef::Objects(objs) => JsonValue::Array(
+                objs.into_iter()
+                    .map(|each| each.into_json_value())
+                    .collect(),
+            ),
             JsonValueRef::Value(v) => v.clone(),
         }
     }
@@ -37,10 +44,11 @@ impl<'v, 'k> Serialize for JsonValueRef<'v, 'k> {
         match *self {
             JsonValueRef::Value(ref v) => v.serialize(serializer),
             JsonValueRef::Array(ref vs) => vs.serialize(serializer),
+            JsonValueRef::Objects(ref objs) => objs.serialize(serializer),
             JsonValueRef::Object(ref vec) => {
-                let mut obj: HashMap<&str, &JsonValueRef> = HashMap::with_capacity(vec.len());
+                let mut obj: HashMap<Cow<str>, &JsonValueRef> = HashMap::with_capacity(vec.len());
                 for (k, v) in vec {
-                    obj.insert(k, v);
+                    obj.insert(unescape_key(k), v);
                 }
                 obj.serialize(serializer)
             }
@@ -1,8 +1,12 @@
 mod json_value_ref;
+mod schema;
 
-use json_value_ref::*;
+pub use json_value_ref::JsonValueRef;
+use reqwest::StatusCode;
+pub use schema::{SchemaNode, SchemaProperty};
 use serde_json::Value as JsonValue;
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use thiserror::Error;
 
 type Key<'a> = &'a str;
@@ -24,6 +28,30 @@ pub enum JsonStructureError {
     InvalidStructureState(String),
 }
 
+impl JsonStructureError {
+    /// stable error code for api clients
+    pub fn code(&self) -> &'static str {
+        match self {
+            JsonStructureError::InvalidJsonStructureDef(_) => "INVALID_STRUCTURE",
+            JsonStructureError::InvalidKey(_) => "INVALID_HEADER_KEY",
+            JsonStructureError::ValueOutOfRange(_, _) => "VALUE_OUT_OF_RANGE",
+            JsonStructureError::InvalidStructureState(_) => "INVALID_STRUCTURE",
+        }
+    }
+
+    /// 400 for the headers which can't be built into the json.
+    /// 500 for the broken states while building the json
+    pub fn http_status(&self) -> StatusCode {
+        match self {
+            JsonStructureError::InvalidJsonStructureDef(_) | JsonStructureError::InvalidKey(_) => {
+                StatusCode::BAD_REQUEST
+            }
+            JsonStructureError::ValueOutOfRange(_, _)
+            | JsonStructureError::InvalidStructureState(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
 ///
 /// ["col1","col2","col3"]
 /// =>
@@ -73,10 +101,28 @@ pub enum JsonStructureError {
 ///
 ///```
 ///
+/// ["col1","items[].id","items[].name","items[].id","items[].name"]
+///
+/// =>
+///```ignore
+///  //pseude code
+///  Structure::Object{
+///    values:vec[("col1",Value(0)),
+///               ("items",ArrayOfObjects("items", vec![
+///                 Object{values:vec![("id",Value(1)), ("name",Value(2))]},
+///                 Object{values:vec![("id",Value(3)), ("name",Value(4))]},
+///               ])) ]
+///  }
+///
+///```
+///
+/// a key which already exists in the last object of the array starts the next object.
+///
 #[derive(Debug, PartialEq)]
 pub enum Structure<'a> {
     Object(Object<'a>),
     Array(Key<'a>, Vec<usize>),
+    ArrayOfObjects(Key<'a>, Vec<Object<'a>>),
     Value(Key<'a>, usize),
 }
 
@@ -89,40 +135,232 @@ impl<'a> Structure<'a> {
         Self::Array(k, v)
     }
 
+    pub fn new_arr_of_objs(k: Key<'a>, objs: Vec<Object<'a>>) -> Self {
+        Self::ArrayOfObjects(k, objs)
+    }
+
     pub fn new_value(k: Key<'a>, v: usize) -> Self {
         Self::Value(k, v)
     }
 
+    /// a value beyond `values` is `ValueOutOfRange`
     pub fn build_json<'v>(&'a self, values: &'v [&JsonValue]) -> Result<JsonValueRef<'v, 'a>> {
+        self.build(values, None)
+    }
+
+    /// `missing` decides how the values beyond a short row are built.
+    /// a bare value beyond the row is `ValueOutOfRange` with `MissingValues::Absent`
+    /// since there is no key to drop
+    pub fn build_json_with<'v>(
+        &'a self,
+        values: &'v [&JsonValue],
+        missing: MissingValues,
+    ) -> Result<JsonValueRef<'v, 'a>> {
+        self.build(values, Some(missing))
+    }
+
+    fn build<'v>(
+        &'a self,
+        values: &'v [&JsonValue],
+        missing: Option<MissingValues>,
+    ) -> Result<JsonValueRef<'v, 'a>> {
         match self {
-            Structure::Object(obj) => obj.build_json(values),
+            Structure::Object(obj) => obj.build(values, missing),
             Structure::Array(key, indices) => {
                 let mut result = Vec::<&JsonValue>::new();
 
                 for index in indices {
-                    match values.get(*index) {
-                        None => {
-                            return Err(JsonStructureError::ValueOutOfRange(
-                                key.to_string(),
-                                *index,
-                            ))
-                        }
+                    match value_at(values, *index, missing) {
                         Some(value) => result.push(value),
+                        None => match missing {
+                            None => {
+                                return Err(JsonStructureError::ValueOutOfRange(
+                                    key.to_string(),
+                                    *index,
+                                ))
+                            }
+                            Some(missing) => result.extend(missing.as_null()),
+                        },
                     }
                 }
 
                 Ok(JsonValueRef::Array(result))
             }
-            Structure::Value(key, index) => match values.get(*index) {
-                None => return Err(JsonStructureError::ValueOutOfRange(key.to_string(), *index)),
+            Structure::ArrayOfObjects(_, objs) => {
+                let mut result = Vec::with_capacity(objs.len());
+                for obj in objs.iter().filter(|obj| {
+                    missing != Some(MissingValues::Absent) || obj.has_value_in(values)
+                }) {
+                    result.push(obj.build(values, missing)?);
+                }
+                Ok(JsonValueRef::Objects(result))
+            }
+            Structure::Value(key, index) => match value_at(values, *index, missing) {
                 Some(value) => Ok(JsonValueRef::Value(value)),
+                None => match missing.and_then(|missing| missing.as_null()) {
+                    Some(null) => Ok(JsonValueRef::Value(null)),
+                    None => Err(JsonStructureError::ValueOutOfRange(key.to_string(), *index)),
+                },
             },
         }
     }
+
+    /// false if all the values of the structure are missing, e.g. beyond a row shorter than the headers
+    fn has_value_in(&self, values: &[&JsonValue]) -> bool {
+        match self {
+            Structure::Object(obj) => obj.has_value_in(values),
+            Structure::Array(_, indices) => {
+                indices.iter().any(|index| has_value_at(values, *index))
+            }
+            Structure::ArrayOfObjects(_, objs) => objs.iter().any(|obj| obj.has_value_in(values)),
+            Structure::Value(_, index) => has_value_at(values, *index),
+        }
+    }
+}
+
+/// with `MissingValues::Absent`, a `null` is missing as well as the values beyond `values`.
+/// e.g. the gap cells of a row that is filled up to a column further right
+fn value_at<'v>(
+    values: &'v [&JsonValue],
+    index: usize,
+    missing: Option<MissingValues>,
+) -> Option<&'v JsonValue> {
+    match values.get(index) {
+        Some(value) if value.is_null() && missing == Some(MissingValues::Absent) => None,
+        Some(value) => Some(*value),
+        None => None,
+    }
+}
+
+fn has_value_at(values: &[&JsonValue], index: usize) -> bool {
+    values.get(index).map_or(false, |value| !value.is_null())
+}
+
+/// how the values beyond a row shorter than the headers are built
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum MissingValues {
+    /// the keys are absent from the object and the values from the array
+    #[default]
+    Absent,
+    /// `null` at the position of the values
+    Null,
+}
+
+impl std::str::FromStr for MissingValues {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "absent" => Ok(MissingValues::Absent),
+            "null" => Ok(MissingValues::Null),
+            _ => Err(format!("invalid missing values:{}", s)),
+        }
+    }
+}
+
+static NULL_VALUE: JsonValue = JsonValue::Null;
+
+/// the order of the values of the duplicated keys grouped into an array
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum ArrayOrder {
+    /// the leftmost column first
+    #[default]
+    LeftToRight,
+    /// the rightmost column first
+    RightToLeft,
+}
+
+impl std::str::FromStr for ArrayOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "left_to_right" => Ok(ArrayOrder::LeftToRight),
+            "right_to_left" => Ok(ArrayOrder::RightToLeft),
+            _ => Err(format!("invalid array order:{}", s)),
+        }
+    }
 }
 
+/// how the duplicated keys like `["favorite","favorite"]` are built
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum DupHeaders {
+    /// `{"favorite":["diving","programming"]}`
+    #[default]
+    Array,
+    /// `{"favorite":"diving","favorite_2":"programming"}`
+    Suffix,
+}
+
+impl std::str::FromStr for DupHeaders {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "array" => Ok(DupHeaders::Array),
+            "suffix" => Ok(DupHeaders::Suffix),
+            _ => Err(format!("invalid dup headers:{}", s)),
+        }
+    }
+}
+
+impl MissingValues {
+    fn as_null(&self) -> Option<&'static JsonValue> {
+        match self {
+            MissingValues::Absent => None,
+            MissingValues::Null => Some(&NULL_VALUE),
+        }
+    }
+}
+
+const KEY_DELIMITER: char = '.';
+const ESCAPE_CHAR: char = '\\';
+const ESCAPABLE_CHARS: [char; 4] = [KEY_DELIMITER, '[', ']', ESCAPE_CHAR];
+
+/// splits at the unescaped `.`. the keys are kept escaped.
+/// `user\.name.first` -> `["user\.name", "first"]`
 pub(crate) fn split_keys<'a>(keys: &'a str) -> Vec<Key<'a>> {
-    keys.split(".").map(|e| e.trim()).collect()
+    let mut result = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+    for (idx, c) in keys.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match c {
+            ESCAPE_CHAR => escaped = true,
+            KEY_DELIMITER => {
+                result.push(keys[start..idx].trim());
+                start = idx + KEY_DELIMITER.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    result.push(keys[start..].trim());
+    result
+}
+
+/// `user\.name` -> `user.name`, `tag\[0\]` -> `tag[0]`.
+/// `\` escapes `.`, `[`, `]` and `\` itself. other backslashes are kept as they are
+pub fn unescape_key(key: &str) -> Cow<str> {
+    if !key.contains(ESCAPE_CHAR) {
+        return Cow::Borrowed(key);
+    }
+
+    let mut result = String::with_capacity(key.len());
+    let mut chars = key.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == ESCAPE_CHAR {
+            if let Some(next) = chars.peek().filter(|next| ESCAPABLE_CHARS.contains(*next)) {
+                result.push(*next);
+                chars.next();
+                continue;
+            }
+        }
+        result.push(c);
+    }
+    Cow::Owned(result)
 }
 
 fn key_seq_to_key_str<'a>(keys: &[Key<'a>]) -> String {
@@ -133,6 +371,162 @@ fn key_seq_to_sub_key_str<'a>(keys: &[Key<'a>], end_idx: usize) -> String {
     keys[0..end_idx].join(".")
 }
 
+const ARRAY_OF_OBJECTS_SUFFIX: &str = "[]";
+
+/// `items[]` -> `items`. None for `items\[\]` or `items\[]`
+fn strip_array_suffix(key: &str) -> Option<&str> {
+    let array_key = key.strip_suffix(ARRAY_OF_OBJECTS_SUFFIX)?;
+    let trailing_escapes = array_key
+        .chars()
+        .rev()
+        .take_while(|c| *c == ESCAPE_CHAR)
+        .count();
+    if trailing_escapes % 2 == 0 {
+        Some(array_key)
+    } else {
+        None
+    }
+}
+
+/// renames the duplicated keys with the suffixes instead of merging them into an array.
+/// `["favorite","favorite","favorite"]` -> `["favorite","favorite_2","favorite_3"]`.
+/// a suffix already used by another key is skipped, and the keys in arrays of objects like `items[].id`
+/// are left as they are since the duplicates start the next object
+pub fn suffix_duplicated_keys<'a>(strs: &[&'a str]) -> Vec<Cow<'a, str>> {
+    let mut used: HashSet<Cow<'a, str>> = strs.iter().map(|each| Cow::Borrowed(*each)).collect();
+    let mut seen: HashSet<&'a str> = HashSet::new();
+    strs.iter()
+        .map(|each| {
+            let in_array_of_objects = split_keys(each)
+                .iter()
+                .any(|key| strip_array_suffix(key).is_some());
+            if in_array_of_objects || seen.insert(*each) {
+                return Cow::Borrowed(*each);
+            }
+
+            let suffixed = (2..)
+                .map(|n| format!("{}_{}", each, n))
+                .find(|suffixed| !used.contains(suffixed.as_str()))
+                .unwrap();
+            used.insert(Cow::Owned(suffixed.clone()));
+            Cow::Owned(suffixed)
+        })
+        .collect()
+}
+
+/// keeps only the dot `paths` of the built json like `["name","address.city"]`.
+/// a path through an array of objects is applied to each object of the array,
+/// keeping the positions of the elements. an element without the path is `{}`, or `null` if it's not an object.
+/// the paths which don't exist in `value` are absent from the result
+pub fn project(value: &JsonValue, paths: &[String]) -> JsonValue {
+    let mut result: Option<JsonValue> = None;
+    for path in paths {
+        let keys: Vec<Cow<str>> = split_keys(path).into_iter().map(unescape_key).collect();
+        if let Some(projected) = project_keys(value, &keys) {
+            result = Some(match result {
+                None => projected,
+                Some(result) => merge_projected(result, projected),
+            });
+        }
+    }
+    match (result, value) {
+        (Some(result), _) => result,
+        (None, JsonValue::Array(_)) => JsonValue::Array(vec![]),
+        (None, _) => JsonValue::Object(serde_json::Map::new()),
+    }
+}
+
+fn project_keys(value: &JsonValue, keys: &[Cow<str>]) -> Option<JsonValue> {
+    let (current_key, rest) = match keys.split_first() {
+        None => return Some(value.clone()),
+        Some(split) => split,
+    };
+    match value {
+        JsonValue::Object(obj) => {
+            let projected = project_keys(obj.get(current_key.as_ref())?, rest)?;
+            let mut result = serde_json::Map::new();
+            result.insert(current_key.to_string(), projected);
+            Some(JsonValue::Object(result))
+        }
+        JsonValue::Array(values) => {
+            let projected: Vec<Option<JsonValue>> = values
+                .iter()
+                .map(|value| project_keys(value, keys))
+                .collect();
+            if projected.iter().all(|value| value.is_none()) {
+                return None;
+            }
+            let projected = projected
+                .into_iter()
+                .zip(values)
+                .map(|(projected, value)| match (projected, value) {
+                    (Some(projected), _) => projected,
+                    (None, JsonValue::Object(_)) => JsonValue::Object(serde_json::Map::new()),
+                    (None, _) => JsonValue::Null,
+                })
+                .collect();
+            Some(JsonValue::Array(projected))
+        }
+        _ => None,
+    }
+}
+
+/// the objects are merged by the keys, and the arrays by the positions
+fn merge_projected(base: JsonValue, other: JsonValue) -> JsonValue {
+    match (base, other) {
+        (JsonValue::Object(mut base), JsonValue::Object(other)) => {
+            for (key, value) in other {
+                let merged = match base.remove(&key) {
+                    None => value,
+                    Some(existing) => merge_projected(existing, value),
+                };
+                base.insert(key, merged);
+            }
+            JsonValue::Object(base)
+        }
+        (JsonValue::Array(base), JsonValue::Array(other)) => {
+            let mut other = other.into_iter();
+            let mut merged: Vec<JsonValue> = base
+                .into_iter()
+                .map(|base| match other.next() {
+                    None => base,
+                    Some(other) => merge_projected(base, other),
+                })
+                .collect();
+            merged.extend(other);
+            JsonValue::Array(merged)
+        }
+        (_, other) => other,
+    }
+}
+
+/// key of the value demoted by the lenient mode.
+/// headers `["user","user.name"]` -> `{"user":{"_value":..,"name":..}}`
+pub const LENIENT_VALUE_KEY: &str = "_value";
+
+/// `user` -> `user._value` to make room for `user.name`. objects are left as they are
+fn demote_into_object<'a>(existing: &mut Structure<'a>) {
+    let demoted = match existing {
+        Structure::Value(_, idx) => Structure::new_value(LENIENT_VALUE_KEY, *idx),
+        Structure::Array(_, indices) => {
+            Structure::new_arr(LENIENT_VALUE_KEY, std::mem::take(indices))
+        }
+        Structure::Object(_) | Structure::ArrayOfObjects(_, _) => return,
+    };
+    let mut obj = Object::new();
+    obj.inner_add(LENIENT_VALUE_KEY, demoted);
+    *existing = Structure::Object(obj);
+}
+
+/// a key skipped by `Object::from_strs_skipping_errors`
+#[derive(Debug, PartialEq)]
+pub struct SkippedKey {
+    pub key: String,
+    /// index of the key in the passed strs
+    pub idx: usize,
+    pub error: JsonStructureError,
+}
+
 #[derive(Debug, PartialEq)]
 pub struct Object<'a> {
     pub keys: Vec<Key<'a>>,
@@ -147,14 +541,71 @@ impl<'a> Object<'a> {
         }
     }
 
-    pub fn from_strs(strs: &'a [&str]) -> Result<Object<'a>> {
+    /// `lenient`: a key which is both a value and an object like `user` and `user.name`
+    /// doesn't fail. the value is moved into `user._value` instead
+    pub fn from_strs(strs: &[&'a str], lenient: bool) -> Result<Object<'a>> {
         let mut obj = Self::new();
         for (idx, each) in strs.iter().enumerate() {
-            obj.add_value(each, idx)?;
+            obj.add_value(*each, idx, lenient)?;
         }
         Ok(obj)
     }
 
+    /// like `from_strs` but the keys which fail are skipped instead of failing the whole object.
+    /// e.g. `["user","user.name","age"]` -> `{"user":..,"age":..}` and `user.name` is skipped
+    pub fn from_strs_skipping_errors(
+        strs: &[&'a str],
+        lenient: bool,
+    ) -> (Object<'a>, Vec<SkippedKey>) {
+        let mut obj = Self::new();
+        let mut skipped: Vec<SkippedKey> = Vec::new();
+        for (idx, each) in strs.iter().enumerate() {
+            if let Err(error) = obj.add_value(*each, idx, lenient) {
+                skipped.push(SkippedKey {
+                    key: each.to_string(),
+                    idx,
+                    error,
+                });
+
+                // the failed key might be added halfway. rebuilds with the keys added so far
+                obj = Self::new();
+                for (added_idx, added) in strs.iter().enumerate().take(idx) {
+                    if skipped
+                        .iter()
+                        .all(|skipped_key| skipped_key.idx != added_idx)
+                    {
+                        obj.add_value(*added, added_idx, lenient)
+                            .expect("the key added once must be added again");
+                    }
+                }
+            }
+        }
+        (obj, skipped)
+    }
+
+    /// the indices of the arrays of the duplicated keys are pushed from left to right.
+    /// `ArrayOrder::RightToLeft` reverses them in the nested objects too.
+    /// the objects of the arrays of objects are kept in order
+    pub fn with_array_order(mut self, order: ArrayOrder) -> Self {
+        if order == ArrayOrder::RightToLeft {
+            self.reverse_arrays();
+        }
+        self
+    }
+
+    fn reverse_arrays(&mut self) {
+        for structure in self.values.values_mut() {
+            match structure {
+                Structure::Array(_, indices) => indices.reverse(),
+                Structure::Object(obj) => obj.reverse_arrays(),
+                Structure::ArrayOfObjects(_, objs) => {
+                    objs.iter_mut().for_each(|obj| obj.reverse_arrays())
+                }
+                Structure::Value(_, _) => {}
+            }
+        }
+    }
+
     pub fn contains_key(&self, key: Key<'a>) -> bool {
         self.values.contains_key(key)
     }
@@ -163,7 +614,7 @@ impl<'a> Object<'a> {
         self.values.get_mut(key)
     }
 
-    fn add_value(&mut self, key: &'a str, idx: usize) -> Result<()> {
+    fn add_value(&mut self, key: &'a str, idx: usize, lenient: bool) -> Result<()> {
         let key_seq = split_keys(key);
 
         if key_seq.is_empty() {
@@ -179,7 +630,7 @@ impl<'a> Object<'a> {
             )));
         }
 
-        self.add_value_with_key_seq(0, key_seq.as_slice(), idx)
+        self.add_value_with_key_seq(0, key_seq.as_slice(), idx, lenient)
     }
 
     /// about key_seq: key_strings "obj1.key1.key2" turns into vec!["obj1","key1","key2"]
@@ -189,6 +640,7 @@ impl<'a> Object<'a> {
         key_idx: usize,
         key_seq: &[Key<'a>],
         idx: usize,
+        lenient: bool,
     ) -> Result<()> {
         if key_seq.len() <= key_idx {
             panic!(
@@ -202,6 +654,22 @@ impl<'a> Object<'a> {
         let current_key = unsafe { key_seq.get_unchecked(key_idx) };
         let is_last_key_of_seq = (key_seq.len() - 1) == key_idx;
 
+        if let Some(array_key) = strip_array_suffix(current_key) {
+            if array_key.is_empty() {
+                return Err(JsonStructureError::InvalidKey(format!(
+                    "invalid key:{}",
+                    key_seq_to_key_str(key_seq)
+                )));
+            }
+            if is_last_key_of_seq {
+                return Err(JsonStructureError::InvalidJsonStructureDef(format!(
+                    "key:`{}` needs the keys of the objects after `[]`",
+                    key_seq_to_key_str(key_seq)
+                )));
+            }
+            return self.add_value_to_array_of_objects(array_key, key_idx, key_seq, idx, lenient);
+        }
+
         if is_last_key_of_seq {
             let overwrite = match self.get_mut(current_key) {
                 None => {
@@ -209,12 +677,22 @@ impl<'a> Object<'a> {
                     None
                 }
                 Some(existing) => match existing {
+                    Structure::Object(obj) if lenient => {
+                        obj.add_value_with_key_seq(0, &[LENIENT_VALUE_KEY], idx, lenient)?;
+                        None
+                    }
                     Structure::Object(_) => {
                         return Err(JsonStructureError::InvalidJsonStructureDef(format!(
                             "key:`{}` is a value but also a object",
                             key_seq_to_key_str(key_seq)
                         )))
                     }
+                    Structure::ArrayOfObjects(_, _) => {
+                        return Err(JsonStructureError::InvalidJsonStructureDef(format!(
+                            "key:`{}` is a value but also an array of objects",
+                            key_seq_to_key_str(key_seq)
+                        )))
+                    }
                     Structure::Array(_, arr) => {
                         arr.push(idx);
                         None
@@ -232,31 +710,102 @@ impl<'a> Object<'a> {
             match self.get_mut(current_key) {
                 None => {
                     let mut new_obj = Object::new();
-                    new_obj.add_value_with_key_seq(key_idx + 1, key_seq, idx)?;
+                    new_obj.add_value_with_key_seq(key_idx + 1, key_seq, idx, lenient)?;
                     self.inner_add(current_key, Structure::Object(new_obj))
                 }
-                Some(existing) => match existing {
-                    Structure::Object(obj) => {
-                        obj.add_value_with_key_seq(key_idx + 1, key_seq, idx)?
-                    }
-                    Structure::Array(_, _arr) => {
-                        return Err(JsonStructureError::InvalidJsonStructureDef(format!(
-                            "key:`{}` supposed to be a object but array",
-                            key_seq_to_sub_key_str(key_seq, key_idx)
-                        )))
+                Some(existing) => {
+                    if lenient {
+                        demote_into_object(existing);
                     }
-                    Structure::Value(_, _idx) => {
-                        return Err(JsonStructureError::InvalidJsonStructureDef(format!(
-                            "key:`{}` supposed to be a object but value",
-                            key_seq_to_sub_key_str(key_seq, key_idx)
-                        )))
+                    match existing {
+                        Structure::Object(obj) => {
+                            obj.add_value_with_key_seq(key_idx + 1, key_seq, idx, lenient)?
+                        }
+                        Structure::Array(_, _arr) => {
+                            return Err(JsonStructureError::InvalidJsonStructureDef(format!(
+                                "key:`{}` supposed to be a object but array",
+                                key_seq_to_sub_key_str(key_seq, key_idx)
+                            )))
+                        }
+                        Structure::ArrayOfObjects(_, _objs) => {
+                            return Err(JsonStructureError::InvalidJsonStructureDef(format!(
+                                "key:`{}` supposed to be a object but array of objects",
+                                key_seq_to_sub_key_str(key_seq, key_idx)
+                            )))
+                        }
+                        Structure::Value(_, _idx) => {
+                            return Err(JsonStructureError::InvalidJsonStructureDef(format!(
+                                "key:`{}` supposed to be a object but value",
+                                key_seq_to_sub_key_str(key_seq, key_idx)
+                            )))
+                        }
                     }
-                },
+                }
             }
         }
         Ok(())
     }
 
+    /// `items[].id` adds `id` to the last object of `items`,
+    /// or to a new object if the last one already has `id`
+    fn add_value_to_array_of_objects(
+        &mut self,
+        array_key: Key<'a>,
+        key_idx: usize,
+        key_seq: &[Key<'a>],
+        idx: usize,
+        lenient: bool,
+    ) -> Result<()> {
+        let obj_key_seq = &key_seq[key_idx + 1..];
+        match self.get_mut(array_key) {
+            None => {
+                let mut new_obj = Object::new();
+                new_obj.add_value_with_key_seq(key_idx + 1, key_seq, idx, lenient)?;
+                self.inner_add(
+                    array_key,
+                    Structure::new_arr_of_objs(array_key, vec![new_obj]),
+                )
+            }
+            Some(Structure::ArrayOfObjects(_, objs)) => {
+                let starts_new_obj = objs
+                    .last()
+                    .map(|last_obj| last_obj.has_value_at(obj_key_seq))
+                    .unwrap_or(true);
+                if starts_new_obj {
+                    objs.push(Object::new());
+                }
+                // objs never be empty here
+                if let Some(last_obj) = objs.last_mut() {
+                    last_obj.add_value_with_key_seq(key_idx + 1, key_seq, idx, lenient)?
+                }
+            }
+            Some(_) => {
+                return Err(JsonStructureError::InvalidJsonStructureDef(format!(
+                    "key:`{}` supposed to be an array of objects",
+                    key_seq_to_sub_key_str(key_seq, key_idx + 1)
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    /// true if the key seq already points a value or an array of values.
+    /// arrays of objects nested in the object are not looked into,
+    /// since their objects are grouped by themselves
+    fn has_value_at(&self, key_seq: &[Key<'a>]) -> bool {
+        let (current_key, rest) = match key_seq.split_first() {
+            None => return false,
+            Some(split) => split,
+        };
+        let current_key = strip_array_suffix(current_key).unwrap_or(*current_key);
+
+        match self.values.get(current_key) {
+            Some(Structure::Value(_, _)) | Some(Structure::Array(_, _)) => rest.is_empty(),
+            Some(Structure::Object(obj)) => obj.has_value_at(rest),
+            Some(Structure::ArrayOfObjects(_, _)) | None => false,
+        }
+    }
+
     fn inner_add(&mut self, key: Key<'a>, v: Structure<'a>) {
         if self.contains_key(key) {
             self.values.insert(key, v); //override
@@ -266,7 +815,33 @@ impl<'a> Object<'a> {
         }
     }
 
+    fn has_value_in(&self, values: &[&JsonValue]) -> bool {
+        self.values
+            .values()
+            .any(|structure| structure.has_value_in(values))
+    }
+
+    /// a value beyond `values` is `ValueOutOfRange`
     pub fn build_json<'v>(&'a self, values: &'v [&JsonValue]) -> Result<JsonValueRef<'v, 'a>> {
+        self.build(values, None)
+    }
+
+    /// `build_json` with `missing` for the values beyond a short row.
+    /// with `MissingValues::Absent` the keys whose values are beyond `values` are absent from the object,
+    /// so a row shorter than the headers is built without its missing trailing columns
+    pub fn build_json_with<'v>(
+        &'a self,
+        values: &'v [&JsonValue],
+        missing: MissingValues,
+    ) -> Result<JsonValueRef<'v, 'a>> {
+        self.build(values, Some(missing))
+    }
+
+    fn build<'v>(
+        &'a self,
+        values: &'v [&JsonValue],
+        missing: Option<MissingValues>,
+    ) -> Result<JsonValueRef<'v, 'a>> {
         let mut value_map = Vec::with_capacity(self.values.len());
         for each_key in &self.keys {
             match self.values.get(each_key) {
@@ -276,8 +851,13 @@ impl<'a> Object<'a> {
                         each_key
                     )))
                 }
+                Some(index)
+                    if missing == Some(MissingValues::Absent) && !index.has_value_in(values) =>
+                {
+                    continue
+                }
                 Some(index) => {
-                    let json_value = index.build_json(values)?;
+                    let json_value = index.build(values, missing)?;
                     value_map.push((*each_key, json_value));
                 }
             }
@@ -298,7 +878,7 @@ mod test {
     #[test]
     fn test_structure_1() {
         let mut obj = Object::new();
-        let add_result = obj.add_value("hello", 0);
+        let add_result = obj.add_value("hello", 0, false);
         assert!(add_result.is_ok());
 
         let mut expected_value = HashMap::new();
@@ -314,13 +894,13 @@ mod test {
     #[test]
     fn test_structure_2() {
         let mut obj = Object::new();
-        let add_result = obj.add_value("hello", 0);
+        let add_result = obj.add_value("hello", 0, false);
         assert!(add_result.is_ok());
 
-        let add_result = obj.add_value("hello2", 1);
+        let add_result = obj.add_value("hello2", 1, false);
         assert!(add_result.is_ok());
 
-        let add_result = obj.add_value("hello", 2);
+        let add_result = obj.add_value("hello", 2, false);
         assert!(add_result.is_ok());
 
         let mut expected_value = HashMap::new();
@@ -337,16 +917,16 @@ mod test {
     #[test]
     fn test_structure_3() {
         let mut obj = Object::new();
-        let add_result = obj.add_value("hello.some", 0);
+        let add_result = obj.add_value("hello.some", 0, false);
         assert!(add_result.is_ok());
 
-        let add_result = obj.add_value("hello.some2", 1);
+        let add_result = obj.add_value("hello.some2", 1, false);
         assert!(add_result.is_ok());
 
-        let add_result = obj.add_value("hello.some", 2);
+        let add_result = obj.add_value("hello.some", 2, false);
         assert!(add_result.is_ok());
 
-        let add_result = obj.add_value("hi", 3);
+        let add_result = obj.add_value("hi", 3, false);
         assert!(add_result.is_ok());
 
         let mut expected_map = HashMap::new();
@@ -371,16 +951,16 @@ mod test {
     #[test]
     fn test_build_json_1() {
         let mut obj = Object::new();
-        let add_result = obj.add_value("hello.some", 0);
+        let add_result = obj.add_value("hello.some", 0, false);
         assert!(add_result.is_ok());
 
-        let add_result = obj.add_value("hello.some2", 1);
+        let add_result = obj.add_value("hello.some2", 1, false);
         assert!(add_result.is_ok());
 
-        let add_result = obj.add_value("hello.some", 2);
+        let add_result = obj.add_value("hello.some", 2, false);
         assert!(add_result.is_ok());
 
-        let add_result = obj.add_value("hi", 3);
+        let add_result = obj.add_value("hi", 3, false);
         assert!(add_result.is_ok());
 
         let obj = Structure::new_obj(obj);
@@ -407,4 +987,603 @@ mod test {
 
         assert_eq!(build_result, expected);
     }
+
+    #[test]
+    fn test_structure_array_of_objects() {
+        let headers = vec![
+            "name",
+            "orders[].id",
+            "orders[].total",
+            "orders[].id",
+            "orders[].total",
+        ];
+        let obj = Object::from_strs(&headers, false).unwrap();
+
+        let order = |id_idx: usize, total_idx: usize| {
+            let mut values = HashMap::new();
+            values.insert("id", Structure::new_value("id", id_idx));
+            values.insert("total", Structure::new_value("total", total_idx));
+            Object {
+                keys: vec!["id", "total"],
+                values,
+            }
+        };
+
+        let mut expected_value = HashMap::new();
+        expected_value.insert("name", Structure::new_value("name", 0));
+        expected_value.insert(
+            "orders",
+            Structure::new_arr_of_objs("orders", vec![order(1, 2), order(3, 4)]),
+        );
+        let expected = Object {
+            keys: vec!["name", "orders"],
+            values: expected_value,
+        };
+
+        assert_eq!(obj, expected);
+    }
+
+    #[test]
+    fn test_structure_array_of_objects_invalid() {
+        for headers in [
+            vec!["orders", "orders[].id"],
+            vec!["orders[].id", "orders"],
+            vec!["orders.id", "orders[].id"],
+            vec!["orders[].id", "orders.id"],
+            vec!["orders[]"],
+        ] {
+            assert!(
+                matches!(
+                    Object::from_strs(&headers, false),
+                    Err(JsonStructureError::InvalidJsonStructureDef(_))
+                ),
+                "{:?}",
+                headers
+            );
+        }
+
+        assert!(matches!(
+            Object::from_strs(&["[].id"], false),
+            Err(JsonStructureError::InvalidKey(_))
+        ));
+    }
+
+    #[test]
+    fn test_build_json_array_of_objects() {
+        let headers = vec![
+            "name",
+            "orders[].id",
+            "orders[].total",
+            "orders[].id",
+            "orders[].total",
+        ];
+        let obj = Structure::new_obj(Object::from_strs(&headers, false).unwrap());
+
+        let values: Vec<JsonValue> = vec![
+            "alice".into(),
+            "o1".into(),
+            100.into(),
+            "o2".into(),
+            200.into(),
+        ];
+        let values: Vec<&JsonValue> = values.iter().collect();
+        let build_result = obj.build_json(&values).unwrap().into_json_value();
+
+        let expected = json!({
+            "name":"alice",
+            "orders":[
+                {"id":"o1","total":100},
+                {"id":"o2","total":200},
+            ],
+        });
+        assert_eq!(build_result, expected);
+    }
+
+    #[test]
+    fn test_project() {
+        let value = json!({
+            "name": "alice",
+            "address": {"city": "kyoto", "zip": "600"},
+            "orders": [
+                {"id": "o1", "total": 100},
+                {"id": "o2", "total": 200},
+            ],
+            "tags": ["a", "b"],
+        });
+        let paths =
+            |paths: &[&str]| -> Vec<String> { paths.iter().map(|p| p.to_string()).collect() };
+
+        assert_eq!(
+            project(&value, &paths(&["name", "address.city"])),
+            json!({"name": "alice", "address": {"city": "kyoto"}})
+        );
+        assert_eq!(
+            project(&value, &paths(&["orders.id", "tags"])),
+            json!({"orders": [{"id": "o1"}, {"id": "o2"}], "tags": ["a", "b"]})
+        );
+        // merged into the same object
+        assert_eq!(
+            project(&value, &paths(&["address.zip", "address.city"])),
+            json!({"address": {"zip": "600", "city": "kyoto"}})
+        );
+        // missing paths are absent
+        assert_eq!(
+            project(
+                &value,
+                &paths(&["name", "address.country", "nothing", "name.first"])
+            ),
+            json!({"name": "alice"})
+        );
+        assert_eq!(project(&value, &paths(&["nothing"])), json!({}));
+        // the elements without the path keep their positions
+        let value = json!({
+            "orders": [
+                {"id": "o1", "total": 100},
+                {"id": "o2"},
+                "o3",
+            ],
+        });
+        assert_eq!(
+            project(&value, &paths(&["orders.id", "orders.total"])),
+            json!({"orders": [{"id": "o1", "total": 100}, {"id": "o2"}, null]})
+        );
+        assert_eq!(
+            project(&value, &paths(&["orders.total"])),
+            json!({"orders": [{"total": 100}, {}, null]})
+        );
+        // escaped dots
+        assert_eq!(
+            project(&json!({"a.b": 1, "a": {"b": 2}}), &paths(&["a\\.b"])),
+            json!({"a.b": 1})
+        );
+    }
+
+    #[test]
+    fn test_build_json_array_order() {
+        let headers = vec![
+            "name",
+            "phone",
+            "phone",
+            "address.zip",
+            "phone",
+            "address.zip",
+        ];
+        let values: Vec<JsonValue> = vec![
+            "alice".into(),
+            "111".into(),
+            "222".into(),
+            "100".into(),
+            "333".into(),
+            "200".into(),
+        ];
+        let values: Vec<&JsonValue> = values.iter().collect();
+
+        let obj = Object::from_strs(&headers, false)
+            .unwrap()
+            .with_array_order(ArrayOrder::LeftToRight);
+        assert_eq!(
+            obj.build_json(&values).unwrap().into_json_value(),
+            json!({
+                "name": "alice",
+                "phone": ["111", "222", "333"],
+                "address": {"zip": ["100", "200"]},
+            })
+        );
+
+        let obj = Object::from_strs(&headers, false)
+            .unwrap()
+            .with_array_order(ArrayOrder::RightToLeft);
+        assert_eq!(
+            obj.build_json(&values).unwrap().into_json_value(),
+            json!({
+                "name": "alice",
+                "phone": ["333", "222", "111"],
+                "address": {"zip": ["200", "100"]},
+            })
+        );
+
+        // the missing values of a short row are dropped in either order
+        assert_eq!(
+            obj.build_json_with(&values[..3], MissingValues::Absent)
+                .unwrap()
+                .into_json_value(),
+            json!({"name": "alice", "phone": ["222", "111"]})
+        );
+    }
+
+    #[test]
+    fn test_build_json_nested_array_of_objects() {
+        let headers = vec![
+            "orders[].id",
+            "orders[].items[].sku",
+            "orders[].items[].sku",
+            "orders[].id",
+            "orders[].items[].sku",
+            "orders[].shipping.city",
+        ];
+        let obj = Structure::new_obj(Object::from_strs(&headers, false).unwrap());
+
+        let values: Vec<JsonValue> = vec![
+            "o1".into(),
+            "s1".into(),
+            "s2".into(),
+            "o2".into(),
+            "s3".into(),
+            "kyoto".into(),
+        ];
+        let values: Vec<&JsonValue> = values.iter().collect();
+        let build_result = obj.build_json(&values).unwrap().into_json_value();
+
+        let expected = json!({
+            "orders":[
+                {"id":"o1","items":[{"sku":"s1"},{"sku":"s2"}]},
+                {"id":"o2","items":[{"sku":"s3"}],"shipping":{"city":"kyoto"}},
+            ],
+        });
+        assert_eq!(build_result, expected);
+    }
+
+    #[test]
+    fn test_structure_strict_key_collision() {
+        for headers in [vec!["user", "user.name"], vec!["user.name", "user"]] {
+            assert!(matches!(
+                Object::from_strs(&headers, false),
+                Err(JsonStructureError::InvalidJsonStructureDef(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_build_json_skipping_errors() {
+        let headers = vec!["user", "user.name", "age", "user.address.city"];
+        let (obj, skipped) = Object::from_strs_skipping_errors(&headers, false);
+        assert_eq!(
+            skipped
+                .iter()
+                .map(|s| (s.key.as_str(), s.idx))
+                .collect::<Vec<_>>(),
+            vec![("user.name", 1), ("user.address.city", 3)]
+        );
+        assert_eq!(
+            skipped[0].error,
+            JsonStructureError::InvalidJsonStructureDef(
+                "key:`user` supposed to be a object but value".to_string()
+            )
+        );
+
+        let obj = Structure::new_obj(obj);
+        let values: Vec<JsonValue> = vec!["u1".into(), "alice".into(), 20.into(), "kyoto".into()];
+        let values: Vec<&JsonValue> = values.iter().collect();
+        let build_result = obj.build_json(&values).unwrap().into_json_value();
+        assert_eq!(build_result, json!({"user":"u1", "age":20}));
+
+        // a key failed halfway doesn't leave an empty object behind
+        let headers = vec!["items[].a", "items[].a[]", "items[].b"];
+        let (obj, skipped) = Object::from_strs_skipping_errors(&headers, false);
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].key, "items[].a[]");
+
+        let obj = Structure::new_obj(obj);
+        let values: Vec<JsonValue> = vec![1.into(), "x".into(), 2.into()];
+        let values: Vec<&JsonValue> = values.iter().collect();
+        let build_result = obj.build_json(&values).unwrap().into_json_value();
+        assert_eq!(build_result, json!({"items":[{"a":1, "b":2}]}));
+
+        // nothing is skipped
+        let headers = vec!["user", "user.name"];
+        let (obj, skipped) = Object::from_strs_skipping_errors(&headers, true);
+        assert!(skipped.is_empty());
+        assert_eq!(obj, Object::from_strs(&headers, true).unwrap());
+    }
+
+    #[test]
+    fn test_build_json_lenient_value_then_object() {
+        let headers = vec!["user", "user.name", "age"];
+        let obj = Structure::new_obj(Object::from_strs(&headers, true).unwrap());
+
+        let values: Vec<JsonValue> = vec!["u1".into(), "alice".into(), 20.into()];
+        let values: Vec<&JsonValue> = values.iter().collect();
+        let build_result = obj.build_json(&values).unwrap().into_json_value();
+
+        let expected = json!({
+            "user":{"_value":"u1","name":"alice"},
+            "age":20,
+        });
+        assert_eq!(build_result, expected);
+
+        // duplicated values are demoted as an array
+        let headers = vec!["user", "user", "user.name"];
+        let obj = Structure::new_obj(Object::from_strs(&headers, true).unwrap());
+
+        let values: Vec<JsonValue> = vec!["u1".into(), "u2".into(), "alice".into()];
+        let values: Vec<&JsonValue> = values.iter().collect();
+        let build_result = obj.build_json(&values).unwrap().into_json_value();
+
+        let expected = json!({
+            "user":{"_value":["u1","u2"],"name":"alice"},
+        });
+        assert_eq!(build_result, expected);
+    }
+
+    #[test]
+    fn test_build_json_lenient_object_then_value() {
+        let headers = vec!["user.name", "user.address.city", "user", "user"];
+        let obj = Structure::new_obj(Object::from_strs(&headers, true).unwrap());
+
+        let values: Vec<JsonValue> = vec!["alice".into(), "kyoto".into(), "u1".into(), "u2".into()];
+        let values: Vec<&JsonValue> = values.iter().collect();
+        let build_result = obj.build_json(&values).unwrap().into_json_value();
+
+        let expected = json!({
+            "user":{
+                "name":"alice",
+                "address":{"city":"kyoto"},
+                "_value":["u1","u2"],
+            },
+        });
+        assert_eq!(build_result, expected);
+
+        // nested key collision
+        let headers = vec!["user.address", "user.address.city"];
+        let obj = Structure::new_obj(Object::from_strs(&headers, true).unwrap());
+
+        let values: Vec<JsonValue> = vec!["somewhere".into(), "kyoto".into()];
+        let values: Vec<&JsonValue> = values.iter().collect();
+        let build_result = obj.build_json(&values).unwrap().into_json_value();
+
+        let expected = json!({
+            "user":{"address":{"_value":"somewhere","city":"kyoto"}},
+        });
+        assert_eq!(build_result, expected);
+    }
+
+    #[test]
+    fn test_suffix_duplicated_keys() {
+        let keys = suffix_duplicated_keys(&["name", "favorite", "favorite", "favorite"]);
+        assert_eq!(keys, vec!["name", "favorite", "favorite_2", "favorite_3"]);
+
+        // the suffix used by another key is skipped
+        let keys = suffix_duplicated_keys(&["favorite", "favorite", "favorite_2"]);
+        assert_eq!(keys, vec!["favorite", "favorite_3", "favorite_2"]);
+
+        // nested keys are suffixed at the end
+        let keys = suffix_duplicated_keys(&["user.tag", "user.tag"]);
+        assert_eq!(keys, vec!["user.tag", "user.tag_2"]);
+
+        // the duplicates in arrays of objects start the next object
+        let keys = suffix_duplicated_keys(&["items[].id", "items[].id"]);
+        assert_eq!(keys, vec!["items[].id", "items[].id"]);
+        let keys: Vec<&str> = keys.iter().map(|key| key.as_ref()).collect();
+        let obj = Object::from_strs(&keys, false).unwrap();
+        assert!(matches!(
+            obj.values.get("items"),
+            Some(Structure::ArrayOfObjects(_, objs)) if objs.len() == 2
+        ));
+
+        use std::str::FromStr;
+        assert_eq!(DupHeaders::from_str("suffix"), Ok(DupHeaders::Suffix));
+        assert!(DupHeaders::from_str("suffixes").is_err());
+    }
+
+    #[test]
+    fn test_split_keys_escaped() {
+        assert_eq!(split_keys("user.name"), vec!["user", "name"]);
+        assert_eq!(split_keys(r"user\.name"), vec![r"user\.name"]);
+        assert_eq!(split_keys(r"a\.b.c\[0\].d"), vec![r"a\.b", r"c\[0\]", "d"]);
+        // escaped escape char doesn't escape the delimiter
+        assert_eq!(split_keys(r"path\\.x"), vec![r"path\\", "x"]);
+
+        assert_eq!(unescape_key(r"user\.name"), "user.name");
+        assert_eq!(unescape_key(r"tag\[0\]"), "tag[0]");
+        assert_eq!(unescape_key(r"path\\"), r"path\");
+        // not escapable
+        assert_eq!(unescape_key(r"a\b"), r"a\b");
+    }
+
+    #[test]
+    fn test_structure_escaped_keys() {
+        let headers = vec![r"user\.name", r"tag\[0\]", r"items\[\].id"];
+        let obj = Object::from_strs(&headers, false).unwrap();
+
+        let mut items = Object::new();
+        items.add_value("id", 2, false).unwrap();
+
+        let mut expected_value = HashMap::new();
+        expected_value.insert(r"user\.name", Structure::new_value(r"user\.name", 0));
+        expected_value.insert(r"tag\[0\]", Structure::new_value(r"tag\[0\]", 1));
+        expected_value.insert(r"items\[\]", Structure::new_obj(items));
+        let expected = Object {
+            keys: vec![r"user\.name", r"tag\[0\]", r"items\[\]"],
+            values: expected_value,
+        };
+
+        assert_eq!(obj, expected);
+    }
+
+    #[test]
+    fn test_build_json_short_row() {
+        let headers = vec![
+            "name",
+            "favorite",
+            "favorite",
+            "address.city",
+            "orders[].id",
+            "orders[].id",
+        ];
+        let obj = Structure::new_obj(Object::from_strs(&headers, false).unwrap());
+
+        let values: Vec<JsonValue> = vec!["alice".into(), "diving".into(), "programming".into()];
+        let values: Vec<&JsonValue> = values.iter().collect();
+        // the values beyond the row are an error without `MissingValues`
+        assert_eq!(
+            obj.build_json(&values).err(),
+            Some(JsonStructureError::ValueOutOfRange("city".to_string(), 3))
+        );
+        let build_result = obj
+            .build_json_with(&values, MissingValues::Absent)
+            .unwrap()
+            .into_json_value();
+        assert_eq!(
+            build_result,
+            json!({"name":"alice","favorite":["diving","programming"]})
+        );
+
+        let values: Vec<JsonValue> = vec![
+            "bob".into(),
+            "diving".into(),
+            "".into(),
+            "kyoto".into(),
+            "o1".into(),
+        ];
+        let values: Vec<&JsonValue> = values.iter().collect();
+        let build_result = obj
+            .build_json_with(&values, MissingValues::Absent)
+            .unwrap()
+            .into_json_value();
+        assert_eq!(
+            build_result,
+            json!({
+                "name":"bob",
+                "favorite":["diving",""],
+                "address":{"city":"kyoto"},
+                "orders":[{"id":"o1"}],
+            })
+        );
+
+        let build_result = obj
+            .build_json_with(&[], MissingValues::Absent)
+            .unwrap()
+            .into_json_value();
+        assert_eq!(build_result, json!({}));
+    }
+
+    #[test]
+    fn test_build_json_missing_values() {
+        // the headers reference up to the index 9 but the row has 7 values
+        let headers = vec![
+            "name",
+            "age",
+            "city",
+            "tag",
+            "tag",
+            "tag",
+            "note",
+            "orders[].id",
+            "orders[].id",
+            "memo",
+        ];
+        let obj = Structure::new_obj(Object::from_strs(&headers, false).unwrap());
+
+        let values: Vec<JsonValue> = vec![
+            "alice".into(),
+            20.into(),
+            "kyoto".into(),
+            "a".into(),
+            "b".into(),
+            "c".into(),
+            "n".into(),
+        ];
+        let values: Vec<&JsonValue> = values.iter().collect();
+
+        let build_result = obj
+            .build_json_with(&values, MissingValues::Absent)
+            .unwrap()
+            .into_json_value();
+        assert_eq!(
+            build_result,
+            json!({"name":"alice","age":20,"city":"kyoto","tag":["a","b","c"],"note":"n"})
+        );
+
+        let build_result = obj
+            .build_json_with(&values, MissingValues::Null)
+            .unwrap()
+            .into_json_value();
+        assert_eq!(
+            build_result,
+            json!({
+                "name":"alice",
+                "age":20,
+                "city":"kyoto",
+                "tag":["a","b","c"],
+                "note":"n",
+                "orders":[{"id":null},{"id":null}],
+                "memo":null,
+            })
+        );
+
+        let value = Structure::new_value("memo", 9);
+        assert_eq!(
+            value
+                .build_json_with(&values, MissingValues::Null)
+                .unwrap()
+                .into_json_value(),
+            JsonValue::Null
+        );
+        assert_eq!(
+            value.build_json(&values).err(),
+            Some(JsonStructureError::ValueOutOfRange("memo".to_string(), 9))
+        );
+
+        let tags = Structure::new_arr("tag", vec![5, 6, 7]);
+        assert_eq!(
+            tags.build_json_with(&values, MissingValues::Null)
+                .unwrap()
+                .into_json_value(),
+            json!(["c", "n", null])
+        );
+
+        // the null values in the row are missing as well
+        let values: Vec<JsonValue> =
+            vec!["alice".into(), JsonValue::Null, JsonValue::Null, "a".into()];
+        let values: Vec<&JsonValue> = values.iter().collect();
+        let obj =
+            Structure::new_obj(Object::from_strs(&["name", "age", "tag", "tag"], false).unwrap());
+        assert_eq!(
+            obj.build_json_with(&values, MissingValues::Absent)
+                .unwrap()
+                .into_json_value(),
+            json!({"name":"alice","tag":["a"]})
+        );
+        assert_eq!(
+            obj.build_json_with(&values, MissingValues::Null)
+                .unwrap()
+                .into_json_value(),
+            json!({"name":"alice","age":null,"tag":[null,"a"]})
+        );
+    }
+
+    #[test]
+    fn test_build_json_escaped_keys() {
+        let headers = vec![
+            r"user\.name",
+            r"tag\[0\]",
+            r"a\.b.c\[0\].d",
+            r"path\\.x",
+            "orders[].id",
+        ];
+        let obj = Structure::new_obj(Object::from_strs(&headers, false).unwrap());
+
+        let values: Vec<JsonValue> = vec![
+            "alice".into(),
+            "t0".into(),
+            "d".into(),
+            "x".into(),
+            "o1".into(),
+        ];
+        let values: Vec<&JsonValue> = values.iter().collect();
+        let build_result = obj.build_json(&values).unwrap();
+
+        let expected = json!({
+            "user.name":"alice",
+            "tag[0]":"t0",
+            "a.b":{"c[0]":{"d":"d"}},
+            "path\\":{"x":"x"},
+            "orders":[{"id":"o1"}],
+        });
+
+        let serialized: JsonValue =
+            serde_json::from_str(&serde_json::to_string(&build_result).unwrap()).unwrap();
+        assert_eq!(serialized, expected);
+        assert_eq!(build_result.into_json_value(), expected);
+    }
 }
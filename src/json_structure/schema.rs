@@ -0,0 +1,141 @@
+use super::{unescape_key, Object, Structure};
+use serde::Serialize;
+
+/// the shape of the json built by `Structure` without values.
+/// `column` is the index of the header which the value comes from
+///
+/// ["name","favorite","favorite","address.city.name"]
+///
+/// =>
+///```ignore
+///  {"type":"object","properties":[
+///    {"key":"name","type":"value","column":0},
+///    {"key":"favorite","type":"array","columns":[1,2]},
+///    {"key":"address","type":"object","properties":[
+///      {"key":"city","type":"object","properties":[
+///        {"key":"name","type":"value","column":3}
+///      ]}
+///    ]}
+///  ]}
+///```
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SchemaNode {
+    Object { properties: Vec<SchemaProperty> },
+    Array { columns: Vec<usize> },
+    ArrayOfObjects { items: Vec<SchemaNode> },
+    Value { column: usize },
+}
+
+/// a key of an object in the order of the headers
+#[derive(Debug, PartialEq, Serialize)]
+pub struct SchemaProperty {
+    pub key: String,
+    #[serde(flatten)]
+    pub node: SchemaNode,
+}
+
+impl SchemaNode {
+    /// the columns shifted by `offset`. for the headers built without the leading ones
+    pub fn with_column_offset(self, offset: usize) -> Self {
+        match self {
+            SchemaNode::Object { properties } => SchemaNode::Object {
+                properties: properties
+                    .into_iter()
+                    .map(|property| SchemaProperty {
+                        key: property.key,
+                        node: property.node.with_column_offset(offset),
+                    })
+                    .collect(),
+            },
+            SchemaNode::Array { columns } => SchemaNode::Array {
+                columns: columns.into_iter().map(|column| column + offset).collect(),
+            },
+            SchemaNode::ArrayOfObjects { items } => SchemaNode::ArrayOfObjects {
+                items: items
+                    .into_iter()
+                    .map(|item| item.with_column_offset(offset))
+                    .collect(),
+            },
+            SchemaNode::Value { column } => SchemaNode::Value {
+                column: column + offset,
+            },
+        }
+    }
+}
+
+impl Structure<'_> {
+    pub fn schema(&self) -> SchemaNode {
+        match self {
+            Structure::Object(obj) => obj.schema(),
+            Structure::Array(_, indices) => SchemaNode::Array {
+                columns: indices.clone(),
+            },
+            Structure::ArrayOfObjects(_, objs) => SchemaNode::ArrayOfObjects {
+                items: objs.iter().map(|obj| obj.schema()).collect(),
+            },
+            Structure::Value(_, index) => SchemaNode::Value { column: *index },
+        }
+    }
+}
+
+impl Object<'_> {
+    pub fn schema(&self) -> SchemaNode {
+        let properties = self
+            .keys
+            .iter()
+            .filter_map(|key| {
+                self.values.get(key).map(|structure| SchemaProperty {
+                    key: unescape_key(key).into_owned(),
+                    node: structure.schema(),
+                })
+            })
+            .collect();
+        SchemaNode::Object { properties }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn schema_test() {
+        let headers = ["name", "favorite", "favorite", "address.city.name"];
+        let obj = Object::from_strs(&headers, false).unwrap();
+
+        let schema = serde_json::to_value(obj.schema()).unwrap();
+        let expected = json!({"type": "object", "properties": [
+            {"key": "name", "type": "value", "column": 0},
+            {"key": "favorite", "type": "array", "columns": [1, 2]},
+            {"key": "address", "type": "object", "properties": [
+                {"key": "city", "type": "object", "properties": [
+                    {"key": "name", "type": "value", "column": 3},
+                ]},
+            ]},
+        ]});
+        assert_eq!(schema, expected);
+    }
+
+    #[test]
+    fn schema_array_of_objects_test() {
+        let headers = ["items[].id", "items[].name", "items[].id", "user\\.name"];
+        let obj = Object::from_strs(&headers, false).unwrap();
+
+        let schema = serde_json::to_value(obj.schema()).unwrap();
+        let expected = json!({"type": "object", "properties": [
+            {"key": "items", "type": "array_of_objects", "items": [
+                {"type": "object", "properties": [
+                    {"key": "id", "type": "value", "column": 0},
+                    {"key": "name", "type": "value", "column": 1},
+                ]},
+                {"type": "object", "properties": [
+                    {"key": "id", "type": "value", "column": 2},
+                ]},
+            ]},
+            {"key": "user.name", "type": "value", "column": 3},
+        ]});
+        assert_eq!(schema, expected);
+    }
+}
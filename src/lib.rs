@@ -0,0 +1,56 @@
+//! Turn your google spread sheet into a json API.
+//!
+//! The binary of this crate serves the api over http, and the same sheet-to-json logic
+//! can be embedded into another application through this library.
+//!
+//! ```no_run
+//! use api_everywhere::spread_sheet::{self, scopes, RefreshingStopper};
+//! use api_everywhere::{FetchRowCondition, HeaderOption, SheetMeta, SheetsClient};
+//! use std::sync::Arc;
+//!
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! let refreshing_stopper = RefreshingStopper::new();
+//! let token_manager = spread_sheet::token_manager_from_service_account_file(
+//!     scopes::SHEET_READ_ONLY,
+//!     "./dev-secret/test-sa-key.json".into(),
+//!     refreshing_stopper.subscribe(),
+//!     None,
+//! )
+//! .await?;
+//! let client = SheetsClient::new(Arc::new(token_manager));
+//!
+//! let sheet_meta = SheetMeta::from_url(
+//!     "https://docs.google.com/spreadsheets/d/1HA4munsvl5UUlb9DKmJvhrwfGlSQ97hSQZf13M3ZO4Y/edit#gid=0",
+//! )?;
+//! let header_search_condition = client
+//!     .create_header_condition_from_sheet_meta(sheet_meta, None, HeaderOption::default())
+//!     .await?;
+//!
+//! let sheet_response = client
+//!     .fetch_sheet_value(
+//!         &header_search_condition,
+//!         &FetchRowCondition::with_pagination(Some(0), Some(10)),
+//!     )
+//!     .await?;
+//! println!("{}", sheet_response.to_json(false)?);
+//!
+//! refreshing_stopper.stop();
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod config;
+pub mod external_service;
+pub mod json_structure;
+
+// used by the binary of this crate. not a part of the library api and not semver stable
+#[doc(hidden)]
+pub mod dry_run;
+#[doc(hidden)]
+pub mod web;
+
+pub use config::Config;
+pub use external_service::spread_sheet;
+pub use external_service::spread_sheet::{
+    FetchRowCondition, HeaderOption, HeaderSearchCondition, JsonOption, SheetMeta, SheetsClient,
+};
@@ -1,18 +1,14 @@
-mod config;
-mod external_service;
-mod json_structure;
-mod web;
-
+use api_everywhere::config::{self, Config};
+use api_everywhere::{dry_run, spread_sheet, web};
 use clap::Parser;
-use config::Config;
 use env_logger::{Builder as EnvLoggerBuilder, Target};
-use external_service::spread_sheet;
 use log;
 use std::env;
 use std::net::IpAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
-use tokio::sync::broadcast;
 
 type Result<T> = std::result::Result<T, AppError>;
 
@@ -24,22 +20,39 @@ pub enum AppError {
     #[error("google token managerr error ")]
     GoogleTokenManagerError,
 
-    #[error("invalid token manager reference")]
-    InvalidTokenManagerReferenceError,
-
     #[error("hyper error")]
     HyperError,
+
+    #[error("http client error")]
+    HttpClientError,
 }
 
 #[derive(Debug, Parser)]
 pub struct Arg {
-    #[clap(short, long, default_value = "127.0.0.1")]
-    pub host: IpAddr,
+    /// repeatable. e.g. `--host 0.0.0.0 --host ::` serves on both ipv4 and ipv6
+    #[clap(short, long, default_value = "127.0.0.1", multiple_occurrences = true)]
+    pub host: Vec<IpAddr>,
     #[clap(short, long, default_value = "4000")]
     pub port: u16,
 
+    /// listens on the unix socket instead of host and port. the rate limit is not applied on it
+    #[clap(long, conflicts_with_all = &["host", "port"])]
+    pub unix_socket: Option<PathBuf>,
+
     #[clap(short, long)]
     pub service_account_file: Option<String>,
+
+    /// comma separated scopes: `sheets.readonly`, `sheets` or `drive.readonly`. overrides `SCOPES`
+    #[clap(long)]
+    pub scopes: Option<String>,
+
+    /// validate the config and the connectivity to google, then exit without starting the server
+    #[clap(long)]
+    pub dry_run_config: bool,
+
+    /// spread sheet id to fetch on --dry-run-config
+    #[clap(long)]
+    pub canary_spread_sheet_id: Option<String>,
 }
 
 #[tokio::main]
@@ -49,7 +62,11 @@ async fn main() -> Result<()> {
     let Arg {
         host,
         port,
+        unix_socket,
         service_account_file,
+        scopes,
+        dry_run_config,
+        canary_spread_sheet_id,
     } = arg;
 
     let mut log_builder = EnvLoggerBuilder::from_default_env();
@@ -63,11 +80,60 @@ async fn main() -> Result<()> {
         conf.set_service_account_file(service_accont_file)
     }
 
-    let (token_refresh_finish_tx, token_refresh_finish_rx) = broadcast::channel(1);
+    if let Some(canary_spread_sheet_id) = canary_spread_sheet_id {
+        conf.canary_spread_sheet_id = Some(canary_spread_sheet_id)
+    }
+
+    if let Some(scopes) = scopes {
+        conf.scopes = Some(scopes)
+    }
+
+    // the token manager holds `&'static` scopes. leaked only once here
+    let scopes: &'static [&'static str] = match conf.scope_urls() {
+        Ok(scopes) => Box::leak(scopes.into_boxed_slice()),
+        Err(e) => {
+            log::error!("{}", e);
+            return Err(e.into());
+        }
+    };
+    log::info!("scopes: {}", scopes.join(","));
+    if conf.sheet_scope.is_some() {
+        log::warn!(
+            "SHEET_SCOPE is deprecated. use SCOPES=sheets.readonly or SCOPES=sheets instead"
+        );
+    }
+
+    // the shared http client is built once, so before any request to google
+    if let Err(e) = spread_sheet::init_reqwest_client(&conf.http_client_option()) {
+        log::error!("invalid http client config: {}", e);
+        return Err(AppError::HttpClientError);
+    }
+    spread_sheet::init_sheet_cache(Duration::from_secs(conf.sheet_cache_ttl_sec));
+    if conf.sheets_api_base_url != spread_sheet::DEFAULT_BASE_ENDPOINT_V4 {
+        log::info!("sheets api base url: {}", conf.sheets_api_base_url);
+    }
+
+    if dry_run_config {
+        let summary = dry_run::dry_run(&conf, scopes).await;
+        println!("{}", serde_json::to_string(&summary).unwrap());
+        std::process::exit(summary.exit_code());
+    }
+
+    // fails fast before binding the port
+    match conf.validate_service_account() {
+        Ok(sa) => log::info!("service account: {}", sa.client_email),
+        Err(e) => {
+            log::error!("invalid service account file: {}", e);
+            return Err(e.into());
+        }
+    }
+
+    // stops the refreshing loops of every token manager at once on shutdown
+    let refreshing_stopper = spread_sheet::RefreshingStopper::new();
     let token_manager = spread_sheet::token_manager_from_service_account_file(
-        spread_sheet::scopes::SHEET_READ_ONLY,
+        scopes,
         conf.service_account_file_as_path_buf()?, //TODO(tacogips) PathBuf to reference type
-        token_refresh_finish_rx,
+        refreshing_stopper.subscribe(),
         None,
     )
     .await;
@@ -82,21 +148,48 @@ async fn main() -> Result<()> {
 
     let token_manager = Arc::new(token_manager);
 
-    log::info!("service is listening at {}", port);
-    if let Err(e) = web::run_server(conf, host, port, token_manager.clone()).await {
-        log::error!("hyper error:{}", e);
+    let reload_credentials_jh = tokio::spawn(web::reload_credentials_on_sighup(
+        token_manager.clone(),
+        conf.service_account_file_as_path_buf()?,
+    ));
+
+    let shutdown_drain = Duration::from_secs(conf.shutdown_timeout_sec);
+    let listen = web::Listen::new(&host, port, unix_socket);
+    log::info!("service is listening at {}", listen);
+    if let Err(e) = web::run_server(conf, listen, token_manager.clone()).await {
+        log::error!("{}", e);
         return Err(AppError::HyperError);
     }
 
-    token_refresh_finish_tx.send(()).unwrap();
-
-    let token_manager = match Arc::try_unwrap(token_manager) {
-        Ok(token_manager) => token_manager,
-        Err(_) => return Err(AppError::InvalidTokenManagerReferenceError),
-    };
-    if let Err(e) = token_manager.wait_until_refreshing_finished().await {
-        log::error!("{}", e);
-        return Err(AppError::GoogleTokenManagerError);
+    // releases the reference to the token manager
+    reload_credentials_jh.abort();
+    let _ = reload_credentials_jh.await;
+
+    refreshing_stopper.stop();
+
+    // requests stuck beyond the drain timeout still hold the token manager
+    match Arc::try_unwrap(token_manager) {
+        Err(_) => log::warn!(
+            "requests are still in flight after the shutdown drain timeout. abandoning them"
+        ),
+        Ok(token_manager) => {
+            match tokio::time::timeout(
+                shutdown_drain,
+                spread_sheet::wait_until_all_refreshing_finished(vec![token_manager]),
+            )
+            .await
+            {
+                Err(_) => log::warn!(
+                    "the token refreshing loop didn't finish within {:?}",
+                    shutdown_drain
+                ),
+                Ok(Err(e)) => {
+                    log::error!("{}", e);
+                    return Err(AppError::GoogleTokenManagerError);
+                }
+                Ok(Ok(())) => {}
+            }
+        }
     }
 
     log::info!("service has shutdown ");
@@ -0,0 +1,144 @@
+use super::error::ApiError;
+use axum::{
+    async_trait,
+    extract::{FromRequest, RequestParts},
+    http::StatusCode,
+};
+use ring::{constant_time, digest};
+use std::sync::Arc;
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// sha256 hashes of the api keys that clients pass with `X-API-Key`.
+/// the authentication is disabled if no keys are configured
+pub struct ApiKeys {
+    hashes: Vec<digest::Digest>,
+}
+
+impl ApiKeys {
+    pub fn new(keys: &[String]) -> Self {
+        let hashes = keys
+            .iter()
+            .map(|key| digest::digest(&digest::SHA256, key.as_bytes()))
+            .collect();
+        Self { hashes }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.hashes.is_empty()
+    }
+
+    pub fn verify(&self, key: &str) -> bool {
+        let key_hash = digest::digest(&digest::SHA256, key.as_bytes());
+        // compare with all the keys not to leak which one matched by the timing
+        self.hashes.iter().fold(false, |matched, hash| {
+            constant_time::verify_slices_are_equal(hash.as_ref(), key_hash.as_ref()).is_ok()
+                || matched
+        })
+    }
+}
+
+/// rejects requests without a valid `X-API-Key` with 401.
+/// use with `extractor_middleware` on the routes to be protected
+pub struct RequireApiKey;
+
+#[async_trait]
+impl<B> FromRequest<B> for RequireApiKey
+where
+    B: Send,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let api_keys = req
+            .extensions()
+            .and_then(|extensions| extensions.get::<Arc<ApiKeys>>())
+            .cloned();
+
+        let api_keys = match api_keys {
+            Some(api_keys) if api_keys.is_enabled() => api_keys,
+            _ => return Ok(Self),
+        };
+
+        let api_key = req
+            .headers()
+            .and_then(|headers| headers.get(API_KEY_HEADER))
+            .and_then(|value| value.to_str().ok());
+
+        match api_key {
+            Some(api_key) if api_keys.verify(api_key) => Ok(Self),
+            Some(_) => Err(ApiError::new(
+                StatusCode::UNAUTHORIZED,
+                "INVALID_API_KEY",
+                "invalid api key",
+            )),
+            None => Err(ApiError::new(
+                StatusCode::UNAUTHORIZED,
+                "API_KEY_REQUIRED",
+                "api key is required",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::body::Body;
+    use axum::extract::extractor_middleware;
+    use axum::http::Request;
+    use axum::routing::get;
+    use axum::{AddExtensionLayer, Router};
+    use tower::ServiceExt;
+
+    fn app(keys: &[&str]) -> Router {
+        let keys: Vec<String> = keys.iter().map(|key| key.to_string()).collect();
+        let protected = Router::new()
+            .route("/sheet", get(|| async { "sheet" }))
+            .route_layer(extractor_middleware::<RequireApiKey>());
+
+        Router::new()
+            .route("/readyz", get(|| async { "ok" }))
+            .merge(protected)
+            .layer(AddExtensionLayer::new(Arc::new(ApiKeys::new(&keys))))
+    }
+
+    async fn status_of(app: Router, uri: &str, api_key: Option<&str>) -> StatusCode {
+        let mut request = Request::builder().uri(uri);
+        if let Some(api_key) = api_key {
+            request = request.header(API_KEY_HEADER, api_key);
+        }
+        app.oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn valid_api_key() {
+        let status = status_of(app(&["key1", "key2"]), "/sheet", Some("key2")).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn missing_api_key() {
+        let status = status_of(app(&["key1"]), "/sheet", None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        // not protected
+        let status = status_of(app(&["key1"]), "/readyz", None).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn wrong_api_key() {
+        let status = status_of(app(&["key1"]), "/sheet", Some("key11")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn auth_disabled() {
+        let status = status_of(app(&[]), "/sheet", None).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+}
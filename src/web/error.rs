@@ -0,0 +1,280 @@
+use crate::external_service::spread_sheet::{SheetApiError, SheetMetaError, SpreadSheetError};
+use crate::json_structure::JsonStructureError;
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::{json, Value as JsonValue};
+
+/// the error response of the api.
+/// `{"code":"SHEET_NOT_FOUND","message":"...","detail":{...}}`
+///
+/// clients should branch on `code`. `message` is for humans and may change
+#[derive(Debug, PartialEq)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub code: &'static str,
+    pub message: String,
+    pub detail: Option<JsonValue>,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            code,
+            message: message.into(),
+            detail: None,
+        }
+    }
+
+    pub fn bad_request(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, code, message)
+    }
+
+    /// invalid query parameters
+    pub fn invalid_query(message: impl Into<String>) -> Self {
+        Self::bad_request("INVALID_QUERY", message)
+    }
+
+    pub fn with_detail(mut self, detail: JsonValue) -> Self {
+        self.detail = Some(detail);
+        self
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        let mut body = json!({
+            "code": self.code,
+            "message": self.message,
+        });
+        if let Some(detail) = self.detail.as_ref() {
+            body["detail"] = detail.clone();
+        }
+        body
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(self.to_json())).into_response()
+    }
+}
+
+/// the status is of `SpreadSheetError::http_status`.
+/// `detail.available_sheets` is added if the requested tab sheet name doesn't exist,
+/// `detail.detected_header_rows` and `detail.suggestion` if the header range is multi-line
+impl From<&SpreadSheetError> for ApiError {
+    fn from(e: &SpreadSheetError) -> Self {
+        let api_error = ApiError::new(e.http_status(), e.code(), e.to_string());
+        if let Some(available_sheets) = e.available_sheets() {
+            return api_error.with_detail(json!({ "available_sheets": available_sheets }));
+        }
+        match (e.detected_header_rows(), e.suggestion()) {
+            (Some(detected_header_rows), Some(suggestion)) => api_error.with_detail(json!({
+                "detected_header_rows": detected_header_rows,
+                "suggestion": suggestion,
+            })),
+            _ => api_error,
+        }
+    }
+}
+
+impl From<&SheetApiError> for ApiError {
+    fn from(e: &SheetApiError) -> Self {
+        let api_error = ApiError::new(e.http_status(), e.code(), e.to_string());
+        match e {
+            SheetApiError::BadReqestError(google_error)
+            | SheetApiError::PermissionDeniedError(google_error)
+            | SheetApiError::RateLimitError(google_error) => api_error.with_detail(json!({
+                "google_status": google_error.status,
+                "google_message": google_error.message,
+            })),
+            _ => api_error,
+        }
+    }
+}
+
+impl From<&JsonStructureError> for ApiError {
+    fn from(e: &JsonStructureError) -> Self {
+        ApiError::new(e.http_status(), e.code(), e.to_string())
+    }
+}
+
+impl From<&SheetMetaError> for ApiError {
+    fn from(e: &SheetMetaError) -> Self {
+        ApiError::new(e.http_status(), e.code(), e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::external_service::spread_sheet::{
+        CellError, GoogleApiError, HeaderError, ValueError,
+    };
+
+    #[test]
+    fn sheet_not_found_test() {
+        let e = SpreadSheetError::HeaderError(HeaderError::SheetNameNotFound(
+            "Sheeet1".to_string(),
+            vec!["Sheet1".to_string(), "orders".to_string()],
+        ));
+        let api_error = ApiError::from(&e);
+        assert_eq!(api_error.status, StatusCode::NOT_FOUND);
+        assert_eq!(
+            api_error.to_json(),
+            json!({
+                "code": "SHEET_NOT_FOUND",
+                "message": "header error :sheet name:Sheeet1 is not found. available sheets:[Sheet1, orders]",
+                "detail": {"available_sheets": ["Sheet1", "orders"]},
+            })
+        );
+
+        let e = SpreadSheetError::ValueError(ValueError::SpreadSheetNotFound("xxx".to_string()));
+        let api_error = ApiError::from(&e);
+        assert_eq!(api_error.status, StatusCode::NOT_FOUND);
+        assert_eq!(api_error.code, "SPREAD_SHEET_NOT_FOUND");
+        assert!(api_error.to_json().get("detail").is_none());
+    }
+
+    #[test]
+    fn bad_range_test() {
+        let e = SpreadSheetError::HeaderError(HeaderError::RangeOutOfGrid("A1:B2".to_string()));
+        let api_error = ApiError::from(&e);
+        assert_eq!(api_error.status, StatusCode::BAD_REQUEST);
+        assert_eq!(api_error.code, "RANGE_OUT_OF_GRID");
+
+        let e = SpreadSheetError::ValueError(ValueError::InvalidColRange(3, 1));
+        assert_eq!(ApiError::from(&e).code, "INVALID_RANGE");
+    }
+
+    #[test]
+    fn multiple_header_suggestion_test() {
+        let e = SpreadSheetError::HeaderError(HeaderError::UnsupportedMultipleHeader(
+            "A1:C2".to_string(),
+            Some(2),
+        ));
+        let api_error = ApiError::from(&e);
+        assert_eq!(api_error.status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            api_error.to_json(),
+            json!({
+                "code": "UNSUPPORTED_MULTIPLE_HEADER",
+                "message": "header error :multiple header not supported:A1:C2. 2 rows look like the headers",
+                "detail": {"detected_header_rows": 2, "suggestion": "try ?header_rows=2"},
+            })
+        );
+
+        // the detail is omitted if the rows couldn't be read
+        let e = SpreadSheetError::HeaderError(HeaderError::UnsupportedMultipleHeader(
+            "A1:C2".to_string(),
+            None,
+        ));
+        assert_eq!(ApiError::from(&e).to_json().get("detail"), None);
+    }
+
+    #[test]
+    fn client_error_status_test() {
+        let status = |e: SpreadSheetError| ApiError::from(&e).status;
+
+        // ranges
+        assert_eq!(
+            status(HeaderError::RangeOutOfGrid("A1:B2".to_string()).into()),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status(HeaderError::CellError(CellError::InvalidCellValue("A".to_string())).into()),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status(ValueError::InvalidColRange(3, 1).into()),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status(ValueError::IncompatibleRanges("A1:B2".to_string(), "D1:D2".to_string()).into()),
+            StatusCode::BAD_REQUEST
+        );
+
+        // pagination
+        assert_eq!(
+            status(ValueError::InvalidRowNumber(10, 1).into()),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status(ValueError::TooManyRowNumber(100, 1000).into()),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status(ValueError::TooManyColumns(100, 1000).into()),
+            StatusCode::BAD_REQUEST
+        );
+
+        // sheet ids
+        let e = SheetMetaError::InvalidSheetId("../abc".to_string());
+        assert_eq!(ApiError::from(&e).status, StatusCode::BAD_REQUEST);
+
+        // rejected by google
+        let e = SheetApiError::BadReqestError(GoogleApiError {
+            code: 400,
+            message: "Unable to parse range: Foo!A1".to_string(),
+            status: "INVALID_ARGUMENT".to_string(),
+        });
+        assert_eq!(ApiError::from(&e).status, StatusCode::BAD_REQUEST);
+
+        // passed through
+        let google_error = |code: u16| GoogleApiError {
+            code,
+            message: "mock".to_string(),
+            status: String::new(),
+        };
+        let e = SheetApiError::PermissionDeniedError(google_error(401));
+        assert_eq!(ApiError::from(&e).status, StatusCode::UNAUTHORIZED);
+        let e = SheetApiError::PermissionDeniedError(google_error(403));
+        assert_eq!(ApiError::from(&e).status, StatusCode::FORBIDDEN);
+        assert_eq!(ApiError::from(&e).code, "SHEET_API_PERMISSION_DENIED");
+        assert_eq!(
+            status(ValueError::SheetApiAccessError(google_error(429)).into()),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+
+        let e = JsonStructureError::InvalidKey("user..name".to_string());
+        assert_eq!(ApiError::from(&e).status, StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn internal_error_status_test() {
+        let status = |e: SpreadSheetError| ApiError::from(&e).status;
+
+        assert_eq!(
+            status(HeaderError::FetchSheetInfoError("abc".to_string()).into()),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+        assert_eq!(
+            status(HeaderError::FetchHeaderApiError("timeout".to_string()).into()),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+        assert_eq!(
+            status(ValueError::FetchValueApiError("timeout".to_string()).into()),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+        assert_eq!(
+            status(ValueError::AppendValueApiError("timeout".to_string()).into()),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+
+        let e = JsonStructureError::InvalidStructureState("user".to_string());
+        assert_eq!(ApiError::from(&e).status, StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[test]
+    fn invalid_structure_test() {
+        let e = JsonStructureError::InvalidJsonStructureDef("user".to_string());
+        let api_error = ApiError::from(&e);
+        assert_eq!(api_error.status, StatusCode::BAD_REQUEST);
+        assert_eq!(api_error.to_json()["code"], "INVALID_STRUCTURE");
+
+        let e = SheetMetaError::InvalidSheetUrl("https://example.com".to_string());
+        assert_eq!(ApiError::from(&e).code, "INVALID_SHEET_URL");
+    }
+}
@@ -1,80 +1,156 @@
+mod auth;
+mod error;
+mod openapi;
+mod rate_limit;
+mod readiness;
+mod request_id;
+mod server_timing;
 mod spread_sheet_handler;
 mod spread_sheet_meta;
+mod xml;
 use crate::config::Config;
 use axum::{
     error_handling::HandleErrorLayer,
-    extract::Extension,
-    http::{Method, StatusCode},
+    extract::{extractor_middleware, Extension},
+    http::{HeaderValue, Method, StatusCode},
     response::IntoResponse,
     routing::{get, get_service},
     AddExtensionLayer, Json, Router,
 };
+use error::ApiError;
 use serde_json::json;
 
-use crate::external_service::spread_sheet::TokenManager;
+use crate::external_service::spread_sheet::{
+    authenticator_from_service_account_file, DefaultConnector, TokenManager,
+};
+use futures::future::{self, FutureExt};
 use futures::stream::StreamExt;
+use hyper::server::conn::AddrIncoming;
 use signal_hook::consts::signal::*;
 use signal_hook::iterator;
 use signal_hook_tokio::{Signals, SignalsInfo};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::fmt;
+use std::future::Future;
+use std::io;
 use std::net::IpAddr;
-use std::net::SocketAddr;
+use std::net::{SocketAddr, TcpListener};
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::net::UnixListener;
+use tokio::sync::oneshot;
 use tower::{BoxError, ServiceBuilder};
-use tower_http::cors::{any, CorsLayer};
+use tower_http::compression::CompressionLayer;
+use tower_http::cors::{any, CorsLayer, Origin};
 use tower_http::services::{ServeDir, ServeFile};
 
-const REQUEST_TIMEOUT_SEC: u64 = 60;
+#[derive(Error, Debug)]
+pub enum ServerError {
+    #[error("hyper error:{0}")]
+    HyperError(#[from] hyper::Error),
+
+    #[error("failed to bind {0}:{1}")]
+    TcpBindError(SocketAddr, io::Error),
+
+    #[error("failed to bind the unix socket {0}:{1}")]
+    UnixSocketError(PathBuf, io::Error),
+}
+
+/// where the server listens on
+#[derive(Debug, Clone, PartialEq)]
+pub enum Listen {
+    /// all the addresses are served by the same app, e.g. `0.0.0.0` and `::`
+    Tcp(Vec<SocketAddr>),
+    /// e.g. behind nginx. the socket file is removed on shutdown
+    Unix(PathBuf),
+}
+
+impl Listen {
+    /// `unix_socket` is preferred to `hosts` and `port`
+    pub fn new(hosts: &[IpAddr], port: u16, unix_socket: Option<PathBuf>) -> Self {
+        match unix_socket {
+            Some(path) => Listen::Unix(path),
+            None => Listen::Tcp(
+                hosts
+                    .iter()
+                    .map(|host| SocketAddr::from((*host, port)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Listen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Listen::Tcp(addrs) => {
+                let addrs: Vec<String> = addrs.iter().map(|addr| addr.to_string()).collect();
+                write!(f, "{}", addrs.join(","))
+            }
+            Listen::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
 pub async fn run_server<HttpConnector>(
     config: Config,
-    host: IpAddr,
-    port: u16,
+    listen: Listen,
     token_manager: Arc<TokenManager<HttpConnector>>,
-) -> Result<(), hyper::Error>
+) -> Result<(), ServerError>
 where
     HttpConnector: Clone + Send + Sync + 'static,
 {
-    let app = Router::new()
-        .route("/meta", get(metadata))
+    // requires `X-API-Key` if api keys are configured
+    let sheet_routes = Router::new()
         .route(
             "/sheet/:spread_sheet_id",
-            get(spread_sheet_handler::get_spread_sheet_value::<HttpConnector>),
+            get(spread_sheet_handler::get_spread_sheet_value::<HttpConnector>)
+                .post(spread_sheet_handler::post_spread_sheet_row::<HttpConnector>),
         )
-        .route("/sheet_meta", get(spread_sheet_meta::get_spread_sheet_meta))
         .route(
-            "/",
-            get_service(ServeFile::new(format!(
-                "{}/index.html",
-                config.playground_file_dir
-            )))
-            .handle_error(|error: std::io::Error| async move {
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    format!("Unhandled internal error: {}", error),
-                )
-            }),
+            "/sheet/:spread_sheet_id/tabs",
+            get(spread_sheet_handler::get_spread_sheet_tabs::<HttpConnector>),
         )
-        .nest(
-            "/_next",
-            get_service(ServeDir::new(format!(
-                "{}/_next",
-                config.playground_file_dir
-            )))
-            .handle_error(|error: std::io::Error| async move {
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    format!("Unhandled internal error: {}", error),
-                )
-            }),
+        .route(
+            "/sheet/:spread_sheet_id/schema",
+            get(spread_sheet_handler::get_spread_sheet_schema::<HttpConnector>),
         )
-        .layer(
-            CorsLayer::new()
-                .allow_headers(any())
-                .allow_origin(any())
-                .allow_methods(vec![Method::GET]),
+        .route(
+            "/sheet/:spread_sheet_id/count",
+            get(spread_sheet_handler::get_spread_sheet_count::<HttpConnector>),
         )
+        .route("/sheet_meta", get(spread_sheet_meta::get_spread_sheet_meta))
+        .route_layer(extractor_middleware::<auth::RequireApiKey>())
+        .route_layer(rate_limit::RateLimitLayer::new(rate_limiter(
+            &config, &listen,
+        )));
+
+    let app = Router::new()
+        .route("/meta", get(metadata))
+        .route("/openapi.json", get(openapi::get_openapi))
+        .route("/readyz", get(readiness::readyz::<HttpConnector>))
+        .merge(sheet_routes);
+
+    let app = with_playground(app, &config)
+        .layer(request_id::RequestIdLayer)
+        .layer(compression_layer(config.compression))
+        .layer(cors_layer(
+            &config.cors_allowed_origins,
+            &config.cors_allowed_methods,
+        ))
         .layer(AddExtensionLayer::new(token_manager))
+        .layer(AddExtensionLayer::new(Arc::new(auth::ApiKeys::new(
+            &config.api_keys,
+        ))))
+        .layer(AddExtensionLayer::new(Arc::new(
+            readiness::ReadinessCache::new(Duration::from_secs(config.readiness_cache_ttl_sec)),
+        )))
         .layer(AddExtensionLayer::new(config.clone()))
+        .layer(AddExtensionLayer::new(AppStartInstant::now()))
         .layer(
             ServiceBuilder::new()
                 .layer(HandleErrorLayer::new(|error: BoxError| async move {
@@ -87,27 +163,363 @@ where
                         ))
                     }
                 }))
-                .timeout(Duration::from_secs(REQUEST_TIMEOUT_SEC))
+                .timeout(request_timeout(&config))
                 .into_inner(),
         );
 
-    let addr = SocketAddr::from((host, port));
-    axum::Server::bind(&addr)
+    let shutdown_timeout = Duration::from_secs(config.shutdown_timeout_sec);
+    match listen {
+        Listen::Tcp(addrs) => {
+            // binds all the addresses before serving any, so that a failure doesn't leave a half running server
+            let listeners = addrs
+                .iter()
+                .map(|addr| bind_tcp(addr).map_err(|e| ServerError::TcpBindError(*addr, e)))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(serve_tcp_listeners_with_shutdown_timeout(
+                listeners,
+                app,
+                server_shutdown_signal(),
+                shutdown_timeout,
+            )
+            .await?)
+        }
+        Listen::Unix(path) => {
+            serve_unix_socket_with_shutdown_timeout(
+                &path,
+                app,
+                server_shutdown_signal(),
+                shutdown_timeout,
+            )
+            .await
+        }
+    }
+}
+
+/// applied to the whole request, so it must be long enough for the largest read
+fn request_timeout(config: &Config) -> Duration {
+    Duration::from_secs(config.request_timeout_sec)
+}
+
+/// adds the static routes of the playground unless `serve_playground` is false
+/// or the playground dir doesn't exist
+fn with_playground(app: Router, config: &Config) -> Router {
+    if !config.serve_playground {
+        return app;
+    }
+    if !Path::new(&config.playground_file_dir).is_dir() {
+        log::warn!(
+            "playground dir not found: {}. the playground is not served",
+            config.playground_file_dir
+        );
+        return app;
+    }
+
+    app.route(
+        "/",
+        get_service(ServeFile::new(format!(
+            "{}/index.html",
+            config.playground_file_dir
+        )))
+        .handle_error(|error: std::io::Error| async move {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Unhandled internal error: {}", error),
+            )
+        }),
+    )
+    .nest(
+        "/_next",
+        get_service(ServeDir::new(format!(
+            "{}/_next",
+            config.playground_file_dir
+        )))
+        .handle_error(|error: std::io::Error| async move {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Unhandled internal error: {}", error),
+            )
+        }),
+    )
+}
+
+/// shuts down gracefully on `shutdown_signal`, but remaining connections are dropped
+/// if they are not finished within `shutdown_timeout`
+pub async fn serve_with_shutdown_timeout<Sig>(
+    server: hyper::server::Builder<AddrIncoming>,
+    app: Router,
+    shutdown_signal: Sig,
+    shutdown_timeout: Duration,
+) -> Result<(), hyper::Error>
+where
+    Sig: Future<Output = ()>,
+{
+    let (shutdown_started_tx, shutdown_started_rx) = oneshot::channel::<()>();
+    let server = server
+        .serve(app.into_make_service_with_connect_info::<SocketAddr, _>())
+        .with_graceful_shutdown(async move {
+            shutdown_signal.await;
+            let _ = shutdown_started_tx.send(());
+        });
+    with_shutdown_timeout(server, shutdown_started_rx, shutdown_timeout).await
+}
+
+/// same as `serve_with_shutdown_timeout` but serves on all the `listeners`.
+/// `shutdown_signal` shuts down all of them
+pub async fn serve_tcp_listeners_with_shutdown_timeout<Sig>(
+    listeners: Vec<TcpListener>,
+    app: Router,
+    shutdown_signal: Sig,
+    shutdown_timeout: Duration,
+) -> Result<(), hyper::Error>
+where
+    Sig: Future<Output = ()>,
+{
+    let shutdown_signal = shutdown_signal.shared();
+    let servers = listeners
+        .into_iter()
+        .map(|listener| {
+            Ok(serve_with_shutdown_timeout(
+                axum::Server::from_tcp(listener)?,
+                app.clone(),
+                shutdown_signal.clone(),
+                shutdown_timeout,
+            ))
+        })
+        .collect::<Result<Vec<_>, hyper::Error>>()?;
+    future::try_join_all(servers).await?;
+    Ok(())
+}
+
+/// ipv6 addresses are bound as v6 only. otherwise `::` takes the ipv4 port too
+/// and `0.0.0.0` can't be bound on the same port
+fn bind_tcp(addr: &SocketAddr) -> io::Result<TcpListener> {
+    let socket = Socket::new(
+        Domain::for_address(*addr),
+        Type::STREAM,
+        Some(Protocol::TCP),
+    )?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(true)?;
+    }
+    socket.set_reuse_address(true)?;
+    socket.bind(&(*addr).into())?;
+    socket.listen(1024)?;
+    socket.set_nonblocking(true)?;
+    Ok(socket.into())
+}
+
+/// same as `serve_with_shutdown_timeout` but listens on the unix socket at `path`.
+/// a stale socket file is removed before binding, and the socket file is removed after the server finished.
+/// the rate limit is not applied on a unix socket. see `rate_limiter`
+pub async fn serve_unix_socket_with_shutdown_timeout<Sig>(
+    path: &Path,
+    app: Router,
+    shutdown_signal: Sig,
+    shutdown_timeout: Duration,
+) -> Result<(), ServerError>
+where
+    Sig: Future<Output = ()>,
+{
+    let (listener, _socket_file) =
+        bind_unix_socket(path).map_err(|e| ServerError::UnixSocketError(path.to_path_buf(), e))?;
+    let incoming = hyper::server::accept::from_stream(futures::stream::unfold(
+        listener,
+        |listener| async move {
+            let conn = listener.accept().await.map(|(stream, _)| stream);
+            Some((conn, listener))
+        },
+    ));
+
+    let (shutdown_started_tx, shutdown_started_rx) = oneshot::channel::<()>();
+    let server = axum::Server::builder(incoming)
         .serve(app.into_make_service())
-        .with_graceful_shutdown(server_shutdown_signal())
-        .await
+        .with_graceful_shutdown(async move {
+            shutdown_signal.await;
+            let _ = shutdown_started_tx.send(());
+        });
+    Ok(with_shutdown_timeout(server, shutdown_started_rx, shutdown_timeout).await?)
+}
+
+/// removes the socket file on drop
+struct UnixSocketFile(PathBuf);
+
+impl Drop for UnixSocketFile {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.0) {
+            log::warn!(
+                "failed to remove the unix socket {}:{}",
+                self.0.display(),
+                e
+            );
+        }
+    }
+}
+
+/// a file at `path` is removed only if it is a socket, that a previous process has left
+fn bind_unix_socket(path: &Path) -> io::Result<(UnixListener, UnixSocketFile)> {
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => {
+            log::info!("removing the stale unix socket {}", path.display());
+            std::fs::remove_file(path)?;
+        }
+        Ok(_) => {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "the path exists and is not a socket",
+            ))
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    let listener = UnixListener::bind(path)?;
+    Ok((listener, UnixSocketFile(path.to_path_buf())))
+}
+
+/// drops `server` if it doesn't finish within `shutdown_timeout` after `shutdown_started_rx` is notified
+async fn with_shutdown_timeout<Server>(
+    server: Server,
+    shutdown_started_rx: oneshot::Receiver<()>,
+    shutdown_timeout: Duration,
+) -> Result<(), hyper::Error>
+where
+    Server: Future<Output = Result<(), hyper::Error>>,
+{
+    tokio::pin!(server);
+
+    let shutdown_timed_out = async move {
+        match shutdown_started_rx.await {
+            Ok(()) => tokio::time::sleep(shutdown_timeout).await,
+            // the server has finished without the signal
+            Err(_) => future::pending::<()>().await,
+        }
+    };
+
+    tokio::select! {
+        result = &mut server => result,
+        _ = shutdown_timed_out => {
+            log::warn!(
+                "graceful shutdown timed out after {:?}. dropping remaining connections",
+                shutdown_timeout
+            );
+            Ok(())
+        }
+    }
+}
+
+/// `None` if the rate limit is not configured
+/// the clients on a unix socket have no address to tell them apart and would share one bucket,
+/// so the rate limit is not applied there
+fn rate_limiter(config: &Config, listen: &Listen) -> Option<Arc<rate_limit::RateLimiter>> {
+    let requests_per_minute = match config.rate_limit_per_minute {
+        None | Some(0) => return None,
+        Some(requests_per_minute) => requests_per_minute,
+    };
+    if let Listen::Unix(path) = listen {
+        log::warn!(
+            "the rate limit is disabled on the unix socket {}. limit the requests at the proxy in front of it",
+            path.display()
+        );
+        return None;
+    }
+    Some(Arc::new(rate_limit::RateLimiter::new(
+        requests_per_minute,
+        config.rate_limit_burst.unwrap_or(requests_per_minute),
+    )))
+}
+
+/// compresses responses with the encoding that the client advertises in `Accept-Encoding`.
+/// (br, gzip or deflate). responses pass through as they are if `enabled` is false
+pub fn compression_layer(enabled: bool) -> CompressionLayer {
+    CompressionLayer::new()
+        .br(enabled)
+        .gzip(enabled)
+        .deflate(enabled)
+}
+
+/// invalid origins are ignored with warnings
+fn parse_cors_origins(origins: &[String]) -> Vec<HeaderValue> {
+    origins
+        .iter()
+        .filter_map(|origin| match HeaderValue::from_str(origin) {
+            Ok(origin) => Some(origin),
+            Err(_) => {
+                log::warn!("ignore invalid cors origin:{}", origin);
+                None
+            }
+        })
+        .collect()
+}
+
+/// invalid methods are ignored with warnings
+fn parse_cors_methods(methods: &[String]) -> Vec<Method> {
+    methods
+        .iter()
+        .filter_map(|method| match Method::from_str(&method.to_uppercase()) {
+            Ok(method) => Some(method),
+            Err(_) => {
+                log::warn!("ignore invalid cors method:{}", method);
+                None
+            }
+        })
+        .collect()
+}
+
+/// allows any origins if `allowed_origins` is empty. that is only for development
+pub fn cors_layer(allowed_origins: &[String], allowed_methods: &[String]) -> CorsLayer {
+    let layer = CorsLayer::new()
+        .allow_headers(any())
+        .allow_methods(parse_cors_methods(allowed_methods));
+
+    let origins = parse_cors_origins(allowed_origins);
+    if origins.is_empty() {
+        layer.allow_origin(any())
+    } else {
+        layer.allow_origin(Origin::list(origins))
+    }
+}
+
+/// when the server started. for the uptime on `/meta`
+#[derive(Debug, Clone, Copy)]
+pub struct AppStartInstant(Instant);
+
+impl AppStartInstant {
+    pub fn now() -> Self {
+        Self(Instant::now())
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.0.elapsed()
+    }
+}
+
+/// version, git commit, scopes and uptime of this server
+fn build_info(config: &Config, started: &AppStartInstant) -> serde_json::Value {
+    json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "git_commit": option_env!("GIT_COMMIT"),
+        "scopes": config.scope_urls().unwrap_or_default(),
+        "uptime_sec": started.uptime().as_secs(),
+    })
 }
 
-pub async fn metadata(Extension(config): Extension<Config>) -> impl IntoResponse {
+pub async fn metadata(
+    Extension(config): Extension<Config>,
+    Extension(started): Extension<AppStartInstant>,
+) -> impl IntoResponse {
     match config.service_account_data() {
         Err(e) => {
             log::error!("serviece account load error {}", e);
-            Err((
+            Err(ApiError::new(
                 StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"error_message":e.to_string()})),
+                "SERVICE_ACCOUNT_ERROR",
+                e.to_string(),
             ))
         }
-        Ok(sa) => Ok(Json(json!({"service_account":sa.client_email}))),
+        Ok(sa) => {
+            let mut meta = build_info(&config, &started);
+            meta["service_account"] = json!(sa.client_email);
+            Ok(Json(meta))
+        }
     }
 }
 
@@ -121,15 +533,44 @@ pub async fn server_shutdown_signal() {
 }
 
 pub fn quit_signal_handler() -> (iterator::backend::Handle, SignalsInfo) {
-    let signals = Signals::new(&[SIGHUP, SIGTERM, SIGINT, SIGQUIT]).unwrap();
+    let signals = Signals::new(&[SIGTERM, SIGINT, SIGQUIT]).unwrap();
     (signals.handle(), signals)
 }
 
+/// reloads the service account file on SIGHUP, so that a rotated key is used without restart
+pub async fn reload_credentials_on_sighup(
+    token_manager: Arc<TokenManager<DefaultConnector>>,
+    service_account_file: PathBuf,
+) {
+    let signals = Signals::new(&[SIGHUP]).unwrap();
+    let handle = signals.handle();
+    let mut signals = signals.fuse();
+    while signals.next().await.is_some() {
+        log::info!(
+            "SIGHUP received. reloading the service account file {}",
+            service_account_file.display()
+        );
+        let reloaded =
+            match authenticator_from_service_account_file(service_account_file.clone()).await {
+                Err(e) => Err(e),
+                Ok(authenticator) => token_manager.reload(authenticator).await,
+            };
+        match reloaded {
+            Ok(()) => log::info!("service account has been reloaded"),
+            Err(e) => log::error!(
+                "failed to reload the service account. keep using the current one :{}",
+                e
+            ),
+        }
+    }
+    handle.close();
+}
+
 pub async fn handle_quit_signals(signals: Signals) {
     let mut signals = signals.fuse();
     while let Some(signal) = signals.next().await {
         match signal {
-            SIGHUP | SIGTERM | SIGINT | SIGQUIT => {
+            SIGTERM | SIGINT | SIGQUIT => {
                 // Shutdown the system;
                 log::info!("shutdown signal has receipt");
                 break;
@@ -138,3 +579,378 @@ pub async fn handle_quit_signals(signals: Signals) {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::{header, Request};
+    use tower::ServiceExt;
+
+    fn compressible_app(compression: bool) -> Router {
+        Router::new()
+            .route("/", get(|| async { "api everywhere ".repeat(100) }))
+            .layer(compression_layer(compression))
+    }
+
+    #[tokio::test]
+    async fn compression_br_test() {
+        let response = compressible_app(true)
+            .oneshot(
+                Request::builder()
+                    .uri("/")
+                    .header(header::ACCEPT_ENCODING, "br")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            response.headers().get(header::CONTENT_ENCODING).unwrap(),
+            "br"
+        );
+    }
+
+    #[tokio::test]
+    async fn compression_gzip_test() {
+        let response = compressible_app(true)
+            .oneshot(
+                Request::builder()
+                    .uri("/")
+                    .header(header::ACCEPT_ENCODING, "gzip")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            response.headers().get(header::CONTENT_ENCODING).unwrap(),
+            "gzip"
+        );
+    }
+
+    #[tokio::test]
+    async fn compression_identity_test() {
+        let response = compressible_app(true)
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        assert!(response.headers().get(header::CONTENT_ENCODING).is_none());
+    }
+
+    #[tokio::test]
+    async fn compression_disabled_test() {
+        let response = compressible_app(false)
+            .oneshot(
+                Request::builder()
+                    .uri("/")
+                    .header(header::ACCEPT_ENCODING, "gzip")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert!(response.headers().get(header::CONTENT_ENCODING).is_none());
+    }
+
+    #[test]
+    fn parse_cors_config_test() {
+        let origins = parse_cors_origins(&[
+            "https://example.com".to_string(),
+            "https://invalid\n.example.com".to_string(),
+        ]);
+        assert_eq!(
+            origins,
+            vec![HeaderValue::from_static("https://example.com")]
+        );
+
+        let methods = parse_cors_methods(&["get".to_string(), "POST".to_string()]);
+        assert_eq!(methods, vec![Method::GET, Method::POST]);
+    }
+
+    async fn allowed_origin_of(
+        allowed_origins: &[String],
+        request_origin: &str,
+    ) -> Option<HeaderValue> {
+        let app = Router::new()
+            .route("/", get(|| async { "ok" }))
+            .layer(cors_layer(allowed_origins, &["GET".to_string()]));
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/")
+                    .header(header::ORIGIN, request_origin)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        response
+            .headers()
+            .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+            .cloned()
+    }
+
+    #[tokio::test]
+    async fn cors_layer_test() {
+        let allowed = vec!["https://example.com".to_string()];
+        assert_eq!(
+            allowed_origin_of(&allowed, "https://example.com").await,
+            Some(HeaderValue::from_static("https://example.com"))
+        );
+        assert_eq!(allowed_origin_of(&allowed, "https://evil.com").await, None);
+
+        // any origins
+        assert_eq!(
+            allowed_origin_of(&[], "https://evil.com").await,
+            Some(HeaderValue::from_static("*"))
+        );
+    }
+
+    async fn playground_status(config: &Config) -> StatusCode {
+        let app = with_playground(
+            Router::new().route("/meta", get(|| async { "meta" })),
+            config,
+        );
+        app.oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[test]
+    fn build_info_test() {
+        let mut config = Config::from_env();
+        config.scopes = None;
+        config.sheet_scope = Some("readonly".to_string());
+        let info = build_info(&config, &AppStartInstant::now());
+
+        assert_eq!(info["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(
+            info["scopes"],
+            json!(config.sheet_scope().unwrap().scopes())
+        );
+        assert_eq!(info["uptime_sec"], 0);
+    }
+
+    #[test]
+    fn request_timeout_test() {
+        let mut config = Config::from_env();
+        config.request_timeout_sec = 300;
+        assert_eq!(request_timeout(&config), Duration::from_secs(300));
+    }
+
+    #[test]
+    fn rate_limiter_test() {
+        let tcp = Listen::Tcp(vec![SocketAddr::from(([127, 0, 0, 1], 4000))]);
+        let unix = Listen::Unix(PathBuf::from("/tmp/api.sock"));
+
+        let mut config = Config::from_env();
+        config.rate_limit_per_minute = Some(60);
+        assert!(rate_limiter(&config, &tcp).is_some());
+        // every client would be in the same bucket
+        assert!(rate_limiter(&config, &unix).is_none());
+
+        config.rate_limit_per_minute = Some(0);
+        assert!(rate_limiter(&config, &tcp).is_none());
+    }
+
+    #[tokio::test]
+    async fn playground_test() {
+        let mut config = Config::from_env();
+        config.serve_playground = true;
+        assert_eq!(playground_status(&config).await, StatusCode::OK);
+
+        config.serve_playground = false;
+        assert_eq!(playground_status(&config).await, StatusCode::NOT_FOUND);
+
+        config.serve_playground = true;
+        config.playground_file_dir = "/not/exists/playground".to_string();
+        assert_eq!(playground_status(&config).await, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn shutdown_timeout_test() {
+        let app = Router::new().route(
+            "/hang",
+            get(|| async {
+                future::pending::<()>().await;
+                "never"
+            }),
+        );
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (signal_tx, signal_rx) = oneshot::channel::<()>();
+        let server = tokio::spawn(serve_with_shutdown_timeout(
+            axum::Server::from_tcp(listener).unwrap(),
+            app,
+            async move {
+                let _ = signal_rx.await;
+            },
+            Duration::from_millis(200),
+        ));
+
+        let hanging_request = tokio::spawn(reqwest::get(format!("http://{}/hang", addr)));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        signal_tx.send(()).unwrap();
+
+        let result = tokio::time::timeout(Duration::from_secs(5), server).await;
+        assert!(result.expect("the server hangs").unwrap().is_ok());
+
+        // the hanging request was dropped
+        let response = hanging_request.await.unwrap();
+        assert!(response.is_err());
+    }
+
+    #[tokio::test]
+    async fn serve_multiple_addresses_test() {
+        let app = Router::new().route("/healthz", get(|| async { "ok" }));
+
+        let listeners = vec![
+            bind_tcp(&SocketAddr::from(([127, 0, 0, 1], 0))).unwrap(),
+            bind_tcp(&SocketAddr::from(([127, 0, 0, 1], 0))).unwrap(),
+        ];
+        let addrs: Vec<SocketAddr> = listeners
+            .iter()
+            .map(|listener| listener.local_addr().unwrap())
+            .collect();
+
+        let (signal_tx, signal_rx) = oneshot::channel::<()>();
+        let server = tokio::spawn(serve_tcp_listeners_with_shutdown_timeout(
+            listeners,
+            app,
+            async move {
+                let _ = signal_rx.await;
+            },
+            Duration::from_millis(200),
+        ));
+
+        for addr in addrs.iter() {
+            let body = reqwest::get(format!("http://{}/healthz", addr))
+                .await
+                .unwrap()
+                .text()
+                .await
+                .unwrap();
+            assert_eq!(body, "ok");
+        }
+
+        // a single signal stops all the listeners
+        signal_tx.send(()).unwrap();
+        let result = tokio::time::timeout(Duration::from_secs(5), server).await;
+        assert!(result.expect("the server hangs").unwrap().is_ok());
+        for addr in addrs.iter() {
+            assert!(reqwest::get(format!("http://{}/healthz", addr))
+                .await
+                .is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn with_shutdown_timeout_test() {
+        let (started_tx, started_rx) = oneshot::channel::<()>();
+        started_tx.send(()).unwrap();
+
+        // a server that never finishes is abandoned after the timeout
+        let result = tokio::time::timeout(
+            Duration::from_secs(5),
+            with_shutdown_timeout(
+                future::pending::<Result<(), hyper::Error>>(),
+                started_rx,
+                Duration::from_millis(100),
+            ),
+        )
+        .await;
+        assert!(result.expect("the drain timeout doesn't work").is_ok());
+
+        // no timeout until the shutdown starts
+        let (_started_tx, started_rx) = oneshot::channel::<()>();
+        let result = tokio::time::timeout(
+            Duration::from_millis(300),
+            with_shutdown_timeout(
+                future::pending::<Result<(), hyper::Error>>(),
+                started_rx,
+                Duration::from_millis(100),
+            ),
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn listen_test() {
+        let host = IpAddr::from([127, 0, 0, 1]);
+        assert_eq!(
+            Listen::new(&[host], 4000, None),
+            Listen::Tcp(vec![SocketAddr::from((host, 4000))])
+        );
+
+        let v6_host = IpAddr::from([0u16; 8]);
+        let listen = Listen::new(&[IpAddr::from([0, 0, 0, 0]), v6_host], 4000, None);
+        assert_eq!(listen.to_string(), "0.0.0.0:4000,[::]:4000");
+
+        let listen = Listen::new(&[host], 4000, Some(PathBuf::from("/tmp/api.sock")));
+        assert_eq!(listen, Listen::Unix(PathBuf::from("/tmp/api.sock")));
+        assert_eq!(listen.to_string(), "unix:/tmp/api.sock");
+    }
+
+    #[tokio::test]
+    async fn unix_socket_lifecycle_test() {
+        let path =
+            std::env::temp_dir().join(format!("api-everywhere-{}.sock", uuid::Uuid::new_v4()));
+
+        // a stale socket left by a previous process
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+
+        let app = Router::new().route("/", get(|| async { "ok" }));
+        let (signal_tx, signal_rx) = oneshot::channel::<()>();
+        let server = tokio::spawn({
+            let path = path.clone();
+            async move {
+                serve_unix_socket_with_shutdown_timeout(
+                    &path,
+                    app,
+                    async move {
+                        let _ = signal_rx.await;
+                    },
+                    Duration::from_millis(200),
+                )
+                .await
+            }
+        });
+
+        // the server accepts connections on the socket
+        let mut connected = false;
+        for _ in 0..50 {
+            if tokio::net::UnixStream::connect(&path).await.is_ok() {
+                connected = true;
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(connected);
+
+        signal_tx.send(()).unwrap();
+        let result = tokio::time::timeout(Duration::from_secs(5), server).await;
+        assert!(result.expect("the server hangs").unwrap().is_ok());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn bind_unix_socket_not_socket_test() {
+        let path =
+            std::env::temp_dir().join(format!("api-everywhere-{}.sock", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "not a socket").unwrap();
+
+        // a regular file is never removed
+        assert!(bind_unix_socket(&path).is_err());
+        assert!(path.exists());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,478 @@
+use axum::{response::IntoResponse, Json};
+use serde_json::{json, Value as JsonValue};
+
+/// `GET /openapi.json`
+pub async fn get_openapi() -> impl IntoResponse {
+    Json(openapi_document())
+}
+
+/// OpenAPI 3.0 document of the http api.
+/// update this when the routes, the query parameters or the responses are changed
+pub fn openapi_document() -> JsonValue {
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "api-everywhere",
+            "description": "serves google spread sheets as json apis",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": {
+            "/sheet/{spread_sheet_id}": {
+                "get": {
+                    "summary": "rows of a tab sheet as json",
+                    "parameters": sheet_value_parameters(),
+                    "responses": {
+                        "200": {
+                            "description": "rows built into json by the headers. `headers` and `rows` with `?raw=true`, `application/x-ndjson` with `?format=ndjson`",
+                            "headers": {
+                                "X-Total-Rows": {
+                                    "description": "number of the data rows of the sheet",
+                                    "schema": {"type": "integer"},
+                                },
+                                "Link": {
+                                    "description": "urls of the next and the previous pages with `rel=\"next\"` and `rel=\"prev\"` (RFC 8288)",
+                                    "schema": {"type": "string"},
+                                },
+                            },
+                            "content": {
+                                "application/json": {
+                                    "schema": {
+                                        "oneOf": [
+                                            {"$ref": "#/components/schemas/GetSpreadSheetValueResponse"},
+                                            {"$ref": "#/components/schemas/GetSpreadSheetRawValueResponse"},
+                                        ],
+                                    },
+                                },
+                                "application/x-ndjson": {
+                                    "schema": {"type": "string"},
+                                },
+                                "application/xml": {
+                                    "schema": {"type": "string"},
+                                },
+                                "application/javascript": {
+                                    "schema": {"type": "string"},
+                                },
+                            },
+                        },
+                        "400": error_response("invalid query parameters, ranges or pagination"),
+                        "401": error_response("missing or wrong `X-API-Key`"),
+                        "404": error_response("the spread sheet or the tab sheet is not found, or the sheet has no rows (`NO_RECORDS`). paginating past the last row is 200 with an empty `data`. `detail.available_sheets` lists the tab sheet names for a wrong `sheet_name`"),
+                        "429": error_response("too many requests"),
+                        "500": error_response("failed to read the sheet"),
+                    },
+                },
+                "post": {
+                    "summary": "appends a row. requires `SCOPES=sheets`",
+                    "parameters": sheet_value_parameters(),
+                    "requestBody": {
+                        "required": true,
+                        "content": {
+                            "application/json": {
+                                "schema": {"type": "object", "description": "values keyed by the headers. nested objects for dotted headers"},
+                            },
+                        },
+                    },
+                    "responses": {
+                        "201": {
+                            "description": "the row is appended",
+                            "content": {
+                                "application/json": {
+                                    "schema": {
+                                        "type": "object",
+                                        "properties": {
+                                            "data": {
+                                                "type": "object",
+                                                "properties": {"updated_range": {"type": "string"}},
+                                            },
+                                        },
+                                    },
+                                },
+                            },
+                        },
+                        "400": error_response("invalid row"),
+                        "401": error_response("missing or wrong `X-API-Key`"),
+                        "403": error_response("the server is configured with the read only scope"),
+                        "500": error_response("failed to append the row"),
+                    },
+                },
+            },
+            "/sheet/{spread_sheet_id}/schema": {
+                "get": {
+                    "summary": "headers and the json structure built from them without reading rows",
+                    "parameters": sheet_value_parameters(),
+                    "responses": {
+                        "200": {
+                            "description": "`structure` is a tree of `object`, `array`, `array_of_objects` and `value`. `column` is the index of `headers.values`",
+                            "content": {
+                                "application/json": {
+                                    "schema": {
+                                        "type": "object",
+                                        "properties": {
+                                            "data": {
+                                                "type": "object",
+                                                "properties": {
+                                                    "headers": {"type": "object"},
+                                                    "structure": {"type": "object"},
+                                                },
+                                            },
+                                        },
+                                    },
+                                },
+                            },
+                        },
+                        "400": error_response("invalid query parameters or headers"),
+                        "401": error_response("missing or wrong `X-API-Key`"),
+                        "404": error_response("the spread sheet or the tab sheet is not found"),
+                        "500": error_response("failed to read the sheet"),
+                    },
+                },
+            },
+            "/sheet/{spread_sheet_id}/count": {
+                "get": {
+                    "summary": "number of the records without reading rows",
+                    "parameters": count_parameters(),
+                    "responses": {
+                        "200": {
+                            "description": "`rows` is counted from the grid size. the empty rows at the bottom of the grid are counted too unless `accurate_rows=true`",
+                            "content": {
+                                "application/json": {
+                                    "schema": {
+                                        "type": "object",
+                                        "properties": {
+                                            "rows": {"type": "integer"},
+                                        },
+                                    },
+                                },
+                            },
+                        },
+                        "400": error_response("invalid query parameters or headers"),
+                        "401": error_response("missing or wrong `X-API-Key`"),
+                        "404": error_response("the spread sheet or the tab sheet is not found"),
+                        "500": error_response("failed to read the sheet"),
+                    },
+                },
+            },
+            "/sheet_meta": {
+                "get": {
+                    "summary": "spread sheet id and tab sheet of a url",
+                    "parameters": [
+                        query_parameter("sheet_url", "string", "url of the spread sheet"),
+                        query_parameter("sheet_id", "string", "spread sheet id. preferred to `sheet_url` if both are passed"),
+                        query_parameter("sheet_name", "string", "tab sheet name"),
+                        query_parameter("gid", "integer", "tab sheet id"),
+                    ],
+                    "responses": {
+                        "200": {
+                            "description": "spread sheet meta",
+                            "content": {
+                                "application/json": {
+                                    "schema": {
+                                        "type": "object",
+                                        "properties": {
+                                            "data": {"$ref": "#/components/schemas/SheetMeta"},
+                                        },
+                                    },
+                                },
+                            },
+                        },
+                        "400": error_response("invalid sheet url or id"),
+                    },
+                },
+            },
+            "/meta": {
+                "get": {
+                    "summary": "metadata of this server",
+                    "responses": {
+                        "200": {
+                            "description": "service account that the sheets must be shared with and the build of this server",
+                            "content": {
+                                "application/json": {
+                                    "schema": {
+                                        "type": "object",
+                                        "properties": {
+                                            "service_account": {"type": "string"},
+                                            "version": {"type": "string"},
+                                            "git_commit": {"type": "string", "nullable": true},
+                                            "scopes": {"type": "array", "items": {"type": "string"}},
+                                            "uptime_sec": {"type": "integer"},
+                                        },
+                                    },
+                                },
+                            },
+                        },
+                        "500": error_response("failed to load the service account"),
+                    },
+                },
+            },
+            "/openapi.json": {
+                "get": {
+                    "summary": "this document",
+                    "responses": {
+                        "200": {
+                            "description": "OpenAPI 3.0 document",
+                            "content": {
+                                "application/json": {"schema": {"type": "object"}},
+                            },
+                        },
+                    },
+                },
+            },
+        },
+        "components": {
+            "schemas": {
+                "GetSpreadSheetValueResponse": {
+                    "type": "object",
+                    "required": ["data"],
+                    "properties": {
+                        "data": {
+                            "description": "array of rows, a single row with `?row=`, or an object with `?group_by=` or `?first_col_as_key=true`",
+                        },
+                        "pagination": {"$ref": "#/components/schemas/Pagination"},
+                        "next_cursor": {"type": "string"},
+                        "warnings": {
+                            "type": "array",
+                            "items": {"type": "string"},
+                            "description": "headers dropped by `?skip_invalid_headers=true`",
+                        },
+                    },
+                },
+                "GetSpreadSheetRawValueResponse": {
+                    "type": "object",
+                    "required": ["headers", "rows"],
+                    "properties": {
+                        "headers": {"type": "array", "items": {"type": "string"}},
+                        "rows": {"type": "array", "items": {"type": "array", "items": {}}},
+                        "pagination": {"$ref": "#/components/schemas/Pagination"},
+                        "next_cursor": {"type": "string"},
+                    },
+                },
+                "Pagination": {
+                    "type": "object",
+                    "properties": {
+                        "offset": {"type": "integer", "nullable": true},
+                        "limit": {"type": "integer", "nullable": true},
+                        "total_rows": {"type": "integer"},
+                        "has_more": {"type": "boolean"},
+                    },
+                },
+                "SheetMeta": {
+                    "type": "object",
+                    "properties": {
+                        "spread_sheet_id": {"type": "string"},
+                        "sheet_id_or_name": {
+                            "type": "object",
+                            "properties": {
+                                "tab_sheet_id": {"type": "integer", "nullable": true},
+                                "tab_sheet_name": {"type": "string", "nullable": true},
+                            },
+                        },
+                    },
+                },
+                "Error": {
+                    "type": "object",
+                    "required": ["code", "message"],
+                    "properties": {
+                        "code": {
+                            "type": "string",
+                            "description": "stable error code like `SHEET_NOT_FOUND`, `RANGE_OUT_OF_GRID` or `INVALID_QUERY`",
+                        },
+                        "message": {"type": "string"},
+                        "detail": {
+                            "type": "object",
+                            "properties": {
+                                "available_sheets": {"type": "array", "items": {"type": "string"}},
+                            },
+                        },
+                    },
+                },
+            },
+        },
+    })
+}
+
+fn sheet_value_parameters() -> JsonValue {
+    json!([
+        {
+            "name": "spread_sheet_id",
+            "in": "path",
+            "required": true,
+            "schema": {"type": "string"},
+        },
+        query_parameter("sheet_id", "integer", "tab sheet id (gid)"),
+        query_parameter("sheet_name", "string", "tab sheet name, percent-encoded as a whole like `Q3%20%26%20Q4`. `+` must be `%2B`, a raw `+` is a space"),
+        query_parameter("sheet_index", "integer", "0-based position of the tab. used if neither `sheet_id` nor `sheet_name` is passed"),
+        query_parameter("offset", "integer", "number of the rows to skip"),
+        query_parameter("limit", "integer", "max number of the rows"),
+        query_parameter("row", "integer", "returns the single row"),
+        query_parameter("single", "boolean", "returns the first row as an object. the other rows are dropped with a `Warning` header. `row` is preferred"),
+        query_parameter(
+            "range",
+            "string",
+            "reads only the range like `B2:F50`. the first row of the range is the header row",
+        ),
+        query_parameter(
+            "ranges",
+            "string",
+            "comma separated ranges of a same width like `A1:C10,E1:G10` read at once. every range starts with the header rows and the rows are concatenated under the headers of the first range. can not be used with `offset` or `limit`",
+        ),
+        query_parameter(
+            "rows",
+            "string",
+            "comma separated row indices like `3,7,12`. the rows beyond the grid are skipped",
+        ),
+        query_parameter("cursor", "string", "`next_cursor` of the previous response. preferred to offset and limit"),
+        query_parameter("header_row", "integer", "1-based row number of the header row. defaults to 1"),
+        query_parameter("header_rows", "integer", "number of the header rows joined into dotted keys. defaults to 1"),
+        query_parameter("trim_headers", "boolean", "trims the headers"),
+        query_parameter("lowercase_headers", "boolean", "lowercases the headers"),
+        query_parameter("raw", "boolean", "returns headers and rows as they are"),
+        query_parameter("group_by", "string", "groups the rows by the value of the column"),
+        query_parameter("rename", "string", "renames headers. e.g. `Full Name:name,DOB:birthday`"),
+        query_parameter("defaults", "string", "values of empty cells for each column. e.g. `status:active,count:0`"),
+        query_parameter("first_col_as_key", "boolean", "keys the rows by the first column"),
+        query_parameter("coerce_numbers", "boolean", "converts numeric strings into numbers"),
+        query_parameter("big_int_as_string", "boolean", "keeps integers beyond 2^53-1 as strings"),
+        query_parameter("lenient_structure", "boolean", "headers like `user` and `user.name` are built into `{\"user\":{\"_value\":..,\"name\":..}}` instead of an error"),
+        query_parameter("skip_invalid_headers", "boolean", "the headers which can't be built into the json are dropped with `warnings` instead of an error"),
+        query_parameter("dup_headers", "string", "`array`(default) merges the duplicated headers into an array. `suffix` keeps them as `favorite`, `favorite_2`..."),
+        query_parameter("project", "string", "comma separated dot paths kept in the json of each row like `name,address.city`. a path through an array of objects is applied to each object. missing paths are absent. can not be used with `raw`"),
+        query_parameter("array_order", "string", "`left_to_right`(default) or `right_to_left`. the order of the values of the duplicated headers merged into an array"),
+        query_parameter("dates", "string", "`iso` converts the serial numbers of `date_columns` into iso 8601 dates. the values are read unformatted"),
+        query_parameter("date_columns", "string", "comma separated headers of the date columns"),
+        query_parameter("render", "string", "`value`(default) or `formula`. `formula` returns the formulas like `=SUM(A1:A2)` instead of the computed values. the headers are read formatted anyway. can not be used with `dates`"),
+        query_parameter("pad", "boolean", "`false` returns the short rows at their natural length instead of filling them with `\"\"`. the missing trailing columns are absent keys. defaults to true"),
+        query_parameter("missing", "string", "`absent`(default) drops the keys of the columns beyond the short rows of `pad=false`. `null` returns them as null"),
+        query_parameter("fresh", "boolean", "fetches the tabs of the spread sheet bypassing the cache"),
+        query_parameter("include_empty_rows", "boolean", "`false` drops the rows whose cells are all empty. the dropped rows still count against `limit`, so a page may have fewer rows and the next cursor is not shifted. defaults to true"),
+        query_parameter("accurate_rows", "boolean", "bounds the rows and the pagination by the last non-empty cell of the first header column instead of the allocated grid size. costs one more request"),
+        query_parameter("tz", "string", "time zone of the dates with time like `Asia/Tokyo`. defaults to the time zone of the spread sheet"),
+        query_parameter("orientation", "string", "`rows`(default) or `key_value`. `key_value` reads the labels down the column A and each column right of the labels becomes a record"),
+        query_parameter("format", "string", "`json`(default), `ndjson` or `xml`. `Accept: application/xml` also chooses `xml`"),
+        query_parameter("callback", "string", "JSONP. wraps the json as `callback(<json>);` with `application/javascript`. only identifiers like `handleRows` are allowed"),
+        query_parameter("manifest", "boolean", "emits `{\"_meta\":{...}}` as the first line of the ndjson stream"),
+    ])
+}
+
+/// the parameters of `sheet_value_parameters` to find the headers and to bound the records
+fn count_parameters() -> JsonValue {
+    let names = [
+        "spread_sheet_id",
+        "sheet_id",
+        "sheet_name",
+        "sheet_index",
+        "header_row",
+        "header_rows",
+        "trim_headers",
+        "lowercase_headers",
+        "orientation",
+        "accurate_rows",
+        "fresh",
+    ];
+    let mut parameters: Vec<JsonValue> = sheet_value_parameters()
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|p| names.contains(&p["name"].as_str().unwrap_or_default()))
+        .cloned()
+        .collect();
+    parameters.push(query_parameter(
+        "range",
+        "string",
+        "counts only the records in the range like `B2:F50`. the first row of the range is the header row",
+    ));
+    JsonValue::Array(parameters)
+}
+
+fn query_parameter(name: &str, schema_type: &str, description: &str) -> JsonValue {
+    json!({
+        "name": name,
+        "in": "query",
+        "required": false,
+        "description": description,
+        "schema": {"type": schema_type},
+    })
+}
+
+fn error_response(description: &str) -> JsonValue {
+    json!({
+        "description": description,
+        "content": {
+            "application/json": {
+                "schema": {"$ref": "#/components/schemas/Error"},
+            },
+        },
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use axum::{routing::get, Router};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn openapi_document_test() {
+        let app = Router::new().route("/openapi.json", get(get_openapi));
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/openapi.json")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let document: JsonValue = serde_json::from_slice(&body).unwrap();
+        assert_eq!(document["openapi"], "3.0.3");
+
+        let paths = document["paths"].as_object().unwrap();
+        for path in [
+            "/sheet/{spread_sheet_id}",
+            "/sheet/{spread_sheet_id}/schema",
+            "/sheet/{spread_sheet_id}/count",
+            "/sheet_meta",
+            "/meta",
+            "/openapi.json",
+        ] {
+            assert!(paths.contains_key(path), "{} is not listed", path);
+        }
+    }
+
+    #[test]
+    fn sheet_value_parameters_test() {
+        // all the query parameters of GetSpreadSheetQuery should be documented
+        let parameters = sheet_value_parameters();
+        let names: Vec<&str> = parameters
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| p["name"].as_str().unwrap())
+            .collect();
+        for name in ["offset", "limit", "cursor", "format", "defaults"] {
+            assert!(names.contains(&name), "{} is not documented", name);
+        }
+    }
+
+    #[test]
+    fn count_parameters_test() {
+        let parameters = count_parameters();
+        let names: Vec<&str> = parameters
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| p["name"].as_str().unwrap())
+            .collect();
+        for name in ["spread_sheet_id", "sheet_name", "range", "accurate_rows"] {
+            assert!(names.contains(&name), "{} is not documented", name);
+        }
+        // the rows are not read
+        for name in ["offset", "limit", "cursor", "format", "project"] {
+            assert!(!names.contains(&name), "{} is documented", name);
+        }
+    }
+}
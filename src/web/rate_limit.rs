@@ -0,0 +1,328 @@
+use super::auth::{ApiKeys, API_KEY_HEADER};
+use super::error::ApiError;
+use axum::{
+    extract::ConnectInfo,
+    http::{header, HeaderValue, Request, StatusCode},
+    response::{IntoResponse, Response},
+};
+use futures::future::{self, Either, Ready};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tower::{Layer, Service};
+
+/// buckets that are full are dropped when the number of the buckets exceeds this.
+/// if it still exceeds, the least recently used half is dropped
+const MAX_BUCKETS: usize = 10_000;
+
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    last_refilled: Instant,
+}
+
+/// token bucket per client. the client is identified by the verified api key or the ip address
+pub struct RateLimiter {
+    requests_per_minute: u32,
+    burst: u32,
+    max_buckets: usize,
+    buckets: Mutex<HashMap<String, Bucket>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_minute: u32, burst: u32) -> Self {
+        Self::with_clock(requests_per_minute, burst, Arc::new(SystemClock))
+    }
+
+    pub fn with_clock(requests_per_minute: u32, burst: u32, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            requests_per_minute,
+            burst: burst.max(1),
+            max_buckets: MAX_BUCKETS,
+            buckets: Mutex::new(HashMap::new()),
+            clock,
+        }
+    }
+
+    pub fn with_max_buckets(mut self, max_buckets: usize) -> Self {
+        self.max_buckets = max_buckets.max(1);
+        self
+    }
+
+    fn refill_per_sec(&self) -> f64 {
+        self.requests_per_minute as f64 / 60.0
+    }
+
+    /// consumes a token of the client. returns the duration to wait if no tokens remain
+    pub fn check(&self, client_key: &str) -> Result<(), Duration> {
+        let now = self.clock.now();
+        let capacity = self.burst as f64;
+        let refill_per_sec = self.refill_per_sec();
+
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= self.max_buckets && !buckets.contains_key(client_key) {
+            buckets.retain(|_, bucket| {
+                let elapsed = now.saturating_duration_since(bucket.last_refilled);
+                bucket.tokens + elapsed.as_secs_f64() * refill_per_sec < capacity
+            });
+        }
+        if buckets.len() >= self.max_buckets && !buckets.contains_key(client_key) {
+            let mut last_refilled: Vec<Instant> = buckets
+                .values()
+                .map(|bucket| bucket.last_refilled)
+                .collect();
+            last_refilled.sort_unstable();
+            let oldest_kept = last_refilled[last_refilled.len() / 2];
+            buckets.retain(|_, bucket| bucket.last_refilled > oldest_kept);
+        }
+
+        let bucket = buckets.entry(client_key.to_string()).or_insert(Bucket {
+            tokens: capacity,
+            last_refilled: now,
+        });
+        let elapsed = now.saturating_duration_since(bucket.last_refilled);
+        bucket.tokens = (bucket.tokens + elapsed.as_secs_f64() * refill_per_sec).min(capacity);
+        bucket.last_refilled = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else if refill_per_sec <= 0.0 {
+            Err(Duration::from_secs(60))
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / refill_per_sec,
+            ))
+        }
+    }
+}
+
+/// rejects requests with 429 and `Retry-After` when the client exceeds the limit.
+/// the requests pass through if `limiter` is None
+#[derive(Clone)]
+pub struct RateLimitLayer {
+    limiter: Option<Arc<RateLimiter>>,
+}
+
+impl RateLimitLayer {
+    pub fn new(limiter: Option<Arc<RateLimiter>>) -> Self {
+        Self { limiter }
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimit {
+            inner,
+            limiter: self.limiter.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimit<S> {
+    inner: S,
+    limiter: Option<Arc<RateLimiter>>,
+}
+
+/// the api key only if it is verified. otherwise a client could get a fresh bucket
+/// on every request by changing the key, so the ip address is used
+fn client_key<B>(req: &Request<B>) -> String {
+    let api_keys = req.extensions().get::<Arc<ApiKeys>>();
+    let verified_api_key = req
+        .headers()
+        .get(API_KEY_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|api_key| {
+            api_keys
+                .map(|api_keys| api_keys.is_enabled() && api_keys.verify(api_key))
+                .unwrap_or(false)
+        });
+    if let Some(api_key) = verified_api_key {
+        return format!("key:{}", api_key);
+    }
+
+    match req.extensions().get::<ConnectInfo<SocketAddr>>() {
+        Some(ConnectInfo(addr)) => format!("ip:{}", addr.ip()),
+        None => "unknown".to_string(),
+    }
+}
+
+fn too_many_requests(retry_after: Duration) -> Response {
+    // rounds up not to let clients retry too early
+    let retry_after_sec = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+    let mut response = ApiError::new(
+        StatusCode::TOO_MANY_REQUESTS,
+        "TOO_MANY_REQUESTS",
+        "too many requests",
+    )
+    .into_response();
+    response
+        .headers_mut()
+        .insert(header::RETRY_AFTER, HeaderValue::from(retry_after_sec));
+    response
+}
+
+impl<S, B> Service<Request<B>> for RateLimit<S>
+where
+    S: Service<Request<B>, Response = Response>,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = Either<Ready<Result<Response, S::Error>>, S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        if let Some(limiter) = self.limiter.as_ref() {
+            if let Err(retry_after) = limiter.check(&client_key(&req)) {
+                return Either::Left(future::ready(Ok(too_many_requests(retry_after))));
+            }
+        }
+        Either::Right(self.inner.call(req))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::body::Body;
+    use axum::routing::get;
+    use axum::{AddExtensionLayer, Router};
+    use tower::ServiceExt;
+
+    struct FakeClock(Mutex<Instant>);
+
+    impl FakeClock {
+        fn advance(&self, duration: Duration) {
+            let mut now = self.0.lock().unwrap();
+            *now += duration;
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> Instant {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    #[test]
+    fn reject_over_burst_and_refill() {
+        let clock = Arc::new(FakeClock(Mutex::new(Instant::now())));
+        // 1 token per second
+        let limiter = RateLimiter::with_clock(60, 3, clock.clone());
+
+        for _ in 0..3 {
+            assert!(limiter.check("a").is_ok());
+        }
+        let retry_after = limiter.check("a").unwrap_err();
+        assert_eq!(retry_after, Duration::from_secs(1));
+
+        // the other client has its own bucket
+        assert!(limiter.check("b").is_ok());
+
+        clock.advance(Duration::from_millis(500));
+        assert!(limiter.check("a").is_err());
+
+        clock.advance(Duration::from_millis(500));
+        assert!(limiter.check("a").is_ok());
+        assert!(limiter.check("a").is_err());
+
+        // refilled up to the burst
+        clock.advance(Duration::from_secs(60));
+        for _ in 0..3 {
+            assert!(limiter.check("a").is_ok());
+        }
+        assert!(limiter.check("a").is_err());
+    }
+
+    #[test]
+    fn max_buckets_test() {
+        let clock = Arc::new(FakeClock(Mutex::new(Instant::now())));
+        let limiter = RateLimiter::with_clock(60, 1, clock.clone()).with_max_buckets(4);
+
+        for n in 0..10 {
+            clock.advance(Duration::from_millis(10));
+            assert!(limiter.check(&format!("client{}", n)).is_ok());
+            assert!(limiter.buckets.lock().unwrap().len() <= 4);
+        }
+        // the recent clients keep their buckets
+        assert!(limiter.check("client9").is_err());
+    }
+
+    #[tokio::test]
+    async fn unverified_api_key_test() {
+        let clock = Arc::new(FakeClock(Mutex::new(Instant::now())));
+        let limiter = Arc::new(RateLimiter::with_clock(60, 1, clock));
+        let app = Router::new()
+            .route("/", get(|| async { "ok" }))
+            .layer(RateLimitLayer::new(Some(limiter)))
+            .layer(AddExtensionLayer::new(Arc::new(ApiKeys::new(&[
+                "key1".to_string()
+            ]))));
+
+        let request = |api_key: &str| {
+            Request::builder()
+                .uri("/")
+                .header(API_KEY_HEADER, api_key)
+                .body(Body::empty())
+                .unwrap()
+        };
+        let status = |api_key: &'static str| {
+            let app = app.clone();
+            async move { app.oneshot(request(api_key)).await.unwrap().status() }
+        };
+
+        // the unverified keys share the bucket of the ip address
+        assert_eq!(status("random1").await, StatusCode::OK);
+        assert_eq!(status("random2").await, StatusCode::TOO_MANY_REQUESTS);
+
+        // the verified key has its own bucket
+        assert_eq!(status("key1").await, StatusCode::OK);
+        assert_eq!(status("key1").await, StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[tokio::test]
+    async fn rate_limit_layer_test() {
+        let clock = Arc::new(FakeClock(Mutex::new(Instant::now())));
+        let limiter = Arc::new(RateLimiter::with_clock(60, 2, clock));
+        let app = Router::new()
+            .route("/", get(|| async { "ok" }))
+            .layer(RateLimitLayer::new(Some(limiter)));
+
+        let request = || {
+            Request::builder()
+                .uri("/")
+                .header(API_KEY_HEADER, "key1")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        for _ in 0..2 {
+            let response = app.clone().oneshot(request()).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        let response = app.clone().oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers().get(header::RETRY_AFTER).unwrap(), "1");
+    }
+}
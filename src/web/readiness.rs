@@ -0,0 +1,161 @@
+use super::error::ApiError;
+use crate::config::Config;
+use crate::external_service::spread_sheet::*;
+use axum::{extract::Extension, http::StatusCode, response::IntoResponse, Json};
+use serde_json::json;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// keeps the last result of the canary probe for `ttl` not to hammer google
+pub struct ReadinessCache {
+    ttl: Duration,
+    last_result: Mutex<Option<(Instant, Result<(), String>)>>,
+}
+
+impl ReadinessCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            last_result: Mutex::new(None),
+        }
+    }
+
+    async fn get_or_probe<P, Fut>(&self, probe: P) -> Result<(), String>
+    where
+        P: FnOnce() -> Fut,
+        Fut: Future<Output = Result<(), String>>,
+    {
+        // holding the lock while probing so that concurrent requests wait for a single probe
+        let mut last_result = self.last_result.lock().await;
+        if let Some((checked_at, result)) = last_result.as_ref() {
+            if checked_at.elapsed() < self.ttl {
+                return result.clone();
+            }
+        }
+
+        let result = probe().await;
+        *last_result = Some((Instant::now(), result.clone()));
+        result
+    }
+}
+
+/// the token is always checked. the canary spread sheet is fetched only if it's configured
+async fn check_readiness<P, Fut>(
+    token_available: bool,
+    canary_spread_sheet_id: Option<SpreadSheetId>,
+    cache: &ReadinessCache,
+    probe: P,
+) -> Result<(), String>
+where
+    P: FnOnce(SpreadSheetId) -> Fut,
+    Fut: Future<Output = Result<(), String>>,
+{
+    if !token_available {
+        return Err("access token is not available".to_string());
+    }
+
+    match canary_spread_sheet_id {
+        None => Ok(()),
+        Some(spread_sheet_id) => cache.get_or_probe(|| probe(spread_sheet_id)).await,
+    }
+}
+
+pub async fn readyz<HttpConnector>(
+    Extension(token_manager): Extension<Arc<TokenManager<HttpConnector>>>,
+    Extension(config): Extension<Config>,
+    Extension(cache): Extension<Arc<ReadinessCache>>,
+) -> impl IntoResponse
+where
+    HttpConnector: Clone + Send + Sync + 'static,
+{
+    let token_available = {
+        let token = token_manager.current_token().load();
+        !token.as_str().is_empty() && !token.is_expired()
+    };
+
+    let result = check_readiness(
+        token_available,
+        config
+            .canary_spread_sheet_id
+            .clone()
+            .map(SpreadSheetId::new),
+        &cache,
+        |spread_sheet_id| async move {
+            config
+                .sheets_client(token_manager.clone())
+                .list_sheet_tabs(&spread_sheet_id)
+                .await
+                .map(|_| ())
+                .map_err(|e| format!("failed to get canary spread sheet: {}", e))
+        },
+    )
+    .await;
+
+    match result {
+        Ok(()) => Ok(Json(json!({"status":"ok"}))),
+        Err(e) => {
+            log::warn!("not ready: {}", e);
+            Err(ApiError::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "NOT_READY",
+                e,
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn canary() -> Option<SpreadSheetId> {
+        Some(SpreadSheetId::new("canary".to_string()))
+    }
+
+    #[tokio::test]
+    async fn readiness_with_canary_success() {
+        let cache = ReadinessCache::new(Duration::from_secs(60));
+        let probed = AtomicUsize::new(0);
+
+        for _ in 0..2 {
+            let result = check_readiness(true, canary(), &cache, |_| async {
+                probed.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            })
+            .await;
+            assert!(result.is_ok());
+        }
+        // the second check is served from the cache
+        assert_eq!(probed.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn readiness_with_canary_failure() {
+        let cache = ReadinessCache::new(Duration::from_secs(0));
+        let result = check_readiness(true, canary(), &cache, |_| async {
+            Err("not found".to_string())
+        })
+        .await;
+        assert_eq!(result, Err("not found".to_string()));
+
+        // expired cache is probed again
+        let result = check_readiness(true, canary(), &cache, |_| async { Ok(()) }).await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn readiness_without_canary() {
+        let cache = ReadinessCache::new(Duration::from_secs(60));
+        let result = check_readiness(true, None, &cache, |_| async {
+            panic!("must not be probed without canary")
+        })
+        .await;
+        assert!(result.is_ok());
+
+        let result = check_readiness(false, None, &cache, |_| async { Ok(()) }).await;
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,163 @@
+use axum::{
+    body::Body,
+    http::{HeaderValue, Request},
+    response::Response,
+};
+use futures::future::BoxFuture;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::task::{Context, Poll};
+use std::time::Instant;
+use tower::{Layer, Service};
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// inbound ids longer than this are replaced with a new one
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// correlation id of a request. available to handlers as `Extension<RequestId>`
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestId(pub String);
+
+impl Display for RequestId {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl RequestId {
+    /// honors the inbound `X-Request-Id` if it's valid
+    fn from_request<B>(req: &Request<B>) -> Self {
+        let inbound = req
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty() && v.len() <= MAX_REQUEST_ID_LEN);
+
+        match inbound {
+            Some(request_id) => Self(request_id.to_string()),
+            None => Self(Uuid::new_v4().to_string()),
+        }
+    }
+}
+
+/// assigns a `RequestId` to each request, echoes it back in `X-Request-Id`
+/// and writes an access log line
+#[derive(Clone, Default)]
+pub struct RequestIdLayer;
+
+impl<S> Layer<S> for RequestIdLayer {
+    type Service = RequestIdService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequestIdService { inner }
+    }
+}
+
+#[derive(Clone)]
+pub struct RequestIdService<S> {
+    inner: S,
+}
+
+impl<S> Service<Request<Body>> for RequestIdService<S>
+where
+    S: Service<Request<Body>, Response = Response>,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<Body>) -> Self::Future {
+        let started_at = Instant::now();
+        let request_id = RequestId::from_request(&req);
+        let method = req.method().clone();
+        let path = req.uri().path().to_string();
+        req.extensions_mut().insert(request_id.clone());
+
+        let response_future = self.inner.call(req);
+        Box::pin(async move {
+            let mut response = response_future.await?;
+            log::info!(
+                "request_id={} method={} path={} status={} latency_ms={}",
+                request_id,
+                method,
+                path,
+                response.status().as_u16(),
+                started_at.elapsed().as_millis()
+            );
+
+            if let Ok(header_value) = HeaderValue::from_str(&request_id.0) {
+                response
+                    .headers_mut()
+                    .insert(REQUEST_ID_HEADER, header_value);
+            }
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::extract::Extension;
+    use axum::routing::get;
+    use axum::Router;
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        Router::new()
+            .route(
+                "/",
+                get(|Extension(request_id): Extension<RequestId>| async move { request_id.0 }),
+            )
+            .layer(RequestIdLayer)
+    }
+
+    #[tokio::test]
+    async fn assign_request_id() {
+        let response = app()
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        let request_id = response
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert!(Uuid::parse_str(&request_id).is_ok());
+
+        // the handler sees the same id
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(body, request_id.as_bytes());
+    }
+
+    #[tokio::test]
+    async fn preserve_inbound_request_id() {
+        let response = app()
+            .oneshot(
+                Request::builder()
+                    .uri("/")
+                    .header(REQUEST_ID_HEADER, "inbound-id-123")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            response.headers().get(REQUEST_ID_HEADER).unwrap(),
+            "inbound-id-123"
+        );
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(body, "inbound-id-123".as_bytes());
+    }
+}
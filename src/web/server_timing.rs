@@ -0,0 +1,103 @@
+use axum::http::HeaderValue;
+use axum::response::Response;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+const SERVER_TIMING_HEADER: &str = "server-timing";
+
+/// durations of each phase of a request, returned as `Server-Timing` header
+/// e.g. `meta;dur=120, header;dur=80, value;dur=95`
+#[derive(Debug, Default)]
+pub struct ServerTiming {
+    phases: Vec<(&'static str, Duration)>,
+}
+
+impl ServerTiming {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn measure<F: Future>(&mut self, phase: &'static str, f: F) -> F::Output {
+        let started_at = Instant::now();
+        let result = f.await;
+        self.phases.push((phase, started_at.elapsed()));
+        result
+    }
+
+    pub fn header_value(&self) -> String {
+        self.phases
+            .iter()
+            .map(|(phase, duration)| format!("{};dur={}", phase, duration.as_millis()))
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+
+    pub fn attach(&self, mut response: Response) -> Response {
+        if self.phases.is_empty() {
+            return response;
+        }
+        if let Ok(header_value) = HeaderValue::from_str(&self.header_value()) {
+            response
+                .headers_mut()
+                .insert(SERVER_TIMING_HEADER, header_value);
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::response::IntoResponse;
+    use tokio::time::sleep;
+
+    /// dur of each phase in the header
+    fn parse_durations(header_value: &str) -> Vec<(String, u128)> {
+        header_value
+            .split(", ")
+            .map(|each| {
+                let (phase, dur) = each.split_once(";dur=").unwrap();
+                (phase.to_string(), dur.parse().unwrap())
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn server_timing_test() {
+        let mut timing = ServerTiming::new();
+        timing
+            .measure("meta", sleep(Duration::from_millis(20)))
+            .await;
+        let header_result = timing
+            .measure("header", async {
+                sleep(Duration::from_millis(10)).await;
+                "header"
+            })
+            .await;
+        assert_eq!(header_result, "header");
+        timing
+            .measure("value", sleep(Duration::from_millis(30)))
+            .await;
+
+        let response = timing.attach("body".into_response());
+        let header_value = response
+            .headers()
+            .get(SERVER_TIMING_HEADER)
+            .unwrap()
+            .to_str()
+            .unwrap();
+
+        let durations = parse_durations(header_value);
+        let phases: Vec<&str> = durations.iter().map(|(phase, _)| phase.as_str()).collect();
+        assert_eq!(phases, vec!["meta", "header", "value"]);
+        assert!(durations[0].1 >= 20);
+        assert!(durations[1].1 >= 10);
+        assert!(durations[2].1 >= 30);
+    }
+
+    #[test]
+    fn no_phase_test() {
+        let response = ServerTiming::new().attach("body".into_response());
+        assert!(response.headers().get(SERVER_TIMING_HEADER).is_none());
+    }
+}
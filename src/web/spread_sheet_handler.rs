@@ -1,176 +1,1341 @@
+use crate::config::Config;
 use crate::external_service::spread_sheet::*;
 use crate::json_structure;
+use crate::web::error::ApiError;
+use crate::web::request_id::RequestId;
+use crate::web::server_timing::ServerTiming;
+use crate::web::xml::{to_xml, XML_CONTENT_TYPE};
 use axum::{
+    body::StreamBody,
     extract::{Extension, Path, Query},
-    http::StatusCode,
-    response::IntoResponse,
+    http::{header, HeaderMap, HeaderValue, StatusCode, Uri},
+    response::{IntoResponse, Response},
     Json,
 };
+use futures::channel::mpsc;
+use futures::SinkExt;
+use once_cell::sync::OnceCell;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use serde_json::Value as JsonValue;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::str::FromStr;
 use std::sync::Arc;
 
+const TOTAL_ROWS_HEADER: &str = "x-total-rows";
+
+const MAX_HEADER_ROWS: usize = 5;
+
+const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+const NDJSON_CHANNEL_BUFFER: usize = 64;
+
+const JSONP_CONTENT_TYPE: &str = "application/javascript";
+static JSONP_CALLBACK_RE: OnceCell<Regex> = OnceCell::new();
+
 // The query parameters for todos index
 #[derive(Debug, Deserialize, Default)]
 pub struct GetSpreadSheetQuery {
     pub sheet_id: Option<u32>,
+    /// percent-encoded like `Q3%20%26%20Q4` or `a%2Fb`. `+` must be `%2B`, a raw `+` is a space.
+    /// decoded once by the query extractor, so a name like `100%25` is kept as `100%`
     pub sheet_name: Option<String>,
+    /// 0-based position of the tab. used if neither `sheet_id` nor `sheet_name` is passed
+    pub sheet_index: Option<usize>,
     pub offset: Option<usize>,
     pub limit: Option<usize>,
     pub row: Option<usize>,
+    /// indices of the rows like `row`. e.g. `3,7,12`
+    pub rows: Option<String>,
+    /// `next_cursor` of the previous response. preferred to offset and limit
+    pub cursor: Option<String>,
+    /// reads only the range like `B2:F50`. the first row of the range is the header row.
+    /// `header_row` is ignored if passed
+    pub range: Option<String>,
+    /// comma separated ranges of a same width like `A1:C10,E1:G10` read at once.
+    /// the rows are concatenated under the headers of the first range
+    pub ranges: Option<String>,
+    /// 1-based row number of the header row. defaults to 1
+    pub header_row: Option<usize>,
+    /// number of the header rows. the values are joined into dotted keys. defaults to 1
+    pub header_rows: Option<usize>,
+    /// `rows`(default) or `key_value`. `key_value` reads the labels down the column A
+    /// and each column right of the labels becomes a record
+    pub orientation: Option<String>,
+    pub trim_headers: Option<bool>,
+    pub lowercase_headers: Option<bool>,
+    /// returns headers and rows as they are without building json objects
+    pub raw: Option<bool>,
+    /// groups the rows by the value of the column
+    pub group_by: Option<String>,
+    /// renames headers. e.g. `Full Name:name,DOB:birthday`
+    pub rename: Option<String>,
+    /// values of empty cells for each column. e.g. `status:active,count:0`
+    pub defaults: Option<String>,
+    /// returns `{"<value of the first column>": {...rest of the columns}}`
+    pub first_col_as_key: Option<bool>,
+    /// converts numeric strings into numbers
+    pub coerce_numbers: Option<bool>,
+    /// keeps integers beyond 2^53-1 as strings
+    pub big_int_as_string: Option<bool>,
+    /// headers like `user` and `user.name` are built into `{"user":{"_value":..,"name":..}}`
+    pub lenient_structure: Option<bool>,
+    /// the headers which can't be built into the json are dropped with `warnings` instead of an error
+    pub skip_invalid_headers: Option<bool>,
+    /// `array`(default) merges the duplicated headers into an array.
+    /// `suffix` keeps them as `favorite`, `favorite_2`...
+    pub dup_headers: Option<String>,
+    /// `left_to_right`(default) or `right_to_left`. the order of the values of the duplicated headers in the array
+    pub array_order: Option<String>,
+    /// comma separated dot paths kept in the json of each row like `name,address.city`
+    pub project: Option<String>,
+    /// `iso` converts the serial numbers of `date_columns` into iso 8601 dates.
+    /// the values are read unformatted, so numbers are returned as numbers
+    pub dates: Option<String>,
+    /// comma separated headers of the date columns
+    pub date_columns: Option<String>,
+    /// time zone of the dates with time like `Asia/Tokyo`. defaults to the time zone of the spread sheet
+    pub tz: Option<String>,
+    /// `json`(default), `ndjson` or `xml`. `xml` is also chosen by `Accept: application/xml`
+    pub format: Option<String>,
+    /// emits `{"_meta":{...}}` as the first line of the ndjson stream
+    pub manifest: Option<bool>,
+    /// wraps the json response as `callback(<json>);` (JSONP)
+    pub callback: Option<String>,
+    /// returns the first row as an object. `row` is preferred if both are passed
+    pub single: Option<bool>,
+    /// `formula` returns the formulas like `=SUM(A1:A2)` instead of the computed values.
+    /// the headers are read formatted anyway
+    pub render: Option<String>,
+    /// `false` returns the short rows at their natural length instead of filling them with `""`.
+    /// the missing trailing columns are absent keys of the json. defaults to true
+    pub pad: Option<bool>,
+    /// `absent`(default) drops the keys of the columns beyond the short rows. `null` returns them as null
+    pub missing: Option<String>,
+    /// fetches the tabs of the spread sheet bypassing the cache of `SHEET_CACHE_TTL_SEC`
+    pub fresh: Option<bool>,
+    /// bounds the rows by the last non-empty cell of the first header column instead of the grid size.
+    /// it costs one more request to scan the column
+    pub accurate_rows: Option<bool>,
+    /// `false` drops the rows whose cells are all empty. the dropped rows still count against
+    /// `limit`, so a page may have fewer rows and the next cursor is not shifted. defaults to true
+    pub include_empty_rows: Option<bool>,
+}
+
+/// how to shape the response from the fetched sheet values
+#[derive(Debug, Default)]
+pub struct OutputOption {
+    pub json: JsonOption,
+    pub raw: bool,
+    pub group_by: Option<String>,
+    /// header -> value of empty cells
+    pub defaults: HashMap<String, String>,
+    /// streams the rows as json lines
+    pub ndjson: bool,
+    /// the same structure as json in xml
+    pub xml: bool,
+    /// the first line of the ndjson stream is a manifest
+    pub manifest: bool,
+    /// headers of the date columns with `?dates=iso`
+    pub date_columns: Vec<String>,
+    pub time_zone: Option<String>,
+    /// validated by `jsonp_callback_regex`
+    pub callback: Option<String>,
+    /// `?single=true` without `?row`. the rows except the first are dropped with a warning
+    pub first_row_only: bool,
+    /// the `Link` of the next and the previous pages are built from it
+    pub request_uri: Option<Uri>,
+}
+
+/// javascript identifiers only, so that the callback can't inject any script
+fn jsonp_callback_regex() -> &'static Regex {
+    JSONP_CALLBACK_RE.get_or_init(|| Regex::new(r"^[A-Za-z_$][A-Za-z0-9_$]{0,127}$").unwrap())
+}
+
+/// "Full Name:name,DOB:birthday" -> {"Full Name":"name", "DOB":"birthday"}
+fn parse_rename_param(rename: &str) -> Result<HashMap<String, String>, String> {
+    parse_map_param("rename", rename)
+}
+
+/// "status:active,count:0" -> {"status":"active", "count":"0"}
+fn parse_defaults_param(defaults: &str) -> Result<HashMap<String, String>, String> {
+    parse_map_param("defaults", defaults)
+}
+
+/// "created_at, birthday" -> ["created_at", "birthday"]
+fn parse_list_param(param: &str) -> Vec<String> {
+    param
+        .split(',')
+        .map(|each| each.trim().to_string())
+        .filter(|each| !each.is_empty())
+        .collect()
+}
+
+/// "3,7,12" -> [3, 7, 12]
+fn parse_rows_param(rows: &str) -> Result<Vec<usize>, String> {
+    rows.split(',')
+        .map(|each| {
+            each.trim().parse::<usize>().map_err(|_| {
+                format!(
+                    "query parameter rows is invalid. row indices are expected but {}",
+                    each
+                )
+            })
+        })
+        .collect()
+}
+
+fn parse_map_param(param_name: &str, param: &str) -> Result<HashMap<String, String>, String> {
+    let mut result = HashMap::new();
+    for each in param.split(',') {
+        match each.split_once(':') {
+            Some((from, to)) if !from.trim().is_empty() && !to.trim().is_empty() => {
+                result.insert(from.trim().to_string(), to.trim().to_string());
+            }
+            _ => {
+                return Err(format!(
+                    "query parameter {} is invalid. `key:value` is expected but {}",
+                    param_name, each
+                ))
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// the default of the option if the query parameter is not given
+fn parse_enum_param<T>(param_name: &str, param: Option<&str>) -> Result<T, String>
+where
+    T: Default + FromStr,
+{
+    match param {
+        None => Ok(T::default()),
+        Some(param) => T::from_str(param)
+            .map_err(|_| format!("query parameter {} is invalid:{}", param_name, param)),
+    }
 }
 
 impl GetSpreadSheetQuery {
-    fn return_as_single_obj(&self) -> bool {
-        self.row.is_some()
+    fn as_output_option(&self, config: &Config) -> Result<OutputOption, String> {
+        let rename = match self.rename.as_ref() {
+            None => HashMap::new(),
+            Some(rename) => parse_rename_param(rename)?,
+        };
+        let defaults = match self.defaults.as_ref() {
+            None => HashMap::new(),
+            Some(defaults) => parse_defaults_param(defaults)?,
+        };
+
+        let first_col_as_key = self.first_col_as_key.unwrap_or(config.first_col_as_key);
+        if first_col_as_key && self.group_by.is_some() {
+            return Err(
+                "query parameter group_by can not be used with first_col_as_key".to_string(),
+            );
+        }
+
+        let first_row_only = self.row.is_none() && self.single.unwrap_or(false);
+
+        let dup_headers = parse_enum_param("dup_headers", self.dup_headers.as_deref())?;
+
+        let projection = match self.project.as_ref() {
+            None => None,
+            Some(_) if self.raw.unwrap_or(false) => {
+                return Err("query parameter project can not be used with raw".to_string())
+            }
+            Some(project) => Some(parse_list_param(project)),
+        };
+
+        let array_order = parse_enum_param("array_order", self.array_order.as_deref())?;
+        let missing_values = parse_enum_param("missing", self.missing.as_deref())?;
+
+        let (ndjson, xml) = match self.format.as_deref() {
+            None | Some("json") => (false, false),
+            Some("ndjson") => (true, false),
+            Some("xml") => (false, true),
+            Some(format) => return Err(format!("query parameter format is invalid:{}", format)),
+        };
+        if xml && self.raw.unwrap_or(false) {
+            return Err("format=xml can not be used with raw".to_string());
+        }
+
+        let callback = match self.callback.as_ref() {
+            None => None,
+            Some(_) if ndjson || xml => {
+                return Err(
+                    "query parameter callback is available only with format=json".to_string(),
+                )
+            }
+            Some(callback) if jsonp_callback_regex().is_match(callback) => Some(callback.clone()),
+            Some(callback) => {
+                return Err(format!("query parameter callback is invalid:{}", callback))
+            }
+        };
+        let manifest = self.manifest.unwrap_or(false);
+        if manifest && !ndjson {
+            return Err(
+                "query parameter manifest is available only with format=ndjson".to_string(),
+            );
+        }
+        if ndjson && (self.raw.unwrap_or(false) || self.group_by.is_some() || first_col_as_key) {
+            return Err(
+                "format=ndjson can not be used with raw, group_by or first_col_as_key".to_string(),
+            );
+        }
+        if first_row_only && (ndjson || self.group_by.is_some()) {
+            return Err(
+                "query parameter single can not be used with format=ndjson or group_by".to_string(),
+            );
+        }
+
+        let date_columns = match (self.dates.as_deref(), self.date_columns.as_ref()) {
+            (None, None) => vec![],
+            (Some("iso"), Some(date_columns)) => parse_list_param(date_columns),
+            (Some("iso"), None) => {
+                return Err("query parameter dates=iso requires date_columns".to_string())
+            }
+            (None, Some(_)) => {
+                return Err(
+                    "query parameter date_columns is available only with dates=iso".to_string(),
+                )
+            }
+            (Some(dates), _) => return Err(format!("query parameter dates is invalid:{}", dates)),
+        };
+
+        Ok(OutputOption {
+            json: JsonOption {
+                as_single_obj: self.row.is_some() || first_row_only,
+                group_by_col_idx: None,
+                rename,
+                first_col_as_key,
+                coerce: CoerceOption {
+                    numbers: self.coerce_numbers.unwrap_or(false),
+                    big_int_as_string: self.big_int_as_string.unwrap_or(false),
+                },
+                lenient_structure: self.lenient_structure.unwrap_or(false),
+                skip_invalid_headers: self.skip_invalid_headers.unwrap_or(false),
+                dates: None,
+                dup_headers,
+                array_order,
+                missing_values,
+                projection,
+            },
+            raw: self.raw.unwrap_or(false),
+            group_by: self.group_by.clone(),
+            defaults,
+            ndjson,
+            xml,
+            manifest,
+            date_columns,
+            time_zone: self.tz.clone(),
+            callback,
+            first_row_only,
+            request_uri: None,
+        })
     }
-    fn as_header_sheet_meta(&self, spread_sheet_id: SpreadSheetId) -> SheetMeta {
+    /// the sheet name of `range` like `Sheet1!B2:F50` is used
+    /// if neither `sheet_id` nor `sheet_name` is passed
+    fn as_header_sheet_meta(
+        &self,
+        spread_sheet_id: SpreadSheetId,
+        range: Option<&RangeRef>,
+    ) -> SheetMeta {
+        let sheet_name = match (&self.sheet_id, &self.sheet_name, &self.sheet_index) {
+            (None, None, None) => range.and_then(|range| range.sheet_name.clone()),
+            _ => self.sheet_name.clone(),
+        };
         SheetMeta::new(
             spread_sheet_id.into_inner(),
             self.sheet_id.clone(),
-            self.sheet_name.clone(),
+            sheet_name,
         )
+        .with_tab_sheet_index(self.sheet_index)
+    }
+
+    fn as_cell_range(&self, config: &Config) -> Result<Option<RangeRef>, ApiError> {
+        self.range
+            .as_ref()
+            .map(|range| parse_range_param(range, config.max_range_cells))
+            .transpose()
+    }
+
+    /// `?ranges=` can't be used with the other ways to choose the rows.
+    /// each range starts with the header rows
+    fn as_cell_ranges(&self, config: &Config) -> Result<Option<Vec<RangeRef>>, ApiError> {
+        let ranges = match self.ranges.as_ref() {
+            None => return Ok(None),
+            Some(ranges) => ranges,
+        };
+        if self.range.is_some()
+            || self.row.is_some()
+            || self.rows.is_some()
+            || self.cursor.is_some()
+            || self.offset.is_some()
+            || self.limit.is_some()
+        {
+            return Err(ApiError::invalid_query(
+                "query parameter ranges can not be used with range, row, rows, cursor, offset or limit"
+                    .to_string(),
+            ));
+        }
+        if self.orientation.as_deref() == Some("key_value") {
+            return Err(ApiError::invalid_query(
+                "orientation=key_value can not be used with ranges".to_string(),
+            ));
+        }
+
+        let ranges = ranges
+            .split(',')
+            .map(|range| range.trim())
+            .filter(|range| !range.is_empty())
+            .map(|range| parse_range_param(range, config.max_range_cells))
+            .collect::<Result<Vec<RangeRef>, ApiError>>()?;
+        validate_cell_ranges(&ranges, config.max_range_cells)?;
+        Ok(Some(ranges))
     }
 
-    fn as_row_search_condition(&self) -> FetchRowCondition {
-        if let Some(row) = self.row {
+    fn as_header_option(&self, config: &Config) -> Result<HeaderOption, String> {
+        let header_row_index = match self.header_row {
+            None => 0,
+            Some(0) => return Err("query parameter header_row must be 1 or greater".to_string()),
+            Some(header_row) => header_row - 1,
+        };
+        let header_rows = match self.header_rows {
+            None => 1,
+            Some(header_rows) if header_rows < 1 || MAX_HEADER_ROWS < header_rows => {
+                return Err(format!(
+                    "query parameter header_rows must be between 1 and {}",
+                    MAX_HEADER_ROWS
+                ))
+            }
+            Some(header_rows) => header_rows,
+        };
+        let orientation: HeaderOrientation =
+            parse_enum_param("orientation", self.orientation.as_deref())?;
+        if orientation == HeaderOrientation::KeyValue && (header_rows > 1 || self.rows.is_some()) {
+            return Err(
+                "orientation=key_value can not be used with header_rows or rows".to_string(),
+            );
+        }
+
+        Ok(HeaderOption {
+            header_row_index,
+            header_rows,
+            trim: self.trim_headers.unwrap_or(config.trim_headers),
+            lowercase: self.lowercase_headers.unwrap_or(config.lowercase_headers),
+            orientation,
+            restrictions: config.restrictions(),
+        })
+    }
+
+    fn as_row_search_condition(&self, config: &Config) -> Result<FetchRowCondition, String> {
+        let condition = if let Some(row) = self.row {
             FetchRowCondition::with_specific_row_idx(row)
+        } else if let Some(rows) = self.rows.as_ref() {
+            FetchRowCondition::with_row_indices(parse_rows_param(rows)?)
+        } else if let Some(cursor) = self.cursor.as_ref() {
+            let cursor = Cursor::decode(cursor).map_err(|e| e.to_string())?;
+            FetchRowCondition::with_cursor(&cursor)
         } else {
             FetchRowCondition::with_pagination(self.offset, self.limit)
+        };
+
+        let formulas = match self.render.as_deref() {
+            None | Some("value") => false,
+            Some("formula") if self.dates.is_some() => {
+                return Err("query parameter render=formula can not be used with dates".to_string())
+            }
+            Some("formula") => true,
+            Some(render) => return Err(format!("query parameter render is invalid:{}", render)),
+        };
+        Ok(condition
+            .with_row_limits(config.row_limits())
+            .with_formulas(formulas)
+            .with_pad(self.pad.unwrap_or(true))
+            .with_accurate_rows(self.accurate_rows.unwrap_or(false))
+            .with_include_empty_rows(self.include_empty_rows.unwrap_or(true)))
+    }
+}
+
+/// parses `?range=` and rejects it before any google api call
+/// when the range is larger than `max_range_cells`
+fn parse_range_param(range: &str, max_range_cells: usize) -> Result<RangeRef, ApiError> {
+    let range_ref = RangeRef::from_str(range).map_err(|e| {
+        ApiError::bad_request(
+            "INVALID_RANGE",
+            format!("query parameter range is invalid {}", e),
+        )
+    })?;
+
+    if range_ref.num_cells() > max_range_cells {
+        return Err(ApiError::bad_request(
+            "RANGE_TOO_LARGE",
+            format!(
+                "query parameter range is too large. max cells is {}, passed {} ({})",
+                max_range_cells,
+                range_ref.num_cells(),
+                range_ref
+            ),
+        ));
+    }
+    Ok(range_ref)
+}
+
+/// the ranges of `?ranges=` must be on a same sheet and have the same number of columns
+fn validate_cell_ranges(ranges: &[RangeRef], max_range_cells: usize) -> Result<(), ApiError> {
+    let first = match ranges.first() {
+        None => {
+            return Err(ApiError::bad_request(
+                "INVALID_RANGE",
+                "query parameter ranges is empty".to_string(),
+            ))
+        }
+        Some(first) => first,
+    };
+
+    for range in ranges.iter().skip(1) {
+        if range.sheet_name.is_some() && range.sheet_name != first.sheet_name {
+            return Err(ApiError::bad_request(
+                "INVALID_RANGE",
+                format!(
+                    "query parameter ranges must be on the sheet of the first range {}, passed {}",
+                    first, range
+                ),
+            ));
         }
+        if range.col_range_size() != first.col_range_size() {
+            return Err(ApiError::bad_request(
+                "INCOMPATIBLE_RANGES",
+                format!(
+                    "query parameter ranges must have the same number of columns. {} has {}, {} has {}",
+                    first,
+                    first.col_range_size(),
+                    range,
+                    range.col_range_size()
+                ),
+            ));
+        }
+    }
+
+    let num_cells = ranges
+        .iter()
+        .fold(0usize, |sum, range| sum.saturating_add(range.num_cells()));
+    if num_cells > max_range_cells {
+        return Err(ApiError::bad_request(
+            "RANGE_TOO_LARGE",
+            format!(
+                "query parameter ranges is too large. max cells is {}, passed {}",
+                max_range_cells, num_cells
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// the header cells of `?range=` and the index of the last record in the range
+fn split_cell_range(
+    range: &RangeRef,
+    header_option: &HeaderOption,
+) -> Result<((CellRef, CellRef), usize), ApiError> {
+    match header_option.orientation {
+        HeaderOrientation::Rows => Ok((
+            header_cells_of_range(range, header_option.header_row_count())?,
+            range.end.row_index,
+        )),
+        // the labels are the first column of the range and the records are the columns
+        HeaderOrientation::KeyValue => Ok((
+            (range.start.clone(), range.end.clone()),
+            range.end.col_index,
+        )),
     }
 }
 
+/// the header cells of `?range=` and `row_search_condition` not to read the records beyond the range
+fn within_cell_range(
+    range: Option<&RangeRef>,
+    header_option: &HeaderOption,
+    row_search_condition: FetchRowCondition,
+) -> Result<(Option<(CellRef, CellRef)>, FetchRowCondition), ApiError> {
+    let range = match range {
+        None => return Ok((None, row_search_condition)),
+        Some(range) => range,
+    };
+    let (header_cells, last_record_idx) = split_cell_range(range, header_option)?;
+    let row_search_condition = match header_option.orientation {
+        HeaderOrientation::Rows => row_search_condition.with_last_row_idx(last_record_idx),
+        HeaderOrientation::KeyValue => row_search_condition.with_last_col_idx(last_record_idx),
+    };
+    Ok((Some(header_cells), row_search_condition))
+}
+
+/// the first `header_row_count` rows of `?range=` are the headers
+fn header_cells_of_range(
+    range: &RangeRef,
+    header_row_count: usize,
+) -> Result<(CellRef, CellRef), ApiError> {
+    if range.row_range_size() < header_row_count {
+        return Err(ApiError::bad_request(
+            "INVALID_RANGE",
+            format!(
+                "query parameter range {} must include {} header rows",
+                range, header_row_count
+            ),
+        ));
+    }
+    Ok((
+        range.start.clone(),
+        CellRef::new(
+            range.end.col_index,
+            range.start.row_index + header_row_count - 1,
+        ),
+    ))
+}
+
+/// `?format=` is preferred to `Accept`
+fn accepts_only_xml(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .map(|accept| {
+            let media_types: Vec<&str> = accept
+                .split(',')
+                .map(|media_type| media_type.split(';').next().unwrap_or("").trim())
+                .collect();
+            media_types
+                .iter()
+                .any(|media_type| *media_type == XML_CONTENT_TYPE || *media_type == "text/xml")
+                && !media_types
+                    .iter()
+                    .any(|media_type| *media_type == "application/json")
+        })
+        .unwrap_or(false)
+}
+
 pub async fn get_spread_sheet_value<HttpConnector>(
     Path(spread_sheet_id): Path<SpreadSheetId>,
-    query: Query<GetSpreadSheetQuery>,
+    Query(mut query): Query<GetSpreadSheetQuery>,
+    request_uri: Uri,
+    request_headers: HeaderMap,
     Extension(token_manager): Extension<Arc<TokenManager<HttpConnector>>>,
+    Extension(config): Extension<Config>,
+    Extension(request_id): Extension<RequestId>,
 ) -> impl IntoResponse
 where
     HttpConnector: Clone + Send + Sync + 'static,
 {
-    let sheet_meta = query.as_header_sheet_meta(spread_sheet_id);
-    inner_get_spread_sheet_value(
+    if query.format.is_none() && accepts_only_xml(&request_headers) {
+        query.format = Some("xml".to_string());
+    }
+    let header_option = match query.as_header_option(&config) {
+        Err(e) => return Err(ApiError::invalid_query(e)),
+        Ok(v) => v,
+    };
+    let mut output_option = match query.as_output_option(&config) {
+        Err(e) => return Err(ApiError::invalid_query(e)),
+        Ok(v) => v,
+    };
+    output_option.request_uri = Some(request_uri);
+    let row_search_condition = match query.as_row_search_condition(&config) {
+        Err(e) => return Err(ApiError::invalid_query(e)),
+        Ok(v) => v,
+    };
+    let row_search_condition =
+        row_search_condition.with_unformatted_values(!output_option.date_columns.is_empty());
+    // the headers are of the first range of `?ranges=`
+    let cell_ranges = query.as_cell_ranges(&config)?;
+    let cell_range = match cell_ranges.as_ref() {
+        Some(ranges) => ranges.first().cloned(),
+        None => query.as_cell_range(&config)?,
+    };
+    let (specified_cell_range, row_search_condition) =
+        within_cell_range(cell_range.as_ref(), &header_option, row_search_condition)?;
+    let row_search_condition = match cell_ranges {
+        Some(ranges) => row_search_condition.with_ranges(ranges),
+        None => row_search_condition,
+    };
+    let sheet_meta = query.as_header_sheet_meta(spread_sheet_id, cell_range.as_ref());
+    let spread_sheet_id = sheet_meta.spread_sheet_id.clone();
+    let result = inner_get_spread_sheet_value(
         sheet_meta,
-        query.as_row_search_condition(),
-        query.return_as_single_obj(),
-        token_manager.clone(),
+        specified_cell_range,
+        header_option,
+        row_search_condition,
+        output_option,
+        config
+            .sheets_client(token_manager.clone())
+            .with_fresh_sheet(query.fresh.unwrap_or(false)),
     )
-    .await
+    .await;
+
+    if let Err(error) = &result {
+        log::warn!(
+            "request_id={} spread_sheet_id={} status={} code={} error={}",
+            request_id,
+            spread_sheet_id,
+            error.status.as_u16(),
+            error.code,
+            error.message
+        );
+    }
+    result
 }
 
-pub async fn inner_get_spread_sheet_value<HttpConnector>(
-    sheet_meta: SheetMeta,
-    row_search_condition: FetchRowCondition,
-    return_as_single_obj: bool,
-    token_manager: Arc<TokenManager<HttpConnector>>,
+/// appends a row from a json object keyed by the headers.
+/// the query parameters to find the headers are the same as `get_spread_sheet_value`
+pub async fn post_spread_sheet_row<HttpConnector>(
+    Path(spread_sheet_id): Path<SpreadSheetId>,
+    query: Query<GetSpreadSheetQuery>,
+    Extension(token_manager): Extension<Arc<TokenManager<HttpConnector>>>,
+    Extension(config): Extension<Config>,
+    Extension(request_id): Extension<RequestId>,
+    Json(row): Json<JsonValue>,
 ) -> impl IntoResponse
 where
     HttpConnector: Clone + Send + Sync + 'static,
 {
-    let header_search_condition =
-        create_header_condition_from_sheet_meta(token_manager.clone(), sheet_meta, None).await;
+    require_writable(&config)?;
 
-    let header_search_condition = match header_search_condition {
+    let header_option = match query.as_header_option(&config) {
+        Err(e) => return Err(ApiError::invalid_query(e)),
+        Ok(v) => v,
+    };
+    let sheet_meta = query.as_header_sheet_meta(spread_sheet_id, None);
+    let spread_sheet_id = sheet_meta.spread_sheet_id.clone();
+
+    let sheets_client = config.sheets_client(token_manager.clone());
+    let result = match sheets_client
+        .create_header_condition_from_sheet_meta(sheet_meta, None, header_option)
+        .await
+    {
+        Err(e) => Err(e),
+        Ok(header_search_condition) => {
+            sheets_client
+                .append_sheet_row(&header_search_condition, &row)
+                .await
+        }
+    };
+
+    match result {
         Err(e) => {
-            if e.is_not_found() {
-                return Err((
-                    StatusCode::NOT_FOUND,
-                    Json(json!({"error_message":e.to_string()})),
-                ));
-            } else {
-                return Err((
-                    StatusCode::BAD_REQUEST,
-                    Json(json!({"error_message":e.to_string()})),
-                ));
+            log::warn!(
+                "request_id={} spread_sheet_id={} error={}",
+                request_id,
+                spread_sheet_id,
+                e
+            );
+            Err(ApiError::from(&e))
+        }
+        Ok(appended) => Ok((
+            StatusCode::CREATED,
+            Json(json!({
+                "data": {
+                    "updated_range": appended.updates.and_then(|updates| updates.updated_range),
+                }
+            })),
+        )),
+    }
+}
+
+/// resolves `date_columns` into the column indices of the headers
+fn as_date_option(
+    output_option: &OutputOption,
+    headers: &RawHeaders,
+    sheet_info: &Sheet,
+) -> Result<Option<DateOption>, ApiError> {
+    if output_option.date_columns.is_empty() {
+        return Ok(None);
+    }
+
+    let tz = resolve_time_zone(output_option.time_zone.as_deref(), sheet_info)
+        .map_err(|e| ApiError::bad_request("INVALID_TIME_ZONE", e.to_string()))?;
+
+    let mut col_indices = vec![];
+    for date_column in output_option.date_columns.iter() {
+        match headers.column_index_of(date_column) {
+            None => {
+                return Err(ApiError::bad_request(
+                    "COLUMN_NOT_FOUND",
+                    format!("date column not found:{}", date_column),
+                ))
             }
+            Some(col_idx) => col_indices.push(col_idx),
+        }
+    }
+    Ok(Some(DateOption { col_indices, tz }))
+}
+
+/// writing requires `sheets` of `SCOPES` (or the deprecated `SHEET_SCOPE=readwrite`)
+fn require_writable(config: &Config) -> Result<(), ApiError> {
+    if config.is_writable() {
+        Ok(())
+    } else {
+        Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            "READ_ONLY_SCOPE",
+            "writing to sheets is not allowed with the read only scope",
+        ))
+    }
+}
+
+pub async fn get_spread_sheet_tabs<HttpConnector>(
+    Path(spread_sheet_id): Path<SpreadSheetId>,
+    Extension(token_manager): Extension<Arc<TokenManager<HttpConnector>>>,
+    Extension(config): Extension<Config>,
+    Extension(request_id): Extension<RequestId>,
+) -> impl IntoResponse
+where
+    HttpConnector: Clone + Send + Sync + 'static,
+{
+    match config
+        .sheets_client(token_manager.clone())
+        .list_sheet_tabs(&spread_sheet_id)
+        .await
+    {
+        Err(e) => {
+            log::warn!(
+                "request_id={} spread_sheet_id={} error={}",
+                request_id,
+                spread_sheet_id,
+                e
+            );
+            Err(ApiError::from(&e))
         }
+        Ok(tabs) => Ok(Json(json!({ "data": tabs }))),
+    }
+}
+
+/// the headers and the json structure built from them. no rows are read
+pub async fn get_spread_sheet_schema<HttpConnector>(
+    Path(spread_sheet_id): Path<SpreadSheetId>,
+    query: Query<GetSpreadSheetQuery>,
+    Extension(token_manager): Extension<Arc<TokenManager<HttpConnector>>>,
+    Extension(config): Extension<Config>,
+    Extension(request_id): Extension<RequestId>,
+) -> impl IntoResponse
+where
+    HttpConnector: Clone + Send + Sync + 'static,
+{
+    let header_option = match query.as_header_option(&config) {
+        Err(e) => return Err(ApiError::invalid_query(e)),
+        Ok(v) => v,
+    };
+    let output_option = match query.as_output_option(&config) {
+        Err(e) => return Err(ApiError::invalid_query(e)),
         Ok(v) => v,
     };
+    let cell_range = query.as_cell_range(&config)?;
+    let specified_cell_range = match cell_range.as_ref() {
+        None => None,
+        Some(range) => Some(split_cell_range(range, &header_option)?.0),
+    };
+    let sheet_meta = query.as_header_sheet_meta(spread_sheet_id, cell_range.as_ref());
+    let spread_sheet_id = sheet_meta.spread_sheet_id.clone();
 
-    let sheet_response = fetch_sheet_value(
-        token_manager.clone(),
-        &header_search_condition,
-        &row_search_condition,
-    )
-    .await;
+    let sheets_client = config
+        .sheets_client(token_manager.clone())
+        .with_fresh_sheet(query.fresh.unwrap_or(false));
+    let headers = match sheets_client
+        .create_header_condition_from_sheet_meta(sheet_meta, specified_cell_range, header_option)
+        .await
+    {
+        Err(e) => Err(e),
+        Ok(header_search_condition) => {
+            sheets_client
+                .read_sheet_headers(&header_search_condition)
+                .await
+        }
+    };
 
-    let mut sheet_response = match sheet_response {
-        Err(e) => {
-            if e.is_not_found() {
-                return Err((
-                    StatusCode::NOT_FOUND,
-                    Json(json!({"error_message":e.to_string()})),
-                ));
-            } else {
-                return Err((
-                    StatusCode::BAD_REQUEST,
-                    Json(json!({"error_message":e.to_string()})),
-                ));
-            }
+    let result = match headers {
+        Err(e) => Err(ApiError::from(&e)),
+        Ok(headers) => build_schema(&headers, &output_option.json)
+            .map(|schema| Json(json!({ "data": schema })))
+            .map_err(|e| ApiError::from(&e)),
+    };
+
+    if let Err(error) = &result {
+        log::warn!(
+            "request_id={} spread_sheet_id={} status={} code={} error={}",
+            request_id,
+            spread_sheet_id,
+            error.status.as_u16(),
+            error.code,
+            error.message
+        );
+    }
+    result
+}
+
+/// `{"rows":N}`. see `count_sheet_records` for how the rows are counted
+pub async fn get_spread_sheet_count<HttpConnector>(
+    Path(spread_sheet_id): Path<SpreadSheetId>,
+    query: Query<GetSpreadSheetQuery>,
+    Extension(token_manager): Extension<Arc<TokenManager<HttpConnector>>>,
+    Extension(config): Extension<Config>,
+    Extension(request_id): Extension<RequestId>,
+) -> impl IntoResponse
+where
+    HttpConnector: Clone + Send + Sync + 'static,
+{
+    let header_option = match query.as_header_option(&config) {
+        Err(e) => return Err(ApiError::invalid_query(e)),
+        Ok(v) => v,
+    };
+    let cell_range = query.as_cell_range(&config)?;
+    let (specified_cell_range, row_search_condition) = within_cell_range(
+        cell_range.as_ref(),
+        &header_option,
+        FetchRowCondition::with_pagination(None, None)
+            .with_accurate_rows(query.accurate_rows.unwrap_or(false)),
+    )?;
+    let sheet_meta = query.as_header_sheet_meta(spread_sheet_id, cell_range.as_ref());
+    let spread_sheet_id = sheet_meta.spread_sheet_id.clone();
+
+    let sheets_client = config
+        .sheets_client(token_manager.clone())
+        .with_fresh_sheet(query.fresh.unwrap_or(false));
+    let count = match sheets_client
+        .create_header_condition_from_sheet_meta(sheet_meta, specified_cell_range, header_option)
+        .await
+    {
+        Err(e) => Err(e),
+        Ok(header_search_condition) => {
+            sheets_client
+                .count_sheet_records(&header_search_condition, &row_search_condition)
+                .await
         }
-        Ok(v) => {
-            if v.is_empty() {
-                return Err((
-                    StatusCode::NOT_FOUND,
-                    Json(json!({"error_message":"no records"})),
-                ));
-            } else {
-                v
-            }
+    };
+
+    let result = match count {
+        Err(e) => Err(ApiError::from(&e)),
+        Ok(count) => Ok(Json(json!({ "rows": count }))),
+    };
+
+    if let Err(error) = &result {
+        log::warn!(
+            "request_id={} spread_sheet_id={} status={} code={} error={}",
+            request_id,
+            spread_sheet_id,
+            error.status.as_u16(),
+            error.code,
+            error.message
+        );
+    }
+    result
+}
+
+/// `{"headers":{...},"structure":{...}}`. see `json_structure::SchemaNode`
+fn build_schema(
+    headers: &RawHeaders,
+    option: &JsonOption,
+) -> Result<JsonValue, json_structure::JsonStructureError> {
+    let keys = structure_headers(headers, option);
+    let (obj, warnings) = build_structure(keys.as_slice(), option)?;
+    // the columns are of `headers`, including the first one used as the keys
+    let skipped_columns = if option.first_col_as_key { 1 } else { 0 };
+    let mut schema = json!({
+        "headers": headers,
+        "structure": obj.schema().with_column_offset(skipped_columns),
+    });
+    if !warnings.is_empty() {
+        schema["warnings"] = json!(warnings);
+    }
+    Ok(schema)
+}
+
+pub async fn inner_get_spread_sheet_value<HttpConnector>(
+    sheet_meta: SheetMeta,
+    specified_cell_range: Option<(CellRef, CellRef)>,
+    header_option: HeaderOption,
+    row_search_condition: FetchRowCondition,
+    mut output_option: OutputOption,
+    sheets_client: SheetsClient<HttpConnector>,
+) -> Result<Response, ApiError>
+where
+    HttpConnector: Clone + Send + Sync + 'static,
+{
+    let mut timing = ServerTiming::new();
+    let header_search_condition = timing
+        .measure(
+            "meta",
+            sheets_client.create_header_condition_from_sheet_meta(
+                sheet_meta,
+                specified_cell_range,
+                header_option,
+            ),
+        )
+        .await;
+
+    let header_search_condition = match header_search_condition {
+        Err(e) => return Err(ApiError::from(&e)),
+        Ok(v) => v,
+    };
+
+    let headers = timing
+        .measure(
+            "header",
+            sheets_client.read_sheet_headers(&header_search_condition),
+        )
+        .await;
+    let sheet_response = match headers {
+        Err(e) => Err(e),
+        Ok(headers) => {
+            timing
+                .measure(
+                    "value",
+                    sheets_client.fetch_sheet_value_with_headers(
+                        &header_search_condition,
+                        headers,
+                        &row_search_condition,
+                    ),
+                )
+                .await
         }
     };
 
-    let json_response = build_json(&mut sheet_response, return_as_single_obj);
+    let mut sheet_response = match sheet_response {
+        Err(e) => return Err(ApiError::from(&e)),
+        Ok(v) => require_records(v, &row_search_condition)?,
+    };
+
+    if let Err(e) = apply_column_defaults(&mut sheet_response, &output_option.defaults) {
+        return Err(ApiError::bad_request("COLUMN_NOT_FOUND", e));
+    }
 
-    let json_response = match json_response {
-        Err(e) => {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                Json(json!({"error_message":e.to_string()})),
-            ))
+    let total_rows = sheet_response.total_rows;
+    let link = match (
+        output_option.request_uri.as_ref(),
+        sheet_response.pagination.as_ref(),
+    ) {
+        (Some(request_uri), Some(pagination)) if !output_option.json.as_single_obj => {
+            pagination_link(request_uri, pagination)
         }
+        _ => None,
+    };
+    if output_option.raw {
+        let raw = build_raw(sheet_response);
+        let response = match output_option.callback.as_deref() {
+            Some(callback) => build_jsonp(callback, &raw),
+            None => Json(raw).into_response(),
+        };
+        return Ok(timing.attach(with_link(with_total_rows(response, total_rows), link)));
+    }
+
+    output_option.json.dates = match as_date_option(
+        &output_option,
+        &sheet_response.headers,
+        &header_search_condition.sheet_info,
+    ) {
+        Err(e) => return Err(e),
         Ok(v) => v,
     };
 
+    if output_option.ndjson {
+        return Ok(timing.attach(with_link(
+            with_total_rows(build_ndjson(sheet_response, output_option), total_rows),
+            link,
+        )));
+    }
+
+    output_option.json.group_by_col_idx = match output_option.group_by.as_ref() {
+        None => None,
+        Some(group_by) => match sheet_response.headers.column_index_of(group_by) {
+            None => {
+                return Err(ApiError::bad_request(
+                    "COLUMN_NOT_FOUND",
+                    format!("group_by column not found:{}", group_by),
+                ))
+            }
+            v @ Some(_) => v,
+        },
+    };
+
+    let json_response = sheet_response.to_json_with_warnings(&output_option.json);
+    let dropped_row_count = if output_option.first_row_only {
+        sheet_response.row_values.values.len().saturating_sub(1)
+    } else {
+        0
+    };
+
+    let (json_response, warnings) = match json_response {
+        Err(e) => return Err(ApiError::from(&e)),
+        Ok(v) => v,
+    };
+
+    let next_cursor = sheet_response.next_cursor().map(|cursor| cursor.encode());
     let response = GetSpreadSheetValueResponse {
         data: json_response,
         pagination: sheet_response.pagination,
+        next_cursor,
+        warnings,
     };
 
-    Ok(Json(response))
+    let response = if output_option.xml {
+        build_xml(&response)
+    } else if let Some(callback) = output_option.callback.as_deref() {
+        build_jsonp(callback, &response)
+    } else {
+        Json(response).into_response()
+    };
+    let response = with_dropped_rows_warning(response, dropped_row_count);
+    Ok(timing.attach(with_link(with_total_rows(response, total_rows), link)))
 }
 
-fn build_json<'a>(
-    sheet_response: &'a mut SheetValueResponse,
-    as_single_obj: bool,
-) -> Result<JsonValue, json_structure::JsonStructureError> {
-    let headers: Vec<&str> = sheet_response
+/// `?single=true` returns the first row even if more rows are read.
+/// `Warning` tells the clients that the other rows are dropped
+fn with_dropped_rows_warning(mut response: Response, dropped_row_count: usize) -> Response {
+    if dropped_row_count > 0 {
+        let warning = format!(
+            "199 api-everywhere \"single=true returned the first row and dropped {} rows\"",
+            dropped_row_count
+        );
+        if let Ok(warning) = HeaderValue::from_str(&warning) {
+            response.headers_mut().insert(header::WARNING, warning);
+        }
+    }
+    response
+}
+
+/// `/**/callback(<json>);`. `callback` must be validated by `jsonp_callback_regex`.
+/// U+2028 and U+2029 are escaped since they are line terminators in old javascript
+fn build_jsonp<T: Serialize>(callback: &str, value: &T) -> Response {
+    let json = serde_json::to_string(value)
+        .unwrap_or_else(|_| "null".to_string())
+        .replace('\u{2028}', "\\u2028")
+        .replace('\u{2029}', "\\u2029");
+    let mut response = format!("/**/{}({});", callback, json).into_response();
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(JSONP_CONTENT_TYPE),
+    );
+    headers.insert(
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+    );
+    response
+}
+
+/// `<response><data>..</data><pagination>..</pagination></response>`
+fn build_xml(response: &GetSpreadSheetValueResponse) -> Response {
+    let value = serde_json::to_value(response).unwrap_or(JsonValue::Null);
+    let mut response = to_xml("response", &value).into_response();
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(XML_CONTENT_TYPE),
+    );
+    response
+}
+
+/// 404 if the sheet has no rows. paginating past the last row is not an error
+/// but an empty `data` with `has_more:false`, so that the clients iterating the pages stop cleanly.
+/// a page whose rows are all empty is not an error either, nor `?rows=` all beyond the grid
+fn require_records(
+    sheet_response: SheetValueResponse,
+    row_search_condition: &FetchRowCondition,
+) -> Result<SheetValueResponse, ApiError> {
+    if sheet_response.is_empty()
+        && !sheet_response.is_empty_page()
+        && !row_search_condition.is_row_selection()
+    {
+        Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            "NO_RECORDS",
+            "no records",
+        ))
+    } else {
+        Ok(sheet_response)
+    }
+}
+
+/// `X-Total-Rows` lets clients size their views without fetching all the rows
+fn with_total_rows(mut response: Response, total_rows: Option<usize>) -> Response {
+    if let Some(total_rows) = total_rows {
+        response
+            .headers_mut()
+            .insert(TOTAL_ROWS_HEADER, HeaderValue::from(total_rows));
+    }
+    response
+}
+
+/// `Link` of the next and the previous pages (RFC 8288).
+/// `next` is omitted on the last page and `prev` on the first page
+fn pagination_link(request_uri: &Uri, pagination: &Pagination) -> Option<HeaderValue> {
+    let offset = pagination.offset().unwrap_or(0);
+    let limit = pagination.limit()?;
+
+    let mut links: Vec<String> = Vec::new();
+    if pagination.has_more() == Some(true) {
+        links.push(format!(
+            "<{}>; rel=\"next\"",
+            page_uri(request_uri, offset + limit, limit)
+        ));
+    }
+    if offset > 0 {
+        links.push(format!(
+            "<{}>; rel=\"prev\"",
+            page_uri(request_uri, offset.saturating_sub(limit), limit)
+        ));
+    }
+
+    if links.is_empty() {
+        None
+    } else {
+        HeaderValue::from_str(&links.join(", ")).ok()
+    }
+}
+
+/// the request uri with `offset` and `limit` replaced. `cursor` is dropped as it's preferred to them
+fn page_uri(request_uri: &Uri, offset: usize, limit: usize) -> String {
+    let mut params: Vec<&str> = request_uri
+        .query()
+        .unwrap_or("")
+        .split('&')
+        .filter(|param| {
+            let key = param.split('=').next().unwrap_or("");
+            !param.is_empty() && !matches!(key, "offset" | "limit" | "cursor")
+        })
+        .collect();
+    let offset = format!("offset={}", offset);
+    let limit = format!("limit={}", limit);
+    params.push(&offset);
+    params.push(&limit);
+    format!("{}?{}", request_uri.path(), params.join("&"))
+}
+
+fn with_link(mut response: Response, link: Option<HeaderValue>) -> Response {
+    if let Some(link) = link {
+        response.headers_mut().insert(header::LINK, link);
+    }
+    response
+}
+
+/// fills the empty cells of the columns in `defaults` (header -> value).
+/// the defaults are coerced later like the other cells.
+/// only the first column of the duplicated headers is filled, the same one `RawHeaders::column_index_of` finds.
+/// the cells of columns not in `defaults` are left as they are
+fn apply_column_defaults(
+    sheet_response: &mut SheetValueResponse,
+    defaults: &HashMap<String, String>,
+) -> Result<(), String> {
+    if defaults.is_empty() {
+        return Ok(());
+    }
+
+    let mut column_defaults: Vec<(usize, &str)> = vec![];
+    for (header, default_value) in defaults {
+        match sheet_response.headers.column_index_of(header) {
+            Some(col_idx) => column_defaults.push((col_idx, default_value.as_str())),
+            None => return Err(format!("defaults column not found:{}", header)),
+        }
+    }
+
+    for each_row in sheet_response.row_values.values.iter_mut() {
+        for (col_idx, default_value) in column_defaults.iter() {
+            let default_value = CellValue::new(JsonValue::String(default_value.to_string()));
+            if *col_idx < each_row.len() {
+                if each_row[*col_idx].is_empty() {
+                    each_row[*col_idx] = default_value;
+                }
+            } else {
+                // the gap cells before the column are null, which are left out of the json like
+                // the cells beyond the row
+                each_row.resize_with(*col_idx, || CellValue::new(JsonValue::Null));
+                each_row.push(default_value);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// headers and rows without json_structure.
+/// dotted headers like `address.city` are returned verbatim
+fn build_raw(sheet_response: SheetValueResponse) -> GetSpreadSheetRawValueResponse {
+    let next_cursor = sheet_response.next_cursor().map(|cursor| cursor.encode());
+    let headers = sheet_response
         .headers
         .values
         .iter()
-        .map(|header_value| header_value.as_str())
+        .map(|header_value| header_value.as_str().to_string())
         .collect();
-    let strcuture_obj = json_structure::Object::from_strs(headers.as_slice())?;
-    let structure_obj = json_structure::Structure::new_obj(strcuture_obj);
-
-    if as_single_obj {
-        // its confirmed that sheet_response is not empty
-        let first_row = sheet_response.row_values.values.get(0).unwrap();
-        let first_row: Vec<&JsonValue> = first_row.iter().map(|v| v.as_inner()).collect();
-        let response_json = structure_obj.build_json(first_row.as_slice())?;
-        Ok(response_json.into_json_value())
-    } else {
-        let mut result = Vec::with_capacity(sheet_response.row_values.values.len());
-        for each_row in &sheet_response.row_values.values {
-            let each_row: Vec<&JsonValue> = each_row.iter().map(|v| v.as_inner()).collect();
-            let response_json = structure_obj.build_json(each_row.as_slice())?;
-            result.push(response_json.into_json_value())
-        }
 
-        Ok(JsonValue::Array(result))
+    let rows = sheet_response
+        .row_values
+        .values
+        .into_iter()
+        .map(|each_row| each_row.into_iter().map(|v| v.into_inner()).collect())
+        .collect();
+
+    GetSpreadSheetRawValueResponse {
+        headers,
+        rows,
+        pagination: sheet_response.pagination,
+        next_cursor,
+    }
+}
+
+/// `{"_meta":{"headers":[...],"total_rows":N}}`. the first line of the ndjson stream.
+/// `warnings` of the dropped headers are added if any
+fn ndjson_manifest(
+    headers: &[Cow<str>],
+    total_rows: Option<usize>,
+    warnings: &[String],
+) -> JsonValue {
+    let mut manifest = json!({
+        "_meta": {
+            "headers": headers,
+            "total_rows": total_rows,
+        }
+    });
+    if !warnings.is_empty() {
+        manifest["_meta"]["warnings"] = json!(warnings);
     }
+    manifest
+}
+
+/// streams each row as a line of json (`application/x-ndjson`).
+/// with `output_option.manifest`, the first line is a manifest distinguished by `_meta` key.
+/// the json of rows are built on a task one by one as the client consumes the stream
+fn build_ndjson(sheet_response: SheetValueResponse, output_option: OutputOption) -> Response {
+    let (mut tx, rx) = mpsc::channel(NDJSON_CHANNEL_BUFFER);
+    tokio::spawn(async move {
+        let headers = structure_headers(&sheet_response.headers, &output_option.json);
+        let (structure_obj, warnings) =
+            match build_structure(headers.as_slice(), &output_option.json) {
+                Err(e) => {
+                    let _ = tx.send(Err(e)).await;
+                    return;
+                }
+                Ok((obj, warnings)) => (json_structure::Structure::new_obj(obj), warnings),
+            };
+
+        if output_option.manifest {
+            let manifest = ndjson_manifest(&headers, sheet_response.total_rows, &warnings);
+            if tx.send(Ok(format!("{}\n", manifest))).await.is_err() {
+                return;
+            }
+        }
+
+        let row_jsons = build_row_jsons(
+            &structure_obj,
+            &sheet_response.row_values.values,
+            &output_option.json,
+        );
+        for each_row_json in row_jsons {
+            let line = each_row_json.map(|row_json| format!("{}\n", row_json));
+            if tx.send(line).await.is_err() {
+                // the client has gone
+                break;
+            }
+        }
+    });
+
+    let mut response = StreamBody::new(rx).into_response();
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(NDJSON_CONTENT_TYPE),
+    );
+    response
 }
 
 #[derive(Serialize, Deserialize)]
@@ -178,4 +1343,1006 @@ pub struct GetSpreadSheetValueResponse {
     pub data: JsonValue,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub pagination: Option<Pagination>,
+    /// pass as `?cursor=` to read the next rows
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+    /// headers dropped by `?skip_invalid_headers=true`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetSpreadSheetRawValueResponse {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<JsonValue>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pagination: Option<Pagination>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::{
+        body::Body,
+        extract::{FromRequest, RequestParts},
+        http::Request,
+    };
+
+    fn sheet_response_fixture() -> SheetValueResponse {
+        serde_json::from_value(json!({
+            "headers": {
+                "range": {
+                    "sheet_name": "grouping",
+                    "start": {"col_index": 0, "row_index": 0},
+                    "end": {"col_index": 3, "row_index": 0},
+                },
+                "values": ["name", "favorite", "favorite", "address.city.name"],
+            },
+            "row_values": {
+                "values": [
+                    ["Alice", "diving", "programming", "kyoto"],
+                    ["Bob", "shopping", "", "tokyo"],
+                ],
+            },
+            "pagination": {"offset": 0, "limit": 2},
+            "total_rows": 999,
+        }))
+        .unwrap()
+    }
+
+    fn empty_sheet_response(offset: usize, total_rows: usize) -> SheetValueResponse {
+        let mut sheet_response = sheet_response_fixture();
+        sheet_response.row_values = RowValues::empty();
+        sheet_response.pagination = Some(
+            serde_json::from_value(json!({
+                "offset": offset,
+                "limit": 2,
+                "total_rows": total_rows,
+                "has_more": offset + 2 < total_rows,
+            }))
+            .unwrap(),
+        );
+        sheet_response.total_rows = Some(total_rows);
+        sheet_response
+    }
+
+    #[test]
+    fn require_records_test() {
+        let paginated = FetchRowCondition::with_pagination(None, None);
+
+        // paginated past the end
+        let sheet_response = require_records(empty_sheet_response(4, 2), &paginated).unwrap();
+        assert_eq!(
+            sheet_response.to_json_with(&JsonOption::default()).unwrap(),
+            json!([])
+        );
+        assert_eq!(sheet_response.pagination.unwrap().has_more(), Some(false));
+
+        // all the rows of the page are empty
+        let sheet_response = require_records(empty_sheet_response(0, 6), &paginated).unwrap();
+        assert_eq!(sheet_response.pagination.unwrap().has_more(), Some(true));
+
+        // the sheet has no rows
+        for offset in [0, 4] {
+            let api_error =
+                require_records(empty_sheet_response(offset, 0), &paginated).unwrap_err();
+            assert_eq!(api_error.status, StatusCode::NOT_FOUND);
+            assert_eq!(api_error.code, "NO_RECORDS");
+        }
+
+        // `?rows=` all beyond the grid
+        let mut sheet_response = empty_sheet_response(0, 6);
+        sheet_response.pagination = None;
+        let rows = FetchRowCondition::with_row_indices(vec![10, 20]);
+        let sheet_response = require_records(sheet_response, &rows).unwrap();
+        assert_eq!(
+            sheet_response.to_json_with(&JsonOption::default()).unwrap(),
+            json!([])
+        );
+
+        assert!(require_records(sheet_response_fixture(), &paginated).is_ok());
+    }
+
+    #[test]
+    fn require_writable_test() {
+        let mut config = Config::from_env();
+        config.scopes = None;
+        config.sheet_scope = Some("readonly".to_string());
+        let api_error = require_writable(&config).unwrap_err();
+        assert_eq!(api_error.status, StatusCode::FORBIDDEN);
+        assert_eq!(api_error.code, "READ_ONLY_SCOPE");
+
+        config.sheet_scope = Some("readwrite".to_string());
+        assert!(require_writable(&config).is_ok());
+    }
+
+    #[test]
+    fn ranges_query_test() {
+        let config = Config::from_env();
+        let query = |ranges: &str| GetSpreadSheetQuery {
+            ranges: Some(ranges.to_string()),
+            ..GetSpreadSheetQuery::default()
+        };
+
+        let ranges = query("A1:C10, E1:G10")
+            .as_cell_ranges(&config)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            ranges,
+            vec![
+                RangeRef::from_str("A1:C10").unwrap(),
+                RangeRef::from_str("E1:G10").unwrap(),
+            ]
+        );
+        assert_eq!(
+            GetSpreadSheetQuery::default()
+                .as_cell_ranges(&config)
+                .unwrap(),
+            None
+        );
+
+        let api_error = query("A1:C10,E1:H10").as_cell_ranges(&config).unwrap_err();
+        assert_eq!(api_error.status, StatusCode::BAD_REQUEST);
+        assert_eq!(api_error.code, "INCOMPATIBLE_RANGES");
+
+        let api_error = query("orders!A1:C10,users!E1:G10")
+            .as_cell_ranges(&config)
+            .unwrap_err();
+        assert_eq!(api_error.code, "INVALID_RANGE");
+        assert!(query("orders!A1:C10,E1:G10")
+            .as_cell_ranges(&config)
+            .is_ok());
+        assert!(query(",").as_cell_ranges(&config).is_err());
+
+        let with_range = GetSpreadSheetQuery {
+            range: Some("A1:C10".to_string()),
+            ..query("A1:C10,E1:G10")
+        };
+        assert!(with_range.as_cell_ranges(&config).is_err());
+
+        // the pages of the concatenated rows are not supported
+        let with_offset = GetSpreadSheetQuery {
+            offset: Some(10),
+            ..query("A1:C10,E1:G10")
+        };
+        assert!(with_offset.as_cell_ranges(&config).is_err());
+        let with_limit = GetSpreadSheetQuery {
+            limit: Some(10),
+            ..query("A1:C10,E1:G10")
+        };
+        assert!(with_limit.as_cell_ranges(&config).is_err());
+    }
+
+    #[test]
+    fn parse_range_param_test() {
+        let result = parse_range_param("A1:ZZ100000", 1_000_000);
+        assert_eq!(result.unwrap_err().status, StatusCode::BAD_REQUEST);
+
+        let result = parse_range_param("A1:A0", 1_000_000);
+        assert_eq!(result.unwrap_err().status, StatusCode::BAD_REQUEST);
+
+        let result = parse_range_param("B2:F50", 1_000_000);
+        assert_eq!(result.unwrap(), RangeRef::from_str("B2:F50").unwrap());
+
+        // just at the cap
+        let result = parse_range_param("A1:B5", 10);
+        assert!(result.is_ok());
+        let result = parse_range_param("A1:B6", 10);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn range_cap_query_test() {
+        let config = Config {
+            max_range_cells: 10,
+            ..Config::from_env()
+        };
+        let query = |range: &str| GetSpreadSheetQuery {
+            range: Some(range.to_string()),
+            ..GetSpreadSheetQuery::default()
+        };
+
+        // rejected before reading the sheet
+        let api_error = query("A1:B6").as_cell_range(&config).unwrap_err();
+        assert_eq!(api_error.status, StatusCode::BAD_REQUEST);
+        assert_eq!(api_error.code, "RANGE_TOO_LARGE");
+
+        // within the cap
+        assert_eq!(
+            query("A1:B5").as_cell_range(&config).unwrap(),
+            Some(RangeRef::from_str("A1:B5").unwrap())
+        );
+
+        // each range of `?ranges=` is capped too
+        let ranges = GetSpreadSheetQuery {
+            ranges: Some("A1:B6,D1:E6".to_string()),
+            ..GetSpreadSheetQuery::default()
+        };
+        assert_eq!(
+            ranges.as_cell_ranges(&config).unwrap_err().code,
+            "RANGE_TOO_LARGE"
+        );
+    }
+
+    #[tokio::test]
+    async fn range_query_test() {
+        let config = Config::from_env();
+        let query = GetSpreadSheetQuery {
+            range: Some("grouping!B2:D4".to_string()),
+            ..GetSpreadSheetQuery::default()
+        };
+        let header_option = query.as_header_option(&config).unwrap();
+        let range = query.as_cell_range(&config).unwrap().unwrap();
+
+        // the first row of the range is the header row
+        let specified_cell_range =
+            header_cells_of_range(&range, header_option.header_row_count()).unwrap();
+        assert_eq!(
+            specified_cell_range,
+            (CellRef::new(1, 1), CellRef::new(3, 1))
+        );
+
+        let sheet_meta = query.as_header_sheet_meta(
+            SpreadSheetId::new("spread_sheet_id".to_string()),
+            Some(&range),
+        );
+        assert_eq!(
+            sheet_meta.sheet_id_or_name.sheet_name(),
+            Some("grouping".to_string())
+        );
+
+        // the rows below the range are not read
+        let row_search_condition = query
+            .as_row_search_condition(&config)
+            .unwrap()
+            .with_last_row_idx(range.end.row_index);
+        assert_eq!(row_search_condition.row_count_to_read(1000), 4);
+
+        // the headers and the rows read from the range
+        let sheet_response: SheetValueResponse = serde_json::from_value(json!({
+            "headers": {
+                "range": {
+                    "sheet_name": "grouping",
+                    "start": {"col_index": 1, "row_index": 1},
+                    "end": {"col_index": 3, "row_index": 1},
+                },
+                "values": ["name", "age", "city"],
+            },
+            "row_values": {
+                "values": [
+                    ["Alice", "21", "kyoto"],
+                    ["Bob", "34", "tokyo"],
+                ],
+            },
+            "pagination": null,
+        }))
+        .unwrap();
+        let output_option = OutputOption {
+            ndjson: true,
+            ..OutputOption::default()
+        };
+        let response = build_ndjson(sheet_response, output_option);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let lines: Vec<JsonValue> = std::str::from_utf8(&body)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                json!({"name": "Alice", "age": "21", "city": "kyoto"}),
+                json!({"name": "Bob", "age": "34", "city": "tokyo"}),
+            ]
+        );
+
+        // the range must include the header rows
+        let query = GetSpreadSheetQuery {
+            range: Some("B2:D2".to_string()),
+            header_rows: Some(2),
+            ..GetSpreadSheetQuery::default()
+        };
+        let header_option = query.as_header_option(&config).unwrap();
+        let range = query.as_cell_range(&config).unwrap().unwrap();
+        let api_error =
+            header_cells_of_range(&range, header_option.header_row_count()).unwrap_err();
+        assert_eq!(api_error.code, "INVALID_RANGE");
+    }
+
+    #[test]
+    fn dates_query_test() {
+        let config = Config::from_env();
+        let query = GetSpreadSheetQuery {
+            dates: Some("iso".to_string()),
+            date_columns: Some("birthday, checked_in_at".to_string()),
+            ..GetSpreadSheetQuery::default()
+        };
+        let output_option = query.as_output_option(&config).unwrap();
+        assert_eq!(
+            output_option.date_columns,
+            vec!["birthday", "checked_in_at"]
+        );
+
+        let headers: RawHeaders = serde_json::from_value(json!({
+            "range": {
+                "sheet_name": null,
+                "start": {"col_index": 0, "row_index": 0},
+                "end": {"col_index": 2, "row_index": 0},
+            },
+            "values": ["name", "birthday", "checked_in_at"],
+        }))
+        .unwrap();
+        let sheet_info: Sheet = serde_json::from_value(json!({
+            "spreadsheetId": "test",
+            "properties": {"title": "test", "timeZone": "Asia/Tokyo"},
+            "sheets": [],
+        }))
+        .unwrap();
+        let date_option = as_date_option(&output_option, &headers, &sheet_info)
+            .unwrap()
+            .unwrap();
+        assert_eq!(date_option.col_indices, vec![1, 2]);
+        assert_eq!(date_option.tz, chrono_tz::Tz::Asia__Tokyo);
+
+        let output_option = OutputOption {
+            date_columns: vec!["created_at".to_string()],
+            ..OutputOption::default()
+        };
+        let api_error = as_date_option(&output_option, &headers, &sheet_info).unwrap_err();
+        assert_eq!(api_error.code, "COLUMN_NOT_FOUND");
+
+        let query = GetSpreadSheetQuery {
+            dates: Some("iso".to_string()),
+            ..GetSpreadSheetQuery::default()
+        };
+        assert!(query.as_output_option(&config).is_err());
+
+        let query = GetSpreadSheetQuery {
+            dates: Some("unix".to_string()),
+            date_columns: Some("birthday".to_string()),
+            ..GetSpreadSheetQuery::default()
+        };
+        assert!(query.as_output_option(&config).is_err());
+    }
+
+    #[test]
+    fn build_schema_test() {
+        let headers = sheet_response_fixture().headers;
+        let schema = build_schema(&headers, &JsonOption::default()).unwrap();
+
+        assert_eq!(
+            schema["headers"]["values"],
+            json!(["name", "favorite", "favorite", "address.city.name"])
+        );
+
+        let properties = &schema["structure"]["properties"];
+        assert_eq!(
+            properties[1],
+            json!({"key": "favorite", "type": "array", "columns": [1, 2]})
+        );
+        assert_eq!(
+            properties[2],
+            json!({"key": "address", "type": "object", "properties": [
+                {"key": "city", "type": "object", "properties": [
+                    {"key": "name", "type": "value", "column": 3},
+                ]},
+            ]})
+        );
+
+        // the structure follows the renamed headers
+        let option = JsonOption {
+            rename: [("address.city.name".to_string(), "city".to_string())]
+                .into_iter()
+                .collect(),
+            ..JsonOption::default()
+        };
+        let schema = build_schema(&headers, &option).unwrap();
+        assert_eq!(
+            schema["structure"]["properties"][2],
+            json!({"key": "city", "type": "value", "column": 3})
+        );
+        assert!(schema.get("warnings").is_none());
+
+        let option = JsonOption {
+            rename: [("favorite".to_string(), "name.first".to_string())]
+                .into_iter()
+                .collect(),
+            skip_invalid_headers: true,
+            ..JsonOption::default()
+        };
+        let schema = build_schema(&headers, &option).unwrap();
+        assert_eq!(
+            schema["warnings"],
+            json!([
+                "header `name.first` is dropped: invalid json structure definition :key:`name` supposed to be a object but value",
+                "header `name.first` is dropped: invalid json structure definition :key:`name` supposed to be a object but value",
+            ])
+        );
+
+        // the first column is the keys
+        let option = JsonOption {
+            first_col_as_key: true,
+            ..JsonOption::default()
+        };
+        let schema = build_schema(&headers, &option).unwrap();
+        let properties = &schema["structure"]["properties"];
+        assert_eq!(
+            properties[0],
+            json!({"key": "favorite", "type": "array", "columns": [1, 2]})
+        );
+        assert_eq!(
+            properties[1]["properties"][0]["properties"][0],
+            json!({"key": "name", "type": "value", "column": 3})
+        );
+    }
+
+    #[test]
+    fn parse_rows_param_test() {
+        assert_eq!(parse_rows_param("3,7,12"), Ok(vec![3, 7, 12]));
+        assert_eq!(parse_rows_param(" 12, 3"), Ok(vec![12, 3]));
+        assert!(parse_rows_param("3,,7").is_err());
+        assert!(parse_rows_param("3,-1").is_err());
+    }
+
+    #[test]
+    fn parse_rename_param_test() {
+        let result = parse_rename_param("Full Name:name, DOB:birthday").unwrap();
+        assert_eq!(result.len(), 2);
+        assert_eq!(result.get("Full Name").unwrap(), "name");
+        assert_eq!(result.get("DOB").unwrap(), "birthday");
+
+        assert!(parse_rename_param("Full Name").is_err());
+        assert!(parse_rename_param("Full Name:").is_err());
+
+        let result = parse_defaults_param("status:active,time:10:00").unwrap();
+        assert_eq!(result.get("status").unwrap(), "active");
+        assert_eq!(result.get("time").unwrap(), "10:00");
+    }
+
+    #[test]
+    fn build_raw_test() {
+        let result = build_raw(sheet_response_fixture());
+        let result = serde_json::to_value(&result).unwrap();
+
+        let expected = json!({
+            "headers": ["name", "favorite", "favorite", "address.city.name"],
+            "rows": [
+                ["Alice", "diving", "programming", "kyoto"],
+                ["Bob", "shopping", "", "tokyo"],
+            ],
+            "pagination": {"offset": 0, "limit": 2},
+            "next_cursor": Cursor::new(2, 2).encode(),
+        });
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn single_query_test() {
+        let config = Config::from_env();
+        let query = |row: Option<usize>, single: Option<bool>| GetSpreadSheetQuery {
+            row,
+            single,
+            ..GetSpreadSheetQuery::default()
+        };
+
+        let output_option = query(None, None).as_output_option(&config).unwrap();
+        assert!(!output_option.json.as_single_obj);
+        assert!(!output_option.first_row_only);
+
+        let output_option = query(None, Some(true)).as_output_option(&config).unwrap();
+        assert!(output_option.json.as_single_obj);
+        assert!(output_option.first_row_only);
+
+        let output_option = query(None, Some(false)).as_output_option(&config).unwrap();
+        assert!(!output_option.json.as_single_obj);
+
+        // row wins
+        for single in [None, Some(true), Some(false)] {
+            let output_option = query(Some(3), single).as_output_option(&config).unwrap();
+            assert!(output_option.json.as_single_obj);
+            assert!(!output_option.first_row_only);
+        }
+
+        let query = GetSpreadSheetQuery {
+            single: Some(true),
+            group_by: Some("name".to_string()),
+            ..GetSpreadSheetQuery::default()
+        };
+        assert!(query.as_output_option(&config).is_err());
+    }
+
+    #[test]
+    fn render_query_test() {
+        let config = Config::from_env();
+        let query = |render: &str| GetSpreadSheetQuery {
+            render: Some(render.to_string()),
+            ..GetSpreadSheetQuery::default()
+        };
+
+        assert!(query("formula").as_row_search_condition(&config).is_ok());
+        assert!(query("value").as_row_search_condition(&config).is_ok());
+        assert!(query("formulas").as_row_search_condition(&config).is_err());
+
+        let query = GetSpreadSheetQuery {
+            dates: Some("iso".to_string()),
+            ..query("formula")
+        };
+        assert!(query.as_row_search_condition(&config).is_err());
+    }
+
+    #[test]
+    fn pad_query_test() {
+        let config = Config::from_env();
+        let condition = GetSpreadSheetQuery::default()
+            .as_row_search_condition(&config)
+            .unwrap();
+        assert!(condition.pad());
+
+        let condition = GetSpreadSheetQuery {
+            pad: Some(false),
+            ..GetSpreadSheetQuery::default()
+        }
+        .as_row_search_condition(&config)
+        .unwrap();
+        assert!(!condition.pad());
+    }
+
+    /// extracts the query of `uri` the same way as the handlers
+    async fn query_of_uri(uri: &str) -> GetSpreadSheetQuery {
+        let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let Query(query) =
+            Query::<GetSpreadSheetQuery>::from_request(&mut RequestParts::new(request))
+                .await
+                .unwrap();
+        query
+    }
+
+    #[tokio::test]
+    async fn sheet_name_query_test() {
+        let sheet_name = |query: GetSpreadSheetQuery| {
+            query
+                .as_header_sheet_meta(SpreadSheetId::new("spread_sheet_id".to_string()), None)
+                .sheet_id_or_name
+                .sheet_name()
+        };
+
+        // decoded once by the query extractor
+        let query = query_of_uri("/sheet/abc?sheet_name=Q3%20%26%20Q4").await;
+        assert_eq!(sheet_name(query), Some("Q3 & Q4".to_string()));
+        let query = query_of_uri("/sheet/abc?sheet_name=a%2Fb").await;
+        assert_eq!(sheet_name(query), Some("a/b".to_string()));
+        let query = query_of_uri("/sheet/abc?sheet_name=a%2Bb").await;
+        assert_eq!(sheet_name(query), Some("a+b".to_string()));
+
+        // not decoded twice. `%25` is the literal `%`
+        let query = query_of_uri("/sheet/abc?sheet_name=100%25").await;
+        assert_eq!(sheet_name(query), Some("100%".to_string()));
+        let query = query_of_uri("/sheet/abc?sheet_name=a%252Fb").await;
+        assert_eq!(sheet_name(query), Some("a%2Fb".to_string()));
+    }
+
+    #[test]
+    fn sheet_index_query_test() {
+        let sheet_meta = GetSpreadSheetQuery {
+            sheet_index: Some(2),
+            ..GetSpreadSheetQuery::default()
+        }
+        .as_header_sheet_meta(
+            SpreadSheetId::new("spread_sheet_id".to_string()),
+            Some(&RangeRef::from_str("grouping!A1:C5").unwrap()),
+        );
+        assert_eq!(sheet_meta.sheet_id_or_name.tab_sheet_index, Some(2));
+        // the sheet name of the range is not used with the index
+        assert_eq!(sheet_meta.sheet_id_or_name.tab_sheet_name, None);
+    }
+
+    #[test]
+    fn include_empty_rows_query_test() {
+        let config = Config::from_env();
+        let condition = GetSpreadSheetQuery::default()
+            .as_row_search_condition(&config)
+            .unwrap();
+        assert!(condition.include_empty_rows());
+
+        let condition = GetSpreadSheetQuery {
+            include_empty_rows: Some(false),
+            ..GetSpreadSheetQuery::default()
+        }
+        .as_row_search_condition(&config)
+        .unwrap();
+        assert!(!condition.include_empty_rows());
+    }
+
+    #[test]
+    fn accurate_rows_query_test() {
+        let config = Config::from_env();
+        let condition = GetSpreadSheetQuery::default()
+            .as_row_search_condition(&config)
+            .unwrap();
+        assert!(!condition.accurate_rows());
+
+        let condition = GetSpreadSheetQuery {
+            accurate_rows: Some(true),
+            ..GetSpreadSheetQuery::default()
+        }
+        .as_row_search_condition(&config)
+        .unwrap();
+        assert!(condition.accurate_rows());
+    }
+
+    #[test]
+    fn dup_headers_query_test() {
+        let config = Config::from_env();
+        let query = |dup_headers: &str| GetSpreadSheetQuery {
+            dup_headers: Some(dup_headers.to_string()),
+            ..GetSpreadSheetQuery::default()
+        };
+
+        let output_option = query("suffix").as_output_option(&config).unwrap();
+        assert_eq!(
+            output_option.json.dup_headers,
+            json_structure::DupHeaders::Suffix
+        );
+        let json = sheet_response_fixture()
+            .to_json_with(&output_option.json)
+            .unwrap();
+        assert_eq!(json[0]["favorite_2"], "programming");
+
+        let output_option = GetSpreadSheetQuery::default()
+            .as_output_option(&config)
+            .unwrap();
+        assert_eq!(
+            output_option.json.dup_headers,
+            json_structure::DupHeaders::Array
+        );
+        assert!(query("arrays").as_output_option(&config).is_err());
+    }
+
+    #[test]
+    fn array_order_query_test() {
+        let config = Config::from_env();
+        let query = |array_order: &str| GetSpreadSheetQuery {
+            array_order: Some(array_order.to_string()),
+            ..GetSpreadSheetQuery::default()
+        };
+
+        let output_option = query("right_to_left").as_output_option(&config).unwrap();
+        assert_eq!(
+            output_option.json.array_order,
+            json_structure::ArrayOrder::RightToLeft
+        );
+        let json = sheet_response_fixture()
+            .to_json_with(&output_option.json)
+            .unwrap();
+        assert_eq!(json[0]["favorite"], json!(["programming", "diving"]));
+
+        let output_option = GetSpreadSheetQuery::default()
+            .as_output_option(&config)
+            .unwrap();
+        assert_eq!(
+            output_option.json.array_order,
+            json_structure::ArrayOrder::LeftToRight
+        );
+        assert!(query("reverse").as_output_option(&config).is_err());
+    }
+
+    #[test]
+    fn project_query_test() {
+        let config = Config::from_env();
+        let query = GetSpreadSheetQuery {
+            project: Some("name, address.city".to_string()),
+            ..GetSpreadSheetQuery::default()
+        };
+        let output_option = query.as_output_option(&config).unwrap();
+        assert_eq!(
+            output_option.json.projection,
+            Some(vec!["name".to_string(), "address.city".to_string()])
+        );
+        let json = sheet_response_fixture()
+            .to_json_with(&output_option.json)
+            .unwrap();
+        assert_eq!(
+            json[0],
+            json!({"name": "Alice", "address": {"city": {"name": "kyoto"}}})
+        );
+
+        assert!(GetSpreadSheetQuery {
+            raw: Some(true),
+            ..query
+        }
+        .as_output_option(&config)
+        .is_err());
+    }
+
+    #[test]
+    fn missing_query_test() {
+        let config = Config::from_env();
+        let query = |missing: &str| GetSpreadSheetQuery {
+            missing: Some(missing.to_string()),
+            ..GetSpreadSheetQuery::default()
+        };
+
+        let output_option = query("null").as_output_option(&config).unwrap();
+        assert_eq!(
+            output_option.json.missing_values,
+            json_structure::MissingValues::Null
+        );
+        let output_option = GetSpreadSheetQuery::default()
+            .as_output_option(&config)
+            .unwrap();
+        assert_eq!(
+            output_option.json.missing_values,
+            json_structure::MissingValues::Absent
+        );
+        assert!(query("nil").as_output_option(&config).is_err());
+    }
+
+    #[test]
+    fn single_first_row_test() {
+        let config = Config::from_env();
+        let query = GetSpreadSheetQuery {
+            single: Some(true),
+            ..GetSpreadSheetQuery::default()
+        };
+        let output_option = query.as_output_option(&config).unwrap();
+        let json = sheet_response_fixture()
+            .to_json_with(&output_option.json)
+            .unwrap();
+        assert_eq!(json["name"], "Alice");
+
+        let response = with_dropped_rows_warning(Json(json).into_response(), 1);
+        assert_eq!(
+            response.headers().get(header::WARNING).unwrap(),
+            "199 api-everywhere \"single=true returned the first row and dropped 1 rows\""
+        );
+
+        let response = with_dropped_rows_warning(Json(json!({})).into_response(), 0);
+        assert!(response.headers().get(header::WARNING).is_none());
+    }
+
+    #[test]
+    fn callback_query_test() {
+        let config = Config::from_env();
+        let query = |callback: &str, format: Option<&str>| GetSpreadSheetQuery {
+            callback: Some(callback.to_string()),
+            format: format.map(|format| format.to_string()),
+            ..GetSpreadSheetQuery::default()
+        };
+
+        let output_option = query("handleRows_1", None)
+            .as_output_option(&config)
+            .unwrap();
+        assert_eq!(output_option.callback.as_deref(), Some("handleRows_1"));
+
+        for invalid in ["alert(1)", "a.b", "1abc", "", "cb;alert"] {
+            assert!(
+                query(invalid, None).as_output_option(&config).is_err(),
+                "{} is accepted",
+                invalid
+            );
+        }
+        assert!(query("cb", Some("ndjson"))
+            .as_output_option(&config)
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn build_jsonp_test() {
+        let response = build_jsonp("handleRows", &json!({"data": [{"name": "Alice\u{2028}"}]}));
+        assert_eq!(
+            response.headers().get(header::CONTENT_TYPE).unwrap(),
+            JSONP_CONTENT_TYPE
+        );
+
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(
+            String::from_utf8(body.to_vec()).unwrap(),
+            r#"/**/handleRows({"data":[{"name":"Alice\u2028"}]});"#
+        );
+    }
+
+    #[test]
+    fn accepts_only_xml_test() {
+        let accept = |value: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::ACCEPT, HeaderValue::from_static(value));
+            accepts_only_xml(&headers)
+        };
+        assert!(accept("application/xml"));
+        assert!(accept("text/xml;q=0.9, */*;q=0.1"));
+        assert!(!accept("application/json, application/xml"));
+        assert!(!accept("*/*"));
+        assert!(!accepts_only_xml(&HeaderMap::new()));
+    }
+
+    #[tokio::test]
+    async fn build_xml_test() {
+        let sheet_response = sheet_response_fixture();
+        let response = GetSpreadSheetValueResponse {
+            data: sheet_response.to_json_with(&JsonOption::default()).unwrap(),
+            pagination: None,
+            next_cursor: None,
+            warnings: vec![],
+        };
+        let response = build_xml(&response);
+        assert_eq!(
+            response.headers().get(header::CONTENT_TYPE).unwrap(),
+            XML_CONTENT_TYPE
+        );
+
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.starts_with(r#"<?xml version="1.0" encoding="UTF-8"?><response><data>"#));
+        assert!(body.contains(
+            "<data><address><city><name>kyoto</name></city></address><favorite>diving</favorite><favorite>programming</favorite><name>Alice</name></data>"
+        ));
+    }
+
+    #[test]
+    fn pagination_link_test() {
+        let request_uri: Uri = "/sheet/abc?trim_headers=true&offset=10&limit=10"
+            .parse()
+            .unwrap();
+
+        // a middle page
+        let pagination = Pagination::new(Some(10), Some(10)).with_total_rows(35, 10);
+        let link = pagination_link(&request_uri, &pagination).unwrap();
+        let links: Vec<&str> = link.to_str().unwrap().split(", ").collect();
+        assert_eq!(
+            links,
+            vec![
+                r#"</sheet/abc?trim_headers=true&offset=20&limit=10>; rel="next""#,
+                r#"</sheet/abc?trim_headers=true&offset=0&limit=10>; rel="prev""#,
+            ]
+        );
+
+        let response = with_link(Json(json!({})).into_response(), Some(link));
+        assert!(response.headers().get(header::LINK).is_some());
+
+        // the first page without offset
+        let request_uri: Uri = "/sheet/abc".parse().unwrap();
+        let pagination = Pagination::new(None, Some(10)).with_total_rows(35, 10);
+        assert_eq!(
+            pagination_link(&request_uri, &pagination).unwrap(),
+            r#"</sheet/abc?offset=10&limit=10>; rel="next""#
+        );
+
+        // the last page read with a cursor
+        let request_uri: Uri = "/sheet/abc?cursor=xyz".parse().unwrap();
+        let pagination = Pagination::new(Some(30), Some(10)).with_total_rows(35, 5);
+        assert_eq!(
+            pagination_link(&request_uri, &pagination).unwrap(),
+            r#"</sheet/abc?offset=20&limit=10>; rel="prev""#
+        );
+
+        // a single page
+        let pagination = Pagination::new(None, Some(10)).with_total_rows(5, 5);
+        assert!(pagination_link(&request_uri, &pagination).is_none());
+    }
+
+    #[test]
+    fn with_total_rows_test() {
+        let sheet_response = sheet_response_fixture();
+        let total_rows = sheet_response.total_rows;
+        let response = with_total_rows(Json(build_raw(sheet_response)).into_response(), total_rows);
+        assert_eq!(
+            response.headers().get(TOTAL_ROWS_HEADER).unwrap(),
+            &HeaderValue::from_static("999")
+        );
+
+        let response = with_total_rows(Json(json!({})).into_response(), None);
+        assert!(response.headers().get(TOTAL_ROWS_HEADER).is_none());
+    }
+
+    #[tokio::test]
+    async fn build_ndjson_test() {
+        let output_option = OutputOption {
+            ndjson: true,
+            ..OutputOption::default()
+        };
+        let response = build_ndjson(sheet_response_fixture(), output_option);
+        assert_eq!(
+            response.headers().get(header::CONTENT_TYPE).unwrap(),
+            NDJSON_CONTENT_TYPE
+        );
+
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body = std::str::from_utf8(&body).unwrap();
+        let lines: Vec<JsonValue> = body
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        let expected = vec![
+            json!({"name": "Alice", "favorite": ["diving", "programming"], "address": {"city": {"name": "kyoto"}}}),
+            json!({"name": "Bob", "favorite": ["shopping", ""], "address": {"city": {"name": "tokyo"}}}),
+        ];
+        assert_eq!(lines, expected);
+    }
+
+    #[tokio::test]
+    async fn build_ndjson_with_manifest_test() {
+        let output_option = OutputOption {
+            ndjson: true,
+            manifest: true,
+            ..OutputOption::default()
+        };
+        let response = build_ndjson(sheet_response_fixture(), output_option);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body = std::str::from_utf8(&body).unwrap();
+        let lines: Vec<JsonValue> = body
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines[0],
+            json!({"_meta": {
+                "headers": ["name", "favorite", "favorite", "address.city.name"],
+                "total_rows": 999,
+            }})
+        );
+        for each_line in &lines[1..] {
+            assert!(each_line.get("_meta").is_none());
+            assert!(each_line.get("name").is_some());
+        }
+    }
+
+    fn defaults_fixture() -> SheetValueResponse {
+        serde_json::from_value(json!({
+            "headers": {
+                "range": {
+                    "sheet_name": null,
+                    "start": {"col_index": 0, "row_index": 0},
+                    "end": {"col_index": 3, "row_index": 0},
+                },
+                "values": ["name", "status", "memo", "count"],
+            },
+            "row_values": {
+                "values": [
+                    ["Alice", "", "", "3"],
+                    ["Bob", "inactive", "", ""],
+                    ["Charlie"],
+                ],
+            },
+            "pagination": null,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn apply_column_defaults_test() {
+        let mut sheet_response = defaults_fixture();
+        let output_option = OutputOption {
+            json: JsonOption {
+                coerce: CoerceOption {
+                    numbers: true,
+                    big_int_as_string: false,
+                },
+                ..JsonOption::default()
+            },
+            defaults: parse_defaults_param("status:active,count:0").unwrap(),
+            ..OutputOption::default()
+        };
+        apply_column_defaults(&mut sheet_response, &output_option.defaults).unwrap();
+
+        let result = sheet_response.to_json_with(&output_option.json).unwrap();
+        let expected = json!([
+            {"name": "Alice", "status": "active", "memo": "", "count": 3},
+            {"name": "Bob", "status": "inactive", "memo": "", "count": 0},
+            {"name": "Charlie", "status": "active", "count": 0},
+        ]);
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn apply_column_defaults_unknown_column_test() {
+        let mut sheet_response = defaults_fixture();
+        let defaults = parse_defaults_param("unknown:0").unwrap();
+        assert!(apply_column_defaults(&mut sheet_response, &defaults).is_err());
+    }
 }
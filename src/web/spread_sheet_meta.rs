@@ -1,5 +1,6 @@
+use super::error::ApiError;
 use crate::external_service::spread_sheet::*;
-use axum::{extract::Query, http::StatusCode, response::IntoResponse, Json};
+use axum::{extract::Query, response::IntoResponse, Json};
 use serde::Deserialize;
 use serde_json::json;
 
@@ -7,32 +8,104 @@ use serde_json::json;
 #[derive(Debug, Deserialize, Default)]
 pub struct GetSpreadSheetMetaQuery {
     pub sheet_url: Option<String>,
+    /// spread sheet id. preferred to `sheet_url` if both are passed
+    pub sheet_id: Option<String>,
+    pub sheet_name: Option<String>,
+    /// tab sheet id
+    pub gid: Option<u32>,
 }
 
-pub async fn get_spread_sheet_meta(query: Query<GetSpreadSheetMetaQuery>) -> impl IntoResponse {
-    if let Some(sheet_url) = &query.sheet_url {
-        let sheet_url = urlencoding::decode(&sheet_url);
-        match sheet_url {
-            Err(e) => Err((
-                StatusCode::BAD_REQUEST,
-                Json(json!({
-                    "error_message": format!("query parameter sheet_url is invalid {}", e)
-                })),
-            )),
-            Ok(sheet_url) => match SheetMeta::from_url(&sheet_url) {
-                Err(e) => Err((
-                    StatusCode::BAD_REQUEST,
-                    Json(json!({
-                        "error_message": format!("query parameter sheet_url is invalid {}", e)
-                    })),
-                )),
-                Ok(meta) => Ok(Json(json!({ "data": meta }))),
-            },
+impl GetSpreadSheetMetaQuery {
+    fn as_sheet_meta(&self) -> Result<SheetMeta, ApiError> {
+        if let Some(sheet_id) = &self.sheet_id {
+            SheetMeta::from_id(sheet_id, self.gid, self.sheet_name.clone()).map_err(|e| {
+                ApiError::bad_request(
+                    e.code(),
+                    format!("query parameter sheet_id is invalid {}", e),
+                )
+            })
+        } else if let Some(sheet_url) = &self.sheet_url {
+            let sheet_url = urlencoding::decode(sheet_url).map_err(|e| {
+                ApiError::bad_request(
+                    "INVALID_SHEET_URL",
+                    format!("query parameter sheet_url is invalid {}", e),
+                )
+            })?;
+            SheetMeta::from_any(&sheet_url).map_err(|e| {
+                ApiError::bad_request(
+                    e.code(),
+                    format!("query parameter sheet_url is invalid {}", e),
+                )
+            })
+        } else {
+            Err(ApiError::invalid_query(
+                "query parameter sheet_url or sheet_id is required",
+            ))
         }
-    } else {
-        Err((
-            StatusCode::BAD_REQUEST,
-            Json(json!({"error_message":"query parameter sheet_url is required"})),
-        ))
+    }
+}
+
+pub async fn get_spread_sheet_meta(query: Query<GetSpreadSheetMetaQuery>) -> impl IntoResponse {
+    match query.as_sheet_meta() {
+        Err(e) => Err(e),
+        Ok(meta) => Ok(Json(json!({ "data": meta }))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SHEET_ID: &str = "1HA4munsvl5UUlb9DKmJvhrwfGlSQ97hSQZf13M3ZO4Y";
+
+    #[test]
+    fn sheet_meta_from_id() {
+        let query = GetSpreadSheetMetaQuery {
+            sheet_id: Some(SHEET_ID.to_string()),
+            sheet_name: Some("grouping".to_string()),
+            ..GetSpreadSheetMetaQuery::default()
+        };
+
+        assert_eq!(
+            query.as_sheet_meta(),
+            Ok(SheetMeta::new(
+                SHEET_ID.to_string(),
+                None,
+                Some("grouping".to_string())
+            ))
+        );
+    }
+
+    #[test]
+    fn sheet_meta_from_id_and_gid() {
+        let query = GetSpreadSheetMetaQuery {
+            // explicit fields are preferred to the url
+            sheet_url: Some("https://docs.google.com/spreadsheets/d/xxxxxxxxxx/edit".to_string()),
+            sheet_id: Some(SHEET_ID.to_string()),
+            gid: Some(2089556915),
+            ..GetSpreadSheetMetaQuery::default()
+        };
+
+        assert_eq!(
+            query.as_sheet_meta(),
+            Ok(SheetMeta::new(SHEET_ID.to_string(), Some(2089556915), None))
+        );
+    }
+
+    #[test]
+    fn sheet_meta_from_invalid_id() {
+        let query = GetSpreadSheetMetaQuery {
+            sheet_id: Some("1HA4;?munsvl5UUlb9DKmJvhrwfGlSQ97hSQZf13M3ZO4Y".to_string()),
+            ..GetSpreadSheetMetaQuery::default()
+        };
+        assert!(query.as_sheet_meta().is_err());
+
+        assert_eq!(
+            GetSpreadSheetMetaQuery::default()
+                .as_sheet_meta()
+                .unwrap_err()
+                .code,
+            "INVALID_QUERY"
+        );
     }
 }
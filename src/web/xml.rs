@@ -0,0 +1,163 @@
+use serde_json::Value as JsonValue;
+
+pub const XML_CONTENT_TYPE: &str = "application/xml";
+
+/// element name of the items of an array in an array
+const ARRAY_ITEM_ELEMENT: &str = "item";
+
+/// serializes `value` into an xml document with the root element `root`.
+/// the keys of objects are elements and the items of arrays are repeated elements
+/// named after the key of the array. no attributes are used
+///
+/// {"name":"Alice","favorite":["diving","programming"]}
+///
+/// =>
+///```ignore
+/// <root><favorite>diving</favorite><favorite>programming</favorite><name>Alice</name></root>
+///```
+pub fn to_xml(root: &str, value: &JsonValue) -> String {
+    let mut xml = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    write_element(&mut xml, &element_name(root), value);
+    xml
+}
+
+fn write_element(xml: &mut String, name: &str, value: &JsonValue) {
+    match value {
+        JsonValue::Null => {
+            xml.push('<');
+            xml.push_str(name);
+            xml.push_str("/>");
+        }
+        JsonValue::Array(items) => {
+            for item in items.iter() {
+                match item {
+                    // an array in an array is wrapped, otherwise the items are flattened
+                    JsonValue::Array(_) => {
+                        open_tag(xml, name);
+                        write_element(xml, ARRAY_ITEM_ELEMENT, item);
+                        close_tag(xml, name);
+                    }
+                    _ => write_element(xml, name, item),
+                }
+            }
+        }
+        JsonValue::Object(obj) => {
+            open_tag(xml, name);
+            for (key, each_value) in obj.iter() {
+                write_element(xml, &element_name(key), each_value);
+            }
+            close_tag(xml, name);
+        }
+        JsonValue::String(s) => {
+            open_tag(xml, name);
+            xml.push_str(&escape_text(s));
+            close_tag(xml, name);
+        }
+        JsonValue::Bool(_) | JsonValue::Number(_) => {
+            open_tag(xml, name);
+            xml.push_str(&value.to_string());
+            close_tag(xml, name);
+        }
+    }
+}
+
+fn open_tag(xml: &mut String, name: &str) {
+    xml.push('<');
+    xml.push_str(name);
+    xml.push('>');
+}
+
+fn close_tag(xml: &mut String, name: &str) {
+    xml.push_str("</");
+    xml.push_str(name);
+    xml.push('>');
+}
+
+fn escape_text(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            // not allowed in xml 1.0
+            c if (c as u32) < 0x20 && !matches!(c, '\t' | '\n' | '\r') => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// the chars not allowed in element names are replaced with `_`.
+/// "Full Name" -> "Full_Name", "1st" -> "_1st"
+fn element_name(key: &str) -> String {
+    let mut name: String = key
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '_' | '-' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let starts_with_name_char = name
+        .chars()
+        .next()
+        .map(|c| c.is_alphabetic() || c == '_')
+        .unwrap_or(false);
+    if !starts_with_name_char || name.to_lowercase().starts_with("xml") {
+        name.insert(0, '_');
+    }
+    name
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    const XML_DECL: &str = r#"<?xml version="1.0" encoding="UTF-8"?>"#;
+
+    #[test]
+    fn nested_object_test() {
+        let value = json!({"name": "Alice", "address": {"city": {"name": "kyoto"}}, "age": 20, "note": null});
+        assert_eq!(
+            to_xml("row", &value),
+            format!(
+                "{}<row><address><city><name>kyoto</name></city></address><age>20</age><name>Alice</name><note/></row>",
+                XML_DECL
+            )
+        );
+    }
+
+    #[test]
+    fn array_test() {
+        let value = json!({"data": [
+            {"name": "Alice", "favorite": ["diving", "programming"]},
+            {"name": "Bob", "favorite": [["a", "b"]]},
+        ]});
+        assert_eq!(
+            to_xml("response", &value),
+            format!(
+                "{}<response>\
+                <data><favorite>diving</favorite><favorite>programming</favorite><name>Alice</name></data>\
+                <data><favorite><item>a</item><item>b</item></favorite><name>Bob</name></data>\
+                </response>",
+                XML_DECL
+            )
+        );
+    }
+
+    #[test]
+    fn escape_test() {
+        let value = json!({"Full Name": "<Tom & Jerry>", "1st": true});
+        assert_eq!(
+            to_xml("row", &value),
+            format!(
+                "{}<row><_1st>true</_1st><Full_Name>&lt;Tom &amp; Jerry&gt;</Full_Name></row>",
+                XML_DECL
+            )
+        );
+    }
+}